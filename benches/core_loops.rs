@@ -0,0 +1,179 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Benchmarks for the hot loops of the model. Run them with `cargo bench`.
+
+use criterion::measurement::WallTime;
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkGroup, BenchmarkId, Criterion,
+};
+use rand::distributions::{Bernoulli, Distribution};
+use rust_agent_based_models::events::Queue;
+use rust_agent_based_models::landscape::CellDynamics;
+use rust_agent_based_models::storage::{GenerationalVec, Storage};
+use rust_agent_based_models::{network, AgentKey, Health, LinkKey};
+use slotmap::SlotMap;
+use std::hint::black_box;
+use wrapping_coords2d::WrappingCoords2d;
+
+type Rng = rand_pcg::Pcg64;
+
+fn rng() -> Rng {
+    rand_pcg::Pcg64::new(0xcafef00dd15ea5e5, 0xa02bdbf7bb3c0a7ac28fa16a64abf96)
+}
+
+/// Agents, 30% of them infected, and the network they form in a few time steps
+fn population<A: Storage<AgentKey, Health>, L: Storage<LinkKey, (AgentKey, AgentKey)>>(
+    n: usize,
+    rng: &mut Rng,
+) -> (A, L) {
+    let infection_distro = Bernoulli::new(0.3).unwrap();
+    let mut health = A::with_capacity(n);
+    while health.len() < n {
+        health.insert(if infection_distro.sample(rng) {
+            Health::I
+        } else {
+            Health::S
+        });
+    }
+    let mut links = L::with_capacity(n);
+    let keys_vec: Vec<AgentKey> = health.keys().collect();
+    links.insert((keys_vec[0], keys_vec[1]));
+    for _ in 0..3 {
+        network::form_links(
+            &keys_vec,
+            &mut links,
+            7,
+            Bernoulli::new(0.01).unwrap(),
+            rng,
+            |_, _| {},
+        );
+    }
+    (health, links)
+}
+
+/// Benchmarks the agent phases on one storage backend; `storage` names the backend.
+fn agents_on<A, L>(group: &mut BenchmarkGroup<WallTime>, storage: &str, n: usize)
+where
+    A: Storage<AgentKey, Health> + Clone,
+    L: Storage<LinkKey, (AgentKey, AgentKey)> + Clone,
+{
+    let mut rng = rng();
+    let (health, links) = population::<A, L>(n, &mut rng);
+    let keys_vec: Vec<AgentKey> = health.keys().collect();
+    let link_distro = Bernoulli::new(0.01).unwrap();
+    group.bench_with_input(
+        BenchmarkId::new(format!("form_links/{}", storage), n),
+        &n,
+        |b, _| {
+            b.iter_batched(
+                || links.clone(),
+                |mut links| {
+                    network::form_links(&keys_vec, &mut links, 7, link_distro, &mut rng, |_, _| {})
+                },
+                BatchSize::LargeInput,
+            )
+        },
+    );
+    let infection_distro = Bernoulli::new(0.4).unwrap();
+    group.bench_with_input(
+        BenchmarkId::new(format!("transmit/{}", storage), n),
+        &n,
+        |b, _| {
+            b.iter_batched(
+                || health.clone(),
+                |mut next_health| {
+                    network::transmit(
+                        &links,
+                        &health,
+                        &mut next_health,
+                        infection_distro,
+                        &mut rng,
+                        &mut Queue::default(),
+                    );
+                    next_health
+                },
+                BatchSize::LargeInput,
+            )
+        },
+    );
+    let survival_distro = Bernoulli::new(0.8).unwrap();
+    group.bench_with_input(
+        BenchmarkId::new(format!("retain_and_prune/{}", storage), n),
+        &n,
+        |b, _| {
+            b.iter_batched(
+                || (health.clone(), links.clone()),
+                |(mut health, mut links)| {
+                    health.retain(|_agent_key, h| match h {
+                        Health::S => true,
+                        Health::I => survival_distro.sample(&mut rng),
+                    });
+                    network::prune(&mut links, &health);
+                    (health, links)
+                },
+                BatchSize::LargeInput,
+            )
+        },
+    );
+}
+
+fn agents(c: &mut Criterion) {
+    let mut group = c.benchmark_group("agents");
+    group.sample_size(20);
+    for &n in &[1_000usize, 10_000, 100_000] {
+        agents_on::<SlotMap<_, _>, SlotMap<_, _>>(&mut group, "slotmap", n);
+        agents_on::<GenerationalVec<_, _>, GenerationalVec<_, _>>(&mut group, "vec", n);
+    }
+    group.finish();
+}
+
+fn cells(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cells");
+    for &side in &[100i32, 500, 2000] {
+        let mut rng = rng();
+        let coord = WrappingCoords2d::new(side, side).unwrap();
+        let initial_infection_distro = Bernoulli::new(0.1).unwrap();
+        let cell_health: Vec<Health> = (0..coord.size())
+            .map(|_| {
+                if initial_infection_distro.sample(&mut rng) {
+                    Health::I
+                } else {
+                    Health::S
+                }
+            })
+            .collect();
+        let mut next_cell_health = cell_health.clone();
+        let mut cell_dynamics = CellDynamics::new(&coord, 0.4, Bernoulli::new(0.8).unwrap(), false);
+        group.bench_with_input(BenchmarkId::new("step", side), &side, |b, _| {
+            b.iter(|| {
+                cell_dynamics.step(
+                    black_box(&cell_health),
+                    &mut next_cell_health,
+                    0,
+                    None,
+                    &mut rng,
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, agents, cells);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Records the enabled features and the git commit of the build, for the manifest of each run, and compiles the
+//! protocol of the grpc feature.
+
+fn main() {
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features.join(","));
+    // The commit is unknown when building outside a git repository or without git.
+    if let Ok(output) = std::process::Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+    {
+        if output.status.success() {
+            println!(
+                "cargo:rustc-env=BUILD_GIT_COMMIT={}",
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    // Messages and service of the grpc feature; protoc comes with the build, so no install is needed.
+    #[cfg(feature = "grpc")]
+    {
+        std::env::set_var(
+            "PROTOC",
+            protoc_bin_vendored::protoc_bin_path()
+                .expect("protoc is not available for this platform"),
+        );
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/simulations.proto"], &["proto"])
+            .expect("Could not compile proto/simulations.proto");
+    }
+    for path in &[".git/HEAD", ".git/refs"] {
+        if std::path::Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Flocking in continuous space, after the boids of Reynolds (1987).
+//!
+//! Each boid sees the boids within its vision and steers by three rules: alignment, towards their mean velocity;
+//! cohesion, towards their center; and separation, away from those that come too close. A little noise perturbs its
+//! heading, and its speed stays between a minimum and a maximum. Scattered boids gather into flocks that fly in one
+//! direction, which the polarization, the length of the mean heading of the boids, measures from 0 to 1.
+//! Run it with `cargo run --release --example boids`; the metrics go to boids.csv. With the `live-view` feature, `--live`
+//! shows the flocks in the terminal as they form.
+
+use clap::Parser;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use rust_agent_based_models::buffered::Buffered;
+#[cfg(feature = "live-view")]
+use rust_agent_based_models::live::LiveView;
+use rust_agent_based_models::model::{self, Model};
+use rust_agent_based_models::space::{Point, Space, SpatialIndex};
+use rust_agent_based_models::storage::Storage;
+use rust_agent_based_models::streams::{Process, RandomStreams};
+use rust_agent_based_models::tables::{self, Value};
+#[cfg(feature = "live-view")]
+use rust_agent_based_models::{theme::Theme, Health};
+use rust_agent_based_models::{AgentKey, Agents};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Simulate boids flocking in continuous space
+#[derive(Parser)]
+struct Args {
+    /// Number of boids
+    #[arg(long, default_value_t = 300)]
+    boids: usize,
+    /// Width and height of the space
+    #[arg(long, default_value_t = 100.0)]
+    size: f64,
+    /// Distance within which a boid sees the others
+    #[arg(long, default_value_t = 5.0)]
+    vision: f64,
+    /// Distance under which boids steer away from each other
+    #[arg(long, default_value_t = 1.0)]
+    separation: f64,
+    /// Weights of alignment, cohesion, and separation
+    #[arg(long, num_args = 3, default_values_t = [0.05, 0.005, 0.05])]
+    weights: Vec<f64>,
+    /// Standard deviation of the noise added to the velocity of each boid at every time step
+    #[arg(long, default_value_t = 0.02)]
+    noise: f64,
+    /// Time steps to simulate
+    #[arg(long, default_value_t = 1000)]
+    time_steps: usize,
+    /// Seed of the random number generators
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Time series of the metrics
+    #[arg(long, default_value = "boids.csv")]
+    output: PathBuf,
+    /// Show the boids in the terminal as they fly
+    #[cfg(feature = "live-view")]
+    #[arg(long)]
+    live: bool,
+}
+
+/// Slowest and fastest speeds of boids
+const SPEEDS: (f64, f64) = (0.5, 1.0);
+
+#[derive(Clone, Copy, Default)]
+struct Boid {
+    position: Point,
+    velocity: Point,
+}
+
+struct Flock {
+    space: Space,
+    vision: f64,
+    separation: f64,
+    /// Weights of alignment, cohesion, and separation
+    weights: [f64; 3],
+    noise_distro: Normal<f64>,
+    // Model state: Position and velocity of each boid, updated in parallel
+    boids: Buffered<Agents<Boid>>,
+}
+
+impl Flock {
+    fn new(args: &Args, noise_distro: Normal<f64>, rng: &mut RandomStreams) -> Self {
+        let space = Space::new(args.size, args.size);
+        // Model initialization: Boids start at random positions, flying in random directions
+        let mut boids = Agents::with_capacity(args.boids);
+        for _ in 0..args.boids {
+            let rng = rng.get(Process::Births);
+            let position = [
+                rng.gen_range(0.0..space.width()),
+                rng.gen_range(0.0..space.height()),
+            ];
+            let heading = rng.gen_range(0.0..std::f64::consts::TAU);
+            let speed = rng.gen_range(SPEEDS.0..=SPEEDS.1);
+            let velocity = [speed * heading.cos(), speed * heading.sin()];
+            boids.insert(Boid { position, velocity });
+        }
+        Self {
+            space,
+            vision: args.vision,
+            separation: args.separation,
+            weights: [args.weights[0], args.weights[1], args.weights[2]],
+            noise_distro,
+            boids: Buffered::new(boids),
+        }
+    }
+
+    fn index(&self) -> SpatialIndex<AgentKey> {
+        SpatialIndex::new(
+            self.space,
+            self.vision,
+            self.boids.read().iter().map(|(k, boid)| (k, boid.position)),
+        )
+    }
+
+    /// Cells of a raster of the space, one per unit of area, infected where boids are
+    #[cfg(feature = "live-view")]
+    fn raster(&self) -> (Vec<Health>, usize) {
+        let (width, height) = (
+            self.space.width().ceil() as usize,
+            self.space.height().ceil() as usize,
+        );
+        let mut cells = vec![Health::S; width * height];
+        for boid in self.boids.read().values() {
+            let [x, y] = boid.position;
+            cells[(y as usize).min(height - 1) * width + (x as usize).min(width - 1)] = Health::I;
+        }
+        (cells, width)
+    }
+}
+
+impl Model for Flock {
+    fn metrics(&self) -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("polarization", "Length of the mean heading of boids"),
+            ("neighbors", "Mean number of boids within vision"),
+            ("speed", "Mean speed of boids"),
+        ]
+    }
+
+    fn measure(&self) -> Vec<f64> {
+        let boids = self.boids.read();
+        let n = boids.len().max(1) as f64;
+        let heading = boids.values().fold([0.0, 0.0], |[x, y], boid| {
+            let speed = boid.velocity[0].hypot(boid.velocity[1]);
+            [x + boid.velocity[0] / speed, y + boid.velocity[1] / speed]
+        });
+        let index = self.index();
+        let mut neighbors = 0;
+        for boid in boids.values() {
+            index.for_each_within(boid.position, |_k, _d| neighbors += 1);
+        }
+        let speed: f64 = boids
+            .values()
+            .map(|boid| boid.velocity[0].hypot(boid.velocity[1]))
+            .sum();
+        vec![
+            heading[0].hypot(heading[1]) / n,
+            // Each boid finds itself too
+            (neighbors as f64 - boids.len() as f64) / n,
+            speed / n,
+        ]
+    }
+
+    fn step(&mut self, _time_step: usize, rng: &mut RandomStreams) {
+        let index = self.index();
+        let (space, separation, [alignment, cohesion, avoidance]) =
+            (self.space, self.separation, self.weights);
+        let noise_distro = self.noise_distro;
+        let (boids, next_boids) = self.boids.read_write();
+        // Dynamics: Each boid steers by the boids within its vision, then flies
+        for (k, boid) in boids.iter() {
+            let (mut velocity, mut center, mut away, mut seen) = ([0.0; 2], [0.0; 2], [0.0; 2], 0);
+            index.for_each_within(boid.position, |other, [dx, dy]| {
+                if other == k {
+                    return;
+                }
+                seen += 1;
+                velocity[0] += boids[other].velocity[0];
+                velocity[1] += boids[other].velocity[1];
+                center[0] += dx;
+                center[1] += dy;
+                let distance = dx.hypot(dy);
+                if distance < separation && distance > 0.0 {
+                    away[0] -= dx / distance;
+                    away[1] -= dy / distance;
+                }
+            });
+            let mut v = boid.velocity;
+            if seen > 0 {
+                let seen = seen as f64;
+                for axis in 0..2 {
+                    v[axis] += alignment * (velocity[axis] / seen - v[axis])
+                        + cohesion * center[axis] / seen
+                        + avoidance * away[axis];
+                }
+            }
+            let rng = rng.get(Process::Movement);
+            v[0] += noise_distro.sample(rng);
+            v[1] += noise_distro.sample(rng);
+            let speed = v[0].hypot(v[1]);
+            let clamped = speed.clamp(SPEEDS.0, SPEEDS.1);
+            let v = if speed > 0.0 {
+                [v[0] * clamped / speed, v[1] * clamped / speed]
+            } else {
+                [clamped, 0.0]
+            };
+            next_boids[k] = Boid {
+                position: space.wrap([boid.position[0] + v[0], boid.position[1] + v[1]]),
+                velocity: v,
+            };
+        }
+        self.boids.swap();
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let noise_distro = Normal::new(0.0, args.noise)?;
+    let mut rng = RandomStreams::new(args.seed.into(), 0xa02bdbf7bb3c0a7ac28fa16a64abf96, true);
+    let mut model = Flock::new(&args, noise_distro, &mut rng);
+    let columns = model::columns(&model);
+    #[cfg(feature = "live-view")]
+    let mut live_view = args.live.then(|| {
+        LiveView::new(
+            0,
+            columns
+                .iter()
+                .skip(1)
+                .map(|column| column.title.clone())
+                .collect(),
+            &Theme::builtin("dark").unwrap(),
+        )
+    });
+    let mut output = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    tables::write_csv_header(&mut output, &columns)?;
+    model::simulate(
+        &mut model,
+        args.time_steps,
+        1,
+        &mut rng,
+        |time_step, model| {
+            let values = model.measure();
+            #[cfg(feature = "live-view")]
+            if let Some(live_view) = &mut live_view {
+                let (cells, width) = model.raster();
+                live_view.show(time_step, Some((&cells, width)), &values);
+            }
+            let mut row = vec![Value::UInt32(time_step as u32)];
+            row.extend(values.into_iter().map(Value::Float64));
+            tables::write_csv_row(&mut output, &row)
+        },
+    )?;
+    output.flush()?;
+    #[cfg(feature = "live-view")]
+    if let Some(live_view) = live_view {
+        live_view.finish();
+    }
+    eprintln!("The metrics are in {}.", args.output.display());
+    Ok(())
+}
@@ -0,0 +1,308 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Evolution of cooperation on the social network of the model, with the games module.
+//!
+//! Agents start as cooperators or defectors at random. At every time step, linked agents play the prisoner's dilemma
+//! or the public goods game, and each agent then imitates a random friend who earned more, following the Fermi rule.
+//! Clusters of cooperators can hold out against defectors when the temptation to defect is small, or the synergy of
+//! the public goods is large. Run it with `cargo run --release --example games`; the metrics go to games.csv and the
+//! figure to games.png.
+
+use clap::Parser;
+use plotters::prelude::*;
+use rand::distributions::Bernoulli;
+use rand::Rng;
+use rust_agent_based_models::buffered::Buffered;
+use rust_agent_based_models::games::{self, Game, Strategy};
+use rust_agent_based_models::model::{self, Model};
+use rust_agent_based_models::network;
+use rust_agent_based_models::storage::Storage;
+use rust_agent_based_models::streams::{Process, RandomStreams};
+use rust_agent_based_models::tables::{self, Value};
+use rust_agent_based_models::theme::Theme;
+use rust_agent_based_models::{AgentKey, Agents, Links};
+use slotmap::SecondaryMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Game that linked agents play
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum GameName {
+    /// Weak prisoner's dilemma on every link
+    PrisonersDilemma,
+    /// Public goods game among each agent and its friends
+    PublicGoods,
+}
+
+/// Simulate the evolution of cooperation on a social network
+#[derive(Parser)]
+struct Args {
+    #[arg(long, value_enum, default_value_t = GameName::PrisonersDilemma)]
+    game: GameName,
+    /// Payoff of a defector against a cooperator in the prisoner's dilemma, where mutual cooperation pays 1
+    #[arg(long, default_value_t = 1.2)]
+    temptation: f64,
+    /// Factor by which the pot of the public goods game grows
+    #[arg(long, default_value_t = 3.0)]
+    synergy: f64,
+    /// Noise of imitation: small values make agents copy friends who earn more almost surely
+    #[arg(long, default_value_t = 0.1)]
+    noise: f64,
+    /// Fraction of cooperators at the start
+    #[arg(long, default_value_t = 0.5)]
+    cooperators: f64,
+    /// Number of agents
+    #[arg(long, default_value_t = 1000)]
+    agents: usize,
+    /// Links of each agent without friends as the network forms
+    #[arg(long, default_value_t = 3)]
+    net_k: usize,
+    /// Time steps to simulate
+    #[arg(long, default_value_t = 500)]
+    time_steps: usize,
+    /// Seed of the random number generators
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Time series of the metrics
+    #[arg(long, default_value = "games.csv")]
+    output: PathBuf,
+    /// Figure of the fraction of cooperators and the payoffs over time
+    #[arg(long, default_value = "games.png")]
+    figure: PathBuf,
+    /// Built-in theme of the figure
+    #[arg(long, default_value = "light")]
+    theme: String,
+}
+
+struct Cooperation {
+    game: Game,
+    noise: f64,
+    // Model state: Strategy of each agent, updated in parallel
+    strategies: Buffered<Agents<Strategy>>,
+    // Model state: Friends of each agent in the fixed social network
+    friends: SecondaryMap<AgentKey, Vec<AgentKey>>,
+    // Model measurements: Payoff of each agent in the last round, and the agents that changed strategy
+    payoffs: SecondaryMap<AgentKey, f64>,
+    changes: usize,
+}
+
+impl Cooperation {
+    fn new(args: &Args, game: Game, rng: &mut RandomStreams) -> Self {
+        // Model initialization: Each agent cooperates with probability --cooperators
+        let mut strategies = Agents::with_capacity(args.agents);
+        for _ in 0..args.agents {
+            strategies.insert(if rng.get(Process::Births).gen_bool(args.cooperators) {
+                Strategy::Cooperate
+            } else {
+                Strategy::Defect
+            });
+        }
+        // Model initialization: The network forms as in the SIS model, and stays fixed
+        let keys_vec: Vec<AgentKey> = strategies.keys().collect();
+        let mut links = Links::with_capacity(args.agents * args.net_k);
+        links.insert((keys_vec[0], keys_vec[1]));
+        for _ in 0..3 {
+            network::form_links(
+                &keys_vec,
+                &mut links,
+                args.net_k,
+                Bernoulli::new(0.1).unwrap(),
+                rng.get(Process::Network),
+                |_link, _agents| {},
+            );
+        }
+        let friends = games::friends(&links);
+        let payoffs = games::play(game, &strategies, &friends);
+        Self {
+            game,
+            noise: args.noise,
+            strategies: Buffered::new(strategies),
+            friends,
+            payoffs,
+            changes: 0,
+        }
+    }
+
+    /// Mean payoff of the agents with this strategy, or 0 if none
+    fn mean_payoff(&self, strategy: Strategy) -> f64 {
+        let (sum, count) = self
+            .strategies
+            .read()
+            .iter()
+            .filter(|&(_, &s)| s == strategy)
+            .fold((0.0, 0), |(sum, count), (k, _)| {
+                (sum + self.payoffs[k], count + 1)
+            });
+        if count == 0 {
+            0.0
+        } else {
+            sum / count as f64
+        }
+    }
+}
+
+impl Model for Cooperation {
+    fn metrics(&self) -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("cooperation", "Fraction of cooperators"),
+            ("payoff_c", "Mean payoff of cooperators"),
+            ("payoff_d", "Mean payoff of defectors"),
+            ("changes", "Agents that changed strategy"),
+        ]
+    }
+
+    fn measure(&self) -> Vec<f64> {
+        let strategies = self.strategies.read();
+        let cooperators = strategies
+            .values()
+            .filter(|&&s| s == Strategy::Cooperate)
+            .count();
+        vec![
+            cooperators as f64 / strategies.len().max(1) as f64,
+            self.mean_payoff(Strategy::Cooperate),
+            self.mean_payoff(Strategy::Defect),
+            self.changes as f64,
+        ]
+    }
+
+    fn step(&mut self, _time_step: usize, rng: &mut RandomStreams) {
+        // Dynamics: Agents imitate friends who earned more in the last round, then play a new round
+        let (strategies, next_strategies) = self.strategies.read_write();
+        self.changes = games::imitate(
+            strategies,
+            next_strategies,
+            &self.payoffs,
+            &self.friends,
+            self.noise,
+            rng.get(Process::Network),
+        );
+        self.strategies.swap();
+        self.payoffs = games::play(self.game, self.strategies.read(), &self.friends);
+    }
+}
+
+/// Draws the fraction of cooperators over time, above the mean payoffs of cooperators and defectors.
+fn draw(path: &Path, theme: &Theme, rows: &[Vec<f64>]) -> Result<(), Box<dyn std::error::Error>> {
+    let root = BitMapBackend::new(path, (1024, 768)).into_drawing_area();
+    root.fill(&theme.background)?;
+    let (top, bottom) = root.split_vertically(384);
+    let text = ("sans-serif", 16).into_font().color(&theme.foreground);
+    let time_steps = rows.len() as i32;
+    let mut chart = ChartBuilder::on(&top)
+        .caption("Cooperation", text.clone())
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0..time_steps, 0.0..1.0)?;
+    chart
+        .configure_mesh()
+        .axis_style(theme.foreground)
+        .label_style(text.clone())
+        .y_desc("Fraction of cooperators")
+        .axis_desc_style(text.clone())
+        .draw()?;
+    chart.draw_series(LineSeries::new(
+        rows.iter().enumerate().map(|(t, row)| (t as i32, row[0])),
+        theme.susceptible,
+    ))?;
+    let (low, high) = rows
+        .iter()
+        .flat_map(|row| [row[1], row[2]])
+        .fold((0.0f64, 1e-9f64), |(low, high), payoff| {
+            (low.min(payoff), high.max(payoff))
+        });
+    let mut chart = ChartBuilder::on(&bottom)
+        .caption("Mean payoffs", text.clone())
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0..time_steps, low..high)?;
+    chart
+        .configure_mesh()
+        .axis_style(theme.foreground)
+        .label_style(text.clone())
+        .x_desc("Time step")
+        .y_desc("Payoff")
+        .axis_desc_style(text.clone())
+        .draw()?;
+    for (column, color, label) in [
+        (1, theme.susceptible, "Cooperators"),
+        (2, theme.infected, "Defectors"),
+    ] {
+        chart
+            .draw_series(LineSeries::new(
+                rows.iter()
+                    .enumerate()
+                    .map(|(t, row)| (t as i32, row[column])),
+                color,
+            ))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+    chart
+        .configure_series_labels()
+        .background_style(theme.background)
+        .border_style(theme.foreground)
+        .label_font(text)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let theme =
+        Theme::builtin(&args.theme).ok_or_else(|| format!("There is no theme {}", args.theme))?;
+    if args.noise.is_nan() || args.noise <= 0.0 || !(0.0..=1.0).contains(&args.cooperators) {
+        return Err("--noise must be positive, and --cooperators between 0 and 1".into());
+    }
+    let game = match args.game {
+        GameName::PrisonersDilemma => Game::weak_prisoners_dilemma(args.temptation),
+        GameName::PublicGoods => Game::PublicGoods {
+            synergy: args.synergy,
+        },
+    };
+    let mut rng = RandomStreams::new(args.seed.into(), 0xa02bdbf7bb3c0a7ac28fa16a64abf96, true);
+    let mut model = Cooperation::new(&args, game, &mut rng);
+    let columns = model::columns(&model);
+    let mut output = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    tables::write_csv_header(&mut output, &columns)?;
+    let mut rows = Vec::with_capacity(args.time_steps);
+    model::simulate(
+        &mut model,
+        args.time_steps,
+        1,
+        &mut rng,
+        |time_step, model| {
+            let values = model.measure();
+            let mut row = vec![Value::UInt32(time_step as u32)];
+            row.extend(values.iter().copied().map(Value::Float64));
+            rows.push(values);
+            tables::write_csv_row(&mut output, &row)
+        },
+    )?;
+    output.flush()?;
+    draw(&args.figure, &theme, &rows)?;
+    eprintln!(
+        "The metrics are in {}, and the figure in {}.",
+        args.output.display(),
+        args.figure.display()
+    );
+    Ok(())
+}
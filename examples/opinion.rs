@@ -0,0 +1,333 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Opinion dynamics on the social network of the model.
+//!
+//! Agents hold an opinion and meet their friends across the links of the network at every time step. With bounded
+//! confidence, after Deffuant and Hegselmann-Krause, each agent moves its opinion, between 0 and 1, towards the mean
+//! opinion of its friends who think alike, those whose opinions differ by less than the confidence bound; opinions
+//! gather into clusters that no longer talk to each other. With the voter model, each agent holds one of a few
+//! discrete opinions and adopts the opinion of a random friend with some probability; the network tends to consensus.
+//! Run it with `cargo run --release --example opinion`; the metrics go to opinion.csv and the figure to opinion.png.
+
+use clap::Parser;
+use plotters::prelude::*;
+use rand::distributions::{Bernoulli, Distribution};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rust_agent_based_models::buffered::Buffered;
+use rust_agent_based_models::model::{self, Model};
+use rust_agent_based_models::network;
+use rust_agent_based_models::storage::Storage;
+use rust_agent_based_models::streams::{Process, RandomStreams};
+use rust_agent_based_models::tables::{self, Value};
+use rust_agent_based_models::theme::Theme;
+use rust_agent_based_models::{AgentKey, Agents, Links};
+use slotmap::SecondaryMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// How agents influence their friends
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum Rule {
+    /// Continuous opinions move towards those of friends within the confidence bound
+    BoundedConfidence,
+    /// Discrete opinions copy that of a random friend
+    Voter,
+}
+
+/// Simulate opinion dynamics on a social network
+#[derive(Parser)]
+struct Args {
+    #[arg(long, value_enum, default_value_t = Rule::BoundedConfidence)]
+    rule: Rule,
+    /// Number of agents
+    #[arg(long, default_value_t = 500)]
+    agents: usize,
+    /// Links of each agent without friends as the network forms
+    #[arg(long, default_value_t = 3)]
+    net_k: usize,
+    /// Largest difference of opinions across which friends influence each other, with bounded confidence
+    #[arg(long, default_value_t = 0.2)]
+    confidence: f64,
+    /// Fraction of the way to the opinion of its friends that an agent moves at each time step, with bounded
+    /// confidence
+    #[arg(long, default_value_t = 0.5)]
+    convergence: f64,
+    /// Number of discrete opinions, with the voter model
+    #[arg(long, default_value_t = 2)]
+    opinions: u32,
+    /// Probability that an agent copies a friend at each time step, with the voter model
+    #[arg(long, default_value_t = 0.1)]
+    adoption_probability: f64,
+    /// Time steps to simulate
+    #[arg(long, default_value_t = 200)]
+    time_steps: usize,
+    /// Seed of the random number generators
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Time series of the metrics
+    #[arg(long, default_value = "opinion.csv")]
+    output: PathBuf,
+    /// Figure of the opinions of every agent and the metrics over time
+    #[arg(long, default_value = "opinion.png")]
+    figure: PathBuf,
+    /// Built-in theme of the figure
+    #[arg(long, default_value = "light")]
+    theme: String,
+}
+
+struct Opinions {
+    rule: Rule,
+    confidence: f64,
+    convergence: f64,
+    adoption_distro: Bernoulli,
+    // Model state: Opinion of each agent, updated in parallel
+    opinion: Buffered<Agents<f64>>,
+    // Model state: Social network, and the friends of each agent
+    links: Links,
+    friends: SecondaryMap<AgentKey, Vec<AgentKey>>,
+    // Model measurements: Opinions of every agent at every time step simulated, for the figure
+    history: Vec<Vec<f64>>,
+}
+
+impl Opinions {
+    fn new(args: &Args, adoption_distro: Bernoulli, rng: &mut RandomStreams) -> Self {
+        // Model initialization: Opinions are uniform, either between 0 and 1 or among the discrete opinions
+        let mut opinion = Agents::with_capacity(args.agents);
+        for _ in 0..args.agents {
+            let rng = rng.get(Process::Births);
+            opinion.insert(match args.rule {
+                Rule::BoundedConfidence => rng.gen::<f64>(),
+                Rule::Voter => rng.gen_range(0..args.opinions) as f64,
+            });
+        }
+        // Model initialization: The network forms as in the SIS model, and stays fixed
+        let keys_vec: Vec<AgentKey> = opinion.keys().collect();
+        let mut links = Links::with_capacity(args.agents * args.net_k);
+        links.insert((keys_vec[0], keys_vec[1]));
+        for _ in 0..3 {
+            network::form_links(
+                &keys_vec,
+                &mut links,
+                args.net_k,
+                Bernoulli::new(0.1).unwrap(),
+                rng.get(Process::Network),
+                |_link, _agents| {},
+            );
+        }
+        let mut friends: SecondaryMap<AgentKey, Vec<AgentKey>> =
+            keys_vec.iter().map(|&k| (k, Vec::new())).collect();
+        for &(key0, key1) in links.values() {
+            friends[key0].push(key1);
+            friends[key1].push(key0);
+        }
+        Self {
+            rule: args.rule,
+            confidence: args.confidence,
+            convergence: args.convergence,
+            adoption_distro,
+            opinion: Buffered::new(opinion),
+            links,
+            friends,
+            history: Vec::new(),
+        }
+    }
+
+    /// Number of clusters of opinions: distinct opinions with the voter model, and groups of opinions separated by
+    /// gaps wider than half the confidence bound otherwise
+    fn clusters(&self) -> usize {
+        let mut opinions: Vec<f64> = self.opinion.read().values().copied().collect();
+        if opinions.is_empty() {
+            return 0;
+        }
+        opinions.sort_by(f64::total_cmp);
+        let gap = match self.rule {
+            Rule::BoundedConfidence => self.confidence / 2.0,
+            Rule::Voter => 0.5,
+        };
+        1 + opinions.windows(2).filter(|w| w[1] - w[0] > gap).count()
+    }
+}
+
+impl Model for Opinions {
+    fn metrics(&self) -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("mean", "Mean opinion"),
+            ("var", "Variance of opinions"),
+            ("clusters", "Clusters of opinions"),
+            ("discordant", "Fraction of links between different opinions"),
+        ]
+    }
+
+    fn measure(&self) -> Vec<f64> {
+        let opinion = self.opinion.read();
+        let n = opinion.len() as f64;
+        let mean = opinion.values().sum::<f64>() / n;
+        let variance = opinion.values().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+        let threshold = match self.rule {
+            Rule::BoundedConfidence => self.confidence,
+            Rule::Voter => 0.5,
+        };
+        let discordant = self
+            .links
+            .values()
+            .filter(|&&(key0, key1)| (opinion[key0] - opinion[key1]).abs() >= threshold)
+            .count();
+        vec![
+            mean,
+            variance,
+            self.clusters() as f64,
+            discordant as f64 / self.links.len().max(1) as f64,
+        ]
+    }
+
+    fn step(&mut self, _time_step: usize, rng: &mut RandomStreams) {
+        self.history
+            .push(self.opinion.read().values().copied().collect());
+        let (confidence, convergence) = (self.confidence, self.convergence);
+        let (opinion, next_opinion) = self.opinion.read_write();
+        // Dynamics: Each agent hears its friends across their links
+        for (k, &x) in opinion.iter() {
+            let friends = &self.friends[k];
+            next_opinion[k] = match self.rule {
+                Rule::BoundedConfidence => {
+                    let (sum, count) = friends
+                        .iter()
+                        .map(|&friend| opinion[friend])
+                        .filter(|y| (y - x).abs() < confidence)
+                        .fold((0.0, 0), |(sum, count), y| (sum + y, count + 1));
+                    if count == 0 {
+                        x
+                    } else {
+                        x + convergence * (sum / count as f64 - x)
+                    }
+                }
+                Rule::Voter => match friends.choose(rng.get(Process::Network)) {
+                    Some(&friend) if self.adoption_distro.sample(rng.get(Process::Network)) => {
+                        opinion[friend]
+                    }
+                    _ => x,
+                },
+            };
+        }
+        self.opinion.swap();
+    }
+}
+
+/// Draws the opinions of every agent over time, and the variance and clusters of opinions below.
+fn draw(
+    path: &PathBuf,
+    theme: &Theme,
+    history: &[Vec<f64>],
+    rows: &[Vec<f64>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = BitMapBackend::new(path, (1024, 768)).into_drawing_area();
+    root.fill(&theme.background)?;
+    let (top, bottom) = root.split_vertically(480);
+    let text = ("sans-serif", 16).into_font().color(&theme.foreground);
+    let time_steps = history.len() as i32;
+    let top_opinion = history.iter().flatten().copied().fold(1.0, f64::max);
+    let mut chart = ChartBuilder::on(&top)
+        .caption("Opinions of agents", text.clone())
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0..time_steps, 0.0..top_opinion)?;
+    chart
+        .configure_mesh()
+        .axis_style(theme.foreground)
+        .label_style(text.clone())
+        .y_desc("Opinion")
+        .axis_desc_style(text.clone())
+        .draw()?;
+    chart.draw_series(history.iter().enumerate().flat_map(|(t, opinions)| {
+        opinions
+            .iter()
+            .map(move |&x| Pixel::new((t as i32, x), theme.series[3]))
+    }))?;
+    let top_variance = rows.iter().map(|row| row[1]).fold(1e-9, f64::max);
+    let top_clusters = rows.iter().map(|row| row[2]).fold(1.0, f64::max);
+    let mut chart = ChartBuilder::on(&bottom)
+        .caption("Variance and clusters of opinions", text.clone())
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .right_y_label_area_size(50)
+        .build_cartesian_2d(0..time_steps, 0.0..top_variance)?
+        .set_secondary_coord(0..time_steps, 0.0..top_clusters);
+    chart
+        .configure_mesh()
+        .axis_style(theme.foreground)
+        .label_style(text.clone())
+        .x_desc("Time step")
+        .y_desc("Variance")
+        .axis_desc_style(text.clone())
+        .draw()?;
+    chart
+        .configure_secondary_axes()
+        .axis_style(theme.foreground)
+        .label_style(text.clone())
+        .y_desc("Clusters")
+        .axis_desc_style(text)
+        .draw()?;
+    chart.draw_series(LineSeries::new(
+        rows.iter().enumerate().map(|(t, row)| (t as i32, row[1])),
+        theme.series[0],
+    ))?;
+    chart.draw_secondary_series(LineSeries::new(
+        rows.iter().enumerate().map(|(t, row)| (t as i32, row[2])),
+        theme.series[1],
+    ))?;
+    root.present()?;
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let theme =
+        Theme::builtin(&args.theme).ok_or_else(|| format!("There is no theme {}", args.theme))?;
+    let mut rng = RandomStreams::new(args.seed.into(), 0xa02bdbf7bb3c0a7ac28fa16a64abf96, true);
+    let adoption_distro = Bernoulli::new(args.adoption_probability)?;
+    let mut model = Opinions::new(&args, adoption_distro, &mut rng);
+    let columns = model::columns(&model);
+    let mut output = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    tables::write_csv_header(&mut output, &columns)?;
+    let mut rows = Vec::with_capacity(args.time_steps);
+    model::simulate(
+        &mut model,
+        args.time_steps,
+        1,
+        &mut rng,
+        |time_step, model| {
+            let values = model.measure();
+            let mut row = vec![Value::UInt32(time_step as u32)];
+            row.extend(values.iter().copied().map(Value::Float64));
+            rows.push(values);
+            tables::write_csv_row(&mut output, &row)
+        },
+    )?;
+    output.flush()?;
+    draw(&args.figure, &theme, &model.history, &rows)?;
+    eprintln!(
+        "The metrics are in {}, and the figure in {}.",
+        args.output.display(),
+        args.figure.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,302 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Wolves, sheep, and grass on the landscape, after the Wolf Sheep Predation model of NetLogo.
+//!
+//! Animals move to a neighboring cell at every time step, spending one unit of energy. Sheep eat the grass of their
+//! cell, which grows back after a while, and wolves eat a sheep of their cell; each meal gives energy. Animals with
+//! energy left give birth with some probability, sharing their energy with the newborn, and die when their energy
+//! runs out. Run it with `cargo run --release --example predator_prey`; the populations go to predator_prey.csv.
+
+use clap::Parser;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rust_agent_based_models::define_components;
+use rust_agent_based_models::deletion::Dependents;
+use rust_agent_based_models::model::{self, Model};
+use rust_agent_based_models::storage::Storage;
+use rust_agent_based_models::streams::{Process, RandomStreams};
+use rust_agent_based_models::tables::{self, Value};
+use rust_agent_based_models::{AgentKey, Agents};
+use slotmap::SecondaryMap;
+use std::io::Write;
+use std::path::PathBuf;
+use wrapping_coords2d::WrappingCoords2d;
+
+/// Simulate wolves, sheep, and grass on the landscape
+#[derive(Parser)]
+struct Args {
+    /// Time steps to simulate; the simulation stops earlier when every animal dies
+    #[arg(long, default_value_t = 1000)]
+    time_steps: usize,
+    /// Seed of the random number generators
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Time series of the populations
+    #[arg(long, default_value = "predator_prey.csv")]
+    output: PathBuf,
+    /// Sheep at the start
+    #[arg(long, default_value_t = 100)]
+    sheep: usize,
+    /// Wolves at the start
+    #[arg(long, default_value_t = 50)]
+    wolves: usize,
+    /// Time steps for grass to grow back after sheep eat it
+    #[arg(long, default_value_t = 30)]
+    grass_regrowth: u32,
+}
+
+/// Species of an animal
+#[derive(Clone, Copy, PartialEq)]
+enum Species {
+    Sheep,
+    Wolf,
+}
+
+/// Parameters of a species
+struct Parameters {
+    /// Energy from each meal
+    gain: f64,
+    /// Probability of giving birth at each time step
+    reproduction_probability: f64,
+}
+
+impl Species {
+    fn parameters(self) -> Parameters {
+        match self {
+            Species::Sheep => Parameters {
+                gain: 4.0,
+                reproduction_probability: 0.04,
+            },
+            Species::Wolf => Parameters {
+                gain: 20.0,
+                reproduction_probability: 0.05,
+            },
+        }
+    }
+}
+
+define_components! {
+    /// Components of animals: their energy, and the cell where they are
+    struct AnimalComponents for AgentKey {
+        energy: f64,
+        cell: usize,
+    }
+}
+
+define_components! {
+    /// Components of cells: whether grass covers them, and the time steps until it grows back
+    struct CellComponents for cells {
+        grass: bool,
+        regrowth: u32,
+    }
+}
+
+struct PredatorPrey {
+    coord: WrappingCoords2d,
+    grass_regrowth: u32,
+    // Model state: Animals and their components
+    species: Agents<Species>,
+    animals: AnimalComponents,
+    // Model state: Cells and their components
+    cells: CellComponents,
+}
+
+impl PredatorPrey {
+    fn new(args: &Args, rng: &mut RandomStreams) -> Self {
+        // Model parameter: Size of the landscape
+        let coord = WrappingCoords2d::new(51, 51).unwrap();
+        // Model initialization: Half of the cells have grass, and the others grow it back at random times
+        let mut cells = CellComponents::with_size(coord.size());
+        for idx in 0..coord.size() {
+            let rng = rng.get(Process::Births);
+            let grass = rng.gen_bool(0.5);
+            cells.set_grass(idx, grass);
+            cells.set_regrowth(
+                idx,
+                if grass {
+                    0
+                } else {
+                    rng.gen_range(1..=args.grass_regrowth.max(1))
+                },
+            );
+        }
+        let mut model = Self {
+            coord,
+            grass_regrowth: args.grass_regrowth,
+            species: Agents::with_capacity(args.sheep + args.wolves),
+            animals: AnimalComponents::default(),
+            cells,
+        };
+        // Model initialization: Animals start at random cells with random energy, up to twice the gain of a meal
+        for (species, n) in [(Species::Sheep, args.sheep), (Species::Wolf, args.wolves)] {
+            for _ in 0..n {
+                let rng = rng.get(Process::Births);
+                let animal = model.species.insert(species);
+                let energy = rng.gen_range(0.0..2.0 * species.parameters().gain);
+                model.animals.set_energy(animal, energy);
+                model
+                    .animals
+                    .set_cell(animal, rng.gen_range(0..model.coord.size()));
+            }
+        }
+        model
+    }
+
+    fn count(&self, species: Species) -> usize {
+        self.species.values().filter(|&&s| s == species).count()
+    }
+
+    /// A random cell among the eight neighbors of a cell
+    fn neighbor<R: Rng>(&self, idx: usize, rng: &mut R) -> usize {
+        let width = self.coord.width() as usize;
+        let (x, y) = ((idx % width) as i32, (idx / width) as i32);
+        let (dx, dy) = *[
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ]
+        .choose(rng)
+        .unwrap();
+        self.coord.index(x + dx, y + dy)
+    }
+}
+
+impl Model for PredatorPrey {
+    fn metrics(&self) -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("sheep", "Sheep"),
+            ("wolves", "Wolves"),
+            ("grass", "Fraction of cells with grass"),
+        ]
+    }
+
+    fn measure(&self) -> Vec<f64> {
+        let grass = self.cells.grass.iter().filter(|&&grass| grass).count();
+        vec![
+            self.count(Species::Sheep) as f64,
+            self.count(Species::Wolf) as f64,
+            grass as f64 / self.coord.size() as f64,
+        ]
+    }
+
+    fn step(&mut self, _time_step: usize, rng: &mut RandomStreams) {
+        // Dynamics: Animals move to a neighboring cell, spending energy
+        let keys: Vec<AgentKey> = self.species.keys().collect();
+        for &animal in &keys {
+            let cell = self.neighbor(self.animals.cell[animal], rng.get(Process::Movement));
+            self.animals.set_cell(animal, cell);
+            self.animals.energy[animal] -= 1.0;
+        }
+        // Dynamics: Sheep eat the grass of their cell
+        for &animal in &keys {
+            let cell = self.animals.cell[animal];
+            if self.species[animal] == Species::Sheep && self.cells.grass[cell] {
+                self.cells.set_grass(cell, false);
+                self.cells.set_regrowth(cell, self.grass_regrowth);
+                self.animals.energy[animal] += Species::Sheep.parameters().gain;
+            }
+        }
+        // Dynamics: Wolves eat a sheep of their cell, if any
+        let mut sheep_by_cell = vec![Vec::new(); self.coord.size()];
+        for &animal in &keys {
+            if self.species[animal] == Species::Sheep && self.animals.energy[animal] >= 0.0 {
+                sheep_by_cell[self.animals.cell[animal]].push(animal);
+            }
+        }
+        let mut eaten = SecondaryMap::new();
+        for &animal in &keys {
+            if self.species[animal] != Species::Wolf {
+                continue;
+            }
+            let prey = &mut sheep_by_cell[self.animals.cell[animal]];
+            if !prey.is_empty() {
+                let idx = rng.get(Process::Survival).gen_range(0..prey.len());
+                eaten.insert(prey.swap_remove(idx), ());
+                self.animals.energy[animal] += Species::Wolf.parameters().gain;
+            }
+        }
+        // Dynamics: Animals eaten or out of energy die
+        let energy = self.animals.energy.clone();
+        let mut dependents = Dependents::new();
+        dependents.register(&mut self.animals);
+        dependents.retain(&mut self.species, |animal, _species| {
+            energy[animal] >= 0.0 && !eaten.contains_key(animal)
+        });
+        // Dynamics: Animals give birth, sharing their energy with the newborn
+        let parents: Vec<AgentKey> = self
+            .species
+            .iter()
+            .filter(|&(_, species)| {
+                rng.get(Process::Births)
+                    .gen_bool(species.parameters().reproduction_probability)
+            })
+            .map(|(animal, _)| animal)
+            .collect();
+        for parent in parents {
+            let energy = self.animals.energy[parent] / 2.0;
+            self.animals.set_energy(parent, energy);
+            let child = self.species.insert(self.species[parent]);
+            self.animals.set_energy(child, energy);
+            self.animals.set_cell(child, self.animals.cell[parent]);
+        }
+        // Dynamics: Grass grows back
+        for (grass, regrowth) in self.cells.grass.iter_mut().zip(&mut self.cells.regrowth) {
+            if !*grass {
+                *regrowth = regrowth.saturating_sub(1);
+                *grass = *regrowth == 0;
+            }
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.species.is_empty()
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+    let mut rng = RandomStreams::new(args.seed.into(), 0xa02bdbf7bb3c0a7ac28fa16a64abf96, true);
+    let mut model = PredatorPrey::new(&args, &mut rng);
+    let columns = model::columns(&model);
+    let mut output = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    tables::write_csv_header(&mut output, &columns)?;
+    let time_steps = model::simulate(
+        &mut model,
+        args.time_steps,
+        1,
+        &mut rng,
+        |time_step, model| {
+            let mut row = vec![Value::UInt32(time_step as u32)];
+            row.extend(model.measure().into_iter().map(Value::Float64));
+            tables::write_csv_row(&mut output, &row)
+        },
+    )?;
+    output.flush()?;
+    eprintln!(
+        "Simulated {} time steps; the populations are in {}.",
+        time_steps,
+        args.output.display()
+    );
+    Ok(())
+}
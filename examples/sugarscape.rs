@@ -0,0 +1,294 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Sugarscape, after Epstein and Axtell (1996): agents harvest sugar from the landscape and accumulate wealth.
+//!
+//! Sugar grows back on the cells up to their capacity, which peaks on two hills. Agents, in random order, look as far
+//! as their vision along the four axes, move to the free cell with the most sugar, and harvest it; then they burn
+//! their metabolism. Agents that run out of sugar die and, with replacement, a newcomer takes their place. Wealth
+//! soon concentrates in the agents with good vision, low metabolism, and a lucky start, and the Gini coefficient of
+//! wealth measures the inequality. Run it with `cargo run --release --example sugarscape`; the metrics go to
+//! sugarscape.csv and histograms of wealth to sugarscape_wealth.csv.
+
+use clap::Parser;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rust_agent_based_models::define_components;
+use rust_agent_based_models::deletion::Dependents;
+use rust_agent_based_models::model::{self, Model};
+use rust_agent_based_models::stats::gini;
+use rust_agent_based_models::storage::Storage;
+use rust_agent_based_models::streams::{Process, RandomStreams, Stream};
+use rust_agent_based_models::tables::{self, Column, ColumnType, Value};
+use rust_agent_based_models::{AgentKey, Agents};
+use std::io::Write;
+use std::path::PathBuf;
+use wrapping_coords2d::WrappingCoords2d;
+
+/// Simulate agents harvesting sugar on the landscape
+#[derive(Parser)]
+struct Args {
+    /// Agents at the start
+    #[arg(long, default_value_t = 250)]
+    agents: usize,
+    /// Let agents die without newcomers to replace them; by default, the population stays constant
+    #[arg(long)]
+    no_replacement: bool,
+    /// Sugar that grows back on each cell at every time step, up to its capacity
+    #[arg(long, default_value_t = 1)]
+    regrowth: u32,
+    /// Time steps to simulate
+    #[arg(long, default_value_t = 500)]
+    time_steps: usize,
+    /// Seed of the random number generators
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Time series of the metrics
+    #[arg(long, default_value = "sugarscape.csv")]
+    output: PathBuf,
+    /// Histograms of wealth, once every --histogram-every time steps
+    #[arg(long, default_value = "sugarscape_wealth.csv")]
+    histograms: PathBuf,
+    #[arg(long, default_value_t = 50)]
+    histogram_every: usize,
+    /// Width of the bins of the histograms of wealth
+    #[arg(long, default_value_t = 10.0)]
+    bin_width: f64,
+}
+
+define_components! {
+    /// Components of agents beyond their cell: the sugar they hold, the sugar they burn at every time step, and how
+    /// far they see
+    struct AgentComponents for AgentKey {
+        wealth: f64,
+        metabolism: u32,
+        vision: u32,
+    }
+}
+
+define_components! {
+    /// Components of cells: the sugar on them, the most sugar they hold, and whether an agent is there
+    struct CellComponents for cells {
+        sugar: u32,
+        capacity: u32,
+        occupied: bool,
+    }
+}
+
+struct Sugarscape {
+    coord: WrappingCoords2d,
+    regrowth: u32,
+    replacement: bool,
+    // Model state: Cell of each agent, and the other components of agents
+    cell: Agents<usize>,
+    agents: AgentComponents,
+    // Model state: Components of cells
+    cells: CellComponents,
+}
+
+impl Sugarscape {
+    fn new(args: &Args, rng: &mut RandomStreams) -> Self {
+        // Model parameter: Size of the landscape, and the centers of the two hills of sugar
+        let coord = WrappingCoords2d::new(50, 50).unwrap();
+        let hills = [(15.0, 35.0), (35.0, 15.0)];
+        // Model initialization: The capacity of each cell falls with the distance to the nearest hill; cells start full
+        let mut cells = CellComponents::with_size(coord.size());
+        let width = coord.width() as usize;
+        for idx in 0..coord.size() {
+            let (x, y) = ((idx % width) as f64, (idx / width) as f64);
+            let distance = hills
+                .iter()
+                .map(|&(hx, hy): &(f64, f64)| (x - hx).hypot(y - hy))
+                .fold(f64::INFINITY, f64::min);
+            let capacity = (4.0 - (distance / 5.0).floor()).max(0.0) as u32;
+            cells.set_capacity(idx, capacity);
+            cells.set_sugar(idx, capacity);
+        }
+        let mut model = Self {
+            coord,
+            regrowth: args.regrowth,
+            replacement: !args.no_replacement,
+            cell: Agents::with_capacity(args.agents),
+            agents: AgentComponents::default(),
+            cells,
+        };
+        for _ in 0..args.agents.min(model.coord.size()) {
+            model.add_agent(rng.get(Process::Births));
+        }
+        model
+    }
+
+    /// Places an agent on a random free cell, with random endowment, metabolism, and vision.
+    fn add_agent(&mut self, rng: &mut Stream) {
+        let idx = loop {
+            let idx = rng.gen_range(0..self.coord.size());
+            if !self.cells.occupied[idx] {
+                break idx;
+            }
+        };
+        self.cells.set_occupied(idx, true);
+        let agent = self.cell.insert(idx);
+        self.agents.set_wealth(agent, rng.gen_range(5..=25) as f64);
+        self.agents.set_metabolism(agent, rng.gen_range(1..=4));
+        self.agents.set_vision(agent, rng.gen_range(1..=6));
+    }
+
+    /// The free cell with the most sugar within the vision of an agent along the four axes, the nearest among equals;
+    /// the agent stays if no cell is better than its own.
+    fn best_cell(&self, idx: usize, vision: u32) -> usize {
+        let width = self.coord.width() as usize;
+        let (x, y) = ((idx % width) as i32, (idx / width) as i32);
+        let mut best = (self.cells.sugar[idx], 0, idx);
+        for distance in 1..=vision as i32 {
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let other = self.coord.index(x + dx * distance, y + dy * distance);
+                let sugar = self.cells.sugar[other];
+                if !self.cells.occupied[other] && sugar > best.0 {
+                    best = (sugar, distance, other);
+                }
+            }
+        }
+        best.2
+    }
+}
+
+impl Model for Sugarscape {
+    fn metrics(&self) -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("agents", "Number of agents"),
+            ("wealth", "Mean wealth of agents"),
+            ("gini", "Gini coefficient of wealth"),
+            ("sugar", "Sugar on the landscape"),
+        ]
+    }
+
+    fn measure(&self) -> Vec<f64> {
+        let n = self.cell.len();
+        let total: f64 = self.agents.wealth.values().sum();
+        vec![
+            n as f64,
+            total / n.max(1) as f64,
+            gini(self.agents.wealth.values().copied()).unwrap_or_default(),
+            self.cells.sugar.iter().map(|&sugar| sugar as f64).sum(),
+        ]
+    }
+
+    fn step(&mut self, _time_step: usize, rng: &mut RandomStreams) {
+        // Dynamics: Agents move in random order, each to the best cell it sees, harvest it, and burn their metabolism
+        let mut keys: Vec<AgentKey> = self.cell.keys().collect();
+        keys.shuffle(rng.get(Process::Movement));
+        for &agent in &keys {
+            let idx = self.cell[agent];
+            let best = self.best_cell(idx, self.agents.vision[agent]);
+            self.cells.set_occupied(idx, false);
+            self.cells.set_occupied(best, true);
+            self.cell[agent] = best;
+            let harvest = std::mem::take(&mut self.cells.sugar[best]);
+            self.agents.wealth[agent] += harvest as f64 - self.agents.metabolism[agent] as f64;
+        }
+        // Dynamics: Agents out of sugar die, and newcomers replace them
+        let wealth = self.agents.wealth.clone();
+        let cells = &mut self.cells;
+        let mut dependents = Dependents::new();
+        dependents.register(&mut self.agents);
+        let mut deaths = 0;
+        dependents.retain(&mut self.cell, |agent, &mut idx| {
+            let survives = wealth[agent] >= 0.0;
+            if !survives {
+                cells.set_occupied(idx, false);
+                deaths += 1;
+            }
+            survives
+        });
+        if self.replacement {
+            for _ in 0..deaths {
+                self.add_agent(rng.get(Process::Births));
+            }
+        }
+        // Dynamics: Sugar grows back
+        for (sugar, &capacity) in self.cells.sugar.iter_mut().zip(&self.cells.capacity) {
+            *sugar = (*sugar + self.regrowth).min(capacity);
+        }
+    }
+
+    fn finished(&self) -> bool {
+        self.cell.is_empty()
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+    let mut rng = RandomStreams::new(args.seed.into(), 0xa02bdbf7bb3c0a7ac28fa16a64abf96, true);
+    let mut model = Sugarscape::new(&args, &mut rng);
+    let columns = model::columns(&model);
+    let mut output = std::io::BufWriter::new(std::fs::File::create(&args.output)?);
+    tables::write_csv_header(&mut output, &columns)?;
+    let mut histograms = std::io::BufWriter::new(std::fs::File::create(&args.histograms)?);
+    tables::write_csv_header(
+        &mut histograms,
+        &[
+            Column::new("time_step", "Time step", ColumnType::UInt32),
+            Column::new("wealth_from", "Wealth from", ColumnType::Float64),
+            Column::new("wealth_to", "Wealth to", ColumnType::Float64),
+            Column::new("agents", "Agents", ColumnType::UInt32),
+        ],
+    )?;
+    let time_steps = model::simulate(
+        &mut model,
+        args.time_steps,
+        1,
+        &mut rng,
+        |time_step, model| -> std::io::Result<()> {
+            let mut row = vec![Value::UInt32(time_step as u32)];
+            row.extend(model.measure().into_iter().map(Value::Float64));
+            tables::write_csv_row(&mut output, &row)?;
+            // Model measurements: Histogram of wealth, in bins of equal width from 0
+            if time_step.is_multiple_of(args.histogram_every.max(1)) {
+                let mut bins: Vec<u32> = Vec::new();
+                for &wealth in model.agents.wealth.values() {
+                    let bin = (wealth / args.bin_width) as usize;
+                    if bins.len() <= bin {
+                        bins.resize(bin + 1, 0);
+                    }
+                    bins[bin] += 1;
+                }
+                for (bin, &agents) in bins.iter().enumerate() {
+                    tables::write_csv_row(
+                        &mut histograms,
+                        &[
+                            Value::UInt32(time_step as u32),
+                            Value::Float64(bin as f64 * args.bin_width),
+                            Value::Float64((bin + 1) as f64 * args.bin_width),
+                            Value::UInt32(agents),
+                        ],
+                    )?;
+                }
+            }
+            Ok(())
+        },
+    )?;
+    output.flush()?;
+    histograms.flush()?;
+    eprintln!(
+        "Simulated {} time steps; the metrics are in {}, and the histograms of wealth in {}.",
+        time_steps,
+        args.output.display(),
+        args.histograms.display()
+    );
+    Ok(())
+}
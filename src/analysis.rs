@@ -0,0 +1,210 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Analysis of the time series of a metric: autocorrelation, the dominant period of its oscillations, and whether it
+//! stays stationary, for endemic and oscillatory regimes.
+//!
+//! Series are sampled at equal intervals; lags and periods count samples, which callers turn into time steps.
+
+/// Summary of the time series of a metric, such as the value of each replicate that comparisons of runs take
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistic {
+    /// Value at the last sampled time step
+    Final,
+    /// Largest value
+    Peak,
+    Mean,
+    /// Sum of the sampled values
+    Total,
+}
+
+impl Statistic {
+    /// Statistic by name: final, peak, mean, or total
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "final" => Ok(Statistic::Final),
+            "peak" => Ok(Statistic::Peak),
+            "mean" => Ok(Statistic::Mean),
+            "total" => Ok(Statistic::Total),
+            _ => Err(format!(
+                "{} is not a statistic; statistics are final, peak, mean, and total",
+                name
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Statistic::Final => "final",
+            Statistic::Peak => "peak",
+            Statistic::Mean => "mean",
+            Statistic::Total => "total",
+        }
+    }
+
+    /// Value of the statistic for a time series; NaN for empty series
+    pub fn evaluate(self, series: &[f64]) -> f64 {
+        if series.is_empty() {
+            return f64::NAN;
+        }
+        match self {
+            Statistic::Final => series[series.len() - 1],
+            Statistic::Peak => series.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Statistic::Mean => series.iter().sum::<f64>() / series.len() as f64,
+            Statistic::Total => series.iter().sum(),
+        }
+    }
+}
+
+/// Mean and biased sample variance
+fn mean_variance(series: &[f64]) -> (f64, f64) {
+    let n = series.len() as f64;
+    let mean = series.iter().sum::<f64>() / n;
+    let variance = series.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance)
+}
+
+/// Autocorrelation of the series at lags 0 to `max_lag`, or up to the length of the series; empty for constant
+/// series, whose autocorrelation is undefined.
+pub fn autocorrelation(series: &[f64], max_lag: usize) -> Vec<f64> {
+    if series.is_empty() {
+        return Vec::new();
+    }
+    let (mean, variance) = mean_variance(series);
+    if variance == 0.0 {
+        return Vec::new();
+    }
+    let n = series.len() as f64;
+    (0..=max_lag.min(series.len() - 1))
+        .map(|lag| {
+            series
+                .iter()
+                .zip(&series[lag..])
+                .map(|(x, y)| (x - mean) * (y - mean))
+                .sum::<f64>()
+                / n
+                / variance
+        })
+        .collect()
+}
+
+/// In-place radix-2 fast Fourier transform of complex values, as pairs of real and imaginary parts; the length must be
+/// a power of two.
+fn fft(values: &mut [(f64, f64)]) {
+    let n = values.len();
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f64::consts::PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (re, im) = values[start + k + len / 2];
+                let odd = (re * cos - im * sin, re * sin + im * cos);
+                let even = values[start + k];
+                values[start + k] = (even.0 + odd.0, even.1 + odd.1);
+                values[start + k + len / 2] = (even.0 - odd.0, even.1 - odd.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Period, in samples, of the strongest oscillation of the series: the peak of its periodogram, without the mean.
+/// None for constant series and for series too short to oscillate.
+pub fn dominant_period(series: &[f64]) -> Option<f64> {
+    if series.len() < 4 {
+        return None;
+    }
+    let (mean, variance) = mean_variance(series);
+    if variance == 0.0 {
+        return None;
+    }
+    // Zero padding to a power of two interpolates the periodogram
+    let len = series.len().next_power_of_two();
+    let mut values: Vec<(f64, f64)> = series.iter().map(|x| (x - mean, 0.0)).collect();
+    values.resize(len, (0.0, 0.0));
+    fft(&mut values);
+    let (peak, _) = values[1..=len / 2]
+        .iter()
+        .map(|(re, im)| re * re + im * im)
+        .enumerate()
+        .fold((0, f64::MIN), |best, (k, power)| {
+            if power > best.1 {
+                (k + 1, power)
+            } else {
+                best
+            }
+        });
+    Some(len as f64 / peak as f64)
+}
+
+/// Summary of how much a series drifts: a stationary series has no trend, no shift of its mean, and a variance ratio
+/// near 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stationarity {
+    /// Slope of the least-squares line through the series, per sample
+    pub trend: f64,
+    /// Mean of the second half of the series minus that of the first half, in standard deviations of the series
+    pub mean_shift: f64,
+    /// Variance of the second half of the series over that of the first half; infinite, or NaN, when the first half is
+    /// constant
+    pub variance_ratio: f64,
+}
+
+/// Stationarity of the series; None for series shorter than four samples.
+pub fn stationarity(series: &[f64]) -> Option<Stationarity> {
+    if series.len() < 4 {
+        return None;
+    }
+    let n = series.len() as f64;
+    let (mean, variance) = mean_variance(series);
+    let time_mean = (n - 1.0) / 2.0;
+    let (covariance, time_variance) =
+        series
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(covariance, time_variance), (t, x)| {
+                let dt = t as f64 - time_mean;
+                (covariance + dt * (x - mean), time_variance + dt * dt)
+            });
+    let (first, second) = series.split_at(series.len() / 2);
+    let ((first_mean, first_variance), (second_mean, second_variance)) =
+        (mean_variance(first), mean_variance(second));
+    Some(Stationarity {
+        trend: covariance / time_variance,
+        mean_shift: if variance > 0.0 {
+            (second_mean - first_mean) / variance.sqrt()
+        } else {
+            0.0
+        },
+        variance_ratio: second_variance / first_variance,
+    })
+}
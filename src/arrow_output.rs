@@ -0,0 +1,205 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Columnar outputs through Apache Arrow, for tables too large for CSV files.
+//!
+//! `ArrowTable` collects rows in Arrow builders and hands a record batch to the file every `BATCH_ROWS` rows,
+//! so memory use stays bounded however long the table grows. The schema comes from the columns of the table.
+
+use crate::tables::{Column, ColumnType, Value};
+use arrow_array::builder::{
+    ArrayBuilder, Float64Builder, StringBuilder, UInt32Builder, UInt64Builder,
+};
+use arrow_array::RecordBatch;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use std::fs::File;
+use std::sync::Arc;
+
+/// Rows per record batch
+const BATCH_ROWS: usize = 1 << 16;
+
+/// File format of a table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    #[cfg(feature = "parquet-output")]
+    Parquet,
+    /// Arrow IPC file format, also known as Feather version 2
+    #[cfg(feature = "arrow-ipc-output")]
+    Ipc,
+}
+
+/// Formats enabled in Cargo.toml; each table is written once per format
+pub const FORMATS: &[Format] = &[
+    #[cfg(feature = "parquet-output")]
+    Format::Parquet,
+    #[cfg(feature = "arrow-ipc-output")]
+    Format::Ipc,
+];
+
+impl Format {
+    /// File name extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "parquet-output")]
+            Format::Parquet => "parquet",
+            #[cfg(feature = "arrow-ipc-output")]
+            Format::Ipc => "arrow",
+        }
+    }
+}
+
+enum Writer {
+    #[cfg(feature = "parquet-output")]
+    Parquet(parquet::arrow::ArrowWriter<File>),
+    #[cfg(feature = "arrow-ipc-output")]
+    Ipc(arrow_ipc::writer::FileWriter<File>),
+}
+
+/// Table written to a file in one of the Arrow formats
+pub struct ArrowTable {
+    schema: SchemaRef,
+    builders: Vec<Box<dyn ArrayBuilder>>,
+    writer: Writer,
+}
+
+fn data_type(ty: ColumnType) -> DataType {
+    match ty {
+        ColumnType::Float64 => DataType::Float64,
+        ColumnType::UInt32 => DataType::UInt32,
+        ColumnType::UInt64 => DataType::UInt64,
+        ColumnType::Utf8 => DataType::Utf8,
+    }
+}
+
+fn builder(ty: ColumnType) -> Box<dyn ArrayBuilder> {
+    match ty {
+        ColumnType::Float64 => Box::new(Float64Builder::with_capacity(BATCH_ROWS)),
+        ColumnType::UInt32 => Box::new(UInt32Builder::with_capacity(BATCH_ROWS)),
+        ColumnType::UInt64 => Box::new(UInt64Builder::with_capacity(BATCH_ROWS)),
+        ColumnType::Utf8 => Box::new(StringBuilder::new()),
+    }
+}
+
+impl ArrowTable {
+    pub fn create(file: File, columns: &[Column], format: Format) -> Result<Self, ArrowError> {
+        // Numbers may be missing, as empty cells are in CSV files
+        let schema: SchemaRef = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|c| Field::new(&c.name, data_type(c.ty), c.ty != ColumnType::Utf8))
+                .collect::<Vec<_>>(),
+        ));
+        let writer = match format {
+            #[cfg(feature = "parquet-output")]
+            Format::Parquet => {
+                let props = parquet::file::properties::WriterProperties::builder()
+                    .set_compression(parquet::basic::Compression::SNAPPY)
+                    .build();
+                Writer::Parquet(
+                    parquet::arrow::ArrowWriter::try_new(file, schema.clone(), Some(props))
+                        .map_err(|e| ArrowError::ExternalError(Box::new(e)))?,
+                )
+            }
+            #[cfg(feature = "arrow-ipc-output")]
+            Format::Ipc => Writer::Ipc(arrow_ipc::writer::FileWriter::try_new(file, &schema)?),
+        };
+        Ok(Self {
+            builders: columns.iter().map(|c| builder(c.ty)).collect(),
+            schema,
+            writer,
+        })
+    }
+
+    /// Appends a row; the values must follow the types of the columns, and an empty text leaves a number missing.
+    pub fn push(&mut self, row: &[Value]) -> Result<(), ArrowError> {
+        fn append_null(any: &mut dyn std::any::Any) -> Option<()> {
+            if any.is::<Float64Builder>() {
+                return any
+                    .downcast_mut::<Float64Builder>()
+                    .map(|b| b.append_null());
+            }
+            if any.is::<UInt32Builder>() {
+                return any.downcast_mut::<UInt32Builder>().map(|b| b.append_null());
+            }
+            any.downcast_mut::<UInt64Builder>().map(|b| b.append_null())
+        }
+        assert_eq!(
+            row.len(),
+            self.builders.len(),
+            "The row does not match the table"
+        );
+        for (builder, value) in self.builders.iter_mut().zip(row.iter()) {
+            let any = builder.as_any_mut();
+            let appended = match *value {
+                Value::Utf8("") if !any.is::<StringBuilder>() => append_null(any),
+                Value::Float64(v) => any
+                    .downcast_mut::<Float64Builder>()
+                    .map(|b| b.append_value(v)),
+                Value::UInt32(v) => any
+                    .downcast_mut::<UInt32Builder>()
+                    .map(|b| b.append_value(v)),
+                Value::UInt64(v) => any
+                    .downcast_mut::<UInt64Builder>()
+                    .map(|b| b.append_value(v)),
+                Value::Utf8(v) => any
+                    .downcast_mut::<StringBuilder>()
+                    .map(|b| b.append_value(v)),
+            };
+            assert!(
+                appended.is_some(),
+                "The type of {:?} does not match its column",
+                value
+            );
+        }
+        if self.builders[0].len() >= BATCH_ROWS {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the rows collected so far as a record batch.
+    fn flush(&mut self) -> Result<(), ArrowError> {
+        if self.builders.first().is_none_or(|b| b.is_empty()) {
+            return Ok(());
+        }
+        let arrays = self.builders.iter_mut().map(|b| b.finish()).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        match &mut self.writer {
+            #[cfg(feature = "parquet-output")]
+            Writer::Parquet(w) => w
+                .write(&batch)
+                .map_err(|e| ArrowError::ExternalError(Box::new(e))),
+            #[cfg(feature = "arrow-ipc-output")]
+            Writer::Ipc(w) => w.write(&batch),
+        }
+    }
+
+    /// Writes the remaining rows and closes the file.
+    pub fn finish(mut self) -> Result<(), ArrowError> {
+        self.flush()?;
+        match self.writer {
+            #[cfg(feature = "parquet-output")]
+            Writer::Parquet(w) => w
+                .close()
+                .map(|_| ())
+                .map_err(|e| ArrowError::ExternalError(Box::new(e))),
+            #[cfg(feature = "arrow-ipc-output")]
+            Writer::Ipc(mut w) => w.finish(),
+        }
+    }
+}
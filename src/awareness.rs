@@ -0,0 +1,101 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Awareness of the disease: a second contagion, such as news or rumors, that spreads over the social network and
+//! protects the agents that it reaches.
+//!
+//! Awareness follows the coupled contagion of Granell, Gómez, and Arenas (2013): at each time step, each aware agent
+//! makes each unaware friend aware with probability `spreading_probability`, and forgets with probability
+//! `forgetting_probability`; infected agents always become aware. The probability of infection of aware agents shrinks
+//! by the fraction `protection`, so awareness can hold an epidemic back, and the epidemic, in turn, feeds awareness.
+
+use crate::define_components;
+use crate::storage::Storage;
+use crate::{AgentKey, Health, LinkKey};
+use rand::Rng;
+
+define_components! {
+    /// Whether each agent is aware of the disease; agents without a value, such as newborns, are not.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AgentAwareness for AgentKey {
+        aware: bool,
+    }
+}
+
+impl AgentAwareness {
+    pub fn is_aware(&self, agent: AgentKey) -> bool {
+        self.aware(agent).copied().unwrap_or(false)
+    }
+}
+
+/// Parameters of awareness
+pub struct Awareness {
+    /// Probability that an aware agent makes an unaware friend aware at each time step
+    pub spreading_probability: f64,
+    /// Probability that an aware agent forgets at each time step
+    pub forgetting_probability: f64,
+    /// Fraction by which awareness reduces the probability of infection of an agent
+    pub protection: f64,
+    /// Probability that each agent of the initial population is aware
+    pub initial_probability: f64,
+}
+
+impl Awareness {
+    /// Factor of the probability of infection of an agent: 1 unless the agent is aware
+    pub fn susceptibility(&self, aware: &AgentAwareness, agent: AgentKey) -> f64 {
+        if aware.is_aware(agent) {
+            1.0 - self.protection
+        } else {
+            1.0
+        }
+    }
+
+    /// Writes the awareness of the next time step, which starts as a copy of the current one: infected agents become
+    /// aware, aware agents forget, and awareness spreads over the links.
+    pub fn spread<
+        L: Storage<LinkKey, (AgentKey, AgentKey)>,
+        A: Storage<AgentKey, Health>,
+        R: Rng,
+    >(
+        &self,
+        links: &L,
+        health: &A,
+        aware: &AgentAwareness,
+        next_aware: &mut AgentAwareness,
+        rng: &mut R,
+    ) {
+        for (k, &h) in health.iter() {
+            if h == Health::I {
+                next_aware.set_aware(k, true);
+            } else if aware.is_aware(k) && rng.gen_bool(self.forgetting_probability) {
+                next_aware.set_aware(k, false);
+            }
+        }
+        links.iter().for_each(|(_link_key, &(key0, key1))| {
+            match (aware.is_aware(key0), aware.is_aware(key1)) {
+                (true, false) if rng.gen_bool(self.spreading_probability) => {
+                    next_aware.set_aware(key1, true)
+                }
+                (false, true) if rng.gen_bool(self.spreading_probability) => {
+                    next_aware.set_aware(key0, true)
+                }
+                _ => {}
+            }
+        });
+    }
+}
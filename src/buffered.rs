@@ -0,0 +1,62 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Parallel updating: every entity reads the state at the beginning of the time step and writes the state of the next one.
+//!
+//! `Buffered` keeps both states and only lends the current one for reading, so model code cannot read a value
+//! that was written earlier in the same time step. `swap()` ends the time step.
+
+/// Current and next state of a layer of the model, such as agent health or cell health
+#[derive(Clone)]
+pub struct Buffered<T> {
+    current: T,
+    next: T,
+}
+
+impl<T: Clone> Buffered<T> {
+    /// Both the current and the next state start as `state`.
+    pub fn new(state: T) -> Self {
+        Self {
+            next: state.clone(),
+            current: state,
+        }
+    }
+
+    /// Makes the next state current. The next state starts over as a copy of it, so entities that do not change
+    /// keep their state.
+    pub fn swap(&mut self) {
+        self.current.clone_from(&self.next);
+    }
+}
+
+impl<T> Buffered<T> {
+    /// State at the beginning of the time step
+    pub fn read(&self) -> &T {
+        &self.current
+    }
+
+    /// State at the end of the time step
+    pub fn write(&mut self) -> &mut T {
+        &mut self.next
+    }
+
+    /// Current state for reading and next state for writing, at the same time
+    pub fn read_write(&mut self) -> (&T, &mut T) {
+        (&self.current, &mut self.next)
+    }
+}
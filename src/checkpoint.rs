@@ -0,0 +1,237 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Checkpoints: the state of a scenario saved to disk, so long runs survive crashes and reboots.
+//!
+//! A checkpoint is a JSON file. `save` writes it next to its final location and then renames it,
+//! so a crash while saving leaves the previous checkpoint intact.
+//!
+//! A resumed scenario must continue exactly as the original run would have. `slotmap::SlotMap` serializes its values,
+//! but deserializing rebuilds the list of free slots in a different order, so agents born after the checkpoint would take
+//! different keys and iterate in a different order. Use `#[serde(with = "checkpoint::slot_map")]` to keep that order.
+//!
+//! `Snapshots` keeps the states of the last few time steps, in the same format, to investigate odd dynamics after a run.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces the checkpoint at `path` with `state`.
+pub fn save<T: Serialize>(path: &Path, state: &T) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut w = BufWriter::with_capacity(1 << 16, fs::File::create(&tmp_path)?);
+        serde_json::to_writer(&mut w, state)?;
+        w.flush()?;
+        w.get_ref().sync_all()?;
+    }
+    fs::rename(&tmp_path, path)
+}
+
+/// Reads the checkpoint at `path`.
+pub fn load<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let r = BufReader::with_capacity(1 << 16, fs::File::open(path)?);
+    Ok(serde_json::from_reader(r)?)
+}
+
+/// Serializes a `SlotMap` together with the order of its free slots; see the module documentation.
+pub mod slot_map {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use slotmap::{Key, KeyData, SlotMap};
+
+    #[derive(Serialize, Deserialize)]
+    struct State<V> {
+        /// Key of each value, as returned by `KeyData::as_ffi`
+        values: Vec<(u64, V)>,
+        /// Index and version of each free slot, in the order the next insertions will reuse them
+        free: Vec<u64>,
+    }
+
+    fn idx(ffi: u64) -> usize {
+        (ffi & 0xffff_ffff) as usize
+    }
+
+    fn version(ffi: u64) -> u32 {
+        (ffi >> 32) as u32
+    }
+
+    pub fn serialize<K: Key, V: Clone + Default + Serialize, S: Serializer>(
+        map: &SlotMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        // Insertions reveal the free slots in order; a new slot has version 1.
+        let mut probe = map.clone();
+        let mut free = Vec::new();
+        loop {
+            let ffi = probe.insert(V::default()).data().as_ffi();
+            if version(ffi) == 1 {
+                break;
+            }
+            free.push(ffi - (1 << 32));
+        }
+        State {
+            values: map
+                .iter()
+                .map(|(k, v)| (k.data().as_ffi(), v.clone()))
+                .collect(),
+            free,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K: Key, V: Default + Deserialize<'de>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SlotMap<K, V>, D::Error> {
+        let state = State::<V>::deserialize(deserializer)?;
+        let slots_len = state
+            .values
+            .iter()
+            .map(|&(ffi, _)| ffi)
+            .chain(state.free.iter().copied())
+            .map(idx)
+            .max()
+            .unwrap_or(0);
+        let mut map = SlotMap::with_capacity_and_key(slots_len);
+        // Slots start occupied with version 1; removing a slot and inserting again reuses it and adds 2 to its version.
+        let mut keys: Vec<K> = (0..slots_len).map(|_| map.insert(V::default())).collect();
+        let mut raise = |map: &mut SlotMap<K, V>, ffi: u64| -> Result<K, D::Error> {
+            let key = keys
+                .get_mut(idx(ffi).wrapping_sub(1))
+                .ok_or_else(|| serde::de::Error::custom("invalid slot index"))?;
+            while version(key.data().as_ffi()) + 1 < version(ffi) {
+                map.remove(*key);
+                *key = map.insert(V::default());
+            }
+            Ok(*key)
+        };
+        for (ffi, value) in state.values {
+            let key = raise(&mut map, ffi)?;
+            if key.data() != KeyData::from_ffi(ffi) {
+                return Err(serde::de::Error::custom("invalid slot version"));
+            }
+            map[key] = value;
+        }
+        let free_keys = state
+            .free
+            .iter()
+            .map(|&ffi| raise(&mut map, ffi))
+            .collect::<Result<Vec<K>, D::Error>>()?;
+        // The last slot removed is the first one reused.
+        free_keys.iter().rev().for_each(|&key| {
+            map.remove(key);
+        });
+        Ok(map)
+    }
+}
+
+/// Serialized states of the last few time steps of a scenario, kept in memory for debugging.
+/// They reach the disk at the end of the scenario, or when a panic unwinds past them.
+pub struct Snapshots {
+    dir: PathBuf,
+    capacity: usize,
+    ring: VecDeque<(usize, Vec<u8>)>,
+}
+
+impl Snapshots {
+    /// Snapshots go to `dir`, which this creates when writing.
+    pub fn new(dir: PathBuf, capacity: usize) -> Self {
+        Self {
+            dir,
+            capacity,
+            ring: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Keeps the state at the beginning of `time_step`, forgetting the oldest state if the ring is full.
+    pub fn push<T: Serialize>(&mut self, time_step: usize, state: &T) -> io::Result<()> {
+        if self.ring.len() == self.capacity {
+            self.ring.pop_front();
+        }
+        self.ring.push_back((time_step, serde_json::to_vec(state)?));
+        Ok(())
+    }
+
+    /// Writes every state kept to `<time step>.json`, in the format of checkpoints, and empties the ring.
+    pub fn write(&mut self) -> io::Result<()> {
+        if self.ring.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        while let Some((time_step, state)) = self.ring.pop_front() {
+            fs::write(self.dir.join(format!("{}.json", time_step)), state)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Snapshots {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            let _ = self.write();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use slotmap::{DefaultKey, SlotMap};
+
+    #[derive(Serialize, Deserialize)]
+    struct World {
+        #[serde(with = "slot_map")]
+        agents: SlotMap<DefaultKey, u32>,
+    }
+
+    /// Keys and values in the order of iteration
+    fn contents(map: &SlotMap<DefaultKey, u32>) -> Vec<(DefaultKey, u32)> {
+        map.iter().map(|(key, &value)| (key, value)).collect()
+    }
+
+    #[test]
+    fn resumed_slot_maps_reuse_free_slots_in_the_same_order() {
+        let mut world = World {
+            agents: SlotMap::new(),
+        };
+        let keys: Vec<DefaultKey> = (0..6).map(|value| world.agents.insert(value)).collect();
+        for &k in &[keys[3], keys[0], keys[4]] {
+            world.agents.remove(k);
+        }
+        world.agents.insert(10);
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        save(&path, &world).unwrap();
+        let mut resumed: World = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(contents(&resumed.agents), contents(&world.agents));
+        // Agents born after the checkpoint take the same keys
+        for value in 20..24 {
+            assert_eq!(resumed.agents.insert(value), world.agents.insert(value));
+        }
+        assert_eq!(contents(&resumed.agents), contents(&world.agents));
+    }
+
+    #[test]
+    fn slot_maps_refuse_invalid_keys() {
+        let json = r#"{"agents": {"values": [[0, 1]], "free": []}}"#;
+        assert!(serde_json::from_str::<World>(json).is_err());
+    }
+}
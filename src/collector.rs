@@ -0,0 +1,293 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Data collection: named reporters measure the model every few time steps.
+//!
+//! A reporter is a closure over the `World`, the read-only view of a scenario at the start of a time step.
+//! Model-level reporters compute one value from the whole world, such as the number of infected cells;
+//! agent-level reporters compute a value for each agent, or skip the agent, and an `Aggregate` turns those values into one.
+//! The reporters, in the order they were registered, become the columns of the time series output file.
+//! A reporter can also appear in the figures, either as a line on a chart or as text above the charts.
+
+#[cfg(feature = "net")]
+use crate::awareness::AgentAwareness;
+use crate::demography::AgentDemography;
+#[cfg(feature = "landscape")]
+use crate::energy::{AgentEnergy, CellResources};
+use crate::events::EventBus;
+use crate::groups::Groups;
+use crate::heterogeneity::AgentTraits;
+use crate::immunity::InfectionHistory;
+use crate::interventions::AgentVaccination;
+#[cfg(feature = "landscape")]
+use crate::population::AgentHomes;
+use crate::reproduction::Transmissions;
+use crate::stats::RunningStats;
+use crate::storage::Storage;
+#[cfg(feature = "landscape")]
+use crate::vectors::Vector;
+#[cfg(feature = "net")]
+use crate::Links;
+use crate::{AgentKey, Agents, Health};
+#[cfg(feature = "net")]
+use slotmap::SecondaryMap;
+use std::borrow::Cow;
+
+/// Read-only view of a scenario, for reporters
+pub struct World<'a> {
+    pub time_step: usize,
+    pub health: &'a Agents<Health>,
+    #[cfg(feature = "net")]
+    pub links: &'a Links,
+    /// Network degree of each agent
+    #[cfg(feature = "net")]
+    pub degree: &'a SecondaryMap<AgentKey, i32>,
+    #[cfg(feature = "landscape")]
+    pub cell_health: &'a [Health],
+    /// Parameters of each agent, if they differ between agents
+    pub traits: Option<&'a AgentTraits>,
+    /// Time step of birth of each agent, if the model keeps ages
+    pub ages: Option<&'a AgentDemography>,
+    /// Whether each agent is aware of the disease, if awareness spreads
+    #[cfg(feature = "net")]
+    pub aware: Option<&'a AgentAwareness>,
+    /// Vectors, in vector-borne scenarios
+    #[cfg(feature = "landscape")]
+    pub vectors: Option<&'a [Vector]>,
+    /// Group of each agent and quarantines of the groups, if agents form groups
+    pub groups: Option<&'a Groups>,
+    /// Cell around which each agent visits the landscape, if agents have homes
+    #[cfg(feature = "landscape")]
+    pub homes: Option<&'a AgentHomes>,
+    /// Energy of each agent and resource of each cell, if agents forage
+    #[cfg(feature = "landscape")]
+    pub energy: Option<&'a AgentEnergy>,
+    #[cfg(feature = "landscape")]
+    pub resources: Option<&'a CellResources>,
+    /// Whether each agent is vaccinated, if a vaccination campaign runs
+    pub vaccinated: Option<&'a AgentVaccination>,
+    /// Who infected whom so far, and when
+    pub transmissions: &'a Transmissions,
+    /// Infections of each agent so far, if infections leave immunity
+    pub history: Option<&'a InfectionHistory>,
+    /// Infections since the start of the scenario, seeds included, up to the previous time step
+    pub infections: usize,
+    /// Agents that arrived and left since the start of the scenario, up to the previous time step
+    pub immigrants: usize,
+    pub emigrants: usize,
+    /// Events published so far in this time step
+    pub events: &'a EventBus,
+}
+
+/// Turns the values of agents into one value; the aggregate of no agents is 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Mean,
+    Min,
+    Max,
+    /// Sample variance, for the spread of parameters that differ between agents
+    Variance,
+}
+
+type ModelFn = Box<dyn Fn(&World) -> f64 + Send + Sync>;
+type AgentFn = Box<dyn Fn(&World, AgentKey, Health) -> Option<f64> + Send + Sync>;
+
+enum Kind {
+    Model(ModelFn),
+    Agents(Aggregate, AgentFn),
+}
+
+/// Chart of the figures, holding the lines of some reporters
+#[derive(Clone, Copy, Debug)]
+pub struct Chart {
+    pub caption: &'static str,
+    /// Description of the vertical axis
+    pub y_desc: &'static str,
+}
+
+/// Line of a reporter on a chart
+#[derive(Clone, Copy, Debug)]
+pub struct Line {
+    /// Index of the chart, as returned by `DataCollector::chart`
+    pub chart: usize,
+    /// Index of the color in the palette of the figures
+    pub color: usize,
+}
+
+/// Named measurement of the model
+pub struct Reporter {
+    /// Short name, such as `c_i`
+    pub name: Cow<'static, str>,
+    pub description: Cow<'static, str>,
+    kind: Kind,
+    /// Line in the figures, if any
+    pub line: Option<Line>,
+    /// Whether the figures show the value as text above the charts
+    pub text: bool,
+}
+
+impl Reporter {
+    /// Draws this reporter as a line on a chart of the figures.
+    pub fn line(&mut self, chart: usize, color: usize) -> &mut Self {
+        self.line = Some(Line { chart, color });
+        self
+    }
+
+    /// Shows this reporter as text above the charts of the figures.
+    pub fn text(&mut self) -> &mut Self {
+        self.text = true;
+        self
+    }
+}
+
+/// Reporters and charts shared by all scenarios
+pub struct DataCollector {
+    reporters: Vec<Reporter>,
+    charts: Vec<Chart>,
+    sample_every: usize,
+}
+
+impl Default for DataCollector {
+    fn default() -> Self {
+        Self {
+            reporters: Vec::new(),
+            charts: Vec::new(),
+            sample_every: 1,
+        }
+    }
+}
+
+impl DataCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects data once every `sample_every` time steps, starting at time step 0.
+    pub fn sample_every(&mut self, sample_every: usize) -> &mut Self {
+        assert!(sample_every > 0, "sample_every must be positive");
+        self.sample_every = sample_every;
+        self
+    }
+
+    /// Whether data is collected at this time step
+    pub fn samples(&self, time_step: usize) -> bool {
+        time_step.is_multiple_of(self.sample_every)
+    }
+
+    /// Number of samples in a time series of `time_series_len` time steps
+    pub fn samples_len(&self, time_series_len: usize) -> usize {
+        time_series_len.div_ceil(self.sample_every)
+    }
+
+    /// Adds a chart to the figures and returns its index.
+    pub fn chart(&mut self, caption: &'static str, y_desc: &'static str) -> usize {
+        self.charts.push(Chart { caption, y_desc });
+        self.charts.len() - 1
+    }
+
+    /// Registers a model-level reporter.
+    pub fn model<F: Fn(&World) -> f64 + Send + Sync + 'static>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        f: F,
+    ) -> &mut Reporter {
+        self.push(name, description, Kind::Model(Box::new(f)))
+    }
+
+    /// Registers an agent-level reporter; agents for which `f` returns `None` do not count.
+    pub fn agents<F: Fn(&World, AgentKey, Health) -> Option<f64> + Send + Sync + 'static>(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        aggregate: Aggregate,
+        f: F,
+    ) -> &mut Reporter {
+        self.push(name, description, Kind::Agents(aggregate, Box::new(f)))
+    }
+
+    fn push(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+        kind: Kind,
+    ) -> &mut Reporter {
+        self.reporters.push(Reporter {
+            name: name.into(),
+            description: description.into(),
+            kind,
+            line: None,
+            text: false,
+        });
+        self.reporters.last_mut().unwrap()
+    }
+
+    pub fn reporters(&self) -> &[Reporter] {
+        &self.reporters
+    }
+
+    pub fn charts(&self) -> &[Chart] {
+        &self.charts
+    }
+
+    /// Values of all reporters, in the order they were registered. Agents are visited only once.
+    pub fn collect(&self, world: &World) -> Vec<f64> {
+        // Sum or extreme, and count, of each agent-level reporter, and the spread of those that aggregate variances
+        let mut totals = vec![(0.0, 0usize); self.reporters.len()];
+        let mut spreads = vec![RunningStats::default(); self.reporters.len()];
+        if self
+            .reporters
+            .iter()
+            .any(|r| matches!(r.kind, Kind::Agents(..)))
+        {
+            Storage::iter(world.health).for_each(|(k, &h)| {
+                self.reporters
+                    .iter()
+                    .zip(totals.iter_mut().zip(spreads.iter_mut()))
+                    .for_each(|(reporter, ((total, count), spread))| {
+                        if let Kind::Agents(aggregate, f) = &reporter.kind {
+                            if let Some(value) = f(world, k, h) {
+                                *total = match (aggregate, *count) {
+                                    (Aggregate::Variance, _) => {
+                                        spread.push(value);
+                                        spread.variance()
+                                    }
+                                    (_, 0) => value,
+                                    (Aggregate::Sum, _) | (Aggregate::Mean, _) => *total + value,
+                                    (Aggregate::Min, _) => total.min(value),
+                                    (Aggregate::Max, _) => total.max(value),
+                                };
+                                *count += 1;
+                            }
+                        }
+                    });
+            });
+        }
+        self.reporters
+            .iter()
+            .zip(totals)
+            .map(|(reporter, (total, count))| match &reporter.kind {
+                Kind::Model(f) => f(world),
+                Kind::Agents(_, _) if count == 0 => 0.0,
+                Kind::Agents(Aggregate::Mean, _) => total / count as f64,
+                Kind::Agents(_, _) => total,
+            })
+            .collect()
+    }
+}
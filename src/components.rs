@@ -0,0 +1,133 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Declaring new components of agents and cells.
+//!
+//! Extending the model usually means adding state to agents or cells, such as the age of each agent or the elevation of each cell.
+//! `define_components!` declares a struct holding one storage per component, with accessors for each component.
+//! Components of agents live in `SecondaryMap`s and forget dead agents through the `Dependent` trait;
+//! components of cells live in `Vec`s indexed like `cell_health`, because cells never die.
+//!
+//! ```ignore
+//! define_components! {
+//!     /// Components of agents
+//!     pub struct AgentComponents for AgentKey {
+//!         age: u32,
+//!         wealth: f64,
+//!     }
+//! }
+//! define_components! {
+//!     /// Components of cells
+//!     pub struct CellComponents for cells {
+//!         elevation: f32,
+//!     }
+//! }
+//! ```
+//!
+//! Each component `age` comes with `age(key)`, `age_mut(key)`, and `set_age(key, value)`; `FIELDS` lists the names of the components.
+//! Register a struct of agent components at the `Dependents` registry where agents die.
+
+#[doc(hidden)]
+pub use paste::paste;
+#[doc(hidden)]
+pub use slotmap::SecondaryMap;
+
+#[macro_export]
+macro_rules! define_components {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident for cells {
+            $($field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $crate::components::paste! {
+            $(#[$meta])*
+            #[derive(Clone, Default)]
+            $vis struct $name {
+                $(pub $field: Vec<$ty>,)*
+            }
+
+            #[allow(dead_code)]
+            impl $name {
+                /// Names of the components
+                pub const FIELDS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+                /// Components of `size` cells, all with default values
+                pub fn with_size(size: usize) -> Self {
+                    Self {
+                        $($field: vec![<$ty>::default(); size],)*
+                    }
+                }
+
+                $(
+                    pub fn $field(&self, idx: usize) -> &$ty {
+                        &self.$field[idx]
+                    }
+
+                    pub fn [<$field _mut>](&mut self, idx: usize) -> &mut $ty {
+                        &mut self.$field[idx]
+                    }
+
+                    pub fn [<set_ $field>](&mut self, idx: usize, value: $ty) {
+                        self.$field[idx] = value;
+                    }
+                )*
+            }
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident for $key:ty {
+            $($field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $crate::components::paste! {
+            $(#[$meta])*
+            #[derive(Clone, Default)]
+            $vis struct $name {
+                $(pub $field: $crate::components::SecondaryMap<$key, $ty>,)*
+            }
+
+            #[allow(dead_code)]
+            impl $name {
+                /// Names of the components
+                pub const FIELDS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+                $(
+                    pub fn $field(&self, key: $key) -> Option<&$ty> {
+                        self.$field.get(key)
+                    }
+
+                    pub fn [<$field _mut>](&mut self, key: $key) -> Option<&mut $ty> {
+                        self.$field.get_mut(key)
+                    }
+
+                    pub fn [<set_ $field>](&mut self, key: $key, value: $ty) {
+                        self.$field.insert(key, value);
+                    }
+                )*
+            }
+
+            impl $crate::deletion::Dependent<$key> for $name {
+                fn forget_dead(&mut self, alive: &dyn Fn($key) -> bool) {
+                    $($crate::deletion::Dependent::forget_dead(&mut self.$field, alive);)*
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,235 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Configuration file of a run: a JSON object that replaces parameters of the model by name, such as
+//! `{"visit_distro": "Gamma(2, 3)", "recovery_distro": "Bernoulli(0.6)"}`.
+//!
+//! Distributions follow the specifications of the `distributions` module; events that either happen or not, such as
+//! births, take Bernoulli distributions. Other parameters, such as the carrying capacity, are JSON numbers, or lists
+//! of them. Parameters that the configuration leaves out keep the values of the program.
+
+use crate::distributions::Spec;
+use crate::validation::Problems;
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+#[derive(Default)]
+pub struct Config {
+    values: Map<String, Value>,
+    /// Parameters of the configuration that the run asked for
+    used: BTreeSet<String>,
+}
+
+impl Config {
+    /// Reads a configuration file; an invalid file is a problem of the run rather than an error.
+    pub fn load(path: &Path, problems: &mut Problems) -> std::io::Result<Self> {
+        let values = match serde_json::from_str(&std::fs::read_to_string(path)?) {
+            Ok(Value::Object(values)) => values,
+            Ok(_) => {
+                problems.require(
+                    false,
+                    &path.display().to_string(),
+                    "a JSON object of parameters by name",
+                );
+                Map::new()
+            }
+            Err(e) => {
+                problems.report(&path.display().to_string(), e);
+                Map::new()
+            }
+        };
+        Ok(Self {
+            values,
+            used: BTreeSet::new(),
+        })
+    }
+
+    /// The distribution of a parameter, from the configuration, or the default one
+    pub fn distribution(&mut self, name: &str, default: Spec, problems: &mut Problems) -> Spec {
+        match self.optional_distribution(name, problems) {
+            Some(spec) => spec,
+            None => {
+                problems.distribution(name, default.build());
+                default
+            }
+        }
+    }
+
+    /// The distribution of a parameter that has none unless the configuration gives one, such as the parameters of
+    /// agents that differ between agents
+    pub fn optional_distribution(&mut self, name: &str, problems: &mut Problems) -> Option<Spec> {
+        self.used.insert(name.to_string());
+        let spec = match self.values.get(name)? {
+            Value::String(spec) => match spec.parse::<Spec>() {
+                Ok(spec) => spec,
+                Err(e) => {
+                    problems.report(name, e);
+                    return None;
+                }
+            },
+            value => {
+                problems.require(
+                    false,
+                    name,
+                    format_args!("a distribution such as \"Normal(50, 10)\", not {}", value),
+                );
+                return None;
+            }
+        };
+        problems.distribution(name, spec.build());
+        Some(spec)
+    }
+
+    /// A number from the configuration, if given there, such as the carrying capacity of the population
+    pub fn optional_number(&mut self, name: &str, problems: &mut Problems) -> Option<f64> {
+        self.used.insert(name.to_string());
+        match self.values.get(name)? {
+            Value::Number(number) => number.as_f64(),
+            value => {
+                problems.require(false, name, format_args!("a number, not {}", value));
+                None
+            }
+        }
+    }
+
+    /// A list of numbers from the configuration, if given there, such as the scenarios that a parameter applies to
+    pub fn optional_numbers(&mut self, name: &str, problems: &mut Problems) -> Option<Vec<f64>> {
+        self.used.insert(name.to_string());
+        match self.values.get(name)? {
+            Value::Array(values) if values.iter().all(Value::is_number) => {
+                Some(values.iter().filter_map(Value::as_f64).collect())
+            }
+            value => {
+                problems.require(
+                    false,
+                    name,
+                    format_args!("a list of numbers, not {}", value),
+                );
+                None
+            }
+        }
+    }
+
+    /// The probability of an event, such as a birth, from the Bernoulli distribution of the configuration, or the
+    /// default probability
+    pub fn probability(&mut self, name: &str, default: f64, problems: &mut Problems) -> f64 {
+        let spec = self.distribution(name, Spec::new("Bernoulli", &[default]), problems);
+        if spec.name == "Bernoulli" {
+            spec.parameters.first().copied().unwrap_or(default)
+        } else {
+            problems.require(
+                false,
+                name,
+                format_args!("a Bernoulli distribution, not {}", spec),
+            );
+            default
+        }
+    }
+
+    /// Reports the parameters of the configuration that this run does not have, such as misspelled ones.
+    pub fn finish(self, problems: &mut Problems) {
+        for name in self.values.keys() {
+            if !self.used.contains(name) {
+                problems.require(
+                    false,
+                    name,
+                    "a parameter of this program; please check its spelling and the cargo features",
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads a configuration from its JSON text, through the file `name`.
+    fn config(name: &str, json: &str, problems: &mut Problems) -> Config {
+        let path =
+            std::env::temp_dir().join(format!("config-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        let config = Config::load(&path, problems).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn parameters_come_from_the_configuration_or_the_defaults() {
+        let mut problems = Problems::default();
+        let mut config = config(
+            "defaults",
+            r#"{"visit_distro": "Gamma(2, 3)", "birth_distro": "Bernoulli(0.25)", "capacity": 500}"#,
+            &mut problems,
+        );
+        let default = Spec::new("Normal", &[50.0, 10.0]);
+        assert_eq!(
+            config.distribution("visit_distro", default.clone(), &mut problems),
+            Spec::new("Gamma", &[2.0, 3.0])
+        );
+        assert_eq!(
+            config.distribution("recovery_distro", default.clone(), &mut problems),
+            default
+        );
+        assert_eq!(config.probability("birth_distro", 0.1, &mut problems), 0.25);
+        assert_eq!(config.probability("death_distro", 0.1, &mut problems), 0.1);
+        assert_eq!(
+            config.optional_number("capacity", &mut problems),
+            Some(500.0)
+        );
+        assert_eq!(config.optional_numbers("scenarios", &mut problems), None);
+        config.finish(&mut problems);
+        assert!(problems.finish().is_ok());
+    }
+
+    #[test]
+    fn mistakes_in_the_configuration_are_problems() {
+        let mut problems = Problems::default();
+        let mut config = config(
+            "mistakes",
+            r#"{"visit_distro": "Gamma(2)", "capacity": "large", "birth_distro": "Normal(1, 2)", "vist_distro": 1}"#,
+            &mut problems,
+        );
+        let default = Spec::new("Normal", &[50.0, 10.0]);
+        config.distribution("visit_distro", default, &mut problems);
+        assert_eq!(config.optional_number("capacity", &mut problems), None);
+        assert_eq!(config.probability("birth_distro", 0.1, &mut problems), 0.1);
+        config.finish(&mut problems);
+        match problems.finish() {
+            Err(crate::error::Error::Invalid(problems)) => {
+                let names: Vec<&str> = problems
+                    .iter()
+                    .map(|problem| problem.split([' ', ':']).next().unwrap())
+                    .collect();
+                assert_eq!(
+                    names,
+                    ["visit_distro", "capacity", "birth_distro", "vist_distro"]
+                );
+            }
+            _ => panic!("expected the problems of the configuration"),
+        }
+    }
+
+    #[test]
+    fn configurations_are_json_objects() {
+        let mut problems = Problems::default();
+        config("array", "[1, 2]", &mut problems);
+        assert!(problems.finish().is_err());
+    }
+}
@@ -0,0 +1,90 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Relational integrity: when entities die, everything that refers to them goes away too.
+//!
+//! Components in a `SecondaryMap` and relations such as links between agents implement `Dependent`.
+//! Register them with `Dependents` right before removing entities; `Dependents::retain` removes the entities
+//! and then cleans every registered dependent, so no component keeps the key of a dead entity.
+
+use crate::storage::{GenerationalVec, Storage};
+use slotmap::{Key, SecondaryMap, SlotMap};
+
+/// Component or relation that refers to entities identified by keys of type `K`
+pub trait Dependent<K: Key> {
+    /// Removes everything that refers to entities for which `alive` returns false
+    fn forget_dead(&mut self, alive: &dyn Fn(K) -> bool);
+}
+
+/// Component of the entities
+impl<K: Key, V> Dependent<K> for SecondaryMap<K, V> {
+    fn forget_dead(&mut self, alive: &dyn Fn(K) -> bool) {
+        self.retain(|k, _v| alive(k));
+    }
+}
+
+/// Relation between pairs of entities, such as a link between two agents; it dies with either entity.
+pub fn forget_dead_pairs<R: Key, K: Key, S: Storage<R, (K, K)>>(
+    relations: &mut S,
+    alive: &dyn Fn(K) -> bool,
+) {
+    relations.retain(|_relation_key, (key0, key1)| alive(*key0) && alive(*key1));
+}
+
+impl<R: Key, K: Key> Dependent<K> for SlotMap<R, (K, K)> {
+    fn forget_dead(&mut self, alive: &dyn Fn(K) -> bool) {
+        forget_dead_pairs(self, alive);
+    }
+}
+
+impl<R: Key, K: Key> Dependent<K> for GenerationalVec<R, (K, K)> {
+    fn forget_dead(&mut self, alive: &dyn Fn(K) -> bool) {
+        forget_dead_pairs(self, alive);
+    }
+}
+
+/// Registry of the dependents of one kind of entity
+pub struct Dependents<'a, K: Key> {
+    dependents: Vec<&'a mut dyn Dependent<K>>,
+}
+
+impl<'a, K: Key> Default for Dependents<'a, K> {
+    fn default() -> Self {
+        Self {
+            dependents: Vec::new(),
+        }
+    }
+}
+
+impl<'a, K: Key> Dependents<'a, K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, dependent: &'a mut dyn Dependent<K>) {
+        self.dependents.push(dependent);
+    }
+
+    /// Keeps only the entities for which `f` returns true, then cleans all registered dependents.
+    pub fn retain<V, S: Storage<K, V>, F: FnMut(K, &mut V) -> bool>(self, entities: &mut S, f: F) {
+        entities.retain(f);
+        for dependent in self.dependents {
+            dependent.forget_dead(&|k| entities.contains_key(k));
+        }
+    }
+}
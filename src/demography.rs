@@ -0,0 +1,111 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Demography: births that slow down as the population approaches its carrying capacity, and natural deaths, unrelated
+//! to the disease, that grow more likely with age.
+//!
+//! Births follow the logistic model: each susceptible agent gives birth with probability
+//! `birth_probability * (1 - n / carrying_capacity)`, where `n` is the number of agents. Natural mortality follows the
+//! Gompertz law: an agent of age `a`, in time steps, dies at each time step with probability
+//! `mortality_baseline * exp(mortality_growth * a)`. Without a carrying capacity, births keep their flat probability;
+//! without a mortality baseline, agents only die of the disease, and the model keeps no ages.
+//!
+//! The population can also be open: immigrants arrive in numbers that follow a Poisson distribution, each of them
+//! infected with a probability, and every agent emigrates with a probability at each time step. Turnover brings in
+//! susceptible agents and imported infections, which can sustain the disease.
+
+use crate::define_components;
+use crate::distributions::BoxedSampler;
+use crate::AgentKey;
+use rand::Rng;
+use rand_distr::{Distribution, Poisson};
+
+define_components! {
+    /// Time step at which each agent was born; agents of the initial population can be born before time step 0.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AgentDemography for AgentKey {
+        born: i64,
+    }
+}
+
+pub struct Demography {
+    /// Probability that a susceptible agent gives birth at each time step, in an empty world
+    pub birth_probability: f64,
+    /// Number of agents at which births stop, if any
+    pub carrying_capacity: Option<f64>,
+    /// Probability of natural death at age 0, at each time step
+    pub mortality_baseline: f64,
+    /// Growth of the logarithm of the probability of natural death with each time step of age
+    pub mortality_growth: f64,
+    /// Ages of the agents of the initial population, if not 0
+    pub initial_age: Option<BoxedSampler>,
+}
+
+impl Demography {
+    /// Whether the model keeps the age of each agent
+    pub fn ages(&self) -> bool {
+        self.mortality_baseline > 0.0 || self.initial_age.is_some()
+    }
+
+    /// Probability that a susceptible agent gives birth in a population of this many agents
+    pub fn birth_probability(&self, population: usize) -> f64 {
+        match self.carrying_capacity {
+            Some(capacity) => {
+                (self.birth_probability * (1.0 - population as f64 / capacity)).max(0.0)
+            }
+            None => self.birth_probability,
+        }
+    }
+
+    /// Probability that an agent of this age, in time steps, dies of natural causes at this time step
+    pub fn mortality(&self, age: i64) -> f64 {
+        (self.mortality_baseline * (self.mortality_growth * age as f64).exp()).min(1.0)
+    }
+}
+
+/// Agents that enter and leave the population, besides births and deaths
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Migration {
+    /// Mean number of immigrants at each time step
+    pub immigration: f64,
+    /// Probability that an immigrant arrives infected
+    pub infected: f64,
+    /// Probability that an agent leaves the population at each time step
+    pub emigration: f64,
+}
+
+impl Migration {
+    /// Number of immigrants that arrive at a time step
+    pub fn immigrants<R: Rng>(&self, rng: &mut R) -> usize {
+        if self.immigration > 0.0 {
+            Poisson::new(self.immigration).unwrap().sample(rng) as usize
+        } else {
+            0
+        }
+    }
+
+    /// Whether an immigrant arrives infected
+    pub fn arrives_infected<R: Rng>(&self, rng: &mut R) -> bool {
+        self.infected > 0.0 && rng.gen_bool(self.infected)
+    }
+
+    /// Whether an agent leaves the population at a time step
+    pub fn emigrates<R: Rng>(&self, rng: &mut R) -> bool {
+        self.emigration > 0.0 && rng.gen_bool(self.emigration)
+    }
+}
@@ -0,0 +1,302 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Training data for emulators, or surrogate models, of the simulation: a design matrix with one row per scenario,
+//! its inputs and the summaries of its outputs, and the metadata to transform and standardize each column.
+//!
+//! Inputs are the infection probability of each scenario and the numeric parameters in the manifests of the runs that
+//! differ from one run to another, such as those of the batches of an exploration; parameters that every run shares
+//! tell an emulator nothing. Outputs are the final, peak, and mean values of each metric of the time series. Counts
+//! take a log1p transformation, since they spread over orders of magnitude, and then every column is standardized by
+//! the mean and standard deviation of its transformed values.
+
+use crate::stats::RunningStats;
+use crate::tables::{self, Column, ColumnType, Value};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Transformation of a column before standardization
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+    Identity,
+    /// ln(1 + x), for counts
+    Log1p,
+}
+
+impl Transform {
+    pub fn apply(self, value: f64) -> f64 {
+        match self {
+            Transform::Identity => value,
+            Transform::Log1p => value.ln_1p(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Transform::Identity => "identity",
+            Transform::Log1p => "log1p",
+        }
+    }
+}
+
+/// Column of the design matrix
+#[derive(Clone, Debug)]
+pub struct DesignColumn {
+    pub name: String,
+    /// Input of the simulation, or summary of its outputs
+    pub input: bool,
+    pub transform: Transform,
+    /// Mean and standard deviation of the transformed values; a constant column has a standard deviation of 1, so that
+    /// standardization leaves it at 0.
+    pub mean: f64,
+    pub sd: f64,
+}
+
+impl DesignColumn {
+    pub fn standardize(&self, value: f64) -> f64 {
+        (self.transform.apply(value) - self.mean) / self.sd
+    }
+}
+
+/// Scenarios of one or more runs, with their inputs and outputs
+#[derive(Clone, Debug, Default)]
+pub struct DesignMatrix {
+    pub columns: Vec<DesignColumn>,
+    /// Directory of each scenario
+    pub scenarios: Vec<PathBuf>,
+    /// Values of the columns for each scenario; NaN where a run lacks a parameter or a metric
+    pub rows: Vec<Vec<f64>>,
+}
+
+/// Numeric parameters of a manifest, by name; nested parameters join their names with dots.
+fn parameters(value: &serde_json::Value, prefix: &str, parameters: &mut BTreeMap<String, f64>) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                let name = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                self::parameters(value, &name, parameters);
+            }
+        }
+        serde_json::Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                parameters.insert(prefix.to_string(), number);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Scenario directories of a run, in order
+fn scenario_dirs(run_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(run_dir)? {
+        let path = entry?.path();
+        let is_scenario = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("scenario-"));
+        if is_scenario && path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+impl DesignMatrix {
+    /// Reads the manifests and the time series of the scenarios of these runs.
+    pub fn read(run_dirs: &[PathBuf]) -> io::Result<Self> {
+        // Parameters of each run, then the inputs and outputs of each scenario by column name
+        let mut runs = Vec::new();
+        for run_dir in run_dirs {
+            let manifest_path = run_dir.join("manifest.json");
+            let manifest: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&manifest_path)?)?;
+            let mut run_parameters = BTreeMap::new();
+            parameters(&manifest["parameters"], "", &mut run_parameters);
+            runs.push((run_dir, run_parameters));
+        }
+        let varying: Vec<String> = runs
+            .iter()
+            .flat_map(|(_, parameters)| parameters.keys())
+            .filter(|name| {
+                let mut values = runs.iter().map(|(_, parameters)| parameters.get(*name));
+                let first = values.next().flatten();
+                values.any(|value| value != first)
+            })
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut inputs = vec!["infection_probability".to_string()];
+        inputs.extend(varying.iter().cloned());
+        let mut outputs: Vec<String> = Vec::new();
+        let mut scenarios = Vec::new();
+        let mut values: Vec<BTreeMap<String, f64>> = Vec::new();
+        for (run_dir, run_parameters) in &runs {
+            for scenario_dir in scenario_dirs(run_dir)? {
+                let ts_path = scenario_dir.join("ts.csv");
+                let (titles, rows) = tables::read_csv(BufReader::new(fs::File::open(&ts_path)?))?;
+                let mut row: BTreeMap<String, f64> = varying
+                    .iter()
+                    .filter_map(|name| Some((name.clone(), *run_parameters.get(name)?)))
+                    .collect();
+                row.insert(
+                    "infection_probability".to_string(),
+                    rows.first().map_or(f64::NAN, |row| row[0]),
+                );
+                // The first two columns are the infection probability and the time step; titles start with the metric
+                // name
+                for (column, title) in titles.iter().enumerate().skip(2) {
+                    let metric = title.split(' ').next().unwrap_or_default();
+                    let mut stats = RunningStats::default();
+                    let mut peak = f64::NEG_INFINITY;
+                    for value in rows.iter().map(|row| row[column]).filter(|v| !v.is_nan()) {
+                        stats.push(value);
+                        peak = peak.max(value);
+                    }
+                    let last = rows.last().map_or(f64::NAN, |row| row[column]);
+                    let peak = if peak.is_finite() { peak } else { f64::NAN };
+                    let mean = if peak.is_nan() {
+                        f64::NAN
+                    } else {
+                        stats.mean()
+                    };
+                    for (statistic, value) in [("final", last), ("peak", peak), ("mean", mean)] {
+                        let name = format!("{}_{}", statistic, metric);
+                        if !outputs.contains(&name) {
+                            outputs.push(name.clone());
+                        }
+                        row.insert(name, value);
+                    }
+                }
+                scenarios.push(scenario_dir);
+                values.push(row);
+            }
+        }
+        let names: Vec<(String, bool)> = (inputs.into_iter().map(|name| (name, true)))
+            .chain(outputs.into_iter().map(|name| (name, false)))
+            .collect();
+        let rows: Vec<Vec<f64>> = values
+            .iter()
+            .map(|row| {
+                names
+                    .iter()
+                    .map(|(name, _)| row.get(name).copied().unwrap_or(f64::NAN))
+                    .collect()
+            })
+            .collect();
+        let columns = names
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (name, input))| {
+                let column: Vec<f64> = rows
+                    .iter()
+                    .map(|row| row[idx])
+                    .filter(|value| !value.is_nan())
+                    .collect();
+                // Outputs that are all whole and non-negative are counts
+                let transform = if !input && column.iter().all(|&v| v >= 0.0 && v.fract() == 0.0) {
+                    Transform::Log1p
+                } else {
+                    Transform::Identity
+                };
+                let mut stats = RunningStats::default();
+                column
+                    .iter()
+                    .for_each(|&value| stats.push(transform.apply(value)));
+                let sd = stats.variance().sqrt();
+                DesignColumn {
+                    name,
+                    input,
+                    transform,
+                    mean: stats.mean(),
+                    sd: if sd > 0.0 { sd } else { 1.0 },
+                }
+            })
+            .collect();
+        Ok(Self {
+            columns,
+            scenarios,
+            rows,
+        })
+    }
+
+    /// Writes the design matrix as CSV: the scenario directory, then the inputs and the outputs, either as simulated or
+    /// transformed and standardized. Missing values stay empty.
+    pub fn write_csv<W: Write>(&self, w: &mut W, standardize: bool) -> io::Result<()> {
+        let columns: Vec<Column> = std::iter::once(Column::new(
+            "scenario_dir",
+            "scenario_dir",
+            ColumnType::Utf8,
+        ))
+        .chain(
+            self.columns
+                .iter()
+                .map(|column| Column::new(&*column.name, &*column.name, ColumnType::Float64)),
+        )
+        .collect();
+        tables::write_csv_header(w, &columns)?;
+        for (scenario_dir, row) in self.scenarios.iter().zip(&self.rows) {
+            let scenario_dir = scenario_dir.display().to_string();
+            let values: Vec<Value> = std::iter::once(Value::Utf8(&scenario_dir))
+                .chain(self.columns.iter().zip(row).map(|(column, &value)| {
+                    if value.is_nan() {
+                        Value::Utf8("")
+                    } else if standardize {
+                        Value::Float64(column.standardize(value))
+                    } else {
+                        Value::Float64(value)
+                    }
+                }))
+                .collect();
+            tables::write_csv_row(w, &values)?;
+        }
+        Ok(())
+    }
+
+    /// Metadata of the columns: role, transformation, and the mean and standard deviation that standardize them
+    pub fn metadata(&self, standardized: bool) -> serde_json::Value {
+        let columns: Vec<serde_json::Value> = self
+            .columns
+            .iter()
+            .map(|column| {
+                json!({
+                    "name": column.name,
+                    "role": if column.input { "input" } else { "output" },
+                    "transform": column.transform.name(),
+                    "mean": column.mean,
+                    "sd": column.sd,
+                })
+            })
+            .collect();
+        json!({
+            "rows": self.rows.len(),
+            "standardized": standardized,
+            "standardization": "z = (transform(x) - mean) / sd",
+            "columns": columns,
+        })
+    }
+}
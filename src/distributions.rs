@@ -0,0 +1,170 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Distributions of real numbers named in the configuration of a run, such as `Normal(50, 10)` or `Gamma(2, 3)`.
+//!
+//! A specification is the name of a distribution of the registry, followed by its parameters in parentheses. The
+//! program builds each specification once, into a boxed sampler, so that users can change the stochastic assumptions
+//! of the model without editing Rust.
+
+use rand::distributions::{Bernoulli, Distribution, Uniform};
+use rand::RngCore;
+use rand_distr::{Beta, Exp, Gamma, LogNormal, Normal, Poisson};
+use std::fmt;
+
+/// A distribution of real numbers that any random number generator can sample, behind a pointer
+pub trait Sampler: Send + Sync {
+    fn sample(&self, rng: &mut dyn RngCore) -> f64;
+}
+
+impl<D: Distribution<f64> + Send + Sync> Sampler for D {
+    fn sample(&self, rng: &mut dyn RngCore) -> f64 {
+        Distribution::sample(self, rng)
+    }
+}
+
+pub type BoxedSampler = Box<dyn Sampler>;
+
+/// Always the same value, for parameters that do not vary
+struct Constant(f64);
+
+impl Distribution<f64> for Constant {
+    fn sample<R: rand::Rng + ?Sized>(&self, _rng: &mut R) -> f64 {
+        self.0
+    }
+}
+
+type Constructor = fn(&[f64]) -> Result<BoxedSampler, String>;
+
+/// Distributions by name, with the names of their parameters and their constructors. Bernoulli samples 1 or 0.
+const REGISTRY: &[(&str, &[&str], Constructor)] = &[
+    ("Bernoulli", &["p"], |p| {
+        let bernoulli = Bernoulli::new(p[0]).map_err(|e| e.to_string())?;
+        Ok(Box::new(bernoulli.map(|b| if b { 1.0 } else { 0.0 })))
+    }),
+    ("Beta", &["alpha", "beta"], |p| {
+        Ok(Box::new(Beta::new(p[0], p[1]).map_err(|e| e.to_string())?))
+    }),
+    ("Constant", &["value"], |p| Ok(Box::new(Constant(p[0])))),
+    ("Exp", &["lambda"], |p| {
+        Ok(Box::new(Exp::new(p[0]).map_err(|e| e.to_string())?))
+    }),
+    ("Gamma", &["shape", "scale"], |p| {
+        Ok(Box::new(Gamma::new(p[0], p[1]).map_err(|e| e.to_string())?))
+    }),
+    ("LogNormal", &["mu", "sigma"], |p| {
+        Ok(Box::new(
+            LogNormal::new(p[0], p[1]).map_err(|e| e.to_string())?,
+        ))
+    }),
+    ("Normal", &["mean", "std_dev"], |p| {
+        Ok(Box::new(
+            Normal::new(p[0], p[1]).map_err(|e| e.to_string())?,
+        ))
+    }),
+    ("Poisson", &["lambda"], |p| {
+        Ok(Box::new(Poisson::new(p[0]).map_err(|e| e.to_string())?))
+    }),
+    // Uniform panics rather than failing
+    ("Uniform", &["low", "high"], |p| {
+        if p[0] < p[1] && (p[1] - p[0]).is_finite() {
+            Ok(Box::new(Uniform::new(p[0], p[1])))
+        } else {
+            Err("low must be less than high, and both finite".to_string())
+        }
+    }),
+];
+
+/// A distribution by name, with its parameters, such as `Normal(50, 10)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spec {
+    pub name: String,
+    pub parameters: Vec<f64>,
+}
+
+impl Spec {
+    pub fn new(name: &str, parameters: &[f64]) -> Self {
+        Self {
+            name: name.to_string(),
+            parameters: parameters.to_vec(),
+        }
+    }
+
+    /// Builds the distribution, checking its name, its number of parameters, and their values.
+    pub fn build(&self) -> Result<BoxedSampler, String> {
+        let (_, names, constructor) = REGISTRY
+            .iter()
+            .find(|(name, _, _)| *name == self.name)
+            .ok_or_else(|| {
+                format!(
+                    "{} is not a known distribution; known distributions are {}",
+                    self.name,
+                    REGISTRY
+                        .iter()
+                        .map(|(name, _, _)| *name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        if names.len() != self.parameters.len() {
+            return Err(format!(
+                "{} takes {} parameters, {}, not {}",
+                self.name,
+                names.len(),
+                names.join(", "),
+                self.parameters.len()
+            ));
+        }
+        constructor(&self.parameters).map_err(|e| format!("{}: {}", self, e))
+    }
+}
+
+impl std::str::FromStr for Spec {
+    type Err = String;
+
+    /// Reads a specification such as `Normal(50, 10)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "\"{}\" is not a distribution such as Normal(50, 10) or Bernoulli(0.01)",
+                s
+            )
+        };
+        let (name, rest) = s.trim().split_once('(').ok_or_else(invalid)?;
+        let parameters = rest.trim_end().strip_suffix(')').ok_or_else(invalid)?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+        let parameters = parameters
+            .split(',')
+            .map(|parameter| parameter.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name: name.to_string(),
+            parameters,
+        })
+    }
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}({})", self.name, parameters.join(", "))
+    }
+}
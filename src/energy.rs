@@ -0,0 +1,111 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Energy of agents: agents spend energy to live and to move, take it from the resources of the cells they visit, and
+//! starve to death when it falls below zero. Foraging and disease then interact on the shared landscape: agents that
+//! search farther for food visit more cells, contaminated ones included, and infection can cost energy.
+//!
+//! Each cell holds a resource, up to `capacity`, that regrows by `regrowth` at each time step. At each visit, an agent
+//! takes up to `intake` of the resource of the cell.
+
+use crate::define_components;
+use crate::{AgentKey, Health};
+
+define_components! {
+    /// Energy of each agent; agents without a value, such as newborns, start with the initial energy.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AgentEnergy for AgentKey {
+        energy: f64,
+    }
+}
+
+define_components! {
+    /// Resource in each cell, which agents turn into energy
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct CellResources for cells {
+        resource: f64,
+    }
+}
+
+/// Parameters of energy; see the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metabolism {
+    /// Energy of agents when they enter the model
+    pub initial: f64,
+    /// Energy spent at each time step
+    pub per_step: f64,
+    /// Energy spent at each visit to a cell
+    pub per_visit: f64,
+    /// Energy that infected agents spend at each time step on top of `per_step`
+    pub infection: f64,
+    /// Most resource that an agent takes from a cell at each visit
+    pub intake: f64,
+    /// Most resource in a cell, and the resource of every cell at the start
+    pub capacity: f64,
+    /// Resource that each cell gains at each time step
+    pub regrowth: f64,
+}
+
+impl Metabolism {
+    /// Resources of a landscape of this many cells, all at capacity
+    pub fn resources(&self, size: usize) -> CellResources {
+        CellResources {
+            resource: vec![self.capacity; size],
+        }
+    }
+
+    /// Resources regrow at the start of a time step.
+    pub fn regrow(&self, resources: &mut CellResources) {
+        resources
+            .resource
+            .iter_mut()
+            .for_each(|resource| *resource = (*resource + self.regrowth).min(self.capacity));
+    }
+
+    /// An agent with this health spends the energy of a time step.
+    pub fn metabolize(&self, energy: &mut AgentEnergy, agent: AgentKey, health: Health) {
+        let cost = match health {
+            Health::S => self.per_step,
+            Health::I => self.per_step + self.infection,
+        };
+        if let Some(entry) = energy.energy.entry(agent) {
+            *entry.or_insert(self.initial) -= cost;
+        }
+    }
+
+    /// An agent visits a cell, spending energy to get there and taking the resource of the cell.
+    pub fn forage(
+        &self,
+        energy: &mut AgentEnergy,
+        resources: &mut CellResources,
+        agent: AgentKey,
+        idx: usize,
+    ) {
+        let resource = resources.resource_mut(idx);
+        let intake = resource.min(self.intake);
+        *resource -= intake;
+        if let Some(energy) = energy.energy_mut(agent) {
+            *energy += intake - self.per_visit;
+        }
+    }
+
+    /// Whether an agent has run out of energy
+    pub fn starved(&self, energy: &AgentEnergy, agent: AgentKey) -> bool {
+        energy.energy(agent).is_some_and(|&energy| energy < 0.0)
+    }
+}
@@ -0,0 +1,87 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Errors of a run: its parameters, and its outputs: files, figures, and videos.
+//!
+//! A run checks its parameters before it starts. Then it reports the errors of its outputs rather than stopping at the
+//! first one: a scenario that cannot write its results stops alone, and a figure or video that fails leaves the others
+//! alone. The program then exits with the status of the first error, following the conventions of sysexits.h, so that
+//! scripts and job schedulers can tell errors apart.
+
+use std::path::PathBuf;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Parameters that the run cannot use, each with its problem
+    #[error("Invalid parameters: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+    /// This program rewrites output files only if --existing asks
+    #[error("{} already exists; rerun with --existing overwrite or --existing skip to redo the run", .0.display())]
+    Exists(PathBuf),
+    /// Reading or writing a file, with the message that explains what the program was doing
+    #[error("{message}: {source}")]
+    Io {
+        message: String,
+        source: std::io::Error,
+    },
+    /// A figure could not be drawn or saved
+    #[error("Could not draw {}: {message}", .path.display())]
+    Figure { path: PathBuf, message: String },
+    /// ffmpeg could not encode a video
+    #[error("Could not create {}: {message}", .path.display())]
+    Video { path: PathBuf, message: String },
+    /// The gpu feature could not find an adapter or open a device
+    #[error(
+        "Could not open the GPU: {0}; please run without the gpu feature or with --no-landscape"
+    )]
+    Gpu(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Exit status of the program for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_CONFIG
+            Error::Invalid(_) => 78,
+            // EX_CANTCREAT
+            Error::Exists(_) => 73,
+            // EX_IOERR
+            Error::Io { .. } => 74,
+            // EX_SOFTWARE
+            Error::Figure { .. } | Error::Video { .. } => 70,
+            // EX_UNAVAILABLE
+            Error::Gpu(_) => 69,
+        }
+    }
+}
+
+/// Adds the message of the program to input and output errors, as `expect` does, without panicking.
+pub trait OrError<T> {
+    fn or_error(self, message: &str) -> Result<T>;
+}
+
+impl<T> OrError<T> for std::io::Result<T> {
+    fn or_error(self, message: &str) -> Result<T> {
+        self.map_err(|source| Error::Io {
+            message: message.to_string(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Events that the dynamics publish during a time step, for downstream systems to consume at its end.
+//!
+//! Rules only describe what happens: who infected whom, which agents were born or died. Bookkeeping such as hooks,
+//! metrics, or a transmission tree reads the events from the `EventBus` after the dynamics finish,
+//! so rules and bookkeeping can change independently. Each queue keeps its events in the order they were published.
+
+use crate::{AgentKey, LinkKey};
+
+/// Route of an infection
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Via {
+    /// Initial infection of the scenario
+    Seed,
+    /// Link between the source and the target
+    Link(LinkKey),
+    /// Infectious cell visited by the target; the value is the cell index.
+    Cell(usize),
+    /// Infected vector on the cell visited by the target; the value is the cell index.
+    Vector(usize),
+    /// Infected member of the group of the target; the value is the group.
+    Group(u32),
+    /// Contact between age groups, drawn from a contact matrix; see the mixing module.
+    Mixing,
+    /// Immigrant that arrives infected; see the demography module.
+    Import,
+}
+
+/// A susceptible agent becomes infected. Only the first infection of an agent in a time step is published.
+#[derive(Clone, Copy, Debug)]
+pub struct InfectionEvent {
+    /// Infectious agent, if the infection came from an agent
+    pub source: Option<AgentKey>,
+    pub target: AgentKey,
+    pub via: Via,
+}
+
+/// An infectious agent infects the susceptible cell it visits.
+#[derive(Clone, Copy, Debug)]
+pub struct CellInfectionEvent {
+    pub source: AgentKey,
+    pub cell: usize,
+}
+
+/// An infectious agent recovers.
+#[derive(Clone, Copy, Debug)]
+pub struct RecoveryEvent {
+    pub agent: AgentKey,
+}
+
+/// An agent dies.
+#[derive(Clone, Copy, Debug)]
+pub struct DeathEvent {
+    pub agent: AgentKey,
+}
+
+/// A new agent emerges from its parent.
+#[derive(Clone, Copy, Debug)]
+pub struct BirthEvent {
+    pub parent: AgentKey,
+    pub agent: AgentKey,
+}
+
+/// A link forms between two agents.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkEvent {
+    pub link: LinkKey,
+    pub agents: (AgentKey, AgentKey),
+}
+
+/// Events of one type, in the order they were published
+#[derive(Clone, Debug)]
+pub struct Queue<E> {
+    events: Vec<E>,
+}
+
+impl<E> Default for Queue<E> {
+    fn default() -> Self {
+        Self { events: Vec::new() }
+    }
+}
+
+impl<E> Queue<E> {
+    pub fn publish(&mut self, event: E) {
+        self.events.push(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Events published so far, without consuming them
+    pub fn iter(&self) -> std::slice::Iter<'_, E> {
+        self.events.iter()
+    }
+
+    /// Consumes all events, keeping the storage for the next time step
+    pub fn drain(&mut self) -> std::vec::Drain<'_, E> {
+        self.events.drain(..)
+    }
+}
+
+/// Event queues of one scenario
+#[derive(Clone, Debug, Default)]
+pub struct EventBus {
+    pub infections: Queue<InfectionEvent>,
+    pub cell_infections: Queue<CellInfectionEvent>,
+    pub recoveries: Queue<RecoveryEvent>,
+    pub deaths: Queue<DeathEvent>,
+    pub births: Queue<BirthEvent>,
+    pub links: Queue<LinkEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops the events that no system consumed
+    pub fn clear(&mut self) {
+        self.infections.drain();
+        self.cell_infections.drain();
+        self.recoveries.drain();
+        self.deaths.drain();
+        self.births.drain();
+        self.links.drain();
+    }
+}
@@ -0,0 +1,199 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Adaptive exploration of the infection probability: batches of simulations that concentrate on the region of
+//! interest, such as where the final size crosses half of the population, rather than a fixed sweep.
+//!
+//! Each batch is a sweep of this program, a child process, whose scenarios take the infection probabilities that the
+//! explorer chooses. After each batch, the explorer ranks the intervals between the probabilities simulated so far,
+//! by the mean final size of their ends as a fraction of the initial population, and spreads the probabilities of the
+//! next batch evenly inside the most interesting ones.
+
+use crate::tables;
+use serde_json::Value;
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// What makes an interval between two infection probabilities interesting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Criterion {
+    /// The mean final size crosses this fraction of the initial population inside the interval.
+    Crossing(f64),
+    /// The mean final size changes the most across the interval, as near a bifurcation.
+    Jump,
+}
+
+/// Infection probabilities simulated so far and the final sizes of their replicates
+#[derive(Clone, Debug, Default)]
+pub struct Explorer {
+    /// Infection probability, batch, and final sizes as fractions of the initial population, in order of probability
+    points: Vec<(f64, usize, Vec<f64>)>,
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+impl Explorer {
+    /// Adds the final size, as a fraction of the initial population, of a replicate of an infection probability
+    /// simulated in this batch.
+    pub fn add(&mut self, infection_probability: f64, batch: usize, final_size: f64) {
+        match self
+            .points
+            .binary_search_by(|(p, _, _)| p.total_cmp(&infection_probability))
+        {
+            Ok(idx) => self.points[idx].2.push(final_size),
+            Err(idx) => self
+                .points
+                .insert(idx, (infection_probability, batch, vec![final_size])),
+        }
+    }
+
+    /// Infection probability, batch of its first simulation, replicates, and mean final size as a fraction of the
+    /// initial population, in order of probability
+    pub fn points(&self) -> impl Iterator<Item = (f64, usize, usize, f64)> + '_ {
+        self.points
+            .iter()
+            .map(|(p, batch, sizes)| (*p, *batch, sizes.len(), mean(sizes)))
+    }
+
+    /// Intervals of infection probabilities at least as wide as `tolerance` that meet the criterion, the most
+    /// interesting first
+    pub fn intervals(&self, criterion: Criterion, tolerance: f64) -> Vec<(f64, f64)> {
+        let points: Vec<(f64, f64)> = self.points().map(|(p, _, _, size)| (p, size)).collect();
+        let mut intervals: Vec<((f64, f64), f64)> = points
+            .windows(2)
+            .filter(|pair| pair[1].0 - pair[0].0 >= tolerance)
+            .filter_map(|pair| {
+                let ((p0, size0), (p1, size1)) = (pair[0], pair[1]);
+                let interest = match criterion {
+                    // The widest crossings are the least certain
+                    Criterion::Crossing(target) => {
+                        if (size0 - target) * (size1 - target) <= 0.0 {
+                            p1 - p0
+                        } else {
+                            0.0
+                        }
+                    }
+                    Criterion::Jump => (size1 - size0).abs(),
+                };
+                Some(((p0, p1), interest)).filter(|_| interest > 0.0)
+            })
+            .collect();
+        intervals.sort_by(|a, b| b.1.total_cmp(&a.1));
+        // Jumps much smaller than the largest one are noise
+        if let (Criterion::Jump, Some(&(_, largest))) = (criterion, intervals.first()) {
+            intervals.retain(|&(_, jump)| jump >= largest / 2.0);
+        }
+        intervals
+            .into_iter()
+            .map(|(interval, _)| interval)
+            .collect()
+    }
+
+    /// Infection probabilities of the next batch: `size` of them, spread evenly inside the intervals, which take turns
+    /// from the most interesting one. Empty when no interval is left.
+    pub fn next_batch(&self, criterion: Criterion, tolerance: f64, size: usize) -> Vec<f64> {
+        let intervals = self.intervals(criterion, tolerance);
+        if intervals.is_empty() {
+            return Vec::new();
+        }
+        let mut counts = vec![0; intervals.len()];
+        for idx in 0..size {
+            counts[idx % intervals.len()] += 1;
+        }
+        let mut batch: Vec<f64> = intervals
+            .iter()
+            .zip(counts)
+            .flat_map(|(&(p0, p1), count)| {
+                (1..=count).map(move |k| p0 + (p1 - p0) * k as f64 / (count + 1) as f64)
+            })
+            .collect();
+        batch.sort_by(f64::total_cmp);
+        batch
+    }
+}
+
+/// Infection probabilities evenly spaced from `low` to `high`, both included
+pub fn grid(low: f64, high: f64, size: usize) -> Vec<f64> {
+    match size {
+        0 => Vec::new(),
+        1 => vec![(low + high) / 2.0],
+        _ => (0..size)
+            .map(|k| low + (high - low) * k as f64 / (size - 1) as f64)
+            .collect(),
+    }
+}
+
+/// Runs a batch in `batch_dir` with `program`: a sweep of these infection probabilities, with the other parameters of
+/// `config` and the command line arguments `args`, and its standard error in `<batch_dir>.log`. Then adds the final
+/// sizes of its scenarios to the explorer.
+pub fn run_batch(
+    explorer: &mut Explorer,
+    program: &Path,
+    batch_dir: &Path,
+    batch: usize,
+    infection_probabilities: &[f64],
+    config: &serde_json::Map<String, Value>,
+    args: &[String],
+) -> io::Result<()> {
+    let mut config = config.clone();
+    config.insert(
+        "infection_probabilities".to_string(),
+        Value::from(infection_probabilities),
+    );
+    let config_path = batch_dir.with_extension("json");
+    fs::write(&config_path, Value::Object(config).to_string())?;
+    let log_path = batch_dir.with_extension("log");
+    let status = Command::new(program)
+        .arg("sweep")
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--output-dir")
+        .arg(batch_dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(fs::File::create(&log_path)?)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "The batch ended with {}; see {}",
+            status,
+            log_path.display()
+        )));
+    }
+    let manifest: Value =
+        serde_json::from_str(&fs::read_to_string(batch_dir.join("manifest.json"))?)?;
+    let n0 = manifest["parameters"]["n0"]
+        .as_f64()
+        .ok_or_else(|| io::Error::other("The manifest of the batch has no n0"))?;
+    let sizes_path = batch_dir.join("final_sizes.csv");
+    let (titles, rows) = tables::read_csv(BufReader::new(fs::File::open(&sizes_path)?))?;
+    let column = |title: &str| {
+        titles.iter().position(|t| t == title).ok_or_else(|| {
+            io::Error::other(format!("{} has no column {}", sizes_path.display(), title))
+        })
+    };
+    let (probability, final_size) = (column("Infection Probability")?, column("Final size")?);
+    for row in rows {
+        explorer.add(row[probability], batch, row[final_size] / n0);
+    }
+    Ok(())
+}
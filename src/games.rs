@@ -0,0 +1,155 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Games between linked agents: each agent holds a strategy, plays with its friends at every time step, and imitates
+//! friends who earn more.
+//!
+//! In the prisoner's dilemma, each link is a game: two cooperators earn the reward, two defectors the punishment, and
+//! a defector facing a cooperator earns the temptation while the cooperator earns the sucker's payoff. In the public
+//! goods game, each agent hosts a game among itself and its friends: cooperators pay 1 into a pot, which grows by the
+//! synergy factor and is shared by every player of the game. Imitation follows the Fermi rule: each agent looks at a
+//! random friend and adopts its strategy with a probability that grows with the difference of their payoffs, and
+//! noise allows mistakes.
+
+use crate::storage::Storage;
+use crate::{AgentKey, LinkKey};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use slotmap::SecondaryMap;
+
+/// Strategy of an agent
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strategy {
+    #[default]
+    Cooperate,
+    Defect,
+}
+
+/// Game played by linked agents
+#[derive(Clone, Copy, Debug)]
+pub enum Game {
+    /// Payoffs of the prisoner's dilemma, with temptation > reward > punishment > sucker
+    PrisonersDilemma {
+        reward: f64,
+        sucker: f64,
+        temptation: f64,
+        punishment: f64,
+    },
+    /// Public goods game, in which the pot grows by the synergy factor
+    PublicGoods { synergy: f64 },
+}
+
+impl Game {
+    /// The weak prisoner's dilemma of Nowak and May (1992): the reward is 1, the temptation is `temptation`, and
+    /// the other payoffs are 0.
+    pub fn weak_prisoners_dilemma(temptation: f64) -> Self {
+        Game::PrisonersDilemma {
+            reward: 1.0,
+            sucker: 0.0,
+            temptation,
+            punishment: 0.0,
+        }
+    }
+}
+
+/// Friends of each agent with links, from the links of the network
+pub fn friends<L: Storage<LinkKey, (AgentKey, AgentKey)>>(
+    links: &L,
+) -> SecondaryMap<AgentKey, Vec<AgentKey>> {
+    let mut friends: SecondaryMap<AgentKey, Vec<AgentKey>> = SecondaryMap::new();
+    for &(key0, key1) in links.values() {
+        friends.entry(key0).unwrap().or_default().push(key1);
+        friends.entry(key1).unwrap().or_default().push(key0);
+    }
+    friends
+}
+
+/// Payoffs that each agent accumulates in one round of the game with its friends
+pub fn play<A: Storage<AgentKey, Strategy>>(
+    game: Game,
+    strategies: &A,
+    friends: &SecondaryMap<AgentKey, Vec<AgentKey>>,
+) -> SecondaryMap<AgentKey, f64> {
+    let mut payoffs: SecondaryMap<AgentKey, f64> = strategies.keys().map(|k| (k, 0.0)).collect();
+    match game {
+        Game::PrisonersDilemma {
+            reward,
+            sucker,
+            temptation,
+            punishment,
+        } => {
+            // Each link plays once, from the agent with the smaller key
+            for (k, agent_friends) in friends {
+                for &friend in agent_friends.iter().filter(|&&friend| k < friend) {
+                    let (a, b) = match (strategies[k], strategies[friend]) {
+                        (Strategy::Cooperate, Strategy::Cooperate) => (reward, reward),
+                        (Strategy::Cooperate, Strategy::Defect) => (sucker, temptation),
+                        (Strategy::Defect, Strategy::Cooperate) => (temptation, sucker),
+                        (Strategy::Defect, Strategy::Defect) => (punishment, punishment),
+                    };
+                    payoffs[k] += a;
+                    payoffs[friend] += b;
+                }
+            }
+        }
+        Game::PublicGoods { synergy } => {
+            for (host, agent_friends) in friends {
+                let players = || std::iter::once(host).chain(agent_friends.iter().copied());
+                let contributions = players()
+                    .filter(|&k| strategies[k] == Strategy::Cooperate)
+                    .count() as f64;
+                let share = synergy * contributions / (agent_friends.len() + 1) as f64;
+                for k in players() {
+                    payoffs[k] += share
+                        - match strategies[k] {
+                            Strategy::Cooperate => 1.0,
+                            Strategy::Defect => 0.0,
+                        };
+                }
+            }
+        }
+    }
+    payoffs
+}
+
+/// Each agent with friends compares its payoff with that of a random friend, and adopts the strategy of the friend
+/// with probability 1 / (1 + exp((own payoff - friend's payoff) / noise)), where the noise is positive. Writes the
+/// strategies that change into the next time step, and returns the number of agents that changed strategy.
+pub fn imitate<A: Storage<AgentKey, Strategy>, R: Rng>(
+    strategies: &A,
+    next_strategies: &mut A,
+    payoffs: &SecondaryMap<AgentKey, f64>,
+    friends: &SecondaryMap<AgentKey, Vec<AgentKey>>,
+    noise: f64,
+    rng: &mut R,
+) -> usize {
+    let mut changes = 0;
+    for (k, &strategy) in strategies.iter() {
+        let friend = match friends.get(k).and_then(|friends| friends.choose(rng)) {
+            Some(&friend) => friend,
+            None => continue,
+        };
+        let probability = 1.0 / (1.0 + ((payoffs[k] - payoffs[friend]) / noise).exp());
+        if strategies[friend] != strategy && rng.gen_bool(probability) {
+            next_strategies[k] = strategies[friend];
+            changes += 1;
+        }
+    }
+    changes
+}
@@ -0,0 +1,307 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! GPU compute backend for the landscape update. Enable it with the `gpu` feature.
+//!
+//! Cell health lives on the GPU in two buffers that take turns as the current and the next state.
+//! Each time step, the CPU uploads the cells infected by agents, the compute shader spreads the disease
+//! across cells and lets infectious cells recover, and the CPU reads the new state back so agents can visit cells.
+//! The shader draws its random numbers from a hash of the cell index and a seed that the CPU samples from the scenario rng;
+//! results are reproducible, but they differ from the CPU version of the model.
+
+use crate::error::{self, Error};
+use crate::Health;
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+const SHADER: &str = r#"
+struct Params {
+    width: u32,
+    height: u32,
+    seed: u32,
+    infection_threshold: u32,
+    recovery_threshold: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Health of each cell at the beginning of the time step: 0 is S, 1 is I.
+@group(0) @binding(1) var<storage, read> cell_health: array<u32>;
+// Health of each cell after agents have visited the landscape.
+@group(0) @binding(2) var<storage, read> visited_cell_health: array<u32>;
+// Health of each cell at the end of the time step.
+@group(0) @binding(3) var<storage, read_write> next_cell_health: array<u32>;
+
+fn pcg_hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let this_cell_index = id.x;
+    if (this_cell_index >= params.width * params.height) {
+        return;
+    }
+    var rng = pcg_hash(this_cell_index ^ pcg_hash(params.seed));
+    var next = visited_cell_health[this_cell_index];
+    if (cell_health[this_cell_index] == 0u) {
+        let x = i32(this_cell_index % params.width);
+        let y = i32(this_cell_index / params.width);
+        let w = i32(params.width);
+        let h = i32(params.height);
+        var infected = false;
+        for (var dy = -1; dy <= 1 && !infected; dy = dy + 1) {
+            for (var dx = -1; dx <= 1 && !infected; dx = dx + 1) {
+                if (dx == 0 && dy == 0) {
+                    continue;
+                }
+                let neighbor_index = u32(((y + dy + h) % h) * w + (x + dx + w) % w);
+                if (cell_health[neighbor_index] == 1u) {
+                    rng = pcg_hash(rng);
+                    infected = rng < params.infection_threshold;
+                }
+            }
+        }
+        if (infected) {
+            next = 1u;
+        }
+    } else {
+        rng = pcg_hash(rng);
+        if (rng < params.recovery_threshold) {
+            next = 0u;
+        }
+    }
+    next_cell_health[this_cell_index] = next;
+}
+"#;
+
+/// Maps a probability to the threshold that a uniform u32 must fall below.
+fn threshold(probability: f64) -> u32 {
+    (probability.clamp(0.0, 1.0) * u32::MAX as f64) as u32
+}
+
+fn encode(health: Health) -> u32 {
+    match health {
+        Health::S => 0,
+        Health::I => 1,
+    }
+}
+
+/// GPU device and compute pipeline, shared by all scenarios
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl Gpu {
+    /// Opens the default GPU adapter and compiles the landscape shader; fails if there is no adapter or device.
+    pub fn new() -> error::Result<Self> {
+        let instance =
+            wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle_from_env());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .map_err(|e| Error::Gpu(e.to_string()))?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))
+                .map_err(|e| Error::Gpu(e.to_string()))?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("landscape"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("landscape"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+        })
+    }
+}
+
+/// Double-buffered cell health of one scenario, living on the GPU
+pub struct Landscape<'a> {
+    gpu: &'a Gpu,
+    /// Shader parameters; the seed changes every time step
+    params: [u32; 8],
+    params_buffer: wgpu::Buffer,
+    /// Cell health after agents have visited the landscape, uploaded every time step
+    visited_buffer: wgpu::Buffer,
+    /// Buffer to read the cell health back to the CPU
+    staging_buffer: wgpu::Buffer,
+    /// Bind group 0 reads state buffer 0 and writes state buffer 1; bind group 1 does the opposite.
+    bind_groups: [wgpu::BindGroup; 2],
+    state_buffers: [wgpu::Buffer; 2],
+    /// Index of the state buffer holding the current cell health
+    current: usize,
+    /// Upload and download storage
+    scratch: Vec<u32>,
+}
+
+impl<'a> Landscape<'a> {
+    pub fn new(
+        gpu: &'a Gpu,
+        width: u32,
+        height: u32,
+        cell_health: &[Health],
+        infection_probability: f64,
+        recovery_probability: f64,
+    ) -> Self {
+        assert_eq!(cell_health.len(), (width * height) as usize);
+        let params = [
+            width,
+            height,
+            0,
+            threshold(infection_probability),
+            threshold(recovery_probability),
+            0,
+            0,
+            0,
+        ];
+        let scratch: Vec<u32> = cell_health.iter().map(|&h| encode(h)).collect();
+        let size = (scratch.len() * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
+        let device = &gpu.device;
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("params"),
+            contents: bytemuck::cast_slice(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let state_buffers = [0, 1].map(|_| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("cell_health"),
+                contents: bytemuck::cast_slice(&scratch),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+            })
+        });
+        let visited_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("visited_cell_health"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = gpu.pipeline.get_bind_group_layout(0);
+        let bind_groups = [0, 1].map(|current: usize| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("landscape"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: state_buffers[current].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: visited_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: state_buffers[1 - current].as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        Self {
+            gpu,
+            params,
+            params_buffer,
+            visited_buffer,
+            staging_buffer,
+            bind_groups,
+            state_buffers,
+            current: 0,
+            scratch,
+        }
+    }
+
+    /// Spreads the disease across cells and lets infectious cells recover.
+    /// `next_cell_health` comes in with the infections caused by agents and leaves with the new state of the landscape.
+    pub fn step(&mut self, next_cell_health: &mut [Health], seed: u32) -> error::Result<()> {
+        let gpu = self.gpu;
+        self.params[2] = seed;
+        gpu.queue
+            .write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&self.params));
+        self.scratch
+            .iter_mut()
+            .zip(next_cell_health.iter())
+            .for_each(|(s, &h)| *s = encode(h));
+        gpu.queue
+            .write_buffer(&self.visited_buffer, 0, bytemuck::cast_slice(&self.scratch));
+        let next = 1 - self.current;
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&gpu.pipeline);
+            pass.set_bind_group(0, &self.bind_groups[self.current], &[]);
+            let workgroups = (self.scratch.len() as u32).div_ceil(WORKGROUP_SIZE);
+            pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.state_buffers[next], 0, &self.staging_buffer, 0, None);
+        gpu.queue.submit(Some(encoder.finish()));
+        self.current = next;
+        let slice = self.staging_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        let read_err = |e: &dyn std::fmt::Display| {
+            Error::Gpu(format!("could not read the landscape back: {}", e))
+        };
+        gpu.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| read_err(&e))?;
+        receiver
+            .recv()
+            .map_err(|e| read_err(&e))?
+            .map_err(|e| read_err(&e))?;
+        {
+            let view = slice.get_mapped_range();
+            let states: &[u32] = bytemuck::cast_slice(&view);
+            next_cell_health
+                .iter_mut()
+                .zip(states.iter())
+                .for_each(|(h, &s)| *h = if s == 0 { Health::S } else { Health::I });
+        }
+        self.staging_buffer.unmap();
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Groups of agents, such as workplaces or herds, and the rules that act on whole groups.
+//!
+//! Each agent belongs to one group for life: agents of the initial population join a random group, and children join
+//! the group of their parent. At each time step, each infected member of a group meets each susceptible member with
+//! `contact_probability`, and the meeting infects as any other exposure does. With a `quarantine_threshold`, a group
+//! whose fraction of infected members reaches the threshold goes into quarantine for `quarantine_duration` time steps:
+//! its members still meet each other, but they neither visit the landscape nor meet their friends in the network.
+
+use crate::define_components;
+use crate::deletion::Dependent;
+use crate::events::{InfectionEvent, Queue, Via};
+use crate::storage::Storage;
+use crate::{AgentKey, Health};
+use rand::Rng;
+
+define_components! {
+    /// Group of each agent
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Membership for AgentKey {
+        group: u32,
+    }
+}
+
+/// Membership of the agents and state of the groups
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct Groups {
+    pub membership: Membership,
+    /// Time step at which the quarantine of each group ends; groups never quarantined have 0
+    pub quarantine_ends: Vec<usize>,
+}
+
+impl Dependent<AgentKey> for Groups {
+    fn forget_dead(&mut self, alive: &dyn Fn(AgentKey) -> bool) {
+        self.membership.forget_dead(alive);
+    }
+}
+
+/// Members and infected members of a group
+#[derive(Clone, Copy, Default)]
+pub struct Census {
+    pub members: u32,
+    pub infected: u32,
+}
+
+impl Groups {
+    /// Whether the group of this agent is in quarantine at this time step; agents without a group are not.
+    pub fn quarantined(&self, agent: AgentKey, time_step: usize) -> bool {
+        self.membership
+            .group(agent)
+            .is_some_and(|&group| time_step < self.quarantine_ends[group as usize])
+    }
+
+    /// Members and infected members of each group
+    pub fn census<A: Storage<AgentKey, Health>>(&self, health: &A) -> Vec<Census> {
+        let mut census = vec![Census::default(); self.quarantine_ends.len()];
+        for (k, &h) in health.iter() {
+            if let Some(&group) = self.membership.group(k) {
+                let census = &mut census[group as usize];
+                census.members += 1;
+                census.infected += (h == Health::I) as u32;
+            }
+        }
+        census
+    }
+}
+
+/// Parameters of the groups
+pub struct GroupRules {
+    /// Number of groups
+    pub groups: usize,
+    /// Probability that an infected member of a group meets a susceptible member at each time step
+    pub contact_probability: f64,
+    /// Fraction of infected members at which a group goes into quarantine, if groups can
+    pub quarantine_threshold: Option<f64>,
+    /// Time steps of a quarantine
+    pub quarantine_duration: usize,
+}
+
+impl GroupRules {
+    /// Groups without members yet
+    pub fn create(&self) -> Groups {
+        Groups {
+            membership: Membership::default(),
+            quarantine_ends: vec![0; self.groups],
+        }
+    }
+
+    /// Puts an agent of the initial population into a random group, and returns the group.
+    pub fn join<R: Rng>(&self, groups: &mut Groups, agent: AgentKey, rng: &mut R) -> u32 {
+        let group = rng.gen_range(0..self.groups) as u32;
+        groups.membership.set_group(agent, group);
+        group
+    }
+
+    /// Starts the quarantine of the groups that reach the threshold at this time step.
+    pub fn quarantine<A: Storage<AgentKey, Health>>(
+        &self,
+        groups: &mut Groups,
+        health: &A,
+        time_step: usize,
+    ) {
+        let threshold = match self.quarantine_threshold {
+            Some(threshold) => threshold,
+            None => return,
+        };
+        let census = groups.census(health);
+        for (quarantine_end, census) in groups.quarantine_ends.iter_mut().zip(census) {
+            if *quarantine_end <= time_step
+                && census.members > 0
+                && census.infected as f64 >= threshold * census.members as f64
+            {
+                *quarantine_end = time_step + self.quarantine_duration;
+            }
+        }
+    }
+
+    /// Infection spreads within groups: each susceptible agent meets each infected member of its group with
+    /// `contact_probability`, and `infects` decides whether the meeting infects it.
+    pub fn transmit<A: Storage<AgentKey, Health>, R: Rng, F: FnMut(&mut R, AgentKey) -> bool>(
+        &self,
+        groups: &Groups,
+        health: &A,
+        next_health: &mut A,
+        mut infects: F,
+        rng: &mut R,
+        infections: &mut Queue<InfectionEvent>,
+    ) {
+        let census = groups.census(health);
+        for (k, &h) in health.iter() {
+            let group = match groups.membership.group(k) {
+                Some(&group) if h == Health::S => group,
+                _ => continue,
+            };
+            let infected = (0..census[group as usize].infected)
+                .any(|_| rng.gen_bool(self.contact_probability) && infects(rng, k));
+            if infected {
+                if next_health[k] == Health::S {
+                    infections.publish(InfectionEvent {
+                        source: None,
+                        target: k,
+                        via: Via::Group(group),
+                    });
+                }
+                next_health[k] = Health::I;
+            }
+        }
+    }
+}
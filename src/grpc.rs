@@ -0,0 +1,322 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Control of simulation runs by other services, over gRPC; see `proto/simulations.proto`.
+//!
+//! Each run is a child process of this program, started with `--managed`: the child reports its progress and metrics
+//! as JSON Lines on its standard output, and stops at the end of its current time step, saving its results, when its
+//! standard input closes. A run that fails leaves the server untouched, and runs stop with the server.
+
+use proto::run_status::State;
+use proto::simulations_server::{Simulations, SimulationsServer};
+use proto::{Metrics, RunId, RunStatus, ScenarioProgress, StartRunRequest};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::{ChildStdin, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+/// Messages of the service, generated from `proto/simulations.proto` by build.rs
+pub mod proto {
+    tonic::include_proto!("rust_agent_based_models");
+}
+
+/// Metrics of each run that new streams receive first
+const HISTORY: usize = 10_000;
+/// Lines of the standard error of each run kept for its status
+const LOG_LINES: usize = 20;
+/// Metrics kept for streams that fall behind
+const BACKLOG: usize = 1024;
+
+struct RunState {
+    state: State,
+    /// The run stops because CancelRun asked
+    cancelled: bool,
+    time_steps: u64,
+    metrics: Vec<String>,
+    scenarios: BTreeMap<u32, ScenarioProgress>,
+    recent: VecDeque<Metrics>,
+    log: VecDeque<String>,
+    /// Sends new metrics to the streams; dropped when the run ends, which ends the streams
+    updates: Option<broadcast::Sender<Metrics>>,
+}
+
+struct Run {
+    id: u64,
+    state: Mutex<RunState>,
+    /// Closing the standard input of the child stops the run
+    stdin: Mutex<Option<ChildStdin>>,
+}
+
+impl Run {
+    fn status(&self) -> RunStatus {
+        let state = self.state.lock().unwrap();
+        RunStatus {
+            id: self.id,
+            state: state.state as i32,
+            time_steps: state.time_steps,
+            scenarios: state.scenarios.values().cloned().collect(),
+            metrics: state.metrics.clone(),
+            log: state.log.iter().cloned().collect::<Vec<_>>().join("\n"),
+        }
+    }
+
+    /// Follows a line of the standard output of the child: the description of the run, the metrics of a sampled time
+    /// step, or the end of a scenario.
+    fn report(&self, line: &str) {
+        let mut state = self.state.lock().unwrap();
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => return push_log(&mut state.log, line),
+        };
+        if let Some(metrics) = message["metrics"].as_array() {
+            state.metrics = metrics
+                .iter()
+                .filter_map(|name| Some(name.as_str()?.to_string()))
+                .collect();
+            state.time_steps = message["time_steps"].as_u64().unwrap_or_default();
+            state.scenarios = message["scenarios"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|id| {
+                    let scenario = id.as_u64()? as u32;
+                    let progress = ScenarioProgress {
+                        scenario,
+                        ..Default::default()
+                    };
+                    Some((scenario, progress))
+                })
+                .collect();
+            return;
+        }
+        let scenario = message["scenario"].as_u64().unwrap_or_default() as u32;
+        if message["finished"].as_bool() == Some(true) {
+            if let Some(progress) = state.scenarios.get_mut(&scenario) {
+                progress.finished = true;
+            }
+            return;
+        }
+        let time_step = message["time_step"].as_u64().unwrap_or_default();
+        let values = state
+            .metrics
+            .iter()
+            .cloned()
+            .zip(
+                message["values"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|value| value.as_f64().unwrap_or(f64::NAN)),
+            )
+            .collect();
+        let metrics = Metrics {
+            scenario,
+            time_step,
+            values,
+        };
+        if let Some(progress) = state.scenarios.get_mut(&scenario) {
+            progress.time_step = time_step;
+        }
+        if state.recent.len() == HISTORY {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(metrics.clone());
+        // Without streams, nobody receives the metrics
+        if let Some(updates) = &state.updates {
+            updates.send(metrics).ok();
+        }
+    }
+}
+
+/// Keeps a line of the standard error of a run; progress messages overwrite one another after carriage returns.
+fn push_log(log: &mut VecDeque<String>, line: &str) {
+    let line = line.rsplit('\r').next().unwrap_or_default().trim_end();
+    if line.is_empty() {
+        return;
+    }
+    if log.len() == LOG_LINES {
+        log.pop_front();
+    }
+    log.push_back(line.to_string());
+}
+
+struct Service {
+    /// This program, which simulates the runs
+    program: PathBuf,
+    runs: Mutex<BTreeMap<u64, Arc<Run>>>,
+    next_id: AtomicU64,
+}
+
+impl Service {
+    fn run(&self, id: &RunId) -> Result<Arc<Run>, Status> {
+        self.runs
+            .lock()
+            .unwrap()
+            .get(&id.id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("There is no run {}", id.id)))
+    }
+}
+
+#[tonic::async_trait]
+impl Simulations for Service {
+    async fn start_run(
+        &self,
+        request: Request<StartRunRequest>,
+    ) -> Result<Response<RunStatus>, Status> {
+        let mut child = std::process::Command::new(&self.program)
+            .args(&request.get_ref().args)
+            .arg("--managed")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Status::internal(format!("Could not start the run: {}", e)))?;
+        let run = Arc::new(Run {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            state: Mutex::new(RunState {
+                state: State::Running,
+                cancelled: false,
+                time_steps: 0,
+                metrics: Vec::new(),
+                scenarios: BTreeMap::new(),
+                recent: VecDeque::new(),
+                log: VecDeque::new(),
+                updates: Some(broadcast::channel(BACKLOG).0),
+            }),
+            stdin: Mutex::new(child.stdin.take()),
+        });
+        let (stdout, stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+        let log_run = Arc::clone(&run);
+        let log = std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                push_log(&mut log_run.state.lock().unwrap().log, &line);
+            }
+        });
+        let reporting_run = Arc::clone(&run);
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                reporting_run.report(&line);
+            }
+            log.join().ok();
+            let exit = child.wait();
+            let mut state = reporting_run.state.lock().unwrap();
+            state.state = match exit {
+                Ok(exit) if exit.success() && state.cancelled => State::Cancelled,
+                Ok(exit) if exit.success() => State::Finished,
+                Ok(exit) => {
+                    push_log(&mut state.log, &format!("The run ended with {}", exit));
+                    State::Failed
+                }
+                Err(e) => {
+                    push_log(
+                        &mut state.log,
+                        &format!("Could not wait for the run: {}", e),
+                    );
+                    State::Failed
+                }
+            };
+            state.updates = None;
+        });
+        self.runs.lock().unwrap().insert(run.id, Arc::clone(&run));
+        Ok(Response::new(run.status()))
+    }
+
+    async fn get_status(&self, request: Request<RunId>) -> Result<Response<RunStatus>, Status> {
+        Ok(Response::new(self.run(request.get_ref())?.status()))
+    }
+
+    type StreamMetricsStream = ReceiverStream<Result<Metrics, Status>>;
+
+    async fn stream_metrics(
+        &self,
+        request: Request<RunId>,
+    ) -> Result<Response<Self::StreamMetricsStream>, Status> {
+        let run = self.run(request.get_ref())?;
+        // Subscribing under the same lock as the copy of the recent metrics loses and repeats none
+        let (recent, updates) = {
+            let state = run.state.lock().unwrap();
+            let recent: Vec<Metrics> = state.recent.iter().cloned().collect();
+            (
+                recent,
+                state.updates.as_ref().map(|updates| updates.subscribe()),
+            )
+        };
+        let (sender, receiver) = mpsc::channel(BACKLOG);
+        tokio::spawn(async move {
+            for metrics in recent {
+                if sender.send(Ok(metrics)).await.is_err() {
+                    return;
+                }
+            }
+            let mut updates = match updates {
+                Some(updates) => updates,
+                None => return,
+            };
+            loop {
+                match updates.recv().await {
+                    Ok(metrics) => {
+                        if sender.send(Ok(metrics)).await.is_err() {
+                            return;
+                        }
+                    }
+                    // Slow streams skip metrics rather than slowing the run down
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn cancel_run(&self, request: Request<RunId>) -> Result<Response<RunStatus>, Status> {
+        let run = self.run(request.get_ref())?;
+        let stdin = run.stdin.lock().unwrap().take();
+        if stdin.is_some() {
+            run.state.lock().unwrap().cancelled = true;
+        }
+        // The run notices that its standard input closed
+        drop(stdin);
+        Ok(Response::new(run.status()))
+    }
+}
+
+/// Serves the Simulations service at this address until the program stops; runs are child processes of this program.
+pub fn serve(address: SocketAddr, program: PathBuf) -> std::io::Result<()> {
+    let service = Service {
+        program,
+        runs: Mutex::new(BTreeMap::new()),
+        next_id: AtomicU64::new(0),
+    };
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .build()?
+        .block_on(
+            tonic::transport::Server::builder()
+                .add_service(SimulationsServer::new(service))
+                .serve(address),
+        )
+        .map_err(std::io::Error::other)
+}
@@ -0,0 +1,132 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Parameters that differ between agents, such as their probability of recovery.
+//!
+//! Each parameter of agents given a distribution in the configuration, such as
+//! `"agent_susceptibility": "Gamma(2, 0.5)"`, is drawn once for each agent when the agent is created, and kept as a
+//! component of the agent. Parameters without a distribution take the value of the model for every agent, and a run
+//! without any keeps no components at all, drawing the same random numbers as before. With a distribution of
+//! mutations, such as `"trait_mutation": "Normal(0, 0.05)"`, agents born during the run inherit the parameters of their
+//! parent instead, each plus a mutation.
+
+use crate::define_components;
+use crate::distributions::BoxedSampler;
+use crate::AgentKey;
+use rand::RngCore;
+
+define_components! {
+    /// Parameters of each agent, drawn when it is created: its probability of recovering at each time step while
+    /// infectious; its susceptibility, which multiplies its probability of infection; its movement scale, which
+    /// stretches the distance from the center of the landscape of the cells that it visits; and its sociability, which
+    /// multiplies its probability of making a new link.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AgentTraits for AgentKey {
+        recovery_probability: f64,
+        susceptibility: f64,
+        movement_scale: f64,
+        sociability: f64,
+    }
+}
+
+/// Distributions of the parameters of agents
+pub struct Heterogeneity {
+    /// Probability of recovery of every agent, unless it has a distribution
+    pub recovery_probability: f64,
+    pub recovery_distro: Option<BoxedSampler>,
+    pub susceptibility_distro: Option<BoxedSampler>,
+    pub movement_scale_distro: Option<BoxedSampler>,
+    pub sociability_distro: Option<BoxedSampler>,
+    /// Mutation added to each parameter that a child inherits from its parent; without it, children draw their own.
+    pub mutation_distro: Option<BoxedSampler>,
+}
+
+impl Heterogeneity {
+    /// Whether any parameter differs between agents
+    pub fn any(&self) -> bool {
+        self.recovery_distro.is_some()
+            || self.susceptibility_distro.is_some()
+            || self.movement_scale_distro.is_some()
+            || self.sociability_distro.is_some()
+    }
+
+    /// Draws the parameters of an agent, and returns them in the order of `AgentTraits::FIELDS`. Probabilities fall
+    /// back into [0, 1], and the other parameters are never negative.
+    pub fn draw(
+        &self,
+        traits: &mut AgentTraits,
+        agent: AgentKey,
+        rng: &mut dyn RngCore,
+    ) -> [f64; 4] {
+        let mut draw = |distro: &Option<BoxedSampler>, default: f64| {
+            distro.as_ref().map_or(default, |distro| distro.sample(rng))
+        };
+        let recovery_probability =
+            draw(&self.recovery_distro, self.recovery_probability).clamp(0.0, 1.0);
+        let susceptibility = draw(&self.susceptibility_distro, 1.0).max(0.0);
+        let movement_scale = draw(&self.movement_scale_distro, 1.0).max(0.0);
+        let sociability = draw(&self.sociability_distro, 1.0).max(0.0);
+        traits.set_recovery_probability(agent, recovery_probability);
+        traits.set_susceptibility(agent, susceptibility);
+        traits.set_movement_scale(agent, movement_scale);
+        traits.set_sociability(agent, sociability);
+        [
+            recovery_probability,
+            susceptibility,
+            movement_scale,
+            sociability,
+        ]
+    }
+
+    /// Gives a child the parameters of its parent, each plus a mutation where the parameter differs between agents, and
+    /// returns them in the order of `AgentTraits::FIELDS`. Without a distribution of mutations, the child draws its
+    /// parameters as in `draw`.
+    pub fn inherit(
+        &self,
+        traits: &mut AgentTraits,
+        parent: AgentKey,
+        child: AgentKey,
+        rng: &mut dyn RngCore,
+    ) -> [f64; 4] {
+        let mutation_distro = match &self.mutation_distro {
+            Some(mutation_distro) => mutation_distro,
+            None => return self.draw(traits, child, rng),
+        };
+        let mut mutate = |distro: &Option<BoxedSampler>, value: f64| match distro {
+            Some(_) => value + mutation_distro.sample(rng),
+            None => value,
+        };
+        let recovery_probability =
+            mutate(&self.recovery_distro, traits.recovery_probability[parent]).clamp(0.0, 1.0);
+        let susceptibility =
+            mutate(&self.susceptibility_distro, traits.susceptibility[parent]).max(0.0);
+        let movement_scale =
+            mutate(&self.movement_scale_distro, traits.movement_scale[parent]).max(0.0);
+        let sociability = mutate(&self.sociability_distro, traits.sociability[parent]).max(0.0);
+        traits.set_recovery_probability(child, recovery_probability);
+        traits.set_susceptibility(child, susceptibility);
+        traits.set_movement_scale(child, movement_scale);
+        traits.set_sociability(child, sociability);
+        [
+            recovery_probability,
+            susceptibility,
+            movement_scale,
+            sociability,
+        ]
+    }
+}
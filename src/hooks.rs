@@ -0,0 +1,146 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Observers of the lifecycle of the model, for custom logging or coupling with other models.
+//!
+//! Attach observers to a scenario's `Hooks` right after it starts: either a type that implements `Observer`,
+//! or a closure through `on_step`, `on_infection`, `on_death`, or `on_link`. The model calls every observer,
+//! in the order they were attached, at the start of each time step; at its end, `consume` hands the observers
+//! the new links, infections, and deaths published to the event bus. Without observers, hooks cost next to nothing.
+
+use crate::events::EventBus;
+use crate::{AgentKey, LinkKey};
+
+/// Reacts to lifecycle events; all methods do nothing by default.
+pub trait Observer {
+    /// Start of a time step
+    fn on_step(&mut self, _time_step: usize) {}
+    /// A susceptible agent becomes infected at the end of a time step.
+    fn on_infection(&mut self, _agent: AgentKey, _time_step: usize) {}
+    /// An agent dies at the end of a time step.
+    fn on_death(&mut self, _agent: AgentKey, _time_step: usize) {}
+    /// A link forms between two agents.
+    fn on_link(&mut self, _link: LinkKey, _agents: (AgentKey, AgentKey), _time_step: usize) {}
+}
+
+struct OnStep<F>(F);
+impl<F: FnMut(usize)> Observer for OnStep<F> {
+    fn on_step(&mut self, time_step: usize) {
+        (self.0)(time_step)
+    }
+}
+
+struct OnInfection<F>(F);
+impl<F: FnMut(AgentKey, usize)> Observer for OnInfection<F> {
+    fn on_infection(&mut self, agent: AgentKey, time_step: usize) {
+        (self.0)(agent, time_step)
+    }
+}
+
+struct OnDeath<F>(F);
+impl<F: FnMut(AgentKey, usize)> Observer for OnDeath<F> {
+    fn on_death(&mut self, agent: AgentKey, time_step: usize) {
+        (self.0)(agent, time_step)
+    }
+}
+
+struct OnLink<F>(F);
+impl<F: FnMut(LinkKey, (AgentKey, AgentKey), usize)> Observer for OnLink<F> {
+    fn on_link(&mut self, link: LinkKey, agents: (AgentKey, AgentKey), time_step: usize) {
+        (self.0)(link, agents, time_step)
+    }
+}
+
+/// Observers attached to one scenario
+#[derive(Default)]
+pub struct Hooks<'a> {
+    observers: Vec<Box<dyn Observer + 'a>>,
+}
+
+impl<'a> Hooks<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True if no observer is attached
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    pub fn attach<O: Observer + 'a>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn on_step<F: FnMut(usize) + 'a>(&mut self, f: F) {
+        self.attach(OnStep(f));
+    }
+
+    pub fn on_infection<F: FnMut(AgentKey, usize) + 'a>(&mut self, f: F) {
+        self.attach(OnInfection(f));
+    }
+
+    pub fn on_death<F: FnMut(AgentKey, usize) + 'a>(&mut self, f: F) {
+        self.attach(OnDeath(f));
+    }
+
+    pub fn on_link<F: FnMut(LinkKey, (AgentKey, AgentKey), usize) + 'a>(&mut self, f: F) {
+        self.attach(OnLink(f));
+    }
+
+    pub fn step(&mut self, time_step: usize) {
+        self.observers.iter_mut().for_each(|o| o.on_step(time_step));
+    }
+
+    pub fn infection(&mut self, agent: AgentKey, time_step: usize) {
+        self.observers
+            .iter_mut()
+            .for_each(|o| o.on_infection(agent, time_step));
+    }
+
+    pub fn death(&mut self, agent: AgentKey, time_step: usize) {
+        self.observers
+            .iter_mut()
+            .for_each(|o| o.on_death(agent, time_step));
+    }
+
+    pub fn link(&mut self, link: LinkKey, agents: (AgentKey, AgentKey), time_step: usize) {
+        self.observers
+            .iter_mut()
+            .for_each(|o| o.on_link(link, agents, time_step));
+    }
+
+    /// Hands the links, infections, and deaths of a time step to the observers, in this order.
+    /// The events stay in the bus for other systems.
+    pub fn consume(&mut self, events: &EventBus, time_step: usize) {
+        if self.is_empty() {
+            return;
+        }
+        events
+            .links
+            .iter()
+            .for_each(|e| self.link(e.link, e.agents, time_step));
+        events
+            .infections
+            .iter()
+            .for_each(|e| self.infection(e.target, time_step));
+        events
+            .deaths
+            .iter()
+            .for_each(|e| self.death(e.agent, time_step));
+    }
+}
@@ -0,0 +1,151 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Immunity after infection: each agent keeps a history of its infections, and the protection they leave wanes with
+//! the time since the agent last recovered, so that agents can be infected again.
+//!
+//! Each prior infection protects independently with probability `protection`, so an agent with `n` prior infections
+//! starts from a protection of `1 - (1 - protection)^n`. The waning curve then scales that protection down with the
+//! time steps since recovery; without one, protection lasts. The susceptibility of the agent, which multiplies its
+//! probability of infection, is one minus its protection.
+
+use crate::components::SecondaryMap;
+use crate::deletion::Dependent;
+use crate::events::EventBus;
+use crate::AgentKey;
+
+/// Fraction of the protection left after some time steps since recovery
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waning {
+    /// Protection halves every `half_life` time steps
+    Exponential { half_life: f64 },
+    /// Protection lasts for a time that follows a Weibull distribution: `exp(-(t / scale)^shape)`. Large shapes
+    /// approach a protection that ends at `scale`.
+    Weibull { scale: f64, shape: f64 },
+}
+
+impl Waning {
+    /// The exponential curve from one parameter, or the Weibull curve from two
+    pub fn from_parameters(parameters: &[f64]) -> Option<Self> {
+        match *parameters {
+            [half_life] if half_life > 0.0 => Some(Waning::Exponential { half_life }),
+            [scale, shape] if scale > 0.0 && shape > 0.0 => Some(Waning::Weibull { scale, shape }),
+            _ => None,
+        }
+    }
+
+    pub fn remaining(&self, time_steps: f64) -> f64 {
+        match *self {
+            Waning::Exponential { half_life } => 0.5f64.powf(time_steps / half_life),
+            Waning::Weibull { scale, shape } => (-(time_steps / scale).powf(shape)).exp(),
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<f64> {
+        match *self {
+            Waning::Exponential { half_life } => vec![half_life],
+            Waning::Weibull { scale, shape } => vec![scale, shape],
+        }
+    }
+}
+
+/// Parameters of immunity; see the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Immunity {
+    /// Probability that an infection protects against the next ones, right after recovery
+    pub protection: f64,
+    /// Waning of the protection, if it wanes
+    pub waning: Option<Waning>,
+}
+
+impl Immunity {
+    /// Protection of an agent at this time step, from its history
+    pub fn protection(&self, history: &InfectionHistory, agent: AgentKey, time_step: usize) -> f64 {
+        let infections = history.infections(agent);
+        if infections == 0 {
+            return 0.0;
+        }
+        let protection = 1.0 - (1.0 - self.protection).powi(infections as i32);
+        match (self.waning, history.recovered_at(agent)) {
+            (Some(waning), Some(recovered_at)) => {
+                protection
+                    * waning.remaining(time_step.saturating_sub(recovered_at as usize) as f64)
+            }
+            _ => protection,
+        }
+    }
+
+    /// Factor of the probability of infection of an agent at this time step
+    pub fn susceptibility(
+        &self,
+        history: &InfectionHistory,
+        agent: AgentKey,
+        time_step: usize,
+    ) -> f64 {
+        1.0 - self.protection(history, agent, time_step)
+    }
+}
+
+/// Infections of each agent so far in a scenario
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct InfectionHistory {
+    /// Number of infections of each agent infected during the scenario
+    infections: SecondaryMap<AgentKey, u32>,
+    /// First time step at which each agent was susceptible again after its last infection
+    recovered_at: SecondaryMap<AgentKey, u32>,
+    /// Infections of agents never infected before, seeds included
+    pub first_infections: usize,
+    /// Infections of agents infected before
+    pub reinfections: usize,
+}
+
+impl InfectionHistory {
+    /// Infections of an agent so far
+    pub fn infections(&self, agent: AgentKey) -> u32 {
+        self.infections.get(agent).copied().unwrap_or(0)
+    }
+
+    /// First time step of the agent after its last recovery, if it recovered
+    pub fn recovered_at(&self, agent: AgentKey) -> Option<u32> {
+        self.recovered_at.get(agent).copied()
+    }
+
+    /// Follows the infections and recoveries of a time step.
+    pub fn consume(&mut self, events: &EventBus, time_step: usize) {
+        for event in events.infections.iter() {
+            let infections = self.infections(event.target);
+            if infections == 0 {
+                self.first_infections += 1;
+            } else {
+                self.reinfections += 1;
+            }
+            self.infections.insert(event.target, infections + 1);
+        }
+        for event in events.recoveries.iter() {
+            self.recovered_at.insert(event.agent, time_step as u32 + 1);
+        }
+    }
+}
+
+impl Dependent<AgentKey> for InfectionHistory {
+    fn forget_dead(&mut self, alive: &dyn Fn(AgentKey) -> bool) {
+        self.infections.forget_dead(alive);
+        self.recovered_at.forget_dead(alive);
+    }
+}
@@ -0,0 +1,244 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Targeted interventions: vaccination campaigns that choose their targets by a rule, such as the agents with the most
+//! links, the oldest ones, those living in a patch of the landscape, or agents at random.
+//!
+//! A rule implements `Targeting`: it receives the `World` at the start of a time step, the agents it may choose from,
+//! and how many to choose. `Ranking` prefers the agents with the highest score, by any measure of the world, and
+//! `Eligible` chooses at random among the agents that satisfy a predicate, so most rules are a closure away; write
+//! them in `main.rs`, where the vaccination of each scenario begins, to compare them without touching the dynamics.
+//!
+//! A campaign starts at time step `start` and repeats every `every` time steps, if given: each round vaccinates the
+//! fraction `fraction` of the agents alive, chosen among the unvaccinated ones, infected or not. Vaccinated agents
+//! stay vaccinated for life, and the vaccine reduces their probability of infection by the fraction `efficacy`.
+
+use crate::collector::World;
+use crate::define_components;
+use crate::storage::Storage;
+use crate::streams::Stream;
+use crate::AgentKey;
+use rand::seq::SliceRandom;
+use std::fmt;
+use std::str::FromStr;
+
+define_components! {
+    /// Whether each agent is vaccinated; agents without a value, such as newborns, are not.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AgentVaccination for AgentKey {
+        vaccinated: bool,
+    }
+}
+
+impl AgentVaccination {
+    pub fn is_vaccinated(&self, agent: AgentKey) -> bool {
+        self.vaccinated(agent).copied().unwrap_or(false)
+    }
+}
+
+/// Rule that chooses the targets of an intervention
+pub trait Targeting: Send + Sync {
+    /// Chooses up to `count` agents among `candidates`, in the world at the start of a time step.
+    fn select(
+        &self,
+        world: &World,
+        candidates: Vec<AgentKey>,
+        count: usize,
+        rng: &mut Stream,
+    ) -> Vec<AgentKey>;
+}
+
+/// Agents at random
+pub struct Random;
+
+impl Targeting for Random {
+    fn select(
+        &self,
+        _world: &World,
+        mut candidates: Vec<AgentKey>,
+        count: usize,
+        rng: &mut Stream,
+    ) -> Vec<AgentKey> {
+        candidates.shuffle(rng);
+        candidates.truncate(count);
+        candidates
+    }
+}
+
+/// Agents with the highest score first, ties in random order; agents without a score are never chosen.
+pub struct Ranking<F>(pub F);
+
+impl<F: Fn(&World, AgentKey) -> Option<f64> + Send + Sync> Targeting for Ranking<F> {
+    fn select(
+        &self,
+        world: &World,
+        mut candidates: Vec<AgentKey>,
+        count: usize,
+        rng: &mut Stream,
+    ) -> Vec<AgentKey> {
+        candidates.shuffle(rng);
+        let mut scored: Vec<(f64, AgentKey)> = candidates
+            .into_iter()
+            .filter_map(|k| Some(((self.0)(world, k)?, k)))
+            .collect();
+        // The sort is stable, so the shuffle breaks ties
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        scored.into_iter().take(count).map(|(_, k)| k).collect()
+    }
+}
+
+/// Agents at random among those that satisfy a predicate
+pub struct Eligible<F>(pub F);
+
+impl<F: Fn(&World, AgentKey) -> bool + Send + Sync> Targeting for Eligible<F> {
+    fn select(
+        &self,
+        world: &World,
+        mut candidates: Vec<AgentKey>,
+        count: usize,
+        rng: &mut Stream,
+    ) -> Vec<AgentKey> {
+        candidates.retain(|&k| (self.0)(world, k));
+        Random.select(world, candidates, count, rng)
+    }
+}
+
+/// Built-in targeting rules, by name, as in `--vaccinate top-degree`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    Random,
+    /// Agents with the most links
+    #[cfg(feature = "net")]
+    TopDegree,
+    /// Agents born first, if the model keeps ages
+    Oldest,
+    /// Agents whose homes lie in the rectangle of cells from (x0, y0) to (x1, y1), inclusive
+    #[cfg(feature = "landscape")]
+    Patch([f64; 4]),
+}
+
+impl Rule {
+    pub fn targeting(self) -> Box<dyn Targeting> {
+        match self {
+            Rule::Random => Box::new(Random),
+            #[cfg(feature = "net")]
+            Rule::TopDegree => Box::new(Ranking(|world: &World, k| {
+                Some(*world.degree.get(k)? as f64)
+            })),
+            Rule::Oldest => Box::new(Ranking(|world: &World, k| {
+                Some(-world.ages?.born(k)? as f64)
+            })),
+            #[cfg(feature = "landscape")]
+            Rule::Patch([x0, y0, x1, y1]) => Box::new(Eligible(move |world: &World, k| {
+                world
+                    .homes
+                    .and_then(|homes| homes.home(k))
+                    .is_some_and(|&[x, y]| {
+                        (x0..x1 + 1.0).contains(&x.floor()) && (y0..y1 + 1.0).contains(&y.floor())
+                    })
+            })),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Rule::Random),
+            #[cfg(feature = "net")]
+            "top-degree" => Ok(Rule::TopDegree),
+            "oldest" => Ok(Rule::Oldest),
+            #[cfg(feature = "landscape")]
+            _ if s.starts_with("patch:") => {
+                let corners: Vec<f64> = s["patch:".len()..]
+                    .split(',')
+                    .map(|corner| corner.trim().parse().ok())
+                    .collect::<Option<_>>()
+                    .filter(|corners: &Vec<f64>| corners.len() == 4)
+                    .ok_or_else(|| format!("{} is not a patch:X0,Y0,X1,Y1 of cells", s))?;
+                Ok(Rule::Patch([
+                    corners[0], corners[1], corners[2], corners[3],
+                ]))
+            }
+            _ => Err(format!(
+                "{} is not a targeting rule: random, top-degree, oldest, or patch:X0,Y0,X1,Y1",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Random => write!(f, "random"),
+            #[cfg(feature = "net")]
+            Rule::TopDegree => write!(f, "top-degree"),
+            Rule::Oldest => write!(f, "oldest"),
+            #[cfg(feature = "landscape")]
+            Rule::Patch([x0, y0, x1, y1]) => write!(f, "patch:{},{},{},{}", x0, y0, x1, y1),
+        }
+    }
+}
+
+/// Vaccination campaign; see the module documentation.
+pub struct Vaccination {
+    pub targeting: Box<dyn Targeting>,
+    /// First time step of the campaign
+    pub start: usize,
+    /// Time steps between rounds, if the campaign repeats
+    pub every: Option<usize>,
+    /// Fraction of the agents alive that each round vaccinates
+    pub fraction: f64,
+    /// Fraction by which the vaccine reduces the probability of infection of an agent
+    pub efficacy: f64,
+}
+
+impl Vaccination {
+    /// Whether a round of the campaign takes place at this time step
+    pub fn due(&self, time_step: usize) -> bool {
+        match (time_step.checked_sub(self.start), self.every) {
+            (Some(elapsed), Some(every)) => elapsed.is_multiple_of(every),
+            (Some(elapsed), None) => elapsed == 0,
+            (None, _) => false,
+        }
+    }
+
+    /// Agents that the round of this time step vaccinates
+    pub fn targets(&self, world: &World, rng: &mut Stream) -> Vec<AgentKey> {
+        let count = (self.fraction * Storage::len(world.health) as f64).round() as usize;
+        let candidates = Storage::keys(world.health)
+            .filter(|&k| {
+                !world
+                    .vaccinated
+                    .is_some_and(|vaccinated| vaccinated.is_vaccinated(k))
+            })
+            .collect();
+        self.targeting.select(world, candidates, count, rng)
+    }
+
+    /// Factor of the probability of infection of an agent: 1 unless the agent is vaccinated
+    pub fn susceptibility(&self, vaccinated: &AgentVaccination, agent: AgentKey) -> f64 {
+        if vaccinated.is_vaccinated(agent) {
+            1.0 - self.efficacy
+        } else {
+            1.0
+        }
+    }
+}
@@ -0,0 +1,296 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Landscape dynamics: the disease spreads across cells and infectious cells recover.
+//!
+//! Cells are stored row by row as one byte each (see `Health`). Counting infected neighbors works on whole rows,
+//! so the compiler can vectorize it; the random draws then happen once per cell that can change its health.
+//! A susceptible cell with `k` infectious neighbors becomes infected with probability `1 - (1 - p)^k`,
+//! which is the same as trying each infectious neighbor in turn.
+//! Counting can also run on several threads, one row at a time; the random draws stay sequential, so results do not change.
+//!
+//! Agents can also catch the disease from cells through a dose-response function: instead of one chance of infection
+//! per visit to an infected cell, an agent takes in a dose at every infected cell it visits during a time step and
+//! becomes infected with the probability that `DoseResponse` gives for the total dose.
+//!
+//! Infectious cells recover with a constant probability at each time step, unless a `Decay` makes recovery depend on
+//! the time since the cell was contaminated, on the season, or on both. The hazard of recovery over a time step then
+//! follows a Weibull survival curve of the pathogen in the environment, `exp(-(t / scale)^shape)`, and a seasonal
+//! factor, a proxy of temperature, multiplies it.
+
+use crate::define_components;
+use crate::Health;
+use rand::distributions::{Bernoulli, Distribution};
+use rand::Rng;
+use rayon::prelude::*;
+use wrapping_coords2d::WrappingCoords2d;
+
+define_components! {
+    /// Time step since which each infectious cell is contaminated; cells contaminated before the timers start count
+    /// from time step 0.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct CellContamination for cells {
+        contaminated_at: u32,
+    }
+}
+
+/// Survival of the pathogen in a cell, following a Weibull curve: `exp(-(t / scale)^shape)` after `t` time steps
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Persistence {
+    pub scale: f64,
+    pub shape: f64,
+}
+
+impl Persistence {
+    /// The exponential curve from one parameter, `[scale]`, or the Weibull curve from two, `[scale, shape]`
+    pub fn from_parameters(parameters: &[f64]) -> Option<Self> {
+        match *parameters {
+            [scale] if scale > 0.0 => Some(Persistence { scale, shape: 1.0 }),
+            [scale, shape] if scale > 0.0 && shape > 0.0 => Some(Persistence { scale, shape }),
+            _ => None,
+        }
+    }
+
+    /// Cumulative hazard of recovery from this many time steps of contamination to the next
+    fn hazard(&self, age: usize) -> f64 {
+        let cumulative = |t: f64| (t / self.scale).powf(self.shape);
+        cumulative(age as f64 + 1.0) - cumulative(age as f64)
+    }
+}
+
+/// Seasonal factor of the decay of contamination: `1 + amplitude cos(2 pi (t - peak) / period)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Seasonality {
+    pub amplitude: f64,
+    /// Time steps in a season cycle, such as a year
+    pub period: f64,
+    /// Time step at which contamination decays fastest, such as the hottest day
+    pub peak: f64,
+}
+
+impl Seasonality {
+    pub fn factor(&self, time_step: usize) -> f64 {
+        let phase = (time_step as f64 - self.peak) / self.period;
+        1.0 + self.amplitude * (std::f64::consts::TAU * phase).cos()
+    }
+}
+
+/// Recovery of infectious cells over time since contamination and over the seasons; see the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decay {
+    /// Persistence of the pathogen; without it, cells recover with `recovery_probability`
+    pub persistence: Option<Persistence>,
+    pub seasonality: Option<Seasonality>,
+    /// Probability of recovery at each time step without persistence, before the seasonal factor
+    pub recovery_probability: f64,
+}
+
+impl Decay {
+    /// Probability that an infectious cell recovers at this time step, after `age` time steps of contamination if
+    /// known
+    pub fn recovery_probability(&self, age: Option<usize>, time_step: usize) -> f64 {
+        let hazard = match (self.persistence, age) {
+            (Some(persistence), Some(age)) => persistence.hazard(age),
+            _ => -(-self.recovery_probability).ln_1p(),
+        };
+        let factor = self
+            .seasonality
+            .map_or(1.0, |seasonality| seasonality.factor(time_step));
+        -(-hazard * factor).exp_m1()
+    }
+}
+
+/// Cell dynamics of one scenario, including storage reused across time steps
+pub struct CellDynamics {
+    width: usize,
+    /// Probability of infection of a susceptible cell, indexed by its number of infectious neighbors
+    infection_distros: Vec<Bernoulli>,
+    recovery_distro: Bernoulli,
+    /// Recovery that depends on the contamination and the season, instead of recovery_distro
+    decay: Option<Decay>,
+    /// 1 for each infectious cell, 0 otherwise
+    infected: Vec<u8>,
+    /// Infectious cells in each horizontal window of three cells
+    row_sums: Vec<u8>,
+    /// Number of infectious neighbors of each cell
+    counts: Vec<u8>,
+    /// Count infected neighbors on the threads of the rayon pool
+    parallel: bool,
+}
+
+impl CellDynamics {
+    pub fn new(
+        coord: &WrappingCoords2d,
+        infection_probability: f64,
+        recovery_distro: Bernoulli,
+        parallel: bool,
+    ) -> Self {
+        let width = coord.width() as usize;
+        assert!(
+            coord.index(1, 0) == 1 % width && coord.index(0, 1) == width % coord.size(),
+            "The landscape must store cells row by row"
+        );
+        let infection_distros = (0..=8)
+            .map(|k| Bernoulli::new(1.0 - (1.0 - infection_probability).powi(k)).unwrap())
+            .collect();
+        Self {
+            width,
+            infection_distros,
+            recovery_distro,
+            decay: None,
+            infected: vec![0; coord.size()],
+            row_sums: vec![0; coord.size()],
+            counts: vec![0; coord.size()],
+            parallel,
+        }
+    }
+
+    /// Infectious cells recover following this decay instead of the constant recovery probability.
+    pub fn with_decay(mut self, decay: Option<Decay>) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// Counts the infectious neighbors of every cell in its Moore neighborhood, wrapping around the edges.
+    fn count_infected_neighbors(&mut self, cell_health: &[Health]) {
+        let w = self.width;
+        self.infected
+            .iter_mut()
+            .zip(cell_health.iter())
+            .for_each(|(i, &h)| *i = h as u8);
+        let row_sums = |(r, s): (&[u8], &mut [u8])| {
+            if w >= 3 {
+                s[1..w - 1]
+                    .iter_mut()
+                    .zip(r[..w - 2].iter().zip(r[1..w - 1].iter()).zip(r[2..].iter()))
+                    .for_each(|(s, ((&a, &b), &c))| *s = a + b + c);
+            }
+            s[0] = r[w - 1] + r[0] + r[1 % w];
+            s[w - 1] = r[(2 * w - 2) % w] + r[w - 1] + r[0];
+        };
+        if self.parallel {
+            self.infected
+                .par_chunks_exact(w)
+                .zip(self.row_sums.par_chunks_exact_mut(w))
+                .for_each(row_sums);
+        } else {
+            self.infected
+                .chunks_exact(w)
+                .zip(self.row_sums.chunks_exact_mut(w))
+                .for_each(row_sums);
+        }
+        let h = self.infected.len() / w;
+        let (all_row_sums, infected) = (&self.row_sums, &self.infected);
+        let counts = |(y, c): (usize, &mut [u8])| {
+            let above = &all_row_sums[((y + h - 1) % h) * w..][..w];
+            let this = &all_row_sums[y * w..][..w];
+            let below = &all_row_sums[((y + 1) % h) * w..][..w];
+            let infected = &infected[y * w..][..w];
+            c.iter_mut()
+                .zip(above.iter().zip(this.iter()).zip(below.iter()))
+                .zip(infected.iter())
+                .for_each(|((c, ((&a, &t), &b)), &i)| *c = a + t + b - i);
+        };
+        if self.parallel {
+            self.counts
+                .par_chunks_exact_mut(w)
+                .enumerate()
+                .for_each(counts);
+        } else {
+            self.counts.chunks_exact_mut(w).enumerate().for_each(counts);
+        }
+    }
+
+    /// Spreads the disease across cells and lets infectious cells recover. The timers of contamination, if any,
+    /// start for the cells that agents or neighbors contaminate.
+    pub fn step<R: Rng>(
+        &mut self,
+        cell_health: &[Health],
+        next_cell_health: &mut [Health],
+        time_step: usize,
+        mut contamination: Option<&mut CellContamination>,
+        rng: &mut R,
+    ) {
+        self.count_infected_neighbors(cell_health);
+        cell_health
+            .iter()
+            .zip(self.counts.iter())
+            .zip(next_cell_health.iter_mut())
+            .enumerate()
+            .for_each(|(idx, ((&h, &k), next_h))| match h {
+                Health::S => {
+                    if k > 0 && self.infection_distros[k as usize].sample(rng) {
+                        *next_h = Health::I;
+                    }
+                    if let (Health::I, Some(contamination)) = (*next_h, contamination.as_deref_mut()) {
+                        contamination.set_contaminated_at(idx, time_step as u32 + 1);
+                    }
+                }
+                Health::I => {
+                    let recovers = match &self.decay {
+                        Some(decay) => {
+                            let age = contamination.as_deref().map(|contamination| {
+                                time_step.saturating_sub(*contamination.contaminated_at(idx) as usize)
+                            });
+                            rng.gen_bool(decay.recovery_probability(age, time_step).min(1.0))
+                        }
+                        None => self.recovery_distro.sample(rng),
+                    };
+                    if recovers {
+                        *next_h = Health::S;
+                    }
+                }
+            });
+    }
+}
+
+/// Probability of infection as a function of the dose taken in during a time step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DoseResponse {
+    /// Each unit of dose infects independently with probability `r`: `1 - exp(-r d)`
+    Exponential { r: f64 },
+    /// Susceptibility that differs between exposures, following a beta distribution: `1 - (1 + d / beta)^-alpha`
+    BetaPoisson { alpha: f64, beta: f64 },
+}
+
+impl DoseResponse {
+    /// The exponential model from one parameter, or the beta-Poisson model from two
+    pub fn from_parameters(parameters: &[f64]) -> Option<Self> {
+        match *parameters {
+            [r] if r > 0.0 => Some(DoseResponse::Exponential { r }),
+            [alpha, beta] if alpha > 0.0 && beta > 0.0 => {
+                Some(DoseResponse::BetaPoisson { alpha, beta })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn probability(&self, dose: f64) -> f64 {
+        match *self {
+            DoseResponse::Exponential { r } => -(-r * dose).exp_m1(),
+            DoseResponse::BetaPoisson { alpha, beta } => 1.0 - (1.0 + dose / beta).powf(-alpha),
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<f64> {
+        match *self {
+            DoseResponse::Exponential { r } => vec![r],
+            DoseResponse::BetaPoisson { alpha, beta } => vec![alpha, beta],
+        }
+    }
+}
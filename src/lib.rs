@@ -0,0 +1,130 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The hot loops of the model, kept apart from `main()` so that the benchmarks at `benches/` can measure them.
+//! The model itself still lives at `main()`.
+
+#[cfg(feature = "arrow-output")]
+pub mod arrow_output;
+#[cfg(feature = "net")]
+pub mod awareness;
+#[cfg(all(
+    feature = "arrow-output",
+    not(any(feature = "parquet-output", feature = "arrow-ipc-output"))
+))]
+compile_error!("The arrow-output feature needs a file format; please enable parquet-output or arrow-ipc-output in Cargo.toml");
+pub mod analysis;
+pub mod buffered;
+#[cfg(feature = "checkpoint")]
+pub mod checkpoint;
+pub mod collector;
+pub mod components;
+pub mod config;
+pub mod deletion;
+pub mod demography;
+#[cfg(feature = "csv-output")]
+pub mod design;
+pub mod distributions;
+#[cfg(feature = "landscape")]
+pub mod energy;
+pub mod error;
+pub mod events;
+#[cfg(feature = "csv-output")]
+pub mod exploration;
+#[cfg(feature = "net")]
+pub mod games;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod groups;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod heterogeneity;
+pub mod hooks;
+pub mod immunity;
+pub mod interventions;
+#[cfg(feature = "landscape")]
+pub mod landscape;
+#[cfg(feature = "live-view")]
+pub mod live;
+pub mod merge;
+pub mod mixing;
+pub mod model;
+#[cfg(feature = "net")]
+pub mod modifiers;
+#[cfg(feature = "netcdf-output")]
+pub mod netcdf;
+#[cfg(feature = "net")]
+pub mod network;
+pub mod observation;
+pub mod outcomes;
+pub mod pareto;
+pub mod population;
+#[cfg(feature = "html-report")]
+pub mod report;
+pub mod reproduction;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod space;
+#[cfg(feature = "landscape")]
+pub mod spatial;
+pub mod stages;
+pub mod stats;
+pub mod storage;
+pub mod strata;
+pub mod streams;
+pub mod tables;
+#[cfg(feature = "mqtt")]
+pub mod telemetry;
+pub mod theme;
+pub mod validation;
+#[cfg(feature = "landscape")]
+pub mod vectors;
+pub mod viral_load;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// Model properties
+/// Health is stored as one byte so that cells can be processed as a byte buffer.
+#[derive(Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Health {
+    #[default]
+    S = 0,
+    I = 1,
+}
+
+// Housekeeping
+slotmap::new_key_type! {
+    pub struct AgentKey;
+    pub struct LinkKey;
+}
+
+/// Container of agents; see the storage module
+#[cfg(not(feature = "vec-storage"))]
+pub type Agents<V> = slotmap::SlotMap<AgentKey, V>;
+/// Container of agents; see the storage module
+#[cfg(feature = "vec-storage")]
+pub type Agents<V> = storage::GenerationalVec<AgentKey, V>;
+
+/// Container of bidirectional links between agents; see the storage module
+#[cfg(not(feature = "vec-storage"))]
+pub type Links = slotmap::SlotMap<LinkKey, (AgentKey, AgentKey)>;
+/// Container of bidirectional links between agents; see the storage module
+#[cfg(feature = "vec-storage")]
+pub type Links = storage::GenerationalVec<LinkKey, (AgentKey, AgentKey)>;
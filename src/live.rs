@@ -0,0 +1,268 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Live view of a running scenario in a window.
+//!
+//! The window redraws the landscape and the series of the data collector at every sampled time step, so a run can be
+//! watched as it happens. Keys control the scenario on display: space pauses and resumes it, `n` advances one sampled
+//! time step while paused, `+` and `-` speed it up and slow it down, and `q` or closing the window lets the scenario
+//! run at full speed without the view. Other scenarios keep running meanwhile. Without a display, such as over SSH
+//! without X forwarding, the view says why it could not open and the scenario runs as usual.
+
+use crate::theme::Theme;
+use crate::Health;
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use plotters::prelude::*;
+use std::time::Duration;
+
+/// Shortest pause between frames when slowing down, in milliseconds
+const MIN_DELAY: u64 = 10;
+/// Size of the window when it opens, in pixels; the window can be resized.
+const SIZE: (usize, usize) = (1280, 720);
+/// Interval between checks of the keys while the scenario waits, in milliseconds
+const POLL: u64 = 20;
+
+pub struct LiveView {
+    /// None once the view closes, or if it could not open
+    window: Option<Window>,
+    scenario_id: u32,
+    theme: Theme,
+    time_step: usize,
+    paused: bool,
+    /// Sampled time steps to advance while paused
+    steps: u64,
+    /// Pause between frames, in milliseconds
+    delay: u64,
+    /// Name and values so far of each series
+    series: Vec<(String, Vec<f64>)>,
+}
+
+impl LiveView {
+    /// Opens the window of a scenario with these series, in the colors of the theme.
+    pub fn new(scenario_id: u32, series_names: Vec<String>, theme: &Theme) -> Self {
+        let options = WindowOptions {
+            resize: true,
+            ..WindowOptions::default()
+        };
+        let window = match Window::new(
+            &format!("Scenario {}", scenario_id),
+            SIZE.0,
+            SIZE.1,
+            options,
+        ) {
+            Ok(mut window) => {
+                // The view paces itself with the delay between frames
+                window.set_target_fps(0);
+                Some(window)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Could not open the live view of scenario {}: {}",
+                    scenario_id, e
+                );
+                None
+            }
+        };
+        Self {
+            window,
+            scenario_id,
+            theme: theme.clone(),
+            time_step: 0,
+            paused: false,
+            steps: 0,
+            delay: 0,
+            series: series_names
+                .into_iter()
+                .map(|name| (name, Vec::new()))
+                .collect(),
+        }
+    }
+
+    /// Draws a sampled time step: the cells of the landscape, in rows of width cells, if any, and the value of each
+    /// series. Then waits as the keys say.
+    pub fn show(&mut self, time_step: usize, cells: Option<(&[Health], usize)>, values: &[f64]) {
+        for ((_name, history), &value) in self.series.iter_mut().zip(values) {
+            history.push(value);
+        }
+        self.time_step = time_step;
+        if self.window.is_none() {
+            return;
+        }
+        self.draw(cells);
+        self.follow_keys();
+        self.wait();
+    }
+
+    /// Draws the landscape on a square at the left of the window and a chart of each series on the rest.
+    fn draw(&mut self, cells: Option<(&[Health], usize)>) {
+        let Some(window) = &mut self.window else {
+            return;
+        };
+        let (width, height) = window.get_size();
+        if width == 0 || height == 0 {
+            window.update();
+            return;
+        }
+        let side = if cells.is_some() {
+            width.min(height)
+        } else {
+            0
+        };
+        let mut rgb = vec![0; width * height * 3];
+        {
+            let root = BitMapBackend::with_buffer(&mut rgb, (width as u32, height as u32))
+                .into_drawing_area();
+            root.fill(&self.theme.background).ok();
+            let (_landscape, charts) = root.split_horizontally(side as u32);
+            let foreground = self.theme.foreground;
+            let areas = charts.split_evenly((self.series.len().max(1), 1));
+            for (idx, ((name, history), area)) in self.series.iter().zip(&areas).enumerate() {
+                let (min, max) = history
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+                        (min.min(value), max.max(value))
+                    });
+                let (min, max) = if max > min {
+                    (min, max)
+                } else {
+                    (min - 1.0, min + 1.0)
+                };
+                let caption = format!("{}: {}", name, history.last().copied().unwrap_or_default());
+                let chart = ChartBuilder::on(area)
+                    .caption(caption, ("sans-serif", 14).into_font().color(&foreground))
+                    .margin(4)
+                    .y_label_area_size(60)
+                    .build_cartesian_2d(0..history.len().max(2) - 1, min..max);
+                let Ok(mut chart) = chart else {
+                    continue;
+                };
+                chart
+                    .configure_mesh()
+                    .disable_mesh()
+                    .disable_x_axis()
+                    .y_labels(3)
+                    .axis_style(foreground)
+                    .label_style(("sans-serif", 12).into_font().color(&foreground))
+                    .draw()
+                    .ok();
+                let color = self.theme.series[idx % self.theme.series.len()];
+                chart
+                    .draw_series(LineSeries::new(history.iter().copied().enumerate(), color))
+                    .ok();
+            }
+            root.present().ok();
+        }
+        // Each pixel of the square takes the color of the cell below it
+        if let Some((cells, cells_width)) = cells {
+            let cells_height = cells.len() / cells_width;
+            for y in 0..side {
+                let row = &cells[y * cells_height / side * cells_width..][..cells_width];
+                for x in 0..side {
+                    let color = match row[x * cells_width / side] {
+                        Health::S => self.theme.susceptible,
+                        Health::I => self.theme.infected,
+                    };
+                    let pixel = 3 * (y * width + x);
+                    rgb[pixel..pixel + 3].copy_from_slice(&[color.0, color.1, color.2]);
+                }
+            }
+        }
+        let buffer: Vec<u32> = rgb
+            .chunks_exact(3)
+            .map(|pixel| u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]]))
+            .collect();
+        window.update_with_buffer(&buffer, width, height).ok();
+    }
+
+    /// Follows the keys pressed since the last update of the window, and shows the state of the view in its title.
+    /// Closing the window closes the view.
+    fn follow_keys(&mut self) {
+        let Some(window) = &mut self.window else {
+            return;
+        };
+        if !window.is_open() {
+            self.window = None;
+            return;
+        }
+        for key in window.get_keys_pressed(KeyRepeat::No) {
+            match key {
+                Key::Space => self.paused = !self.paused,
+                Key::N => self.steps += 1,
+                // + shares its key with =
+                Key::Equal | Key::NumPadPlus => {
+                    let delay = self.delay / 2;
+                    self.delay = if delay < MIN_DELAY { 0 } else { delay };
+                }
+                Key::Minus | Key::NumPadMinus => self.delay = (self.delay * 2).max(MIN_DELAY),
+                Key::Q | Key::Escape => {
+                    self.window = None;
+                    return;
+                }
+                _ => {}
+            }
+        }
+        window.set_title(&format!(
+            "Scenario {}, time step {}{}. Space: pause, n: step, +/-: speed ({} ms per step), q: close",
+            self.scenario_id,
+            self.time_step,
+            if self.paused { " (paused)" } else { "" },
+            self.delay
+        ));
+    }
+
+    /// Updates the window, to receive keys, and waits a moment.
+    fn poll(&mut self) {
+        if let Some(window) = &mut self.window {
+            window.update();
+        }
+        self.follow_keys();
+        std::thread::sleep(Duration::from_millis(POLL));
+    }
+
+    /// Holds the scenario while paused, unless a step is due, then pauses between frames.
+    fn wait(&mut self) {
+        while self.paused && self.window.is_some() {
+            if self.steps > 0 {
+                self.steps -= 1;
+                break;
+            }
+            self.poll();
+        }
+        let start = std::time::Instant::now();
+        while self.window.is_some() && start.elapsed() < Duration::from_millis(self.delay) {
+            self.poll();
+        }
+    }
+
+    /// Ends the view when the scenario finishes; unless closed already, the window stays until it is closed.
+    pub fn finish(mut self) {
+        if let Some(window) = &mut self.window {
+            window.set_title(&format!(
+                "Scenario {} finished at time step {}; q: close",
+                self.scenario_id, self.time_step
+            ));
+        }
+        while let Some(window) = &mut self.window {
+            window.update();
+            if !window.is_open() || window.is_key_down(Key::Q) || window.is_key_down(Key::Escape) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(POLL));
+        }
+    }
+}
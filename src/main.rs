@@ -19,716 +19,2605 @@
 // Use the tags begin-similar-code and end-similar-code to mark a block of code that is similar between rust-agent-based-models and wasm-agent-based-models.
 // begin-similar-code 0
 
+use clap::Parser;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 ///! This software uses the Entity-Component-System (ECS) architecture and other principles discussed at https://kyren.github.io/2018/09/14/rustconf-talk.html
 #[cfg(feature = "graphics")]
 use plotters::prelude::*;
-#[cfg(feature = "net")]
-use rand::distributions::weighted::{WeightedError, WeightedIndex};
 use rand::distributions::{Bernoulli, Distribution};
-#[cfg(feature = "landscape")]
-use rand_distr::Normal;
+use rand::Rng;
 use rayon::prelude::*;
-use slotmap::{SecondaryMap, SlotMap};
+use rust_agent_based_models::analysis::{self, Statistic};
+#[cfg(feature = "arrow-output")]
+use rust_agent_based_models::arrow_output::{ArrowTable, FORMATS};
+#[cfg(feature = "net")]
+use rust_agent_based_models::awareness::{AgentAwareness, Awareness};
+use rust_agent_based_models::buffered::Buffered;
+#[cfg(feature = "checkpoint")]
+use rust_agent_based_models::checkpoint;
+#[cfg(any(feature = "agent-metrics", feature = "net-metrics"))]
+use rust_agent_based_models::collector::Aggregate;
+use rust_agent_based_models::collector::{DataCollector, World};
+use rust_agent_based_models::config::Config;
+use rust_agent_based_models::deletion::{Dependent, Dependents};
+use rust_agent_based_models::demography::{AgentDemography, Demography, Migration};
+#[cfg(feature = "csv-output")]
+use rust_agent_based_models::design::DesignMatrix;
+use rust_agent_based_models::distributions::Spec;
+#[cfg(feature = "landscape")]
+use rust_agent_based_models::energy::{AgentEnergy, CellResources, Metabolism};
+use rust_agent_based_models::error::{self, Error, OrError};
+#[cfg(feature = "landscape")]
+use rust_agent_based_models::events::CellInfectionEvent;
+#[cfg(feature = "net")]
+use rust_agent_based_models::events::LinkEvent;
+use rust_agent_based_models::events::{
+    BirthEvent, DeathEvent, EventBus, InfectionEvent, RecoveryEvent, Via,
+};
+#[cfg(feature = "csv-output")]
+use rust_agent_based_models::exploration::{self, Criterion, Explorer};
+#[cfg(feature = "gpu")]
+use rust_agent_based_models::gpu;
+use rust_agent_based_models::groups::GroupRules;
+#[cfg(feature = "checkpoint")]
+use rust_agent_based_models::groups::Groups;
+#[cfg(feature = "grpc")]
+use rust_agent_based_models::grpc;
+use rust_agent_based_models::heterogeneity::{AgentTraits, Heterogeneity};
+use rust_agent_based_models::hooks::Hooks;
+use rust_agent_based_models::immunity::{Immunity, InfectionHistory, Waning};
+use rust_agent_based_models::interventions::{AgentVaccination, Rule, Vaccination};
+#[cfg(all(feature = "landscape", not(feature = "gpu")))]
+use rust_agent_based_models::landscape;
+#[cfg(all(feature = "landscape", not(feature = "gpu")))]
+use rust_agent_based_models::landscape::CellContamination;
+#[cfg(feature = "landscape")]
+use rust_agent_based_models::landscape::{Decay, DoseResponse, Persistence, Seasonality};
+#[cfg(feature = "live-view")]
+use rust_agent_based_models::live::LiveView;
+use rust_agent_based_models::merge;
+use rust_agent_based_models::mixing::ContactMatrix;
+#[cfg(feature = "net")]
+use rust_agent_based_models::modifiers::{
+    Exposure, Layer, LayerFactors, LinkLayers, Pipeline, QuarantineFactor, WeekendFactor,
+};
+#[cfg(feature = "netcdf-output")]
+use rust_agent_based_models::netcdf::LandscapeWriter;
+#[cfg(feature = "net")]
+use rust_agent_based_models::network;
+#[cfg(feature = "csv-output")]
+use rust_agent_based_models::observation::Observation;
+#[cfg(feature = "csv-output")]
+use rust_agent_based_models::outcomes;
+#[cfg(feature = "csv-output")]
+use rust_agent_based_models::pareto::{self, Objective};
+#[cfg(feature = "landscape")]
+use rust_agent_based_models::population::AgentHomes;
+use rust_agent_based_models::population::{Population, SyntheticPopulation};
+#[cfg(feature = "html-report")]
+use rust_agent_based_models::report;
+#[cfg(any(feature = "csv-output", feature = "graphics"))]
+use rust_agent_based_models::reproduction;
+use rust_agent_based_models::reproduction::Transmissions;
+#[cfg(feature = "server")]
+use rust_agent_based_models::server::Server;
+#[cfg(feature = "spatial-metrics")]
+use rust_agent_based_models::spatial;
+use rust_agent_based_models::stages;
+use rust_agent_based_models::stats;
+#[cfg(feature = "net-graphics")]
+use rust_agent_based_models::stats::power_law_exponent;
+use rust_agent_based_models::stats::RunningStats;
+use rust_agent_based_models::storage::Storage;
+use rust_agent_based_models::strata;
+use rust_agent_based_models::streams;
+use rust_agent_based_models::streams::{Process, RandomStreams, Stream};
+use rust_agent_based_models::tables;
+use rust_agent_based_models::tables::{Column, ColumnType, Value};
+#[cfg(feature = "mqtt")]
+use rust_agent_based_models::telemetry::Publisher;
+use rust_agent_based_models::theme;
+#[cfg(feature = "graphics")]
+use rust_agent_based_models::theme::Theme;
+use rust_agent_based_models::validation::Problems;
+#[cfg(feature = "landscape")]
+use rust_agent_based_models::vectors::{self, Vector, VectorPopulation};
+use rust_agent_based_models::viral_load::ViralLoad;
+use rust_agent_based_models::{AgentKey, Agents, Health};
+#[cfg(feature = "net")]
+use rust_agent_based_models::{LinkKey, Links};
+#[cfg(any(
+    feature = "trajectories",
+    feature = "genealogy",
+    feature = "csv-output",
+    feature = "network-snapshots",
+    all(feature = "checkpoint", feature = "net")
+))]
+use slotmap::Key;
+#[cfg(feature = "net")]
+use slotmap::SecondaryMap;
+use std::collections::BTreeMap;
 #[cfg(feature = "net-graphics")]
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeSet;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 // use std::fmt::Write as FmtWrite; // See https://doc.rust-lang.org/std/macro.writeln.html
-#[cfg(feature = "csv-output")]
 use std::io::Write as IoWrite; // See https://doc.rust-lang.org/std/macro.writeln.html
+#[cfg(feature = "landscape-graphics")]
+use std::io::{Read, Seek, SeekFrom};
 #[cfg(feature = "landscape")]
 use wrapping_coords2d::WrappingCoords2d;
 
-// Model properties
-#[derive(Clone, Copy, PartialEq)]
-enum Health {
-    S,
-    I,
-}
-
-// Housekeeping
-slotmap::new_key_type! {
-    struct AgentKey;
-    struct LinkKey;
-}
+/// Set by Ctrl+C or SIGTERM: scenarios stop at the end of their current time step and save what they have.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
 /// Simulation results for a time step
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    not(any(
+        feature = "csv-output",
+        feature = "arrow-output",
+        feature = "jsonl",
+        feature = "graphics"
+    )),
+    allow(dead_code)
+)]
 struct TimeStepResults {
     /// Time step
     time_step: u32,
-    /// Number of agents
-    #[cfg(feature = "agent-metrics")]
-    n: u32,
-    /// Susceptibles
-    #[cfg(feature = "agent-metrics")]
-    s: u32,
-    /// Infected
-    #[cfg(feature = "agent-metrics")]
-    i: u32,
-    /// Maximum network degree of susceptibles
-    #[cfg(feature = "net-metrics")]
-    d_s: i32,
-    /// Maximum network degree of infectious
-    #[cfg(feature = "net-metrics")]
-    d_i: i32,
-    /// Infected cells
-    #[cfg(feature = "landscape-metrics")]
-    c_i: u32,
+    /// Values of the reporters of the data collector
+    values: Vec<f64>,
     /// Histogram of network degrees
     #[cfg(feature = "net-graphics")]
     degree_histogram: BTreeMap<i32, u32>,
-    /// Health status
-    #[cfg(feature = "landscape-graphics")]
-    cell_health: Vec<Health>,
 }
 
-/// Simulation scenario, including parameters and results
-#[derive(Clone, Default)]
-struct Scenario {
-    /// Sequential scenario number
-    id: u32,
-    /// Model parameter: Infection probability
-    infection_probability: f64,
-    /// Simulation results: Set of network degrees that ever ocurred in this scenario
-    #[cfg(feature = "net-graphics")]
-    histogram_degrees_set: BTreeSet<i32>,
-    /// Simulation results: Maximum network degree that ever ocurred in this scenario
-    #[cfg(feature = "net-graphics")]
-    histogram_max_degree: i32,
-    /// Simulation results: Height of the network degree histogram for this scenario
-    #[cfg(feature = "net-graphics")]
-    histogram_height: u32,
-    /// Simulation results: Height of the time series figure for agents for this scenario
-    #[cfg(feature = "graphics")]
-    agent_time_series_height: u32,
-    /// Simulation results: Height of the time series figure for agents for this scenario
-    #[cfg(feature = "landscape-graphics")]
-    cell_time_series_height: u32,
-    /// Simulation results for all time steps
-    time_series: std::vec::Vec<TimeStepResults>,
+impl TimeStepResults {
+    /// Row of the time series output files
+    #[cfg(any(feature = "csv-output", feature = "arrow-output", feature = "jsonl"))]
+    fn row(&self, infection_probability: f64) -> Vec<Value<'static>> {
+        let mut row = Vec::with_capacity(2 + self.values.len());
+        row.push(Value::Float64(infection_probability));
+        row.push(Value::UInt32(self.time_step));
+        row.extend(self.values.iter().map(|&value| Value::Float64(value)));
+        row
+    }
 }
 
-// end-similar-code 0
-
-fn main() {
-    // Only use one thread to facilitate debugging. One thread makes the program sequential.
-    #[cfg(debug_assertions)] // Only when debugging should this instruction happen.
-    #[rustfmt::skip] // Prevent rustfmt (and thus vscode) from splitting this long line.
-    rayon::ThreadPoolBuilder::new().num_threads(1).build_global().unwrap();
-    // Delete any png, csv, and mkv files from previous simulations.
-    for dir in &[".", "img", "img_dark"] {
-        for res in std::fs::read_dir(dir).unwrap() {
-            if let Ok(entry) = res {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "csv"
-                        || extension == "log"
-                        || extension == "mkv"
-                        || extension == "png"
-                    {
-                        if let Some(file_name_os_str) = path.file_name() {
-                            if let Some(file_name) = file_name_os_str.to_str() {
-                                if let Err(e) = fs::remove_file(path.clone()) {
-                                    panic!(
-                                        "Could not remove file {} from a previous simulation: {}",
-                                        file_name, e
-                                    );
-                                }
-                            }
-                        }
-                    }
+/// Prints the state saved in a checkpoint: agent, link, and cell counts, and optionally an agent and the links
+#[cfg(feature = "checkpoint")]
+fn inspect(checkpoint_path: &std::path::Path, args: &Args) -> error::Result<()> {
+    let checkpoint: Checkpoint = checkpoint::load(checkpoint_path).or_error(&format!(
+        "Could not read checkpoint {}",
+        checkpoint_path.display()
+    ))?;
+    let count = |health: &mut dyn Iterator<Item = &Health>| {
+        health.fold((0, 0), |(s, i), &h| match h {
+            Health::S => (s + 1, i),
+            Health::I => (s, i + 1),
+        })
+    };
+    println!(
+        "Scenario {}, infection probability {}, at the beginning of time step {}",
+        checkpoint.scenario.id, checkpoint.scenario.infection_probability, checkpoint.time_step
+    );
+    let (s, i) = count(&mut checkpoint.health.values());
+    println!("Agents: {} ({} susceptible, {} infected)", s + i, s, i);
+    #[cfg(feature = "net")]
+    println!("Links: {}", checkpoint.links.len());
+    #[cfg(feature = "landscape")]
+    {
+        let (s, i) = count(&mut checkpoint.cell_health.iter());
+        println!("Cells: {} ({} susceptible, {} infected)", s + i, s, i);
+    }
+    if let Some(ffi) = args.agent {
+        let agent = AgentKey::from(slotmap::KeyData::from_ffi(ffi));
+        if checkpoint.health.contains_key(agent) {
+            println!(
+                "Agent {}: {}",
+                ffi,
+                match checkpoint.health[agent] {
+                    Health::S => "susceptible",
+                    Health::I => "infected",
                 }
-            }
+            );
+        } else {
+            println!("Agent {}: not alive", ffi);
         }
+        #[cfg(feature = "net")]
+        checkpoint
+            .links
+            .values()
+            .filter(|&&(key0, key1)| key0 == agent || key1 == agent)
+            .for_each(|&(key0, key1)| {
+                let friend = if key0 == agent { key1 } else { key0 };
+                println!("Agent {}: linked to {}", ffi, friend.data().as_ffi());
+            });
     }
-    // begin-similar-code 1
-    // Model parameter: Initial number of agents
-    let n0: usize = 1000;
-    // Model parameter: Scale-free network parameter: new links per agent
     #[cfg(feature = "net")]
-    let net_k: usize = 7;
-    // Model parameter: Dimensions of the virtual landscape, in number of cells
+    if args.links {
+        checkpoint.links.iter().for_each(|(link, &(key0, key1))| {
+            println!(
+                "Link {}: {} {}",
+                link.data().as_ffi(),
+                key0.data().as_ffi(),
+                key1.data().as_ffi()
+            );
+        });
+    }
+    Ok(())
+}
+
+/// Creates an output file that is written in chunks of 64 KiB; output files are rewritten only if --existing asks.
+fn create_output_file(
+    path: &std::path::Path,
+    err: &str,
+    existing: Existing,
+) -> error::Result<std::io::BufWriter<fs::File>> {
+    if existing == Existing::Error && path.exists() {
+        return Err(Error::Exists(path.to_path_buf()));
+    }
+    Ok(std::io::BufWriter::with_capacity(
+        1 << 16,
+        fs::File::create(path).or_error(err)?,
+    ))
+}
+
+/// Command line: a subcommand, or the options of the run subcommand
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    args: Args,
+    /// What to do with outputs that already exist in the output directory, for example to redo an interrupted run.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    existing: Existing,
+}
+
+/// Options of the simulation
+#[derive(clap::Args)]
+struct Args {
+    /// Directory for the outputs of this run; it must be new or empty, unless --existing says otherwise. Defaults to
+    /// run-<time> in the current directory.
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Give movement, births, deaths, recovery, and links random number streams of their own, the same in every scenario.
+    #[arg(long)]
+    common_random_numbers: bool,
+    /// Number of threads; defaults to one per CPU core, or to 1 in debug builds to facilitate debugging.
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+    /// Simulate scenarios in parallel, split the work of each simulation across threads, or both.
+    #[arg(long, value_enum, default_value_t = Parallelism::Both)]
+    parallelism: Parallelism,
+    /// Leave the social network out of this run: agents make no links, and the network metrics and panels disappear.
+    #[cfg(feature = "net")]
+    #[arg(long)]
+    no_network: bool,
+    /// Leave the landscape out of this run: agents visit no cells, cells stay susceptible, and the landscape metrics,
+    /// panel, and files disappear.
     #[cfg(feature = "landscape")]
-    let coord = WrappingCoords2d::new(100, 100).unwrap();
-    let birth_distro = Bernoulli::new(0.01).unwrap();
-    let initial_infection_distro = Bernoulli::new(0.3).unwrap();
-    // Model parameter: probability of infection
-    let infection_probabilities = [0.2f64, 0.4, 0.6];
-    // Normal distribution to choose cells in the landscape
+    #[arg(long)]
+    no_landscape: bool,
+    /// Replace distributions of the model with those of this JSON file, such as {"visit_distro": "Gamma(2, 3)"}.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Start every scenario from the agents listed in FILE, a CSV or Parquet file, instead of identical susceptible
+    /// agents; see the population module for its columns.
+    #[arg(long, value_name = "FILE")]
+    #[cfg_attr(feature = "checkpoint", arg(conflicts_with = "warm_start"))]
+    population: Option<PathBuf>,
+    /// Agents also meet agents of every age group, linked or not, as often as the contact matrix in FILE says; see the
+    /// mixing module for its format.
+    #[arg(long, value_name = "FILE")]
+    contact_matrix: Option<PathBuf>,
+    /// Vaccinate the agents that RULE chooses: random, top-degree, oldest, or patch:X0,Y0,X1,Y1 for the agents whose
+    /// homes lie in that rectangle of cells; see the interventions module for the campaign.
+    #[arg(long, value_name = "RULE")]
+    vaccinate: Option<Rule>,
+    /// Only simulate the scenario with this id.
+    #[arg(long, value_name = "ID", conflicts_with = "shard")]
+    scenario_index: Option<u32>,
+    /// Simulate each infection probability N times, each replicate with a random number stream of its own. Scenario
+    /// ids count the replicates of the first infection probability, then those of the next, and so on.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    replicates: u32,
+    /// Pair the replicates of each infection probability: the second replicate of each pair draws the complement of
+    /// every random number of the first, so that the mean of the pair varies less; see the streams module. Needs an
+    /// even number of replicates.
+    #[arg(long)]
+    antithetic: bool,
+    /// Only simulate shard K of N, counting from 0: the scenarios whose id leaves remainder K when divided by N.
+    /// Defaults to the task of a Slurm array job, if any; merge the output directories of the shards afterwards.
+    #[arg(long, value_name = "K/N")]
+    shard: Option<Shard>,
+    /// Log every random number drawn to rng.trace.gz in the directory of each scenario.
+    #[cfg(feature = "rng-trace")]
+    #[arg(long)]
+    trace_rng: bool,
+    /// Print the first random number that differs between two traces written by --trace-rng, and exit.
+    #[cfg(feature = "rng-trace")]
+    #[arg(long, num_args = 2, value_names = ["TRACE", "TRACE"])]
+    compare_rng_traces: Option<Vec<PathBuf>>,
+    /// Write every link of the network, with the health of the agents at both ends, to network.csv.gz in the directory of
+    /// each scenario every STEPS time steps; 0 disables the snapshots.
+    #[cfg(feature = "network-snapshots")]
+    #[arg(long, value_name = "STEPS", default_value_t = 0)]
+    network_every: usize,
+    /// Count the agents in each health state by this attribute, at every sampled time step, in strata.csv in the
+    /// directory of each scenario; repeat to count them by several.
+    #[cfg(feature = "csv-output")]
+    #[arg(long, value_enum, value_name = "ATTRIBUTE")]
+    stratify: Vec<Attribute>,
+    /// Save the state of each scenario every STEPS time steps and at the end; 0 disables checkpoints.
+    #[cfg(feature = "checkpoint")]
+    #[arg(long, value_name = "STEPS", default_value_t = 0)]
+    checkpoint_every: usize,
+    /// Resume the scenarios of an interrupted run from their checkpoints in DIR; outputs go to a new directory.
+    #[cfg(feature = "checkpoint")]
+    #[arg(long, value_name = "DIR")]
+    resume: Option<PathBuf>,
+    /// Start every scenario from the agents, links, and cells saved in CHECKPOINT, such as the end state of an earlier run.
+    #[cfg(feature = "checkpoint")]
+    #[arg(long, value_name = "CHECKPOINT", conflicts_with = "resume")]
+    warm_start: Option<PathBuf>,
+    /// Run a baseline with the parameters of the first scenario until STEP, then branch every scenario from its state.
+    #[cfg(feature = "checkpoint")]
+    #[arg(long, value_name = "STEP", conflicts_with_all = ["resume", "scenario_index", "shard"])]
+    branch_at: Option<usize>,
+    /// Print the state saved in CHECKPOINT, such as a snapshot, and exit.
+    #[cfg(feature = "checkpoint")]
+    #[arg(long, value_name = "CHECKPOINT")]
+    inspect: Option<PathBuf>,
+    /// With --inspect, also print the health of the agent with this key, as in trajectories, and its links.
+    #[cfg(feature = "checkpoint")]
+    #[arg(long, value_name = "KEY", requires = "inspect")]
+    agent: Option<u64>,
+    /// With --inspect, also print every link.
+    #[cfg(all(feature = "checkpoint", feature = "net"))]
+    #[arg(long, requires = "inspect")]
+    links: bool,
+    /// Show the scenario with this id in a window as it runs, with keys to pause, step, and change speed.
+    #[cfg(feature = "live-view")]
+    #[arg(long, value_name = "ID")]
+    live_view: Option<u32>,
+    /// Serve a dashboard of the running simulation at this address, such as 127.0.0.1:8080; port 0 picks a free port.
+    #[cfg(feature = "server")]
+    #[arg(long, value_name = "ADDRESS")]
+    serve: Option<std::net::SocketAddr>,
+    /// Run under the grpc subcommand: report progress and metrics as JSON Lines on the standard output, and stop
+    /// when the standard input closes.
+    #[cfg(feature = "grpc")]
+    #[arg(long, hide = true)]
+    managed: bool,
+    /// Publish the metrics of every sampled time step to this MQTT broker, as HOST or HOST:PORT.
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "BROKER")]
+    mqtt_broker: Option<String>,
+    /// Topic of the MQTT messages; each scenario publishes to TOPIC/scenario-NN.
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "TOPIC", default_value = "rust-agent-based-models")]
+    mqtt_topic: String,
+    #[command(flatten)]
+    video: VideoOptions,
+    #[cfg(feature = "graphics")]
+    #[command(flatten)]
+    figure_options: FigureOptions,
+}
+
+/// Size, fonts, and panels of the figures
+#[cfg(feature = "graphics")]
+#[derive(Clone, clap::Args)]
+struct FigureOptions {
+    /// Width of the figures, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 1920)]
+    figure_width: u32,
+    /// Height of the figures, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 1080)]
+    figure_height: u32,
+    /// Width of the landscape panel at the right of the figures; defaults to the height of the figures, for square cells.
     #[cfg(feature = "landscape")]
-    let visit_distro = Normal::new(50.0f32, 10f32).unwrap();
-    #[cfg(feature = "net")]
-    let link_distro = Bernoulli::new(0.01).unwrap();
-    let recovery_distro = Bernoulli::new(0.8).unwrap();
-    let survival_distro = Bernoulli::new(0.8).unwrap();
-    // end-similar-code 1
-    // Model parameter: Last time step of the simulation in each scenario
-    let last_time_step = 100u32;
-    let time_series_len = last_time_step as usize + 1;
-    let mut scenarios = vec![
+    #[arg(long, value_name = "PIXELS")]
+    landscape_width: Option<u32>,
+    /// Also draw the bare landscape of each figure, PIXELS by PIXELS per cell, to the heatmaps directory of its theme.
+    #[cfg(feature = "landscape-graphics")]
+    #[arg(long, value_name = "PIXELS")]
+    heatmap_scale: Option<u32>,
+    /// Font family, such as Calibri or DejaVu Sans; families missing on this computer fall back to sans-serif.
+    #[arg(long, value_name = "FAMILY", default_value = "sans-serif")]
+    font: String,
+    /// Font size of text and captions
+    #[arg(long, value_name = "POINTS", default_value_t = 30)]
+    title_font_size: u32,
+    /// Font size of axis labels and legends
+    #[arg(long, value_name = "POINTS", default_value_t = 17)]
+    label_font_size: u32,
+    /// Space around each chart, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 5)]
+    chart_margin: u32,
+    /// Leave this panel out of the figures; repeat to hide several.
+    #[arg(long = "hide-panel", value_enum, value_name = "PANEL")]
+    hidden_panels: Vec<Panel>,
+    /// Add this optional panel, phase-plane, incidence, reproduction, or ccdf, to the figures; repeat to add several.
+    #[arg(long = "show-panel", value_enum, value_name = "PANEL")]
+    shown_panels: Vec<Panel>,
+    /// Arrange the panels on a grid, such as "text landscape / charts landscape": rows separated by slashes, each a
+    /// list of panel names, with chart-N for the Nth chart and . for an empty cell; a panel named in several cells
+    /// spans them. Panels left out of the layout are hidden.
+    #[arg(long, value_name = "AREAS")]
+    layout: Option<Layout>,
+    /// Draw the power law that best fits the network degrees on the ccdf panel.
+    #[cfg(feature = "net-graphics")]
+    #[arg(long)]
+    power_law_fit: bool,
+    /// Draw a set of figures in this theme, built-in or from the theme file; repeat to draw several.
+    #[arg(long = "figure-theme", value_name = "THEME", default_values = ["light", "dark"])]
+    figure_themes: Vec<String>,
+    /// JSON file of user-defined themes
+    #[arg(long, value_name = "FILE")]
+    theme_file: Option<PathBuf>,
+    /// Draw one figure every N landscape frames; videos lower their frame rate to match, keeping their pace.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    frame_stride: usize,
+    /// Raise the frame stride as needed so that each scenario gets at most this many figures.
+    #[arg(long, value_name = "N")]
+    max_frames: Option<usize>,
+}
+
+/// Attribute of agents by which strata.csv breaks down their health
+#[cfg(feature = "csv-output")]
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum Attribute {
+    /// Age group, among the bands between the ages of age_groups
+    Age,
+    /// Group of the agent
+    Group,
+    /// Members of the group of the agent
+    GroupSize,
+}
+
+#[cfg(feature = "csv-output")]
+impl Attribute {
+    fn name(self) -> &'static str {
+        match self {
+            Attribute::Age => "age",
+            Attribute::Group => "group",
+            Attribute::GroupSize => "group-size",
+        }
+    }
+}
+
+/// Part of the figures
+#[cfg(feature = "graphics")]
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum Panel {
+    /// Parameters, time, and text reporters at the top left
+    Text,
+    /// Histogram of the network degrees of agents
+    Degrees,
+    /// Charts of the data collector
+    Charts,
+    /// Landscape at the right
+    Landscape,
+    /// Optional: infected against susceptibles, traced up to the current time step
+    PhasePlane,
+    /// Optional: infections so far, seeds included
+    Incidence,
+    /// Optional: effective reproduction number of the agents infected at each time step, next to R = 1
+    Reproduction,
+    /// Optional: complementary cumulative distribution of the network degrees of agents, on log-log axes
+    Ccdf,
+}
+
+#[cfg(feature = "graphics")]
+impl Panel {
+    fn name(self) -> &'static str {
+        match self {
+            Panel::Text => "text",
+            Panel::Degrees => "degrees",
+            Panel::Charts => "charts",
+            Panel::Landscape => "landscape",
+            Panel::PhasePlane => "phase-plane",
+            Panel::Incidence => "incidence",
+            Panel::Reproduction => "reproduction",
+            Panel::Ccdf => "ccdf",
+        }
+    }
+
+    /// Optional panels only appear when asked for
+    fn optional(self) -> bool {
+        matches!(
+            self,
+            Panel::PhasePlane | Panel::Incidence | Panel::Reproduction | Panel::Ccdf
+        )
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl FigureOptions {
+    fn shows(&self, panel: Panel) -> bool {
+        !self.hidden_panels.contains(&panel)
+            && match &self.layout {
+                Some(layout) => layout.contains(panel),
+                None => !panel.optional() || self.shown_panels.contains(&panel),
+            }
+    }
+
+    /// Checks that the font loads, that the reporters of the panels shown exist, and that the layout names only the
+    /// charts that the figures have.
+    fn check(&self, collector: &DataCollector, problems: &mut Problems) {
+        if let Err(e) = (self.font.as_str(), self.title_font_size)
+            .into_font()
+            .box_size("0")
         {
-            let mut scenario = Scenario::default();
-            scenario
-                .time_series
-                .resize_with(time_series_len, Default::default);
-            scenario
+            problems.report(
+                "--font",
+                format_args!("could not load {}: {}", self.font, e),
+            );
+        }
+        for (panel, names) in [
+            (Panel::PhasePlane, &["s", "i"][..]),
+            (Panel::Incidence, &["ci"][..]),
+        ] {
+            if !self.shows(panel) {
+                continue;
+            }
+            for name in names {
+                if !collector
+                    .reporters()
+                    .iter()
+                    .any(|reporter| reporter.name == *name)
+                {
+                    problems.report(
+                        "--show-panel",
+                        format_args!(
+                            "the {} panel needs the {} reporter, which this build leaves out",
+                            panel.name(),
+                            name
+                        ),
+                    );
+                }
+            }
+        }
+        let Some(layout) = &self.layout else {
+            return;
         };
-        infection_probabilities.len()
-    ];
-    {
-        let mut scenarios_iter = scenarios.iter_mut();
-        let mut id = 0;
-        #[allow(clippy::explicit_counter_loop)]
-        for &infection_probability in infection_probabilities.iter() {
-            let scenario: &mut Scenario = scenarios_iter.next().unwrap();
-            scenario.id = id;
-            scenario.infection_probability = infection_probability;
-            id += 1;
+        let charts = collector.charts().len();
+        for (name, _) in &layout.areas {
+            let chart = name
+                .strip_prefix("chart-")
+                .and_then(|n| n.parse::<usize>().ok());
+            if chart.is_some_and(|n| n > charts) {
+                problems.report(
+                    "--layout",
+                    format_args!("{} is not among the {} charts of the figures", name, charts),
+                );
+            }
         }
-        assert!(
-            scenarios_iter.next().is_none(),
-            "checking that all scenarios are initialized"
-        )
     }
-    let clean_term =
-        "\r                                                                         \r";
-    #[cfg(feature = "net-graphics")]
-    let compress_histogram = true;
-    scenarios
-        .par_iter_mut()
-        .for_each(|scenario: &mut Scenario| {
-            // begin-similar-code 2
-            // Use Pcg64 for reproducible random numbers; change to thread_rng for production
-            // let mut rng = rand::thread_rng();
-            #[allow(clippy::unreadable_literal)]
-            let mut rng =
-                rand_pcg::Pcg64::new(0xcafef00dd15ea5e5, 0xa02bdbf7bb3c0a7ac28fa16a64abf96);
-            // Model state: Agent health
-            let mut health = SlotMap::with_capacity_and_key(2 * n0);
-            // Model state: Bidirectional links between agents
-            #[cfg(feature = "net")]
-            let mut links = slotmap::SlotMap::with_capacity_and_key(n0 * n0);
-            // Model state: Health status of each cell in the landscape
-            #[cfg(feature = "landscape")]
-            let mut cell_health = vec![Health::S; coord.size()];
-            // Model state: Cell health storage for the next time step. This implements parallel updating of cells.
-            #[cfg(feature = "landscape")]
-            let mut next_cell_health = cell_health.clone();
-            // Model initialization: Agents
-            while health.len() < n0 {
-                let _k: AgentKey = health.insert(Health::S);
-            }
-            let infection_distro = Bernoulli::new(scenario.infection_probability).unwrap();
-            // end-similar-code 2
-            for (time_step, time_step_results) in scenario.time_series.iter_mut().enumerate() {
-                // Simple, fast models do not need to print the time_step. Printing is slow.
-                if time_step % 50 == 0 {
-                    eprint!(
-                        "{}infection_probability = {}, time_step = {}",
-                        clean_term, scenario.infection_probability, time_step
+
+    /// Themes of the figures, looked up in the theme file first and then among the built-in themes; reports the theme
+    /// file if it cannot be read, and the themes that are in neither.
+    fn themes(&self, problems: &mut Problems) -> Vec<Theme> {
+        let user_themes = match &self.theme_file {
+            Some(path) => theme::load(path).unwrap_or_else(|e| {
+                problems.report(
+                    "--theme-file",
+                    format_args!("could not read {}: {}", path.display(), e),
+                );
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        self.figure_themes
+            .iter()
+            .filter_map(|name| {
+                let theme = user_themes
+                    .iter()
+                    .find(|theme| theme.name == *name)
+                    .cloned()
+                    .or_else(|| Theme::builtin(name));
+                if theme.is_none() {
+                    problems.report(
+                        "--figure-theme",
+                        format_args!(
+                            "unknown theme {}; built-in themes are {}",
+                            name,
+                            theme::BUILTIN.join(", ")
+                        ),
                     );
                 }
-                // begin-similar-code 3
-                // Initialization of this time step: Network seed
-                #[cfg(feature = "net")]
-                {
-                    if links.is_empty() && health.len() > 1 {
-                        let mut h_it = health.iter();
-                        let (key0, _value) = h_it.next().unwrap();
-                        let (key1, _value) = h_it.next().unwrap();
-                        let _link_id: LinkKey = links.insert((key0, key1));
-                    }
-                    // Initialization of this time step: Network
-                    let keys_vec: Vec<AgentKey> = health.keys().collect();
-                    let mut idx_map = SecondaryMap::with_capacity(health.capacity());
-                    let mut weights_vec: Vec<i32> = {
-                        let mut weights_map = SecondaryMap::with_capacity(health.capacity());
-                        keys_vec.iter().enumerate().for_each(|(idx, &k)| {
-                            weights_map.insert(k, 0);
-                            idx_map.insert(k, idx);
-                        });
-                        links.values().for_each(|&(key0, key1)| {
-                            weights_map[key0] += 1;
-                            weights_map[key1] += 1;
-                        });
-                        keys_vec.iter().map(|&k| weights_map[k]).collect()
-                    };
-                    keys_vec
-                        .iter()
-                        .enumerate()
-                        .for_each(|(agent_idx, &agent_key)| {
-                            let new_links = if weights_vec[agent_idx] == 0 {
-                                net_k
-                            } else if link_distro.sample(&mut rng) {
-                                1
-                            } else {
-                                0
-                            };
-                            if new_links > 0 {
-                                let mut weights_tmp = weights_vec.clone();
-                                // This agent cannot make a link to itself; set its weight to 0.
-                                weights_tmp[agent_idx] = 0;
-                                // Friends are ineligible for a new link; set friends' weights to 0.
-                                links.values().for_each(|&(key0, key1)| {
-                                    if key0 == agent_key {
-                                        weights_tmp[idx_map[key1]] = 0;
-                                    }
-                                    if key1 == agent_key {
-                                        weights_tmp[idx_map[key0]] = 0;
-                                    }
-                                });
-                                match WeightedIndex::new(weights_tmp) {
-                                    Ok(mut dist) => {
-                                        let mut k = 0;
-                                        loop {
-                                            let friend_idx = dist.sample(&mut rng);
-                                            links.insert((agent_key, keys_vec[friend_idx]));
-                                            weights_vec[agent_idx] += 1;
-                                            weights_vec[friend_idx] += 1;
-                                            k += 1;
-                                            if k == new_links {
-                                                break;
-                                            }
-                                            // Make friend ineligible for a new link; set its weight to 0.
-                                            if dist.update_weights(&[(friend_idx, &0)]).is_err() {
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    Err(WeightedError::AllWeightsZero) => {}
-                                    Err(e) => {
-                                        panic!("Internal error OsXJWc0sHx: {}. Please debug.", e)
-                                    }
-                                }
-                            }
-                        });
-                    // Model measurements: Network
-                    #[cfg(feature = "net-metrics")]
-                    {
-                        time_step_results.d_s = match keys_vec
-                            .iter()
-                            .zip(weights_vec.iter())
-                            .filter(|(&k, _w)| health[k] == Health::S)
-                            .max_by_key(|(_k, &w)| w)
-                        {
-                            Some((_k, &w)) => w,
-                            None => 0,
-                        };
-                        time_step_results.d_i = match keys_vec
-                            .iter()
-                            .zip(weights_vec.iter())
-                            .filter(|(&k, _w)| health[k] == Health::I)
-                            .max_by_key(|(_k, &w)| w)
-                        {
-                            Some((_k, &w)) => w,
-                            None => 0,
-                        };
-                    }
-                    #[cfg(feature = "net-graphics")]
-                    {
-                        for weight in weights_vec {
-                            *time_step_results
-                                .degree_histogram
-                                .entry(weight)
-                                .or_insert(0) += 1;
-                        }
-                        for (&weight, &frequency) in &time_step_results.degree_histogram {
-                            if compress_histogram {
-                                scenario.histogram_degrees_set.insert(weight);
-                            } else if scenario.histogram_max_degree < weight {
-                                scenario.histogram_max_degree = weight;
-                            }
-                            if scenario.histogram_height < frequency {
-                                scenario.histogram_height = frequency;
-                            }
-                        }
-                    }
+                theme
+            })
+            .collect()
+    }
+}
+
+/// Arrangement of the panels of the figures on a grid
+#[cfg(feature = "graphics")]
+#[derive(Clone)]
+struct Layout {
+    /// Names of the panels in each cell, row by row
+    rows: Vec<Vec<String>>,
+    /// Each panel and the cells it spans: first column, first row, and the columns and rows past the last
+    areas: Vec<(String, [usize; 4])>,
+}
+
+#[cfg(feature = "graphics")]
+impl Layout {
+    fn contains(&self, panel: Panel) -> bool {
+        self.areas.iter().any(|(name, _)| {
+            name == panel.name() || (panel == Panel::Charts && name.starts_with("chart-"))
+        })
+    }
+
+    /// Part of the figure that this panel takes, if any
+    fn area<DB: DrawingBackend>(
+        &self,
+        figure: &DrawingArea<DB, plotters::coord::Shift>,
+        name: &str,
+    ) -> Option<DrawingArea<DB, plotters::coord::Shift>> {
+        let &(_, [column0, row0, column1, row1]) =
+            self.areas.iter().find(|(area_name, _)| area_name == name)?;
+        let (width, height) = figure.dim_in_pixel();
+        let x = |column: usize| (column as u64 * width as u64 / self.rows[0].len() as u64) as u32;
+        let y = |row: usize| (row as u64 * height as u64 / self.rows.len() as u64) as u32;
+        Some(figure.clone().shrink(
+            (x(column0), y(row0)),
+            (x(column1) - x(column0), y(row1) - y(row0)),
+        ))
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl std::str::FromStr for Layout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<Vec<String>> = s
+            .split('/')
+            .map(|row| row.split_whitespace().map(str::to_string).collect())
+            .collect();
+        if rows[0].is_empty() || rows.iter().any(|row| row.len() != rows[0].len()) {
+            return Err(format!(
+                "The rows of the layout {} must have the same number of cells",
+                s
+            ));
+        }
+        let mut areas: Vec<(String, [usize; 4])> = Vec::new();
+        for (row_idx, row) in rows.iter().enumerate() {
+            for (column_idx, name) in row.iter().enumerate() {
+                if name == "." {
+                    continue;
                 }
-                // Model measurements: agents
-                {
-                    time_step_results.time_step = time_step as u32;
-                    #[cfg(feature = "agent-metrics")]
-                    {
-                        time_step_results.n = health.len() as u32;
-                        health.values().for_each(|h| match h {
-                            Health::S => time_step_results.s += 1,
-                            Health::I => time_step_results.i += 1,
-                        });
-                    }
-                    #[cfg(feature = "landscape-metrics")]
-                    {
-                        time_step_results.c_i =
-                            cell_health.iter().filter(|&&h| h == Health::I).count() as u32;
-                    }
-                    #[cfg(feature = "graphics")]
-                    {
-                        if scenario.agent_time_series_height < time_step_results.n {
-                            scenario.agent_time_series_height = time_step_results.n;
-                        }
-                    }
-                    #[cfg(feature = "landscape-graphics")]
-                    {
-                        if scenario.cell_time_series_height < time_step_results.c_i {
-                            scenario.cell_time_series_height = time_step_results.c_i;
-                        }
-                        time_step_results.cell_health = cell_health.clone();
-                    }
+                let known = <Panel as clap::ValueEnum>::value_variants()
+                    .iter()
+                    .any(|panel| panel.name() == name)
+                    || name
+                        .strip_prefix("chart-")
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .is_some_and(|n| n > 0);
+                if !known {
+                    return Err(format!("Unknown panel {} in the layout {}", name, s));
                 }
-                // Dynamics: infection spreads
-                {
-                    // Model state: Agent health the next time step
-                    let mut next_health = SecondaryMap::with_capacity(health.capacity());
-                    #[cfg(feature = "net")]
-                    links.values().for_each(|&(key0, key1)| {
-                        let h0 = health[key0];
-                        let h1 = health[key1];
-                        if h0 == Health::S && h1 == Health::I && infection_distro.sample(&mut rng) {
-                            next_health.insert(key0, Health::I);
-                        }
-                        if h1 == Health::S && h0 == Health::I && infection_distro.sample(&mut rng) {
-                            next_health.insert(key1, Health::I);
-                        }
-                    });
-                    if time_step == 0 {
-                        health.iter().for_each(|(k, &h)| {
-                            if h == Health::S && initial_infection_distro.sample(&mut rng) {
-                                next_health.insert(k, Health::I);
-                            }
-                        });
-                    }
-                    health.iter().for_each(|(k, &h)| {
-                        // Choose a random cell to visit
-                        #[cfg(feature = "landscape")]
-                        let x = visit_distro.sample(&mut rng) as i32;
-                        #[cfg(feature = "landscape")]
-                        let y = visit_distro.sample(&mut rng) as i32;
-                        #[cfg(feature = "landscape")]
-                        let idx = coord.index(x, y);
-                        match h {
-                            Health::S => {
-                                #[cfg(feature = "landscape")]
-                                {
-                                    if cell_health[idx] == Health::I
-                                        && infection_distro.sample(&mut rng)
-                                    {
-                                        // Cell infects agent
-                                        next_health.insert(k, Health::I);
-                                    }
-                                }
-                            }
-                            Health::I => {
-                                #[cfg(feature = "landscape")]
-                                {
-                                    if cell_health[idx] == Health::S
-                                        && infection_distro.sample(&mut rng)
-                                    {
-                                        // Agent infects cell
-                                        next_cell_health[idx] = Health::I;
-                                    }
-                                }
-                                if recovery_distro.sample(&mut rng) {
-                                    next_health.insert(k, Health::S);
-                                }
-                            }
-                        };
-                    });
-                    // Dynamics: Disease spreads across cells and infectious cells recover
-                    #[cfg(feature = "landscape")]
-                    coord.for_each8(|this_cell_index, neighbors| {
-                        match cell_health[this_cell_index] {
-                            Health::S => {
-                                for neighbor_index in neighbors {
-                                    if cell_health[*neighbor_index] == Health::I
-                                        && infection_distro.sample(&mut rng)
-                                    {
-                                        next_cell_health[this_cell_index] = Health::I;
-                                        break;
-                                    }
-                                }
-                            }
-                            Health::I => {
-                                if recovery_distro.sample(&mut rng) {
-                                    next_cell_health[this_cell_index] = Health::S;
-                                }
-                            }
-                        }
-                    });
-                    // Dynamics: After spreading the infection, some infectious agents die
-                    health.retain(|_agent_key, h| match h {
-                        Health::S => true,
-                        Health::I => survival_distro.sample(&mut rng),
-                    });
-                    // Dynamics: Remaining agents update in parallel
-                    next_health.iter().for_each(|(k, &next_h)| {
-                        if let Some(h) = health.get_mut(k) {
-                            *h = next_h;
-                        }
-                    });
-                    // Dynamics: cells update in parallel
-                    #[cfg(feature = "landscape")]
-                    {
-                        cell_health = next_cell_health.clone();
+                match areas.iter_mut().find(|(area_name, _)| area_name == name) {
+                    Some((_, [column0, row0, column1, row1])) => {
+                        *column0 = (*column0).min(column_idx);
+                        *row0 = (*row0).min(row_idx);
+                        *column1 = (*column1).max(column_idx + 1);
+                        *row1 = (*row1).max(row_idx + 1);
                     }
+                    None => areas.push((
+                        name.clone(),
+                        [column_idx, row_idx, column_idx + 1, row_idx + 1],
+                    )),
                 }
-                // Dynamics: Prune network
-                #[cfg(feature = "net")]
-                links.retain(|_link_key, (key0, key1)| {
-                    health.contains_key(*key0) && health.contains_key(*key1)
-                });
-                // Dynamics: New agents emerge
-                let nb = health
-                    .values()
-                    .filter(|&&h| h == Health::S && birth_distro.sample(&mut rng))
-                    .count();
-                for _ in 0..nb {
-                    health.insert(Health::S);
-                }
-                // end-similar-code 3
             }
-        });
-    eprint!("{}Simulation complete. Saving to disk... ", clean_term);
-    // begin-similar-code 4
-    #[cfg(feature = "graphics")]
-    let mut agent_time_series_height = 0;
-    #[cfg(feature = "landscape-graphics")]
-    let mut cell_time_series_height = 0;
-    #[cfg(feature = "net-graphics")]
-    let mut histogram_degrees_set = BTreeSet::new();
-    #[cfg(feature = "net-graphics")]
-    let mut histogram_max_degree = 0;
-    #[cfg(feature = "net-graphics")]
-    let mut histogram_height = 0;
-    // end-similar-code 4
-    #[cfg(feature = "csv-output")]
-    let ts_name = "ts.csv";
+        }
+        // Each panel fills a rectangle of cells
+        for (name, [column0, row0, column1, row1]) in &areas {
+            if rows[*row0..*row1]
+                .iter()
+                .any(|row| row[*column0..*column1].iter().any(|cell| cell != name))
+            {
+                return Err(format!(
+                    "The cells of {} in the layout {} do not form a rectangle",
+                    name, s
+                ));
+            }
+        }
+        Ok(Self { rows, areas })
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let rows: Vec<String> = self.rows.iter().map(|row| row.join(" ")).collect();
+        write!(f, "{}", rows.join(" / "))
+    }
+}
+
+/// How ffmpeg encodes the figures of each scenario into a video
+#[derive(clap::Args)]
+struct VideoOptions {
+    /// Frames per second of the videos
+    #[arg(long, value_name = "FPS", default_value_t = 20)]
+    fps: u32,
+    /// Video codec, such as libx264 or libvpx-vp9; defaults to the choice of ffmpeg for the container.
+    #[arg(long, value_name = "CODEC")]
+    codec: Option<String>,
+    /// Container of the videos, as a file extension such as mkv, mp4, or webm
+    #[arg(long, value_name = "EXTENSION", default_value = "mkv")]
+    container: String,
+    /// Constant rate factor of the codec: lower values give better quality and larger files.
+    #[arg(long, value_name = "CRF")]
+    crf: Option<u32>,
+    /// Theme of the figures to encode, such as light or dark
+    #[arg(long, value_name = "THEME", default_value = "dark")]
+    theme: String,
+    /// Also join the videos of the scenarios, in order, into one video in the output directory of the run.
+    #[arg(long)]
+    join_videos: bool,
+}
+
+/// Steps of the workflow, which can run on their own
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Simulate the scenarios, then create their figures and videos; this is the default.
+    Run(Args),
+    /// Simulate the scenarios and save their results, without figures or videos.
+    Sweep(Args),
+    /// Draw the figures of each scenario of a run again, from its output files, into a new directory.
+    #[cfg(all(feature = "graphics", feature = "csv-output"))]
+    Plot {
+        /// Output directory of the run
+        #[arg(value_name = "DIR")]
+        run_dir: PathBuf,
+        /// Directory for the figures; it must be new or empty, unless --existing says otherwise. Defaults to run-<time>
+        /// in the current directory.
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+        #[command(flatten)]
+        figure_options: FigureOptions,
+    },
+    /// Encode the figures of each scenario of a run into a video.
+    Video {
+        /// Output directory of the run
+        #[arg(value_name = "DIR")]
+        run_dir: PathBuf,
+        /// Only encode the scenario with this id; repeat to encode several.
+        #[arg(long = "scenario", value_name = "ID")]
+        scenarios: Vec<u32>,
+        #[command(flatten)]
+        options: VideoOptions,
+    },
+    /// Summarize the time series of each scenario of a run into analysis.csv in its directory, with the
+    /// autocorrelation of each metric in autocorrelation.csv; or compare two runs.
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Analyze {
+        /// Output directory of the run
+        #[arg(value_name = "DIR", required = true)]
+        run_dir: Option<PathBuf>,
+        /// Only analyze the metric with this name, such as i; repeat to analyze several.
+        #[arg(long = "metric", value_name = "NAME")]
+        metrics: Vec<String>,
+        /// Longest lag of the autocorrelation, in samples of the time series
+        #[arg(long, value_name = "SAMPLES", default_value_t = 20)]
+        max_lag: usize,
+        /// Summary of the time series of each replicate for replicates.csv: final, peak, mean, or total
+        #[arg(long, value_name = "STATISTIC", default_value = "final")]
+        statistic: String,
+        /// Batches of each time series for the confidence interval of its mean
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        batches: usize,
+        /// Leave the time steps before STEP out of the batches, while the model leaves its initial state.
+        #[arg(long, value_name = "STEP", default_value_t = 0)]
+        warm_up: usize,
+        #[command(subcommand)]
+        command: Option<AnalyzeCommand>,
+    },
+    /// Write report.html in the directory of a run: interactive charts of the time series, tables of the metrics, and
+    /// key figures of each scenario, in one file to share.
+    #[cfg(feature = "html-report")]
+    Report {
+        /// Output directory of the run
+        #[arg(value_name = "DIR")]
+        run_dir: PathBuf,
+        /// Theme of the figures to show, such as light or dark
+        #[arg(long, value_name = "THEME", default_value = "light")]
+        theme: String,
+        /// Figures of each scenario to show, from the first to the last
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        key_frames: usize,
+        /// Embed this copy of plotly.min.js, so that the charts are interactive offline; otherwise, plotly comes from its
+        /// CDN, and the charts are static images where it cannot load.
+        #[arg(long, value_name = "FILE")]
+        plotly: Option<PathBuf>,
+    },
+    /// Combine the output directories of the shards of a run into one directory.
+    Merge {
+        /// Directory for the merged outputs; it must be new or empty, unless --existing says otherwise.
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+        /// Output directories of the shards
+        #[arg(required = true, value_name = "SHARD_DIR")]
+        shards: Vec<PathBuf>,
+    },
+    /// Run the stages of an experiment in order, such as a calibration and the projections that start from its end
+    /// states; see src/stages.rs for the plan.
+    Stages {
+        /// JSON file with the stages, their dependencies, and their arguments
+        #[arg(value_name = "PLAN")]
+        plan: PathBuf,
+        /// Directory for the outputs of the stages, one directory each; it must be new or empty, unless --existing
+        /// says otherwise.
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+    },
+    /// Explore infection probabilities adaptively: simulate them in batches, each closer than the last to where the
+    /// final size crosses a target or jumps, and summarize them in exploration.csv.
     #[cfg(feature = "csv-output")]
-    let ts_err = &*format!("Error writing time series output file {}", ts_name);
+    Explore {
+        /// Directory for the batches and the summary; it must be new or empty, unless --existing says otherwise.
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+        /// Parameters of the model in every batch, as with run
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+        /// Lowest infection probability to explore
+        #[arg(long, value_name = "P", default_value_t = 0.05)]
+        min_probability: f64,
+        /// Highest infection probability to explore
+        #[arg(long, value_name = "P", default_value_t = 0.95)]
+        max_probability: f64,
+        /// Infection probabilities of the first batch, evenly spaced from the lowest to the highest
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        initial: usize,
+        /// Infection probabilities of each later batch
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        batch_size: usize,
+        /// Batches after the first
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        iterations: usize,
+        /// Refine where the mean final size crosses this fraction of the initial population
+        #[arg(long, value_name = "FRACTION", default_value_t = 0.5)]
+        target: f64,
+        /// Refine where the mean final size changes the most instead, as near a bifurcation
+        #[arg(long)]
+        jump: bool,
+        /// Narrowest interval between infection probabilities to refine
+        #[arg(long, value_name = "WIDTH", default_value_t = 0.001)]
+        tolerance: f64,
+        /// Arguments of every batch, after --, such as --replicates 10
+        #[arg(last = true, value_name = "ARGS")]
+        args: Vec<String>,
+    },
+    /// Compare the scenarios of one or more runs on several objectives, such as the peak of infections and the final
+    /// number of vaccinated agents, and mark those on the Pareto front in pareto.csv; with figures, draw them too.
     #[cfg(feature = "csv-output")]
-    let ts_path = std::path::Path::new(ts_name);
+    Pareto {
+        /// Output directories of the runs, such as the batches of an exploration
+        #[arg(required = true, value_name = "RUN_DIR")]
+        run_dirs: Vec<PathBuf>,
+        /// Minimize this summary of the time series of a metric, such as peak:i; statistics are final, peak, mean, and
+        /// total. Repeat for several objectives.
+        #[arg(long = "minimize", value_name = "STATISTIC:METRIC")]
+        minimize: Vec<String>,
+        /// Maximize this summary of the time series of a metric, such as final:s
+        #[arg(long = "maximize", value_name = "STATISTIC:METRIC")]
+        maximize: Vec<String>,
+        /// Directory for pareto.csv and its figures; defaults to the run directory when there is only one.
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+        #[cfg(feature = "graphics")]
+        #[command(flatten)]
+        figure_options: FigureOptions,
+    },
+    /// Write training data for emulators of the model to design.csv: one row per scenario of the runs, with its inputs
+    /// and the final, peak, and mean values of its metrics; design.json describes how to transform and standardize
+    /// each column.
     #[cfg(feature = "csv-output")]
-    if ts_path.exists() {
-        panic!(
-            "This program just tried to rewrite {}; please debug",
-            ts_name
+    Design {
+        /// Output directories of the runs, such as the batches of an exploration
+        #[arg(required = true, value_name = "RUN_DIR")]
+        run_dirs: Vec<PathBuf>,
+        /// Directory for design.csv and design.json; defaults to the run directory when there is only one.
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+        /// Write the values transformed and standardized, as design.json describes, rather than as simulated
+        #[arg(long)]
+        standardize: bool,
+    },
+    /// Serve a gRPC API to start, follow, and cancel runs from other services; see proto/simulations.proto.
+    #[cfg(feature = "grpc")]
+    Grpc {
+        /// Address of the server
+        #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:50051")]
+        listen: std::net::SocketAddr,
+    },
+}
+
+/// Analyses of several runs
+#[derive(clap::Subcommand)]
+enum AnalyzeCommand {
+    /// Compare two runs, such as before and after a change to the model: for each infection probability and metric,
+    /// test whether the replicates of the runs differ, and save the tests and effect sizes to comparison.csv.
+    Compare {
+        /// Output directory of the first run, such as the baseline
+        #[arg(value_name = "FIRST_DIR")]
+        first: PathBuf,
+        /// Output directory of the second run
+        #[arg(value_name = "SECOND_DIR")]
+        second: PathBuf,
+        /// Directory for comparison.csv
+        #[arg(long, value_name = "DIR")]
+        output_dir: PathBuf,
+        /// Only compare the metric with this name, such as i; repeat to compare several.
+        #[arg(long = "metric", value_name = "NAME")]
+        metrics: Vec<String>,
+        /// Summary of the time series of each replicate to compare: final, peak, mean, or total
+        #[arg(long, value_name = "STATISTIC", default_value = "final")]
+        statistic: String,
+    },
+}
+
+/// Output directories of the scenarios of a run, in order
+fn scenario_dirs(run_dir: &Path) -> error::Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(run_dir)
+        .or_error(&format!(
+            "Could not read the directory {}",
+            run_dir.display()
+        ))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("scenario-"))
+        })
+        .collect();
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Frame stride of the figures of a run, from its manifest
+fn frame_stride(run_dir: &Path) -> usize {
+    fs::read_to_string(run_dir.join("manifest.json"))
+        .ok()
+        .and_then(|manifest| serde_json::from_str::<serde_json::Value>(&manifest).ok())
+        .and_then(|manifest| manifest["figures"]["frame_stride"].as_u64())
+        .map_or(1, |frame_stride| frame_stride as usize)
+}
+
+/// Whether a component of the model, network or landscape, ran in a run, from its manifest
+#[cfg(all(
+    feature = "graphics",
+    feature = "csv-output",
+    any(feature = "net", feature = "landscape")
+))]
+fn ran_component(run_dir: &Path, component: &str) -> bool {
+    fs::read_to_string(run_dir.join("manifest.json"))
+        .ok()
+        .and_then(|manifest| serde_json::from_str::<serde_json::Value>(&manifest).ok())
+        .and_then(|manifest| manifest["components"][component].as_bool())
+        .unwrap_or(true)
+}
+
+/// Encodes the figures of a scenario into a video, such as video_dark.mkv, with ffmpeg.
+/// Figures drawn once every frame_stride frames lower the frame rate, so that videos keep their pace.
+fn encode_video(
+    scenario_dir: &Path,
+    options: &VideoOptions,
+    frame_stride: usize,
+    existing: Existing,
+    clean_term: &str,
+) -> error::Result<()> {
+    let frames = theme::frames_dir_name(&options.theme);
+    let video_path = scenario_dir.join(format!("video_{}.{}", options.theme, options.container));
+    let log_path = scenario_dir.join(format!("video_{}.log", options.theme));
+    let frames_dir = scenario_dir.join(&frames);
+    if !frames_dir.is_dir() {
+        eprintln!(
+            "{}{} has no {} figures to encode.",
+            clean_term,
+            scenario_dir.display(),
+            frames
         );
+        return Ok(());
     }
-    #[cfg(feature = "csv-output")]
-    let mut ts_file = fs::File::create(ts_path).expect(ts_err);
-    #[cfg(feature = "csv-output")]
-    {
-        write!(&mut ts_file, "Infection Probability").expect(ts_err);
-        write!(&mut ts_file, ",Time step").expect(ts_err);
-        #[cfg(feature = "net-csv")]
-        {
-            write!(&mut ts_file, ",d_s Maximum network degree of susceptibles").expect(ts_err);
-            write!(&mut ts_file, ",d_i Maximum network degree of infectious").expect(ts_err);
+    if video_path.exists() {
+        match existing {
+            Existing::Error => return Err(Error::Exists(video_path)),
+            Existing::Skip => return Ok(()),
+            Existing::Overwrite => {}
         }
-        #[cfg(feature = "landscape-csv")]
-        write!(&mut ts_file, ",c_i Infected cells").expect(ts_err);
-        write!(&mut ts_file, ",n Number of agents").expect(ts_err);
-        write!(&mut ts_file, ",s Susceptibles").expect(ts_err);
-        writeln!(&mut ts_file, ",i Infected").expect(ts_err);
     }
-    scenarios.iter().for_each(|scenario| {
-        #[cfg(feature = "csv-output")]
-        scenario.time_series.iter().for_each(|time_step_results| {
-            write!(&mut ts_file, "{}", scenario.infection_probability).expect(ts_err);
-            write!(&mut ts_file, ",{}", time_step_results.time_step).expect(ts_err);
-            #[cfg(feature = "net-csv")]
+    // GIF delays are in hundredths of a second
+    #[cfg(feature = "gif-video")]
+    let gif_delay = (100 * frame_stride as u32 / options.fps.max(1)).max(1) as u16;
+    // Animated GIFs need no ffmpeg
+    #[cfg(feature = "gif-video")]
+    if options.container == "gif" {
+        return write_gif(&frames_dir, &video_path, gif_delay, existing, clean_term);
+    }
+    eprint!(
+        "{}Writing {}; open log file {} to follow progress.",
+        clean_term,
+        video_path.display(),
+        log_path.display()
+    );
+    let mut ffmpeg = std::process::Command::new("ffmpeg");
+    // Debug levels for the "level" variable: warning 24, info 32, verbose 40
+    ffmpeg
+        .env("FFREPORT", format!("file={}:level=32", log_path.display()))
+        .arg("-r")
+        .arg(if frame_stride == 1 {
+            options.fps.to_string()
+        } else {
+            format!("{}/{}", options.fps, frame_stride)
+        })
+        .arg("-i")
+        .arg(frames_dir.join("%d.png"))
+        .args(["-loglevel", "warning", "-hide_banner"]);
+    if existing == Existing::Overwrite {
+        ffmpeg.arg("-y");
+    }
+    if let Some(codec) = &options.codec {
+        ffmpeg.args(["-c:v", codec]);
+    }
+    if let Some(crf) = options.crf {
+        ffmpeg.args(["-crf", &crf.to_string()]);
+    }
+    match ffmpeg.arg(&video_path).status() {
+        Ok(ffmpeg_status) if ffmpeg_status.success() => {
+            eprintln!(
+                "{}Created {}. Learn more by reviewing {}.",
+                clean_term,
+                video_path.display(),
+                log_path.display()
+            );
+            Ok(())
+        }
+        Ok(ffmpeg_status) => Err(Error::Video {
+            path: video_path,
+            message: format!(
+                "ffmpeg ended with {}; please review {} to learn more",
+                ffmpeg_status,
+                log_path.display()
+            ),
+        }),
+        Err(e) => {
+            // Without ffmpeg, an animated GIF still shows the run
+            #[cfg(feature = "gif-video")]
             {
-                write!(&mut ts_file, ",{}", time_step_results.d_s).expect(ts_err);
-                write!(&mut ts_file, ",{}", time_step_results.d_i).expect(ts_err);
-            }
-            #[cfg(feature = "landscape-csv")]
-            write!(&mut ts_file, ",{}", time_step_results.c_i).expect(ts_err);
-            write!(&mut ts_file, ",{}", time_step_results.n).expect(ts_err);
-            write!(&mut ts_file, ",{}", time_step_results.s).expect(ts_err);
-            writeln!(&mut ts_file, ",{}", time_step_results.i).expect(ts_err);
+                eprintln!(
+                    "{}Could not run ffmpeg for {}: {}.",
+                    clean_term,
+                    video_path.display(),
+                    e
+                );
+                write_gif(
+                    &frames_dir,
+                    &video_path.with_extension("gif"),
+                    gif_delay,
+                    existing,
+                    clean_term,
+                )
+            }
+            #[cfg(not(feature = "gif-video"))]
+            Err(Error::Video {
+                path: video_path,
+                message: format!("Could not run ffmpeg: {}", e),
+            })
+        }
+    }
+}
+
+/// Joins the videos of these scenarios, in order, into one video of the run, such as video_dark.mkv, with ffmpeg.
+/// Scenarios without a video are left out.
+fn join_videos(
+    run_dir: &Path,
+    scenario_dirs: &[&Path],
+    options: &VideoOptions,
+    existing: Existing,
+    clean_term: &str,
+) -> error::Result<()> {
+    let video_name = format!("video_{}.{}", options.theme, options.container);
+    let video_path = run_dir.join(&video_name);
+    let list_path = run_dir.join(format!("video_{}.txt", options.theme));
+    let log_path = run_dir.join(format!("video_{}.log", options.theme));
+    if options.container == "gif" {
+        return Err(Error::Video {
+            path: video_path,
+            message: "ffmpeg joins videos, but not animated GIFs".to_string(),
         });
-        // begin-similar-code 5
-        #[cfg(feature = "graphics")]
+    }
+    if video_path.exists() {
+        match existing {
+            Existing::Error => return Err(Error::Exists(video_path)),
+            Existing::Skip => return Ok(()),
+            Existing::Overwrite => {}
+        }
+    }
+    // The concat demuxer of ffmpeg reads the videos from a list, with quotes escaped
+    let mut list = String::new();
+    for scenario_dir in scenario_dirs {
+        let scenario_video = scenario_dir.join(&video_name);
+        if scenario_video.is_file() {
+            let scenario_video = fs::canonicalize(&scenario_video)
+                .or_error(&format!("Could not find {}", scenario_video.display()))?;
+            list.push_str(&format!(
+                "file '{}'\n",
+                scenario_video.display().to_string().replace('\'', "'\\''")
+            ));
+        }
+    }
+    if list.is_empty() {
+        eprintln!(
+            "{}{} has no videos of scenarios to join.",
+            clean_term,
+            run_dir.display()
+        );
+        return Ok(());
+    }
+    fs::write(&list_path, list).or_error(&format!("Error writing {}", list_path.display()))?;
+    eprint!(
+        "{}Writing {}; open log file {} to follow progress.",
+        clean_term,
+        video_path.display(),
+        log_path.display()
+    );
+    let mut ffmpeg = std::process::Command::new("ffmpeg");
+    ffmpeg
+        .env("FFREPORT", format!("file={}:level=32", log_path.display()))
+        .args(["-f", "concat", "-safe", "0", "-i"])
+        .arg(&list_path)
+        .args(["-c", "copy", "-loglevel", "warning", "-hide_banner"]);
+    if existing == Existing::Overwrite {
+        ffmpeg.arg("-y");
+    }
+    match ffmpeg.arg(&video_path).status() {
+        Ok(ffmpeg_status) if ffmpeg_status.success() => {
+            eprintln!(
+                "{}Created {}. Learn more by reviewing {}.",
+                clean_term,
+                video_path.display(),
+                log_path.display()
+            );
+            Ok(())
+        }
+        Ok(ffmpeg_status) => Err(Error::Video {
+            path: video_path,
+            message: format!(
+                "ffmpeg ended with {}; please review {} to learn more",
+                ffmpeg_status,
+                log_path.display()
+            ),
+        }),
+        Err(e) => Err(Error::Video {
+            path: video_path,
+            message: format!("Could not run ffmpeg: {}", e),
+        }),
+    }
+}
+
+/// Reads a figure as a frame of an animated GIF
+#[cfg(feature = "gif-video")]
+fn gif_frame(frame_path: &Path, delay: u16) -> std::io::Result<gif::Frame<'static>> {
+    let mut decoder = png::Decoder::new(fs::File::open(frame_path)?);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+    let (width, height) = (info.width as u16, info.height as u16);
+    // Speed 30 of 30 quantizes colors quickly; figures have few colors anyway.
+    let mut frame = match info.color_type {
+        png::ColorType::Rgb => {
+            gif::Frame::from_rgb_speed(width, height, &pixels[..info.buffer_size()], 30)
+        }
+        png::ColorType::Rgba => {
+            gif::Frame::from_rgba_speed(width, height, &mut pixels[..info.buffer_size()], 30)
+        }
+        color_type => {
+            return Err(std::io::Error::other(format!(
+                "{} has unsupported colors {:?}",
+                frame_path.display(),
+                color_type
+            )))
+        }
+    };
+    frame.delay = delay;
+    Ok(frame)
+}
+
+/// Encodes the figures in frames_dir, numbered from 1, into an animated GIF, without ffmpeg.
+/// Each frame lasts delay hundredths of a second.
+#[cfg(feature = "gif-video")]
+fn write_gif(
+    frames_dir: &Path,
+    gif_path: &Path,
+    delay: u16,
+    existing: Existing,
+    clean_term: &str,
+) -> error::Result<()> {
+    if gif_path.exists() {
+        match existing {
+            Existing::Error => return Err(Error::Exists(gif_path.to_path_buf())),
+            Existing::Skip => return Ok(()),
+            Existing::Overwrite => {}
+        }
+    }
+    eprint!("{}Writing {}... ", clean_term, gif_path.display());
+    let encode = || -> std::io::Result<usize> {
+        let frame_paths: Vec<PathBuf> = (1..)
+            .map(|file_number| frames_dir.join(format!("{}.png", file_number)))
+            .take_while(|frame_path| frame_path.exists())
+            .collect();
+        let mut encoder = None;
+        // Quantizing colors takes most of the time, so frames are quantized in parallel, a few at a time
+        for chunk in frame_paths.chunks(64) {
+            let frames = chunk
+                .par_iter()
+                .map(|frame_path| gif_frame(frame_path, delay))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            for frame in &frames {
+                let encoder = match &mut encoder {
+                    Some(encoder) => encoder,
+                    None => encoder.insert({
+                        let mut encoder = gif::Encoder::new(
+                            std::io::BufWriter::new(fs::File::create(gif_path)?),
+                            frame.width,
+                            frame.height,
+                            &[],
+                        )
+                        .map_err(std::io::Error::other)?;
+                        encoder
+                            .set_repeat(gif::Repeat::Infinite)
+                            .map_err(std::io::Error::other)?;
+                        encoder
+                    }),
+                };
+                encoder.write_frame(frame).map_err(std::io::Error::other)?;
+            }
+        }
+        Ok(frame_paths.len())
+    };
+    let frames = encode().map_err(|e| Error::Video {
+        path: gif_path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    eprintln!(
+        "{}Created {} with {} frames.",
+        clean_term,
+        gif_path.display(),
+        frames
+    );
+    Ok(())
+}
+
+/// Summarizes the time series in ts.csv of each scenario of a run: count, mean, variance, and final value of each metric
+fn analyze(
+    run_dir: &Path,
+    metrics: &[String],
+    max_lag: usize,
+    estimates: &Estimates,
+    existing: Existing,
+) -> error::Result<()> {
+    let analysis_path = run_dir.join("analysis.csv");
+    let analysis_name = analysis_path.display();
+    let analysis_err = &*format!("Error writing analysis output file {}", analysis_name);
+    let mut analysis_file = create_output_file(&analysis_path, analysis_err, existing)?;
+    writeln!(
+        &mut analysis_file,
+        "Infection Probability,Metric,Time steps,Mean,Variance,Final,Lag-1 autocorrelation,Dominant period,Trend,Mean shift,Variance ratio,Mean after warm-up,Batch means half-width"
+    )
+    .or_error(analysis_err)?;
+    // Antithetic pairs of replicates count as one sample of the replicates
+    let pairs = antithetic_pairs(run_dir);
+    let mut replicate_values: BTreeMap<(u64, String), BTreeMap<u32, Vec<f64>>> = BTreeMap::new();
+    let acf_path = run_dir.join("autocorrelation.csv");
+    let acf_name = acf_path.display();
+    let acf_err = &*format!("Error writing analysis output file {}", acf_name);
+    let mut acf_file = create_output_file(&acf_path, acf_err, existing)?;
+    writeln!(
+        &mut acf_file,
+        "Infection Probability,Metric,Lag,Autocorrelation"
+    )
+    .or_error(acf_err)?;
+    for (idx, scenario_dir) in scenario_dirs(run_dir)?.into_iter().enumerate() {
+        let ts_path = scenario_dir.join("ts.csv");
+        let (titles, rows) = fs::File::open(&ts_path)
+            .and_then(|f| tables::read_csv(std::io::BufReader::new(f)))
+            .or_error(&format!("Could not read {}", ts_path.display()))?;
+        let Some(last) = rows.last() else {
+            continue;
+        };
+        let sample = pairs
+            .as_ref()
+            .and_then(|pairs| pairs.get(scenario_dir.file_name()?.to_str()?).copied())
+            .unwrap_or(idx as u32);
+        println!(
+            "{} (infection probability {}):",
+            scenario_dir.display(),
+            last[0]
+        );
+        // Lags and periods in time steps rather than samples
+        let sample_every = match rows.get(..2) {
+            Some([first, second]) => second[1] - first[1],
+            _ => 1.0,
+        };
+        // The first two columns are the infection probability and the time step; titles start with the metric name
+        for (column, title) in titles.iter().enumerate().skip(2).filter(|(_, title)| {
+            metrics.is_empty()
+                || metrics
+                    .iter()
+                    .any(|metric| title.split(' ').next() == Some(metric.as_str()))
+        }) {
+            let series: Vec<f64> = rows.iter().map(|row| row[column]).collect();
+            let mut stats = RunningStats::default();
+            series.iter().for_each(|&value| stats.push(value));
+            let acf = analysis::autocorrelation(&series, max_lag);
+            let period = analysis::dominant_period(&series).map(|period| period * sample_every);
+            let stationarity = analysis::stationarity(&series);
+            let after_warm_up: Vec<f64> = rows
+                .iter()
+                .filter(|row| row[1] >= estimates.warm_up as f64)
+                .map(|row| row[column])
+                .collect();
+            let batch_means = stats::batch_means(&after_warm_up, estimates.batches);
+            replicate_values
+                .entry((last[0].to_bits(), title.clone()))
+                .or_default()
+                .entry(sample)
+                .or_default()
+                .push(estimates.statistic.evaluate(&series));
+            let optional =
+                |value: Option<f64>| value.map_or(String::new(), |value| value.to_string());
+            writeln!(
+                &mut analysis_file,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                last[0],
+                title,
+                stats.count(),
+                stats.mean(),
+                stats.variance(),
+                last[column],
+                optional(acf.get(1).copied()),
+                optional(period),
+                optional(stationarity.map(|s| s.trend / sample_every)),
+                optional(stationarity.map(|s| s.mean_shift)),
+                optional(stationarity.map(|s| s.variance_ratio)),
+                optional(batch_means.map(|(mean, _)| mean)),
+                optional(batch_means.map(|(_, half_width)| half_width)),
+            )
+            .or_error(analysis_err)?;
+            for (lag, autocorrelation) in acf.iter().enumerate() {
+                writeln!(
+                    &mut acf_file,
+                    "{},{},{},{}",
+                    last[0],
+                    title,
+                    lag as f64 * sample_every,
+                    autocorrelation
+                )
+                .or_error(acf_err)?;
+            }
+            println!(
+                "  {:<30} mean {:>12.4} sd {:>12.4} final {:>12.4} period {:>8}",
+                title,
+                stats.mean(),
+                stats.variance().sqrt(),
+                last[column],
+                period.map_or("-".to_string(), |period| format!("{:.1}", period))
+            );
+        }
+    }
+    analysis_file.flush().or_error(analysis_err)?;
+    acf_file.flush().or_error(acf_err)?;
+    eprintln!("Analysis saved to {} and {}.", analysis_name, acf_name);
+    let replicates_path = run_dir.join("replicates.csv");
+    let replicates_name = replicates_path.display();
+    let replicates_err = &*format!("Error writing analysis output file {}", replicates_name);
+    let mut replicates_file = create_output_file(&replicates_path, replicates_err, existing)?;
+    tables::write_csv_header(&mut replicates_file, &replicates_columns())
+        .or_error(replicates_err)?;
+    for ((probability, title), samples) in &replicate_values {
+        // The mean of each antithetic pair is one independent sample
+        let values: Vec<f64> = samples
+            .values()
+            .map(|values| values.iter().sum::<f64>() / values.len() as f64)
+            .collect();
+        let interval = stats::confidence_interval(&values);
+        let optional = |value: Option<f64>| value.map_or(Value::Utf8(""), Value::Float64);
+        let row = [
+            Value::Float64(f64::from_bits(*probability)),
+            Value::Utf8(title),
+            Value::UInt32(samples.values().map(Vec::len).sum::<usize>() as u32),
+            Value::UInt32(values.len() as u32),
+            Value::Float64(values.iter().sum::<f64>() / values.len() as f64),
+            optional(interval.map(|(_, half_width)| half_width)),
+        ];
+        tables::write_csv_row(&mut replicates_file, &row).or_error(replicates_err)?;
+    }
+    replicates_file.flush().or_error(replicates_err)?;
+    eprintln!(
+        "Confidence intervals across replicates saved to {}.",
+        replicates_name
+    );
+    Ok(())
+}
+
+/// Estimates of analyze that carry confidence intervals
+struct Estimates {
+    /// Summary of the time series of each replicate, for the interval across replicates
+    statistic: Statistic,
+    /// Batches of each time series, for the interval of its mean
+    batches: usize,
+    /// Time steps left out of the batches, while the model leaves its initial state
+    warm_up: usize,
+}
+
+/// Pair of each scenario of a run with antithetic replicates, by the name of its directory, from the manifest of the
+/// run; None for runs without antithetic replicates. Numbers of pairs repeat across infection probabilities.
+fn antithetic_pairs(run_dir: &Path) -> Option<BTreeMap<String, u32>> {
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(run_dir.join("manifest.json")).ok()?).ok()?;
+    if manifest["parameters"]["antithetic"].as_bool() != Some(true) {
+        return None;
+    }
+    manifest["scenarios"]
+        .as_array()?
+        .iter()
+        .map(|scenario| {
+            let dir = Path::new(scenario["dir"].as_str()?).file_name()?.to_str()?;
+            let replicate = scenario["replicate"].as_u64()? as u32;
+            Some((dir.to_string(), replicate / 2))
+        })
+        .collect()
+}
+
+/// Columns of replicates.csv: a statistic of each metric across the replicates of each infection probability, with
+/// the half-width of its 95% confidence interval
+fn replicates_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("metric", "Metric", ColumnType::Utf8),
+        Column::new("replicates", "Replicates", ColumnType::UInt32),
+        Column::new("samples", "Independent samples", ColumnType::UInt32),
+        Column::new("mean", "Mean", ColumnType::Float64),
+        Column::new("half_width", "95% half-width", ColumnType::Float64),
+    ]
+}
+
+/// Value of the statistic of each metric in each replicate of a run, by infection probability and metric title, from
+/// the ts.csv of its scenarios; only the metrics named, or all without names. Antithetic pairs of replicates count as
+/// one replicate, with the mean of the pair, as in analyze.
+fn replicate_values(
+    run_dir: &Path,
+    metrics: &[String],
+    statistic: Statistic,
+) -> error::Result<BTreeMap<(u64, String), Vec<f64>>> {
+    let pairs = antithetic_pairs(run_dir);
+    let mut values: BTreeMap<(u64, String), BTreeMap<u32, Vec<f64>>> = BTreeMap::new();
+    for (idx, scenario_dir) in scenario_dirs(run_dir)?.into_iter().enumerate() {
+        let ts_path = scenario_dir.join("ts.csv");
+        let (titles, rows) = fs::File::open(&ts_path)
+            .and_then(|f| tables::read_csv(std::io::BufReader::new(f)))
+            .or_error(&format!("Could not read {}", ts_path.display()))?;
+        let Some(first) = rows.first() else {
+            continue;
+        };
+        let sample = pairs
+            .as_ref()
+            .and_then(|pairs| pairs.get(scenario_dir.file_name()?.to_str()?).copied())
+            .unwrap_or(idx as u32);
+        // The first two columns are the infection probability and the time step; titles start with the metric name
+        for (column, title) in titles.iter().enumerate().skip(2).filter(|(_, title)| {
+            metrics.is_empty()
+                || metrics
+                    .iter()
+                    .any(|metric| title.split(' ').next() == Some(metric.as_str()))
+        }) {
+            let series: Vec<f64> = rows.iter().map(|row| row[column]).collect();
+            values
+                .entry((first[0].to_bits(), title.clone()))
+                .or_default()
+                .entry(sample)
+                .or_default()
+                .push(statistic.evaluate(&series));
+        }
+    }
+    Ok(values
+        .into_iter()
+        .map(|(key, samples)| {
+            let values = samples
+                .values()
+                .map(|values| values.iter().sum::<f64>() / values.len() as f64)
+                .collect();
+            (key, values)
+        })
+        .collect())
+}
+
+/// Compares the replicates of two runs, for each infection probability and metric that both have: the
+/// Kolmogorov-Smirnov and rank-sum tests, and the difference of means, Cohen's d, and Cliff's delta of the second run
+/// with respect to the first. Writes comparison.csv to the output directory.
+fn compare(
+    first: &Path,
+    second: &Path,
+    output_dir: &Path,
+    metrics: &[String],
+    statistic: Statistic,
+    existing: Existing,
+) -> error::Result<()> {
+    let first_values = replicate_values(first, metrics, statistic)?;
+    let second_values = replicate_values(second, metrics, statistic)?;
+    let comparison_path = output_dir.join("comparison.csv");
+    let comparison_name = comparison_path.display();
+    let comparison_err = &*format!("Error writing comparison output file {}", comparison_name);
+    let mut comparison_file = create_output_file(&comparison_path, comparison_err, existing)?;
+    tables::write_csv_header(&mut comparison_file, &comparison_columns())
+        .or_error(comparison_err)?;
+    println!(
+        "{} of each replicate, {} with respect to {}:",
+        statistic.name(),
+        second.display(),
+        first.display()
+    );
+    let optional = |value: Option<f64>| value.map_or(Value::Utf8(""), Value::Float64);
+    let mut compared = 0;
+    for ((probability, title), a) in &first_values {
+        let Some(b) = second_values.get(&(*probability, title.clone())) else {
+            continue;
+        };
+        compared += 1;
+        let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let ks = stats::kolmogorov_smirnov(a, b);
+        let rank_sum = stats::rank_sum(a, b);
+        let row = [
+            Value::Float64(f64::from_bits(*probability)),
+            Value::Utf8(title),
+            Value::UInt32(a.len() as u32),
+            Value::UInt32(b.len() as u32),
+            Value::Float64(mean(a)),
+            Value::Float64(mean(b)),
+            Value::Float64(mean(b) - mean(a)),
+            optional(stats::cohens_d(a, b)),
+            optional(stats::cliffs_delta(a, b)),
+            optional(ks.map(|(distance, _)| distance)),
+            optional(ks.map(|(_, p)| p)),
+            optional(rank_sum.map(|(u, _)| u)),
+            optional(rank_sum.map(|(_, p)| p)),
+        ];
+        tables::write_csv_row(&mut comparison_file, &row).or_error(comparison_err)?;
+        println!(
+            "  {:<8} {:<30} difference {:>12.4} Cliff's delta {:>7.3} KS p {:>7.4} rank-sum p {:>7.4}",
+            f64::from_bits(*probability),
+            title,
+            mean(b) - mean(a),
+            stats::cliffs_delta(a, b).unwrap_or(f64::NAN),
+            ks.map_or(f64::NAN, |(_, p)| p),
+            rank_sum.map_or(f64::NAN, |(_, p)| p),
+        );
+    }
+    comparison_file.flush().or_error(comparison_err)?;
+    if compared == 0 {
+        eprintln!("The runs share no infection probability and metric to compare.");
+    }
+    eprintln!("Comparison saved to {}.", comparison_name);
+    Ok(())
+}
+
+/// Columns of comparison.csv: the replicates of the two runs, and how they differ
+fn comparison_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("metric", "Metric", ColumnType::Utf8),
+        Column::new(
+            "replicates_first",
+            "Replicates of the first run",
+            ColumnType::UInt32,
+        ),
+        Column::new(
+            "replicates_second",
+            "Replicates of the second run",
+            ColumnType::UInt32,
+        ),
+        Column::new("mean_first", "Mean of the first run", ColumnType::Float64),
+        Column::new("mean_second", "Mean of the second run", ColumnType::Float64),
+        Column::new("difference", "Difference of means", ColumnType::Float64),
+        Column::new("cohens_d", "Cohen's d", ColumnType::Float64),
+        Column::new("cliffs_delta", "Cliff's delta", ColumnType::Float64),
+        Column::new(
+            "ks_statistic",
+            "Kolmogorov-Smirnov statistic",
+            ColumnType::Float64,
+        ),
+        Column::new("ks_p", "Kolmogorov-Smirnov p-value", ColumnType::Float64),
+        Column::new("rank_sum_u", "Rank-sum U", ColumnType::Float64),
+        Column::new("rank_sum_p", "Rank-sum p-value", ColumnType::Float64),
+    ]
+}
+
+/// Compares the scenarios of the runs on the objectives, from their ts.csv, and writes pareto.csv to the output
+/// directory: the values of the objectives for each scenario, and whether it lies on the Pareto front. Returns both.
+#[cfg(feature = "csv-output")]
+fn pareto_front(
+    run_dirs: &[PathBuf],
+    objectives: &[Objective],
+    output_dir: &Path,
+    existing: Existing,
+) -> error::Result<(Vec<Vec<f64>>, Vec<bool>)> {
+    let mut scenarios = Vec::new();
+    let mut points = Vec::new();
+    let mut run_scenario_dirs = Vec::new();
+    for run_dir in run_dirs {
+        run_scenario_dirs.extend(scenario_dirs(run_dir)?);
+    }
+    for scenario_dir in run_scenario_dirs {
+        let ts_path = scenario_dir.join("ts.csv");
+        let (titles, rows) = fs::File::open(&ts_path)
+            .and_then(|f| tables::read_csv(std::io::BufReader::new(f)))
+            .or_error(&format!("Could not read {}", ts_path.display()))?;
+        // Titles start with the metric name
+        let point = objectives
+            .iter()
+            .map(|objective| {
+                let column = titles
+                    .iter()
+                    .position(|title| title.split(' ').next() == Some(objective.metric.as_str()))
+                    .ok_or_else(|| {
+                        Error::Invalid(vec![format!(
+                            "{} has no metric {}",
+                            ts_path.display(),
+                            objective.metric
+                        )])
+                    })?;
+                let series: Vec<f64> = rows.iter().map(|row| row[column]).collect();
+                Ok(objective.evaluate(&series))
+            })
+            .collect::<error::Result<Vec<f64>>>()?;
+        let infection_probability = rows.first().map_or(f64::NAN, |row| row[0]);
+        scenarios.push((scenario_dir, infection_probability));
+        points.push(point);
+    }
+    let front = pareto::front(objectives, &points);
+    let pareto_path = output_dir.join("pareto.csv");
+    let pareto_name = pareto_path.display();
+    let pareto_err = &*format!("Error writing Pareto front output file {}", pareto_name);
+    let mut pareto_file = create_output_file(&pareto_path, pareto_err, existing)?;
+    let columns: Vec<Column> = vec![
+        Column::new("scenario_dir", "Scenario directory", ColumnType::Utf8),
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+    ]
+    .into_iter()
+    .chain(objectives.iter().enumerate().map(|(idx, objective)| {
+        Column::new(
+            format!("objective_{}", idx),
+            objective.to_string(),
+            ColumnType::Float64,
+        )
+    }))
+    .chain([Column::new("front", "Pareto front", ColumnType::UInt32)])
+    .collect();
+    tables::write_csv_header(&mut pareto_file, &columns).or_error(pareto_err)?;
+    for (((scenario_dir, infection_probability), point), &on_front) in
+        scenarios.iter().zip(&points).zip(&front)
+    {
+        let scenario_dir = scenario_dir.display().to_string();
+        let row: Vec<Value> = vec![
+            Value::Utf8(&scenario_dir),
+            Value::Float64(*infection_probability),
+        ]
+        .into_iter()
+        .chain(point.iter().map(|&value| Value::Float64(value)))
+        .chain([Value::UInt32(on_front as u32)])
+        .collect();
+        tables::write_csv_row(&mut pareto_file, &row).or_error(pareto_err)?;
+    }
+    pareto_file.flush().or_error(pareto_err)?;
+    eprintln!("Pareto front saved to {}.", pareto_name);
+    Ok((points, front))
+}
+
+/// Draws the scenarios that pareto compares on its first two objectives, with the Pareto front in the color of
+/// infected agents, into pareto-<theme>.png in the output directory.
+#[cfg(all(feature = "graphics", feature = "csv-output"))]
+fn draw_pareto(
+    objectives: &[Objective],
+    points: &[Vec<f64>],
+    front: &[bool],
+    output_dir: &Path,
+    options: &FigureOptions,
+    themes: &[Theme],
+    existing: Existing,
+) -> error::Result<()> {
+    let complete: Vec<(f64, f64, bool)> = points
+        .iter()
+        .zip(front)
+        .map(|(point, &on_front)| (point[0], point[1], on_front))
+        .filter(|&(x, y, _)| x.is_finite() && y.is_finite())
+        .collect();
+    // Ranges with a margin, so that no point lies on an axis
+    let range = |values: &mut dyn Iterator<Item = f64>| {
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        let margin = if max > min { (max - min) / 20.0 } else { 1.0 };
+        (min - margin)..(max + margin)
+    };
+    let x_range = range(&mut complete.iter().map(|&(x, _, _)| x));
+    let y_range = range(&mut complete.iter().map(|&(_, y, _)| y));
+    // With two objectives, the front is a staircase from one end to the other
+    let mut front_line: Vec<(f64, f64)> = complete
+        .iter()
+        .filter(|&&(_, _, on_front)| on_front)
+        .map(|&(x, y, _)| (x, y))
+        .collect();
+    front_line.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let font = options.font.as_str();
+    for theme in themes {
+        let figure_path = output_dir.join(format!("pareto-{}.png", theme.name));
+        if figure_path.exists() {
+            match existing {
+                Existing::Error => return Err(Error::Exists(figure_path)),
+                Existing::Skip => continue,
+                Existing::Overwrite => {}
+            }
+        }
+        let draw = || -> Result<(), Box<dyn std::error::Error>> {
+            let drawing_area =
+                BitMapBackend::new(&figure_path, (options.figure_width, options.figure_height))
+                    .into_drawing_area();
+            let color0 = &theme.foreground;
+            let text0 = (font, options.title_font_size).into_font().color(color0);
+            let text1 = (font, options.label_font_size).into_font().color(color0);
+            drawing_area.fill(&theme.background)?;
+            let mut chart = ChartBuilder::on(&drawing_area)
+                .x_label_area_size(40)
+                .y_label_area_size(100)
+                .margin(options.chart_margin + 10)
+                .caption(
+                    format!(
+                        "Pareto front: {} of {} scenarios",
+                        front_line.len(),
+                        complete.len()
+                    ),
+                    text0,
+                )
+                .build_cartesian_2d(x_range.clone(), y_range.clone())?;
+            chart
+                .configure_mesh()
+                .light_line_style(color0.mix(0.1))
+                .bold_line_style(color0.mix(0.2))
+                .x_desc(objectives[0].to_string())
+                .y_desc(objectives[1].to_string())
+                .axis_style(color0)
+                .axis_desc_style(text1.clone())
+                .label_style(text1)
+                .draw()?;
+            chart.draw_series(
+                complete
+                    .iter()
+                    .filter(|&&(_, _, on_front)| !on_front)
+                    .map(|&(x, y, _)| Circle::new((x, y), 4, color0.mix(0.4).filled())),
+            )?;
+            if objectives.len() == 2 {
+                chart.draw_series(LineSeries::new(front_line.iter().copied(), &theme.infected))?;
+            }
+            chart.draw_series(
+                front_line
+                    .iter()
+                    .map(|&(x, y)| Circle::new((x, y), 6, theme.infected.filled())),
+            )?;
+            drawing_area.present()?;
+            Ok(())
+        };
+        draw().map_err(|e| Error::Figure {
+            path: figure_path.clone(),
+            message: e.to_string(),
+        })?;
+        eprintln!("Pareto front drawn in {}.", figure_path.display());
+    }
+    Ok(())
+}
+
+/// What the figures show and how, the same for every scenario
+#[cfg(feature = "graphics")]
+struct FigureSettings<'a> {
+    collector: &'a DataCollector,
+    #[cfg(feature = "landscape")]
+    coord: &'a WrappingCoords2d,
+    /// Draw figures once every render_every time steps
+    render_every: usize,
+    time_series_len: usize,
+    /// Only show the network degrees that ever occur
+    #[cfg(feature = "net-graphics")]
+    compress_histogram: bool,
+    options: &'a FigureOptions,
+    themes: Vec<Theme>,
+    /// Draw one of every frame_stride frames of landscape frames, such as one every 10 frames for long runs
+    frame_stride: usize,
+    /// What happens to figures that already exist
+    existing: Existing,
+}
+
+#[cfg(feature = "graphics")]
+impl FigureSettings<'_> {
+    /// Description of the figures for the manifest; videos take the frame stride from it.
+    fn manifest(&self) -> serde_json::Value {
+        let options = self.options;
+        #[allow(unused_mut)]
+        let mut manifest = serde_json::json!({
+            "width": options.figure_width,
+            "height": options.figure_height,
+            "font": options.font,
+            "title_font_size": options.title_font_size,
+            "label_font_size": options.label_font_size,
+            "chart_margin": options.chart_margin,
+            "hidden_panels": options
+                .hidden_panels
+                .iter()
+                .map(|panel| panel.name())
+                .collect::<Vec<_>>(),
+            "shown_panels": options
+                .shown_panels
+                .iter()
+                .map(|panel| panel.name())
+                .collect::<Vec<_>>(),
+            "layout": options.layout.as_ref().map(Layout::to_string),
+            "themes": options.figure_themes,
+            "theme_file": options.theme_file,
+            "frame_stride": self.frame_stride,
+            "max_frames": options.max_frames,
+        });
+        #[cfg(feature = "landscape")]
         {
-            #[cfg(feature = "net-graphics")]
+            manifest["landscape_width"] = options.landscape_width.into();
+        }
+        #[cfg(feature = "net-graphics")]
+        {
+            manifest["power_law_fit"] = options.power_law_fit.into();
+        }
+        manifest
+    }
+}
+
+/// Reads the results of a scenario of an earlier run from ts.csv, and degrees.csv for network figures.
+#[cfg(all(feature = "graphics", feature = "csv-output"))]
+fn load_scenario(scenario_dir: &Path, settings: &FigureSettings) -> error::Result<Scenario> {
+    let read_csv = |file_name: &str| {
+        let path = scenario_dir.join(file_name);
+        fs::File::open(&path)
+            .and_then(|f| tables::read_csv(std::io::BufReader::new(f)))
+            .or_error(&format!("Could not read {}", path.display()))
+    };
+    let (titles, rows) = read_csv("ts.csv")?;
+    if titles.len() != 2 + settings.collector.reporters().len() {
+        return Err(Error::Invalid(vec![format!(
+            "The columns of {} do not match the reporters; please plot with the features of the run",
+            scenario_dir.join("ts.csv").display()
+        )]));
+    }
+    let mut scenario = Scenario {
+        id: scenario_dir
+            .file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("scenario-")?.parse().ok())
+            .unwrap_or_default(),
+        infection_probability: rows.first().map_or(0.0, |row| row[0]),
+        dir: scenario_dir.to_path_buf(),
+        time_series: rows
+            .iter()
+            .map(|row| TimeStepResults {
+                time_step: row[1] as u32,
+                values: row[2..].to_vec(),
+                #[cfg(feature = "net-graphics")]
+                degree_histogram: BTreeMap::new(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+    #[cfg(feature = "net-graphics")]
+    {
+        // Rows of degrees.csv: time step, degree, and number of agents; time steps follow the order of ts.csv
+        let (_, degree_rows) = read_csv("degrees.csv")?;
+        let mut time_series = scenario.time_series.iter_mut().peekable();
+        for row in degree_rows {
+            while time_series
+                .peek()
+                .is_some_and(|results| (results.time_step as f64) < row[0])
             {
-                if compress_histogram {
-                    for degree in scenario.histogram_degrees_set.iter() {
-                        histogram_degrees_set.insert(degree);
-                    }
-                } else if histogram_max_degree < scenario.histogram_max_degree {
-                    histogram_max_degree = scenario.histogram_max_degree;
-                }
-                if histogram_height < scenario.histogram_height {
-                    histogram_height = scenario.histogram_height;
-                }
+                time_series.next();
             }
-            if agent_time_series_height < scenario.agent_time_series_height {
-                agent_time_series_height = scenario.agent_time_series_height;
+            if let Some(results) = time_series
+                .peek_mut()
+                .filter(|results| results.time_step as f64 == row[0])
+            {
+                results
+                    .degree_histogram
+                    .insert(row[1] as i32, row[2] as u32);
             }
         }
-        #[cfg(feature = "landscape-graphics")]
-        {
-            if cell_time_series_height < scenario.cell_time_series_height {
-                cell_time_series_height = scenario.cell_time_series_height;
+    }
+    // Runs before the reproduction panel existed have no reproduction.csv, and draw it empty
+    if scenario_dir.join("reproduction.csv").exists() {
+        let (_, rows) = read_csv("reproduction.csv")?;
+        scenario.incidence = rows.iter().map(|row| row[2] as u32).collect();
+        scenario.secondary = rows.iter().map(|row| row[3] as u32).collect();
+    }
+    let time_series = std::mem::take(&mut scenario.time_series);
+    time_series
+        .iter()
+        .for_each(|results| scenario.record_extremes(results, settings));
+    scenario.time_series = time_series;
+    Ok(scenario)
+}
+
+/// Cell of each pixel along an axis of the landscape panel; cells share the pixels as evenly as possible,
+/// the first ones taking one pixel more, as DrawingArea::split_evenly does.
+#[cfg(feature = "landscape-graphics")]
+fn cell_of_pixels(pixels: u32, cells: u32) -> Vec<usize> {
+    let (pixels, cells) = (pixels as usize, cells as usize);
+    let (size, wider) = (pixels / cells, pixels % cells);
+    (0..pixels)
+        .map(|pixel| {
+            if pixel < wider * (size + 1) {
+                pixel / (size + 1)
+            } else {
+                wider + (pixel - wider * (size + 1)) / size
+            }
+        })
+        .collect()
+}
+
+/// Pixels of a landscape, in RGB: the row of cells of each row of pixels is in `rows`, and the column of cells of each
+/// column of pixels is in `columns`.
+#[cfg(feature = "landscape-graphics")]
+fn landscape_pixels(
+    cell_health: &[Health],
+    width: usize,
+    columns: &[usize],
+    rows: &[usize],
+    colors: [(u8, u8, u8); 2],
+) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(columns.len() * rows.len() * 3);
+    // Pixel rows of the same row of cells are copies of each other
+    let mut row_pixels = Vec::with_capacity(columns.len() * 3);
+    for (y, &row) in rows.iter().enumerate() {
+        if y == 0 || rows[y - 1] != row {
+            let cells = &cell_health[row * width..];
+            row_pixels.clear();
+            for &column in columns {
+                let (r, g, b) = colors[cells[column] as usize];
+                row_pixels.extend_from_slice(&[r, g, b]);
             }
         }
-        // end-similar-code 5
-    });
-    #[cfg(feature = "csv-output")]
-    eprintln!("{}Time series saved to {}.", clean_term, ts_name);
-    #[cfg(feature = "graphics")]
-    #[allow(unused_variables)]
-    {
-        // begin-similar-code 6
+        pixels.extend_from_slice(&row_pixels);
+    }
+    pixels
+}
+
+/// Extents of the axes of the figures: the largest values of the scenarios that the figures fit
+#[cfg(feature = "graphics")]
+#[derive(Clone, PartialEq)]
+struct Axes {
+    /// Height of each chart
+    chart_heights: Vec<f64>,
+    /// Network degrees that ever occur, for compressed histograms
+    #[cfg(feature = "net-graphics")]
+    histogram_degrees_set: BTreeSet<i32>,
+    #[cfg(feature = "net-graphics")]
+    histogram_max_degree: i32,
+    #[cfg(feature = "net-graphics")]
+    histogram_height: u32,
+    /// Largest numbers of susceptible and infected agents, for the phase plane
+    phase_plane: Option<(f64, f64)>,
+    /// Largest cumulative incidence
+    incidence: Option<f64>,
+    /// Largest effective reproduction number, and at least 1
+    reproduction: Option<f64>,
+    /// Largest network degree and number of linked agents, for the CCDF
+    #[cfg(feature = "net-graphics")]
+    ccdf: Option<(i32, u32)>,
+}
+
+#[cfg(feature = "graphics")]
+impl Axes {
+    /// Axes that fit a scenario, for the panels that the figures show
+    fn of(settings: &FigureSettings, scenario: &Scenario) -> Self {
+        let options = settings.options;
+        // The phase plane and the cumulative incidence show reporters of the data collector
+        let reporter_idx = |name: &str| {
+            settings
+                .collector
+                .reporters()
+                .iter()
+                .position(|reporter| reporter.name == name)
+        };
+        let max_value = |idx: usize| {
+            scenario
+                .time_series
+                .iter()
+                .map(|time_step_results| time_step_results.values[idx])
+                .fold(0f64, f64::max)
+        };
+        // The CCDF shows the degrees from 1 to the largest, and probabilities down to one agent
         #[cfg(feature = "net-graphics")]
+        let ccdf = options.shows(Panel::Ccdf).then(|| {
+            let histograms = || {
+                scenario
+                    .time_series
+                    .iter()
+                    .map(|time_step_results| &time_step_results.degree_histogram)
+            };
+            let max_degree = histograms()
+                .filter_map(|histogram| histogram.keys().next_back())
+                .fold(1, |max_degree, &degree| max_degree.max(degree));
+            let max_linked = histograms()
+                .map(|histogram| histogram.range(1..).map(|(_, &agents)| agents).sum::<u32>())
+                .fold(1, u32::max);
+            (max_degree, max_linked)
+        });
+        Self {
+            chart_heights: scenario.chart_heights.clone(),
+            #[cfg(feature = "net-graphics")]
+            histogram_degrees_set: if settings.compress_histogram {
+                scenario.histogram_degrees_set.clone()
+            } else {
+                BTreeSet::new()
+            },
+            #[cfg(feature = "net-graphics")]
+            histogram_max_degree: if settings.compress_histogram {
+                0
+            } else {
+                scenario.histogram_max_degree
+            },
+            #[cfg(feature = "net-graphics")]
+            histogram_height: scenario.histogram_height,
+            phase_plane: options
+                .shows(Panel::PhasePlane)
+                .then(|| Some((max_value(reporter_idx("s")?), max_value(reporter_idx("i")?))))
+                .flatten(),
+            incidence: options
+                .shows(Panel::Incidence)
+                .then(|| Some(max_value(reporter_idx("ci")?)))
+                .flatten(),
+            reproduction: options.shows(Panel::Reproduction).then(|| {
+                reproduction::reproduction(&scenario.incidence, &scenario.secondary)
+                    .into_iter()
+                    .flatten()
+                    .fold(1f64, f64::max)
+            }),
+            #[cfg(feature = "net-graphics")]
+            ccdf,
+        }
+    }
+
+    /// Widens these axes to fit the scenarios of `other` too.
+    fn widen(&mut self, other: &Self) {
+        let max = |a: Option<f64>, b: Option<f64>| a.zip(b).map(|(a, b)| a.max(b)).or(a).or(b);
+        // begin-similar-code 5
+        for (height, &other_height) in self
+            .chart_heights
+            .iter_mut()
+            .zip(other.chart_heights.iter())
         {
-            if compress_histogram {
-                assert!(!histogram_degrees_set.is_empty());
+            if *height < other_height {
+                *height = other_height;
             }
-            assert!(histogram_height > 0);
         }
-        assert!(agent_time_series_height > 0);
-        #[cfg(feature = "landscape-graphics")]
-        assert!(cell_time_series_height > 0);
         #[cfg(feature = "net-graphics")]
         {
-            // A little extra space in the chart:
-            histogram_height += 1;
-            histogram_max_degree += 1;
+            self.histogram_degrees_set
+                .extend(other.histogram_degrees_set.iter().copied());
+            self.histogram_max_degree = self.histogram_max_degree.max(other.histogram_max_degree);
+            self.histogram_height = self.histogram_height.max(other.histogram_height);
+            self.ccdf = match (self.ccdf, other.ccdf) {
+                (Some((d0, l0)), Some((d1, l1))) => Some((d0.max(d1), l0.max(l1))),
+                (ccdf, other_ccdf) => ccdf.or(other_ccdf),
+            };
         }
-        agent_time_series_height += 1;
-        #[cfg(feature = "landscape-graphics")]
-        {
-            cell_time_series_height += 1;
+        // end-similar-code 5
+        self.phase_plane = match (self.phase_plane, other.phase_plane) {
+            (Some((s0, i0)), Some((s1, i1))) => Some((s0.max(s1), i0.max(i1))),
+            (phase_plane, other_phase_plane) => phase_plane.or(other_phase_plane),
+        };
+        self.incidence = max(self.incidence, other.incidence);
+        self.reproduction = max(self.reproduction, other.reproduction);
+    }
+}
+
+/// Figures of a scenario drawn while the run went on: its id, the axes they fit, and the seconds they took, with the
+/// figures that failed
+#[cfg(feature = "graphics")]
+type DrawnFigures = (u32, Axes, f64, Vec<Error>);
+
+/// Draws the figures of each scenario of the run as it finishes, while other scenarios still run, on axes that fit the
+/// scenarios finished so far; create_figures draws again the scenarios whose axes turn out too narrow. The figures take
+/// a pool of threads of their own, so that scenarios waiting to send their results never hold up the drawing.
+#[cfg(feature = "graphics")]
+fn draw_during_run(
+    settings: &FigureSettings,
+    run_dir: &Path,
+    finished: std::sync::mpsc::Receiver<Scenario>,
+) -> Vec<DrawnFigures> {
+    let mut drawn = Vec::new();
+    // Without threads for the figures, create_figures draws every scenario after the run
+    let Ok(pool) = rayon::ThreadPoolBuilder::new()
+        .num_threads(rayon::current_num_threads())
+        .build()
+    else {
+        finished.into_iter().for_each(drop);
+        return drawn;
+    };
+    let mut axes: Option<Axes> = None;
+    for scenario in finished {
+        // Figures and videos cover whole runs, so an interrupted run draws no more of them
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            continue;
         }
-        #[cfg(feature = "net-graphics")]
-        let x_degree: std::vec::Vec<_> = histogram_degrees_set.iter().enumerate().collect();
-        let figure_margin = 5;
-        #[cfg(feature = "net-graphics")]
-        let bar_margin = 3;
-        let thick_stroke = 4;
-        let text_size0 = 30;
-        let text_size1 = 17;
-        let x_label_area_size = 40;
-        #[cfg(feature = "net-graphics")]
-        let x_label_offset = 1;
-        let y_label_area_size = 60;
-        // end-similar-code 6
-        scenarios.iter().for_each(|scenario| {
-            eprint!(
-                "{}Creating figures for scenario {}/{}... ",
-                clean_term,
-                scenario.id,
-                scenarios.len()
-            );
-            let figure_scenario_counter = scenario.id * time_series_len as u32;
-            scenario
-                .time_series
-                .par_iter()
-                .for_each(|time_step_results| {
-                    let file_number = figure_scenario_counter + time_step_results.time_step + 1;
-                    for &dark_figures in &[false, true] {
-                        let figure_prefix = "img";
-                        let figure_file_name = format!(
-                            "{}{}/{}.png",
-                            figure_prefix,
-                            if dark_figures { "_dark" } else { "" },
-                            file_number
-                        );
-                        let figure_path = std::path::Path::new(&figure_file_name);
-                        if figure_path.exists() {
-                            panic!(
-                                "This program just tried to rewrite {}; please debug",
-                                figure_path.to_str().unwrap()
-                            );
-                        }
-                        let drawing_area =
-                            BitMapBackend::new(figure_path, (1920, 1080)).into_drawing_area();
-                        // begin-similar-code 7
-                        let background_color = if dark_figures { &BLACK } else { &WHITE };
-                        let transparent_color = background_color.mix(0.);
-                        let color0 = if dark_figures { &WHITE } else { &BLACK };
-                        let color01 = color0.mix(0.1);
-                        let color02 = color0.mix(0.2);
-                        let color1 = if dark_figures {
-                            &plotters::style::RGBColor(255, 192, 0)
-                        } else {
-                            &RED
-                        };
-                        let color2 = &plotters::style::RGBColor(0, 176, 80);
-                        let color3 = &plotters::style::RGBColor(32, 56, 100);
-                        let color_s = color2;
-                        let color_i = color3;
-                        let color0t = color0.stroke_width(thick_stroke);
-                        let color1t = color1.stroke_width(thick_stroke);
-                        let color2t = color2.stroke_width(thick_stroke);
-                        let color3t = color3.stroke_width(thick_stroke);
-                        let color_st = color2t;
-                        let color_it = color3t;
-                        let fill0 = color0.filled();
-                        let fill01 = color01.filled();
-                        let fill02 = color02.filled();
-                        let fill1 = color1.filled();
-                        let fill2 = color2.filled();
-                        let fill3 = color3.filled();
-                        let text0 = ("Calibri", text_size0).into_font().color(color0);
-                        let text1 = ("Calibri", text_size1).into_font().color(color0);
-                        drawing_area.fill(background_color).unwrap();
-                        let (left_area, right_area) = drawing_area.split_horizontally(1920 - 1080);
-                        let left_panels = left_area.split_evenly((4, 1));
-                        left_panels[0]
-                            .draw_text(
-                                &format!(
-                                    "infection_probability = {}",
-                                    scenario.infection_probability
-                                ),
-                                &text0,
-                                (50, 10),
-                            )
-                            .unwrap();
-                        #[cfg(feature = "net-graphics")]
-                        {
-                            left_panels[0]
-                                .draw_text(
-                                    &format!(
-                                        "d_s Max degree of susceptibles: {}",
-                                        time_step_results.d_s
-                                    ),
-                                    &text0,
-                                    (50, 100),
-                                )
-                                .unwrap();
-                            left_panels[0]
-                                .draw_text(
-                                    &format!(
-                                        "d_i Max degree of infectious agents: {}",
-                                        time_step_results.d_i
-                                    ),
-                                    &text0,
-                                    (50, 140),
-                                )
-                                .unwrap();
-                        }
-                        left_panels[0]
-                            .draw_text(
-                                &format!("time: {}", time_step_results.time_step),
-                                &text0,
-                                (500, 10),
-                            )
-                            .unwrap();
+        let figures_clock = std::time::Instant::now();
+        let scenario_axes = Axes::of(settings, &scenario);
+        let axes = match &mut axes {
+            Some(axes) => {
+                axes.widen(&scenario_axes);
+                axes
+            }
+            None => axes.insert(scenario_axes),
+        };
+        let failures =
+            pool.install(|| draw_figures(settings, axes, &scenario, run_dir, settings.existing));
+        drawn.push((
+            scenario.id,
+            axes.clone(),
+            figures_clock.elapsed().as_secs_f64(),
+            failures,
+        ));
+    }
+    drawn
+}
+
+/// Draws the figures of each scenario in each theme into the frames directories of the scenario in run_dir, on axes
+/// that fit every scenario. Figures drawn during the run, in `drawn`, stay if their axes are the same, and are drawn
+/// again otherwise. Landscapes come from the landscape frames file in the directory of each scenario.
+/// Returns the errors of the figures that failed; the others are saved anyway.
+#[cfg(feature = "graphics")]
+fn create_figures(
+    settings: &FigureSettings,
+    scenarios: &mut [Scenario],
+    drawn: Vec<DrawnFigures>,
+    run_dir: &Path,
+    clean_term: &str,
+) -> Vec<Error> {
+    let Some(axes) = scenarios
+        .iter()
+        .map(|scenario| Axes::of(settings, scenario))
+        .reduce(|mut axes, scenario_axes| {
+            axes.widen(&scenario_axes);
+            axes
+        })
+    else {
+        return Vec::new();
+    };
+    let scenarios_len = scenarios.len();
+    let mut drawn: BTreeMap<_, _> = drawn
+        .into_iter()
+        .map(|(id, axes, seconds, failures)| (id, (axes, seconds, failures)))
+        .collect();
+    let mut failures = Vec::new();
+    for scenario in scenarios.iter_mut() {
+        let existing = match drawn.remove(&scenario.id) {
+            Some((drawn_axes, seconds, drawn_failures)) => {
+                scenario.phase_seconds[Phase::Figures as usize] += seconds;
+                if drawn_axes == axes {
+                    failures.extend(drawn_failures);
+                    continue;
+                }
+                // Figures drawn during the run on narrower axes give way to the new ones
+                Existing::Overwrite
+            }
+            None => settings.existing,
+        };
+        let figures_clock = std::time::Instant::now();
+        eprint!(
+            "{}Creating figures for scenario {}/{}... ",
+            clean_term, scenario.id, scenarios_len
+        );
+        failures.extend(draw_figures(settings, &axes, scenario, run_dir, existing));
+        scenario.phase_seconds[Phase::Figures as usize] += figures_clock.elapsed().as_secs_f64();
+    }
+    for e in &failures {
+        eprintln!("{}{}.", clean_term, e);
+    }
+    eprintln!(
+        "{}Figures saved to the frames directories of each scenario, one per theme{}.",
+        clean_term,
+        if failures.is_empty() {
+            String::new()
+        } else {
+            format!(", except {} that failed", failures.len())
+        }
+    );
+    failures
+}
+
+/// Draws the figures of a scenario in each theme on these axes; see create_figures. Returns the errors of the figures
+/// that failed.
+#[cfg(feature = "graphics")]
+#[allow(unused_variables)] // The palette of the figures
+fn draw_figures(
+    settings: &FigureSettings,
+    axes: &Axes,
+    scenario: &Scenario,
+    run_dir: &Path,
+    existing: Existing,
+) -> Vec<Error> {
+    let collector = settings.collector;
+    #[cfg(feature = "landscape")]
+    let coord = settings.coord;
+    let render_every = settings.render_every;
+    let frame_stride = settings.frame_stride;
+    let time_series_len = settings.time_series_len;
+    #[cfg(feature = "net-graphics")]
+    let compress_histogram = settings.compress_histogram;
+    let options = settings.options;
+    // begin-similar-code 4
+    let mut chart_heights = axes.chart_heights.clone();
+    #[cfg(feature = "net-graphics")]
+    let histogram_degrees_set = &axes.histogram_degrees_set;
+    #[cfg(feature = "net-graphics")]
+    let mut histogram_max_degree = axes.histogram_max_degree;
+    #[cfg(feature = "net-graphics")]
+    let mut histogram_height = axes.histogram_height;
+    // end-similar-code 4
+    // begin-similar-code 6
+    #[cfg(feature = "net-graphics")]
+    {
+        if compress_histogram {
+            assert!(!histogram_degrees_set.is_empty());
+        }
+        assert!(histogram_height > 0);
+    }
+    assert!(chart_heights.iter().all(|&height| height > 0.0));
+    #[cfg(feature = "net-graphics")]
+    {
+        // A little extra space in the chart:
+        histogram_height += 1;
+        histogram_max_degree += 1;
+    }
+    chart_heights.iter_mut().for_each(|height| *height += 1.0);
+    #[cfg(feature = "net-graphics")]
+    let x_degree: std::vec::Vec<_> = histogram_degrees_set.iter().enumerate().collect();
+    let figure_margin = options.chart_margin;
+    #[cfg(feature = "net-graphics")]
+    let bar_margin = 3;
+    let thick_stroke = 4;
+    let text_size0 = options.title_font_size;
+    let text_size1 = options.label_font_size;
+    let x_label_area_size = 40;
+    #[cfg(feature = "net-graphics")]
+    let x_label_offset = 1;
+    let y_label_area_size = 60;
+    // end-similar-code 6
+    // The font and the reporters of the panels were checked before the run; see FigureOptions::check
+    let font = options.font.as_str();
+    let figure_size = (options.figure_width, options.figure_height);
+    #[cfg(feature = "landscape")]
+    let landscape_width = if options.shows(Panel::Landscape) {
+        options.landscape_width.unwrap_or(options.figure_height)
+    } else {
+        0
+    };
+    #[cfg(not(feature = "landscape"))]
+    let landscape_width = 0;
+    let chart_panel_count = if options.shows(Panel::Charts) {
+        collector.charts().len()
+    } else {
+        0
+    };
+    let left_panel_count = usize::from(options.shows(Panel::Text))
+        + usize::from(cfg!(feature = "net-graphics") && options.shows(Panel::Degrees))
+        + usize::from(cfg!(feature = "net-graphics") && options.shows(Panel::Ccdf))
+        + usize::from(options.shows(Panel::PhasePlane))
+        + usize::from(options.shows(Panel::Incidence))
+        + usize::from(options.shows(Panel::Reproduction))
+        + chart_panel_count;
+    // The phase plane and the cumulative incidence show reporters of the data collector, with a little extra space
+    let reporter_idx = |name: &str| {
+        collector
+            .reporters()
+            .iter()
+            .position(|reporter| reporter.name == name)
+    };
+    let phase_plane = axes.phase_plane.and_then(|(s_height, i_height)| {
+        Some((
+            reporter_idx("s")?,
+            reporter_idx("i")?,
+            s_height + 1.0,
+            i_height + 1.0,
+        ))
+    });
+    let incidence = axes
+        .incidence
+        .and_then(|ci_height| Some((reporter_idx("ci")?, ci_height + 1.0)));
+    // The effective reproduction number keeps R = 1 in view
+    let reproduction_height = axes.reproduction.map(|height| height + 1.0);
+    #[cfg(feature = "net-graphics")]
+    let ccdf_range = axes
+        .ccdf
+        .map(|(max_degree, max_linked)| (max_degree as f64 * 1.5, 0.5 / max_linked as f64));
+    // Heatmaps give each cell a square of pixels: the cell of each column and each row of pixels
+    #[cfg(feature = "landscape-graphics")]
+    let heatmap_cells = options.heatmap_scale.map(|scale| {
+        let cells_of_pixels = |cells: i32| -> Vec<usize> {
+            (0..cells as usize * scale as usize)
+                .map(|pixel| pixel / scale as usize)
+                .collect()
+        };
+        (
+            cells_of_pixels(coord.width()),
+            cells_of_pixels(coord.height()),
+        )
+    });
+    let figure_failures = std::sync::Mutex::new(Vec::new());
+    let failures = &figure_failures;
+    let figures_dir = run_dir.join(scenario.dir.file_name().unwrap());
+    // R_t of the time steps with infections
+    let r_t: Vec<(u32, f64)> = match reproduction_height {
+        Some(_) => reproduction::reproduction(&scenario.incidence, &scenario.secondary)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(time_step, r_t)| Some((time_step as u32, r_t?)))
+            .collect(),
+        None => Vec::new(),
+    };
+    #[cfg(feature = "landscape-graphics")]
+    if heatmap_cells.is_some() {
+        for theme in &settings.themes {
+            let dir = figures_dir.join(theme::heatmaps_dir_name(&theme.name));
+            if let Err(e) = fs::create_dir_all(&dir).or_error(&format!(
+                "Could not create the output directory {}",
+                dir.display()
+            )) {
+                failures.lock().unwrap().push(e);
+            }
+        }
+    }
+    #[cfg(feature = "landscape-graphics")]
+    let frames_path = landscape_frames_path(&scenario.dir);
+    #[cfg(feature = "landscape-graphics")]
+    let frames_err = &*format!(
+        "Error reading landscape frames file {}",
+        frames_path.display()
+    );
+    // Index in the time series of each figure to draw
+    let frames = scenario
+        .time_series
+        .iter()
+        .enumerate()
+        .filter(|(_, time_step_results)| {
+            (time_step_results.time_step as usize).is_multiple_of(render_every * frame_stride)
+        })
+        .map(|(idx, _)| idx);
+    // One thread reads the landscape of each figure, in order, into a bounded queue, and the other threads draw the
+    // figures as they come; landscapes go back to the reader to fill again, so memory stays bounded by the queue.
+    let (queue, queued) =
+        std::sync::mpsc::sync_channel::<(usize, Vec<Health>)>(2 * rayon::current_num_threads());
+    let (recycler, recycled) = std::sync::mpsc::channel::<Vec<Health>>();
+    #[cfg(feature = "landscape-graphics")]
+    let reads_landscape = options.shows(Panel::Landscape) || heatmap_cells.is_some();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            #[cfg(feature = "landscape-graphics")]
+            let mut frames_file = None;
+            #[cfg(feature = "landscape-graphics")]
+            let mut frame = vec![0u8; if reads_landscape { coord.size() } else { 0 }];
+            for idx in frames {
+                #[allow(unused_mut)]
+                let mut cell_health = recycled.try_recv().unwrap_or_default();
+                #[cfg(feature = "landscape-graphics")]
+                if reads_landscape {
+                    let frame_number = scenario.time_series[idx].time_step as usize / render_every;
+                    let mut read_frame = || -> std::io::Result<()> {
+                        if frames_file.is_none() {
+                            frames_file = Some(fs::File::open(&frames_path)?);
+                        }
+                        let frames_file = frames_file.as_mut().unwrap();
+                        frames_file
+                            .seek(SeekFrom::Start(frame_number as u64 * coord.size() as u64))?;
+                        frames_file.read_exact(&mut frame)
+                    };
+                    // Without its landscape, the figure of this frame fails alone
+                    if let Err(e) = read_frame().or_error(frames_err) {
+                        failures.lock().unwrap().push(e);
+                        continue;
+                    }
+                    cell_health.clear();
+                    cell_health.extend(frame.iter().map(|&h| {
+                        if h == Health::I as u8 {
+                            Health::I
+                        } else {
+                            Health::S
+                        }
+                    }));
+                }
+                if queue.send((idx, cell_health)).is_err() {
+                    break;
+                }
+            }
+        });
+        queued
+            .into_iter()
+            .par_bridge()
+            .for_each(|(idx, cell_health)| {
+                let time_step_results = &scenario.time_series[idx];
+                // Lines of the charts up to this time step, and from it on
+                let (past, future) = (&scenario.time_series[..=idx], &scenario.time_series[idx..]);
+                // Figures and landscape frames are numbered consecutively
+                let frame_number = time_step_results.time_step as usize / render_every;
+                let file_number = frame_number / frame_stride + 1;
+                for theme in &settings.themes {
+                    let figure_path = figures_dir
+                        .join(theme::frames_dir_name(&theme.name))
+                        .join(format!("{}.png", file_number));
+                    if figure_path.exists() {
+                        match existing {
+                            Existing::Error => {
+                                failures.lock().unwrap().push(Error::Exists(figure_path));
+                                continue;
+                            }
+                            Existing::Skip => continue,
+                            Existing::Overwrite => {}
+                        }
+                    }
+                    // A figure that fails leaves the other figures alone
+                    let draw = || -> Result<(), Box<dyn std::error::Error>> {
+                        let drawing_area =
+                            BitMapBackend::new(&figure_path, figure_size).into_drawing_area();
+                        // begin-similar-code 7
+                        let background_color = &theme.background;
+                        let transparent_color = background_color.mix(0.);
+                        let color0 = &theme.foreground;
+                        let color01 = color0.mix(0.1);
+                        let color02 = color0.mix(0.2);
+                        let color1 = &theme.series[1];
+                        let color2 = &theme.series[2];
+                        let color3 = &theme.series[3];
+                        let color_s = &theme.susceptible;
+                        let color_i = &theme.infected;
+                        let color0t = color0.stroke_width(thick_stroke);
+                        let color1t = color1.stroke_width(thick_stroke);
+                        let color2t = color2.stroke_width(thick_stroke);
+                        let color3t = color3.stroke_width(thick_stroke);
+                        let color_st = color_s.stroke_width(thick_stroke);
+                        let color_it = color_i.stroke_width(thick_stroke);
+                        let fill0 = color0.filled();
+                        let fill01 = color01.filled();
+                        let fill02 = color02.filled();
+                        let fill1 = color1.filled();
+                        let fill2 = color2.filled();
+                        let fill3 = color3.filled();
+                        let text0 = (font, text_size0).into_font().color(color0);
+                        let text1 = (font, text_size1).into_font().color(color0);
+                        drawing_area.fill(background_color)?;
+                        // Panels in the order they are drawn, the panel of each chart, and the landscape panel
+                        let (left_panels, chart_panels, right_area) = match &options.layout {
+                            None => {
+                                let (left_area, right_area) = drawing_area.split_horizontally(
+                                    options.figure_width.saturating_sub(landscape_width),
+                                );
+                                // Top panel for text, then the network degree histogram, then the charts of the data
+                                // collector
+                                let mut left_panels =
+                                    left_area.split_evenly((left_panel_count.max(1), 1));
+                                let chart_panels: Vec<_> = left_panels
+                                    .split_off(left_panel_count - chart_panel_count)
+                                    .into_iter()
+                                    .map(Some)
+                                    .collect();
+                                (left_panels, chart_panels, right_area)
+                            }
+                            Some(layout) => {
+                                let area = |name: &str| layout.area(&drawing_area, name);
+                                let left_panels = [
+                                    Panel::Text,
+                                    Panel::Degrees,
+                                    Panel::Ccdf,
+                                    Panel::PhasePlane,
+                                    Panel::Incidence,
+                                    Panel::Reproduction,
+                                ]
+                                .iter()
+                                .filter(|&&panel| {
+                                    options.shows(panel)
+                                        && (cfg!(feature = "net-graphics")
+                                            || !matches!(panel, Panel::Degrees | Panel::Ccdf))
+                                })
+                                .filter_map(|panel| area(panel.name()))
+                                .collect();
+                                // Charts without a panel of their own share the charts panel
+                                let own_panels: Vec<_> = (1..=chart_panel_count)
+                                    .map(|n| area(&format!("chart-{}", n)))
+                                    .collect();
+                                let shared =
+                                    own_panels.iter().filter(|panel| panel.is_none()).count();
+                                let mut shared_panels = area("charts")
+                                    .map(|panel| panel.split_evenly((shared.max(1), 1)))
+                                    .unwrap_or_default()
+                                    .into_iter();
+                                let chart_panels = own_panels
+                                    .into_iter()
+                                    .map(|panel| panel.or_else(|| shared_panels.next()))
+                                    .collect();
+                                let right_area = area("landscape")
+                                    .unwrap_or_else(|| drawing_area.clone().shrink((0, 0), (0, 0)));
+                                (left_panels, chart_panels, right_area)
+                            }
+                        };
+                        let mut left_panels = left_panels.into_iter();
+                        let palette = [&theme.series[0], color1, color2, color3];
+                        if options.shows(Panel::Text) {
+                            let text_panel = left_panels.next().unwrap();
+                            text_panel.draw_text(
+                                &format!(
+                                    "infection_probability = {}",
+                                    scenario.infection_probability
+                                ),
+                                &text0,
+                                (50, 10),
+                            )?;
+                            for (row, (reporter, value)) in collector
+                                .reporters()
+                                .iter()
+                                .zip(time_step_results.values.iter())
+                                .filter(|(reporter, _value)| reporter.text)
+                                .enumerate()
+                            {
+                                text_panel.draw_text(
+                                    &format!(
+                                        "{} {}: {}",
+                                        reporter.name, reporter.description, value
+                                    ),
+                                    &text0,
+                                    (50, 100 + (text_size0 as i32 + 10) * row as i32),
+                                )?;
+                            }
+                            text_panel.draw_text(
+                                &format!("time: {}", time_step_results.time_step),
+                                &text0,
+                                (500, 10),
+                            )?;
+                        }
                         #[cfg(feature = "net-graphics")]
-                        {
+                        if options.shows(Panel::Degrees) {
                             let x_range = if compress_histogram {
                                 0..x_degree.len() as i32
                             } else {
                                 0..histogram_max_degree
                             };
-                            let mut chart = ChartBuilder::on(&left_panels[1])
+                            let mut chart = ChartBuilder::on(&left_panels.next().unwrap())
                                 .x_label_area_size(x_label_area_size)
                                 .y_label_area_size(y_label_area_size)
                                 .margin(figure_margin)
                                 .caption("Network degree of agents", text0.clone())
-                                .build_cartesian_2d(x_range, 0..histogram_height)
-                                .unwrap();
+                                .build_cartesian_2d(x_range, 0..histogram_height)?;
                             chart
                                 .configure_mesh()
                                 .light_line_style(&color01)
@@ -753,247 +2642,5561 @@ fn main() {
                                         format!("{}", x_position)
                                     }
                                 })
-                                .draw()
-                                .unwrap();
-                            chart
-                                .draw_series(
-                                    Histogram::vertical(&chart)
-                                        .style(background_color.filled())
-                                        .margin(bar_margin)
-                                        .data(time_step_results.degree_histogram.iter().map(
-                                            |(degree, weight)| {
-                                                (
-                                                    if compress_histogram {
-                                                        x_degree
-                                                            .iter()
-                                                            .find(|&(_, &deg)| deg == degree)
-                                                            .unwrap()
-                                                            .0
-                                                            as i32
-                                                    } else {
-                                                        *degree
-                                                    },
-                                                    *weight,
-                                                )
-                                            },
-                                        )),
-                                )
-                                .unwrap();
-                            chart
-                                .draw_series(
-                                    Histogram::vertical(&chart)
-                                        .style(color0)
-                                        .margin(bar_margin)
-                                        .data(time_step_results.degree_histogram.iter().map(
-                                            |(degree, weight)| {
-                                                (
-                                                    if compress_histogram {
-                                                        x_degree
-                                                            .iter()
-                                                            .find(|&(_, &deg)| deg == degree)
-                                                            .unwrap()
-                                                            .0
-                                                            as i32
-                                                    } else {
-                                                        *degree
-                                                    },
-                                                    *weight,
-                                                )
-                                            },
-                                        )),
-                                )
-                                .unwrap();
+                                .draw()?;
+                            chart.draw_series(
+                                Histogram::vertical(&chart)
+                                    .style(background_color.filled())
+                                    .margin(bar_margin)
+                                    .data(time_step_results.degree_histogram.iter().map(
+                                        |(degree, weight)| {
+                                            (
+                                                if compress_histogram {
+                                                    x_degree
+                                                        .iter()
+                                                        .find(|&&(_, deg)| deg == degree)
+                                                        .unwrap()
+                                                        .0
+                                                        as i32
+                                                } else {
+                                                    *degree
+                                                },
+                                                *weight,
+                                            )
+                                        },
+                                    )),
+                            )?;
+                            chart.draw_series(
+                                Histogram::vertical(&chart)
+                                    .style(color0)
+                                    .margin(bar_margin)
+                                    .data(time_step_results.degree_histogram.iter().map(
+                                        |(degree, weight)| {
+                                            (
+                                                if compress_histogram {
+                                                    x_degree
+                                                        .iter()
+                                                        .find(|&&(_, deg)| deg == degree)
+                                                        .unwrap()
+                                                        .0
+                                                        as i32
+                                                } else {
+                                                    *degree
+                                                },
+                                                *weight,
+                                            )
+                                        },
+                                    )),
+                            )?;
                         }
-                        {
-                            let mut chart = ChartBuilder::on(&left_panels[2])
+                        #[cfg(feature = "net-graphics")]
+                        if let Some((max_degree, min_probability)) = ccdf_range {
+                            let histogram = &time_step_results.degree_histogram;
+                            let linked: u32 = histogram.range(1..).map(|(_, &agents)| agents).sum();
+                            // Fraction of linked agents with each degree or more
+                            let mut remaining = linked;
+                            let ccdf: Vec<(f64, f64)> = histogram
+                                .range(1..)
+                                .map(|(&degree, &agents)| {
+                                    let point = (degree as f64, remaining as f64 / linked as f64);
+                                    remaining -= agents;
+                                    point
+                                })
+                                .collect();
+                            let mut chart = ChartBuilder::on(&left_panels.next().unwrap())
                                 .x_label_area_size(x_label_area_size)
                                 .y_label_area_size(y_label_area_size)
                                 .margin(figure_margin)
-                                .caption("Populations of agents", text0.clone())
+                                .caption("Agents with this network degree or more", text0.clone())
                                 .build_cartesian_2d(
-                                    0..(time_series_len as u32),
-                                    0..agent_time_series_height,
-                                )
-                                .unwrap();
+                                    (1f64..max_degree).log_scale(),
+                                    (min_probability..1.5f64).log_scale(),
+                                )?;
                             chart
                                 .configure_mesh()
                                 .light_line_style(&color01)
                                 .bold_line_style(&color02)
-                                .y_desc("Number of agents")
-                                .x_desc("Time")
+                                .y_desc("Fraction of agents")
+                                .y_label_formatter(&|y| format!("{}", y))
+                                .x_desc("Network degree")
+                                .x_label_formatter(&|x| format!("{}", x))
                                 .axis_style(color0)
                                 .axis_desc_style(text1.clone())
                                 .label_style(text1.clone())
-                                .draw()
-                                .unwrap();
-                            chart
-                                .draw_series(LineSeries::new(
-                                    scenario
-                                        .time_series
+                                .draw()?;
+                            chart.draw_series(LineSeries::new(ccdf.iter().copied(), color0t))?;
+                            let fit = if options.power_law_fit {
+                                power_law_exponent(
+                                    histogram
                                         .iter()
-                                        .skip_while(|tsr| {
-                                            tsr.time_step < time_step_results.time_step
-                                        })
-                                        .map(|time_step_results| {
-                                            (time_step_results.time_step, time_step_results.n)
-                                        }),
-                                    color0,
-                                ))
-                                .unwrap();
+                                        .map(|(&degree, &agents)| (degree as f64, agents as f64)),
+                                    1.0,
+                                )
+                            } else {
+                                None
+                            };
+                            if let Some(alpha) = fit {
+                                // The CCDF of a power law with exponent alpha is a power law with exponent alpha - 1
+                                chart
+                                    .draw_series(LineSeries::new(
+                                        ccdf.iter()
+                                            .map(|&(degree, _)| (degree, degree.powf(1.0 - alpha))),
+                                        color1t,
+                                    ))?
+                                    .label(format!("Power law fit, exponent {:.2}", alpha))
+                                    .legend(move |(x, y)| {
+                                        PathElement::new(vec![(x, y), (x + 20, y)], color1t)
+                                    });
+                                chart
+                                    .configure_series_labels()
+                                    .label_font(text1.clone())
+                                    .border_style(color0)
+                                    .draw()?;
+                            }
+                        }
+                        if let Some((s_idx, i_idx, s_height, i_height)) = phase_plane {
+                            let mut chart = ChartBuilder::on(&left_panels.next().unwrap())
+                                .x_label_area_size(x_label_area_size)
+                                .y_label_area_size(y_label_area_size)
+                                .margin(figure_margin)
+                                .caption("Phase plane", text0.clone())
+                                .build_cartesian_2d(0f64..s_height, 0f64..i_height)?;
                             chart
-                                .draw_series(LineSeries::new(
-                                    scenario
-                                        .time_series
-                                        .iter()
-                                        .take_while(|tsr| {
-                                            tsr.time_step <= time_step_results.time_step
-                                        })
-                                        .map(|time_step_results| {
-                                            (time_step_results.time_step, time_step_results.n)
-                                        }),
-                                    color0t.clone(),
-                                ))
-                                .unwrap()
-                                .label("n Number of agents")
-                                .legend(|(x, y)| {
-                                    PathElement::new(vec![(x, y), (x + 20, y)], color0t.clone())
-                                });
+                                .configure_mesh()
+                                .light_line_style(color01)
+                                .bold_line_style(color02)
+                                .y_desc("Infected")
+                                .y_label_formatter(&|y| format!("{}", y))
+                                .x_desc("Susceptibles")
+                                .x_label_formatter(&|x| format!("{}", x))
+                                .axis_style(color0)
+                                .axis_desc_style(text1.clone())
+                                .label_style(text1.clone())
+                                .draw()?;
+                            let point =
+                                |tsr: &TimeStepResults| (tsr.values[s_idx], tsr.values[i_idx]);
+                            chart.draw_series(LineSeries::new(future.iter().map(point), color3))?;
+                            chart.draw_series(LineSeries::new(past.iter().map(point), color3t))?;
+                            chart.draw_series(std::iter::once(Circle::new(
+                                point(time_step_results),
+                                2 * thick_stroke,
+                                fill1,
+                            )))?;
+                        }
+                        if let Some((ci_idx, ci_height)) = incidence {
+                            let mut chart = ChartBuilder::on(&left_panels.next().unwrap())
+                                .x_label_area_size(x_label_area_size)
+                                .y_label_area_size(y_label_area_size)
+                                .margin(figure_margin)
+                                .caption("Cumulative incidence", text0.clone())
+                                .build_cartesian_2d(0..(time_series_len as u32), 0f64..ci_height)?;
                             chart
-                                .draw_series(LineSeries::new(
-                                    scenario
-                                        .time_series
-                                        .iter()
-                                        .skip_while(|tsr| {
-                                            tsr.time_step < time_step_results.time_step
-                                        })
-                                        .map(|time_step_results| {
-                                            (time_step_results.time_step, time_step_results.i)
-                                        }),
-                                    color_i,
-                                ))
-                                .unwrap();
+                                .configure_mesh()
+                                .light_line_style(color01)
+                                .bold_line_style(color02)
+                                .y_desc("Infections so far")
+                                .y_label_formatter(&|y| format!("{}", y))
+                                .x_desc("Time")
+                                .axis_style(color0)
+                                .axis_desc_style(text1.clone())
+                                .label_style(text1.clone())
+                                .draw()?;
+                            let series =
+                                |tsr: &TimeStepResults| (tsr.time_step, tsr.values[ci_idx]);
                             chart
-                                .draw_series(LineSeries::new(
-                                    scenario
-                                        .time_series
-                                        .iter()
-                                        .take_while(|tsr| {
-                                            tsr.time_step <= time_step_results.time_step
-                                        })
-                                        .map(|time_step_results| {
-                                            (time_step_results.time_step, time_step_results.i)
-                                        }),
-                                    color_it.clone(),
-                                ))
-                                .unwrap()
-                                .label("i Infected agents")
-                                .legend(|(x, y)| {
-                                    PathElement::new(vec![(x, y), (x + 20, y)], color_it.clone())
-                                });
+                                .draw_series(LineSeries::new(future.iter().map(series), color1))?;
+                            chart.draw_series(LineSeries::new(past.iter().map(series), color1t))?;
+                        }
+                        if let Some(r_height) = reproduction_height {
+                            let mut chart = ChartBuilder::on(&left_panels.next().unwrap())
+                                .x_label_area_size(x_label_area_size)
+                                .y_label_area_size(y_label_area_size)
+                                .margin(figure_margin)
+                                .caption("Effective reproduction number", text0.clone())
+                                .build_cartesian_2d(0..(time_series_len as u32), 0f64..r_height)?;
                             chart
-                                .configure_series_labels()
-                                .label_font(text1.clone())
-                                .border_style(color0)
-                                .draw()
-                                .unwrap();
+                                .configure_mesh()
+                                .light_line_style(color01)
+                                .bold_line_style(color02)
+                                .y_desc("R_t")
+                                .y_label_formatter(&|y| format!("{}", y))
+                                .x_desc("Time")
+                                .axis_style(color0)
+                                .axis_desc_style(text1.clone())
+                                .label_style(text1.clone())
+                                .draw()?;
+                            // Above R = 1 the infection grows, and below it declines
+                            chart.draw_series(LineSeries::new(
+                                [(0, 1.0), (time_series_len as u32, 1.0)],
+                                color0,
+                            ))?;
+                            let now = r_t.partition_point(|&(time_step, _)| {
+                                time_step <= time_step_results.time_step
+                            });
+                            chart.draw_series(LineSeries::new(
+                                r_t[now.saturating_sub(1)..].iter().copied(),
+                                color1,
+                            ))?;
+                            chart.draw_series(LineSeries::new(
+                                r_t[..now].iter().copied(),
+                                color1t,
+                            ))?;
                         }
-                        #[cfg(feature = "landscape")]
+                        for ((chart_idx, (chart_desc, &chart_height)), chart_panel) in collector
+                            .charts()
+                            .iter()
+                            .zip(chart_heights.iter())
+                            .enumerate()
+                            .zip(chart_panels)
+                            .filter_map(|(chart, panel)| Some((chart, panel?)))
                         {
-                            let mut chart = ChartBuilder::on(&left_panels[3])
+                            let mut chart = ChartBuilder::on(&chart_panel)
                                 .x_label_area_size(x_label_area_size)
                                 .y_label_area_size(y_label_area_size)
                                 .margin(figure_margin)
-                                .caption("Infection of cells", text0.clone())
+                                .caption(chart_desc.caption, text0.clone())
                                 .build_cartesian_2d(
                                     0..(time_series_len as u32),
-                                    0..cell_time_series_height,
-                                )
-                                .unwrap();
+                                    0f64..chart_height,
+                                )?;
                             chart
                                 .configure_mesh()
                                 .light_line_style(&color01)
                                 .bold_line_style(&color02)
-                                .y_desc("Number of infected cells")
+                                .y_desc(chart_desc.y_desc)
+                                .y_label_formatter(&|y| format!("{}", y))
                                 .x_desc("Time")
                                 .axis_style(color0)
                                 .axis_desc_style(text1.clone())
-                                .label_style(text1)
-                                .draw()
-                                .unwrap();
-                            chart
-                                .draw_series(LineSeries::new(
-                                    scenario
-                                        .time_series
-                                        .iter()
-                                        .skip_while(|tsr| {
-                                            tsr.time_step < time_step_results.time_step
-                                        })
-                                        .map(|time_step_results| {
-                                            (time_step_results.time_step, time_step_results.c_i)
-                                        }),
-                                    color_i,
-                                ))
-                                .unwrap();
+                                .label_style(text1.clone())
+                                .draw()?;
+                            for (reporter_idx, reporter) in collector.reporters().iter().enumerate()
+                            {
+                                let line = match reporter.line {
+                                    Some(line) if line.chart == chart_idx => line,
+                                    _ => continue,
+                                };
+                                let color = palette[line.color];
+                                let color_t = color.stroke_width(thick_stroke);
+                                let series = |tsr: &TimeStepResults| {
+                                    (tsr.time_step, tsr.values[reporter_idx])
+                                };
+                                chart.draw_series(LineSeries::new(
+                                    future.iter().map(series),
+                                    color,
+                                ))?;
+                                chart
+                                    .draw_series(LineSeries::new(past.iter().map(series), color_t))?
+                                    .label(format!("{} {}", reporter.name, reporter.description))
+                                    .legend(move |(x, y)| {
+                                        PathElement::new(vec![(x, y), (x + 20, y)], color_t)
+                                    });
+                            }
                             chart
-                                .draw_series(LineSeries::new(
-                                    scenario
-                                        .time_series
-                                        .iter()
-                                        .take_while(|tsr| {
-                                            tsr.time_step <= time_step_results.time_step
-                                        })
-                                        .map(|time_step_results| {
-                                            (time_step_results.time_step, time_step_results.c_i)
-                                        }),
-                                    color_it,
-                                ))
-                                .unwrap();
+                                .configure_series_labels()
+                                .label_font(text1.clone())
+                                .border_style(color0)
+                                .draw()?;
                         }
                         #[cfg(feature = "landscape")]
-                        {
+                        if options.shows(Panel::Landscape) {
                             let landscape = right_area.margin(10, 10, 10, 10);
-                            let cells = landscape
-                                .split_evenly((coord.height() as usize, coord.width() as usize));
-                            cells
-                                .iter()
-                                .zip(time_step_results.cell_health.iter())
-                                .for_each(|(cell, health)| {
-                                    cell.fill(match health {
-                                        Health::S => color_s,
-                                        Health::I => color_i,
-                                    })
-                                    .unwrap();
-                                });
+                            // Cells become pixels of one bitmap, rather than one drawing area each
+                            let (width, height) = landscape.dim_in_pixel();
+                            let columns = cell_of_pixels(width, coord.width() as u32);
+                            let rows = cell_of_pixels(height, coord.height() as u32);
+                            let pixels = landscape_pixels(
+                                &cell_health,
+                                coord.width() as usize,
+                                &columns,
+                                &rows,
+                                [color_s.rgb(), color_i.rgb()],
+                            );
+                            landscape.draw(
+                                &BitMapElement::with_owned_buffer((0, 0), (width, height), pixels)
+                                    .unwrap(),
+                            )?;
                         }
                         // end-similar-code 7
+                        // Plotters saves figures when they drop, ignoring errors
+                        drawing_area.present()?;
+                        Ok(())
+                    };
+                    if let Err(e) = draw() {
+                        failures.lock().unwrap().push(Error::Figure {
+                            path: figure_path,
+                            message: e.to_string(),
+                        });
+                    }
+                }
+                // Bare landscapes, with a square of pixels per cell, numbered as the figures
+                #[cfg(feature = "landscape-graphics")]
+                if let Some((columns, rows)) = &heatmap_cells {
+                    for theme in &settings.themes {
+                        let heatmap_path = figures_dir
+                            .join(theme::heatmaps_dir_name(&theme.name))
+                            .join(format!("{}.png", file_number));
+                        if heatmap_path.exists() {
+                            match existing {
+                                Existing::Error => {
+                                    failures.lock().unwrap().push(Error::Exists(heatmap_path));
+                                    continue;
+                                }
+                                Existing::Skip => continue,
+                                Existing::Overwrite => {}
+                            }
+                        }
+                        let draw = || -> Result<(), Box<dyn std::error::Error>> {
+                            let size = (columns.len() as u32, rows.len() as u32);
+                            let drawing_area =
+                                BitMapBackend::new(&heatmap_path, size).into_drawing_area();
+                            let pixels = landscape_pixels(
+                                &cell_health,
+                                coord.width() as usize,
+                                columns,
+                                rows,
+                                [theme.susceptible.rgb(), theme.infected.rgb()],
+                            );
+                            drawing_area.draw(
+                                &BitMapElement::with_owned_buffer((0, 0), size, pixels).unwrap(),
+                            )?;
+                            drawing_area.present()?;
+                            Ok(())
+                        };
+                        if let Err(e) = draw() {
+                            failures.lock().unwrap().push(Error::Figure {
+                                path: heatmap_path,
+                                message: e.to_string(),
+                            });
+                        }
+                    }
+                }
+                recycler.send(cell_health).ok();
+            });
+    });
+    figure_failures.into_inner().unwrap()
+}
+
+/// Draws the epidemic curves of the replicates of each infection probability, to show how much they vary: each
+/// figure shows the infected agents of every replicate as faint lines, and their mean up to its time step. The figures
+/// of each infection probability go to a directory of its own, such as replicates-01/frames, numbered as the figures of
+/// the scenarios. Returns each directory, with the figures that failed, if any.
+#[cfg(feature = "graphics")]
+fn replicates_figures(
+    settings: &FigureSettings,
+    scenarios: &[Scenario],
+    run_dir: &Path,
+    replicates: u32,
+) -> Vec<(PathBuf, Result<(), Vec<Error>>)> {
+    let options = settings.options;
+    let Some(i_idx) = settings
+        .collector
+        .reporters()
+        .iter()
+        .position(|reporter| reporter.name == "i")
+    else {
+        eprintln!("The figures of replicates need the i reporter, which this build leaves out.");
+        return Vec::new();
+    };
+    let mut groups: Vec<(u32, Vec<&Scenario>)> = Vec::new();
+    for scenario in scenarios {
+        let group = scenario.id / replicates;
+        match groups.last_mut() {
+            Some((last, members)) if *last == group => members.push(scenario),
+            _ => groups.push((group, vec![scenario])),
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(group, members)| {
+            let replicates_dir = run_dir.join(format!("replicates-{:02}", group));
+            let failures = std::sync::Mutex::new(Vec::new());
+            // Mean of the replicates at each sampled time step, over the replicates that reached it
+            let len = members
+                .iter()
+                .map(|scenario| scenario.time_series.len())
+                .max()
+                .unwrap_or(0);
+            let mean: Vec<(u32, f64)> = (0..len)
+                .map(|idx| {
+                    let mut stats = RunningStats::default();
+                    let mut time_step = 0;
+                    for tsr in members.iter().filter_map(|scenario| scenario.time_series.get(idx)) {
+                        stats.push(tsr.values[i_idx]);
+                        time_step = tsr.time_step;
+                    }
+                    (time_step, stats.mean())
+                })
+                .collect();
+            let height = members
+                .iter()
+                .flat_map(|scenario| scenario.time_series.iter())
+                .map(|tsr| tsr.values[i_idx])
+                .fold(0f64, f64::max)
+                + 1.0;
+            let font = options.font.as_str();
+            let thick_stroke = 4;
+            for theme in &settings.themes {
+                let frames_dir = replicates_dir.join(theme::frames_dir_name(&theme.name));
+                if let Err(e) = fs::create_dir_all(&frames_dir).or_error(&format!(
+                    "Could not create the output directory {}",
+                    frames_dir.display()
+                )) {
+                    failures.lock().unwrap().push(e);
+                }
+            }
+            mean.par_iter()
+                .enumerate()
+                .filter(|(_, &(time_step, _))| {
+                    (time_step as usize)
+                        .is_multiple_of(settings.render_every * settings.frame_stride)
+                })
+                .for_each(|(idx, &(time_step, _))| {
+                    let file_number = time_step as usize / settings.render_every / settings.frame_stride + 1;
+                    for theme in &settings.themes {
+                        let figure_path = replicates_dir
+                            .join(theme::frames_dir_name(&theme.name))
+                            .join(format!("{}.png", file_number));
+                        if figure_path.exists() {
+                            match settings.existing {
+                                Existing::Error => {
+                                    failures.lock().unwrap().push(Error::Exists(figure_path));
+                                    continue;
+                                }
+                                Existing::Skip => continue,
+                                Existing::Overwrite => {}
+                            }
+                        }
+                        let draw = || -> Result<(), Box<dyn std::error::Error>> {
+                            let drawing_area = BitMapBackend::new(
+                                &figure_path,
+                                (options.figure_width, options.figure_height),
+                            )
+                            .into_drawing_area();
+                            let color0 = &theme.foreground;
+                            let text0 = (font, options.title_font_size).into_font().color(color0);
+                            let text1 = (font, options.label_font_size).into_font().color(color0);
+                            drawing_area.fill(&theme.background)?;
+                            let mut chart = ChartBuilder::on(&drawing_area)
+                                .x_label_area_size(40)
+                                .y_label_area_size(60)
+                                .margin(options.chart_margin + 10)
+                                .caption(
+                                    format!(
+                                        "Infected agents in {} replicates, infection_probability = {}, time: {}",
+                                        members.len(),
+                                        members[0].infection_probability,
+                                        time_step
+                                    ),
+                                    text0,
+                                )
+                                .build_cartesian_2d(0..(settings.time_series_len as u32), 0f64..height)?;
+                            chart
+                                .configure_mesh()
+                                .light_line_style(color0.mix(0.1))
+                                .bold_line_style(color0.mix(0.2))
+                                .y_desc("Infected agents")
+                                .y_label_formatter(&|y| format!("{}", y))
+                                .x_desc("Time")
+                                .axis_style(color0)
+                                .axis_desc_style(text1.clone())
+                                .label_style(text1)
+                                .draw()?;
+                            for scenario in &members {
+                                chart.draw_series(LineSeries::new(
+                                    scenario
+                                        .time_series
+                                        .iter()
+                                        .map(|tsr| (tsr.time_step, tsr.values[i_idx])),
+                                    color0.mix(0.25),
+                                ))?;
+                            }
+                            chart.draw_series(LineSeries::new(
+                                mean[..=idx].iter().copied(),
+                                theme.infected.stroke_width(thick_stroke),
+                            ))?;
+                            drawing_area.present()?;
+                            Ok(())
+                        };
+                        if let Err(e) = draw() {
+                            failures.lock().unwrap().push(Error::Figure {
+                                path: figure_path,
+                                message: e.to_string(),
+                            });
+                        }
                     }
                 });
-        });
-        eprint!("{}Figures saved to the img and img_dark directories.\nWriting video.mkv; open log file video.log to follow progress.", clean_term);
-        // Debug levels for the "level" variable: warning 24, info 32, verbose 40
-        match std::process::Command::new("ffmpeg")
-            .env("FFREPORT", "file=video.log:level=32")
-            .args(&[
-                "-r",
-                "20",
-                "-i",
-                "img_dark/%d.png",
-                "-loglevel",
-                "warning",
-                "-hide_banner",
-                "video_dark.mkv",
-            ])
-            .status()
-        {
-            Ok(ffmpeg_status) => eprintln!(
-                "{}Created video.mkv; {}. Learn more by reviewing video.log.",
-                clean_term, ffmpeg_status
-            ),
-            Err(e) => eprintln!(
-                "{}Could not create video.mkv: {}.\nPlease review file video.log, if it exists, to learn more.",
-                clean_term, e
-            ),
+            let failures = failures.into_inner().unwrap();
+            let result = if failures.is_empty() {
+                Ok(())
+            } else {
+                Err(failures)
+            };
+            (replicates_dir, result)
+        })
+        .collect()
+}
+
+/// Slice of the scenarios that one process simulates, such as one job of an array job on a cluster
+#[derive(Clone, Copy)]
+struct Shard {
+    index: usize,
+    count: usize,
+}
+
+impl Shard {
+    /// Shard of this task of a Slurm array job, if the process is one
+    fn from_slurm() -> Option<Self> {
+        let var = |name| std::env::var(name).ok()?.parse::<usize>().ok();
+        let task = var("SLURM_ARRAY_TASK_ID")?;
+        let count = var("SLURM_ARRAY_TASK_COUNT")?;
+        let index = task.checked_sub(var("SLURM_ARRAY_TASK_MIN").unwrap_or(0))?;
+        (index < count).then_some(Self { index, count })
+    }
+
+    fn contains(&self, id: u32) -> bool {
+        id as usize % self.count == self.index
+    }
+}
+
+impl std::str::FromStr for Shard {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("{} is not a shard K/N with K < N", s);
+        let (index, count) = s.split_once('/').ok_or_else(err)?;
+        let index = index.parse().map_err(|_| err())?;
+        let count = count.parse().map_err(|_| err())?;
+        if index < count {
+            Ok(Self { index, count })
+        } else {
+            Err(err())
+        }
+    }
+}
+
+impl std::fmt::Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// How the simulations of the scenarios share the threads
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum Parallelism {
+    /// Scenarios simulate in parallel; each simulation runs on one thread
+    Across,
+    /// Scenarios simulate one after another; each simulation uses all threads
+    Within,
+    /// Scenarios simulate in parallel, and each simulation uses idle threads
+    Both,
+}
+
+/// What happens to output files, figures, and videos that already exist, such as those of an interrupted run
+#[derive(Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+enum Existing {
+    /// Stop with an error; the program never rewrites output files
+    #[default]
+    Error,
+    /// Rewrite them
+    Overwrite,
+    /// Keep the figures and videos that exist and create only the missing ones; rewrite the other outputs, which the
+    /// simulation reproduces
+    Skip,
+}
+
+/// State of a scenario at the beginning of a time step, saved to checkpoint.json in the directory of the scenario
+#[cfg(feature = "checkpoint")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    /// Next time step to simulate
+    time_step: usize,
+    rng: RandomStreams,
+    #[cfg_attr(not(feature = "vec-storage"), serde(with = "checkpoint::slot_map"))]
+    health: Agents<Health>,
+    #[cfg(feature = "net")]
+    #[cfg_attr(not(feature = "vec-storage"), serde(with = "checkpoint::slot_map"))]
+    links: Links,
+    /// Layer of each link
+    #[cfg(feature = "net")]
+    #[serde(default)]
+    link_layers: LinkLayers,
+    #[cfg(feature = "landscape")]
+    cell_health: Vec<Health>,
+    /// Time step since which each cell is contaminated, if contamination persists
+    #[cfg(all(feature = "landscape", not(feature = "gpu")))]
+    #[serde(default)]
+    cell_contamination: Option<CellContamination>,
+    /// Parameters of each agent, if they differ between agents
+    #[serde(default)]
+    traits: Option<AgentTraits>,
+    /// Time step of birth of each agent, if the model keeps ages
+    #[serde(default)]
+    ages: Option<AgentDemography>,
+    /// Whether each agent is aware of the disease, if awareness spreads
+    #[cfg(feature = "net")]
+    #[serde(default)]
+    aware: Option<AgentAwareness>,
+    /// Vectors, in vector-borne scenarios
+    #[cfg(feature = "landscape")]
+    #[serde(default)]
+    vectors: Option<Vec<Vector>>,
+    /// Group of each agent and quarantines of the groups, if agents form groups
+    #[serde(default)]
+    groups: Option<Groups>,
+    /// Cell around which each agent visits the landscape, if agents come from a population file with positions
+    #[cfg(feature = "landscape")]
+    #[serde(default)]
+    homes: Option<AgentHomes>,
+    /// Energy of each agent and resource of each cell, if agents forage
+    #[cfg(feature = "landscape")]
+    #[serde(default)]
+    energy: Option<AgentEnergy>,
+    #[cfg(feature = "landscape")]
+    #[serde(default)]
+    resources: Option<CellResources>,
+    /// Whether each agent is vaccinated, if a vaccination campaign runs
+    #[serde(default)]
+    vaccinated: Option<AgentVaccination>,
+    /// Who infected whom so far
+    #[serde(default)]
+    transmissions: Transmissions,
+    /// Infections of each agent so far, if infections leave immunity
+    #[serde(default)]
+    history: Option<InfectionHistory>,
+    /// Parameters and results of the scenario so far
+    scenario: Scenario,
+}
+
+/// Date and time in UTC: year, month, day, hours, minutes, and seconds
+fn utc(time: std::time::SystemTime) -> [i64; 6] {
+    let secs = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("The system clock is set before 1970; please fix it")
+        .as_secs() as i64;
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01; see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    [
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+    ]
+}
+
+/// Each run writes its outputs to a new directory named after the time it started, such as run-20201231-235959 in UTC.
+/// Runs that start within the same second write to run-20201231-235959-2, run-20201231-235959-3, and so on.
+fn run_dir_name(started: std::time::SystemTime) -> PathBuf {
+    let [year, month, day, h, m, s] = utc(started);
+    let name = format!(
+        "run-{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, h, m, s
+    );
+    std::iter::once(PathBuf::from(&name))
+        .chain((2..).map(|n| PathBuf::from(format!("{}-{}", name, n))))
+        .find(|run_dir| !run_dir.exists())
+        .unwrap()
+}
+
+/// Creates the output directory of a run, which must be new or empty unless outputs that exist are overwritten or skipped
+fn create_run_dir(run_dir: &Path, existing: Existing) -> error::Result<()> {
+    if existing == Existing::Error && run_dir.exists() {
+        let is_empty = fs::read_dir(run_dir)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if !is_empty {
+            return Err(Error::Exists(run_dir.to_path_buf()));
+        }
+    }
+    fs::create_dir_all(run_dir).or_error(&format!(
+        "Could not create the output directory {}",
+        run_dir.display()
+    ))
+}
+
+/// Time in ISO 8601 format, such as 2020-12-31T23:59:59Z
+fn iso_8601(time: std::time::SystemTime) -> String {
+    let [year, month, day, h, m, s] = utc(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, h, m, s
+    )
+}
+
+/// Columnar copies of an output table, one file per format enabled in Cargo.toml
+#[cfg(feature = "arrow-output")]
+struct Tables {
+    tables: Vec<(String, ArrowTable)>,
+}
+
+#[cfg(feature = "arrow-output")]
+impl Tables {
+    /// Creates the file stem.parquet and so on; output files are never rewritten.
+    fn create(
+        stem: &std::path::Path,
+        columns: &[Column],
+        existing: Existing,
+    ) -> error::Result<Self> {
+        let tables = FORMATS
+            .iter()
+            .map(|&format| {
+                let path = stem.with_extension(format.extension());
+                let name = path.display().to_string();
+                if existing == Existing::Error && path.exists() {
+                    return Err(Error::Exists(path));
+                }
+                let file = fs::File::create(&path)
+                    .or_error(&format!("Error creating output file {}", name))?;
+                let table = ArrowTable::create(file, columns, format)
+                    .map_err(std::io::Error::other)
+                    .or_error(&format!("Error writing output file {}", name))?;
+                Ok((name, table))
+            })
+            .collect::<error::Result<_>>()?;
+        Ok(Self { tables })
+    }
+
+    fn push(&mut self, row: &[Value]) -> error::Result<()> {
+        for (name, table) in &mut self.tables {
+            table
+                .push(row)
+                .map_err(std::io::Error::other)
+                .or_error(&format!("Error writing output file {}", name))?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> error::Result<()> {
+        for (name, table) in self.tables {
+            table
+                .finish()
+                .map_err(std::io::Error::other)
+                .or_error(&format!("Error writing output file {}", name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Landscape frames do not stay in memory; they go to this file, one byte per cell, one time step after the other.
+#[cfg(feature = "landscape-graphics")]
+fn landscape_frames_path(scenario_dir: &std::path::Path) -> PathBuf {
+    scenario_dir.join("landscape.cells")
+}
+
+/// Agent-level output: one row per agent per sampled time step, in long format
+#[cfg(feature = "trajectories")]
+fn trajectories_columns(
+    #[cfg(feature = "landscape")] landscape: bool,
+    viral_load: bool,
+) -> Vec<Column> {
+    #[allow(unused_mut)]
+    let mut columns = vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("agent", "Agent", ColumnType::UInt64),
+        Column::new("health", "Health", ColumnType::Utf8),
+        #[cfg(feature = "net")]
+        Column::new("degree", "Degree", ColumnType::UInt32),
+    ];
+    // The log10 viral load of infected agents, if the model has one; missing for susceptible agents
+    if viral_load {
+        columns.push(Column::new("viral_load", "Viral load", ColumnType::Float64));
+    }
+    // Without the landscape, agents visit no cells
+    #[cfg(feature = "landscape")]
+    if landscape {
+        columns.push(Column::new("x", "x", ColumnType::UInt32));
+        columns.push(Column::new("y", "y", ColumnType::UInt32));
+    }
+    columns
+}
+
+/// Cell-level output: one row per cell every cells_every time steps, in long format
+#[cfg(feature = "cells-csv")]
+fn cells_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("cell", "Cell", ColumnType::UInt32),
+        Column::new("x", "x", ColumnType::UInt32),
+        Column::new("y", "y", ColumnType::UInt32),
+        Column::new("health", "Health", ColumnType::Utf8),
+    ]
+}
+
+/// Network snapshot output: one row per link every --network-every time steps, with the health of its agents
+#[cfg(feature = "network-snapshots")]
+fn network_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("agent0", "First agent", ColumnType::UInt64),
+        Column::new("agent1", "Second agent", ColumnType::UInt64),
+        Column::new("health0", "Health of the first agent", ColumnType::Utf8),
+        Column::new("health1", "Health of the second agent", ColumnType::Utf8),
+    ]
+}
+
+/// Genealogy output: one row per agent born during the run, with its parent
+#[cfg(feature = "genealogy")]
+fn genealogy_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step of birth", ColumnType::UInt32),
+        Column::new("parent", "Parent", ColumnType::UInt64),
+        Column::new("agent", "Agent", ColumnType::UInt64),
+    ]
+}
+
+/// Membership output: one row per agent, with its group, written when the agent joins the group or when the scenario
+/// starts or resumes
+#[cfg(feature = "csv-output")]
+fn groups_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("agent", "Agent", ColumnType::UInt64),
+        Column::new("group", "Group", ColumnType::UInt32),
+    ]
+}
+
+/// Observation output: one row per time step, with the new infections and the cases that surveillance observes
+#[cfg(feature = "csv-output")]
+fn observed_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("incidence", "New infections", ColumnType::UInt32),
+        Column::new("observed", "Observed cases", ColumnType::UInt32),
+    ]
+}
+
+/// Reproduction output: one row per time step, with the agents infected at that time step, the agents they infected
+/// in turn, and the ratio of the two, R_t
+#[cfg(feature = "csv-output")]
+fn reproduction_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("incidence", "New infections", ColumnType::UInt32),
+        Column::new("secondary", "Secondary infections", ColumnType::UInt32),
+        Column::new("r_t", "R_t", ColumnType::Float64),
+    ]
+}
+
+/// Infection probabilities of an adaptive exploration, with the mean final size of their replicates
+#[cfg(feature = "csv-output")]
+fn exploration_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("batch", "Batch", ColumnType::UInt32),
+        Column::new("replicates", "Replicates", ColumnType::UInt32),
+        Column::new(
+            "final_size",
+            "Mean final size (fraction of n0)",
+            ColumnType::Float64,
+        ),
+    ]
+}
+
+/// Strata output: one row per sampled time step, attribute, stratum, and health state, with the number of agents
+#[cfg(feature = "csv-output")]
+fn strata_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("time_step", "Time step", ColumnType::UInt32),
+        Column::new("attribute", "Attribute", ColumnType::Utf8),
+        Column::new("stratum", "Stratum", ColumnType::Utf8),
+        Column::new("health", "Health", ColumnType::Utf8),
+        Column::new("agents", "Agents", ColumnType::UInt32),
+    ]
+}
+
+/// Label of a health status in output files
+#[cfg(any(
+    feature = "csv-output",
+    feature = "trajectories",
+    feature = "cells-csv",
+    feature = "network-snapshots"
+))]
+fn health_label(h: Health) -> &'static str {
+    match h {
+        Health::S => "S",
+        Health::I => "I",
+    }
+}
+
+/// Summary statistics of the time series of a scenario, for the streaming feature
+#[cfg(feature = "streaming")]
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+struct TimeSeriesSummary {
+    /// Statistics of each reporter of the data collector
+    stats: Vec<RunningStats>,
+}
+
+#[cfg(feature = "streaming")]
+impl TimeSeriesSummary {
+    fn push(&mut self, time_step_results: &TimeStepResults) {
+        self.stats
+            .resize_with(time_step_results.values.len(), Default::default);
+        self.stats
+            .iter_mut()
+            .zip(time_step_results.values.iter())
+            .for_each(|(stats, &value)| stats.push(value));
+    }
+}
+
+/// Phases of a run, for the report of where the time goes
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "graphics"), allow(dead_code))] // Figures and Video
+enum Phase {
+    Network,
+    Agents,
+    Cells,
+    Metrics,
+    Output,
+    Bookkeeping,
+    Figures,
+    Video,
+}
+
+const PHASES: usize = 8;
+
+const PHASE_NAMES: [&str; PHASES] = [
+    "Network formation",
+    "Agent dynamics",
+    "Cell dynamics",
+    "Metrics",
+    "Output files",
+    "Bookkeeping",
+    "Figures",
+    "Video",
+];
+
+/// Stopwatch that charges the time since the previous lap to a phase
+struct PhaseTimer {
+    clock: std::time::Instant,
+    seconds: [f64; PHASES],
+}
+
+impl PhaseTimer {
+    fn new() -> Self {
+        Self {
+            clock: std::time::Instant::now(),
+            seconds: [0.0; PHASES],
+        }
+    }
+
+    fn lap(&mut self, phase: Phase) {
+        let now = std::time::Instant::now();
+        self.seconds[phase as usize] += (now - self.clock).as_secs_f64();
+        self.clock = now;
+    }
+}
+
+/// Simulation scenario, including parameters and results
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+struct Scenario {
+    /// Sequential scenario number
+    id: u32,
+    /// Model parameter: Infection probability
+    infection_probability: f64,
+    /// Model parameter: Replicate of this infection probability, from 0; replicates differ in their random numbers
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    replicate: u32,
+    /// Output directory of this scenario, inside the directory of the run
+    dir: PathBuf,
+    /// Model parameter: Hosts infect each other only through vectors; see the vectors module
+    #[cfg(feature = "landscape")]
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    vector_borne: bool,
+    /// Model parameter: Initial state of the random number generator
+    rng_state: u128,
+    /// Model parameter: Stream of the random number generator
+    rng_stream: u128,
+    /// Model parameter: Draw the complement of every random number of the stream, as the second replicate of an
+    /// antithetic pair
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    antithetic: bool,
+    /// Simulation results: Wall-clock time to simulate this scenario, in seconds
+    seconds: f64,
+    /// Simulation results: Wall-clock time spent in each phase of the run, in seconds; see PHASE_NAMES
+    phase_seconds: [f64; PHASES],
+    /// Simulation results: Number of time steps simulated before an interruption stopped this scenario
+    interrupted_at: Option<usize>,
+    /// Simulation results: Infections so far, seeds included
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    infections: usize,
+    /// Simulation results: Agents that arrived and left so far, if the population is open
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    immigrants: usize,
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    emigrants: usize,
+    /// Simulation results: New infections at each time step so far, seeds included
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    incidence: Vec<u32>,
+    /// Simulation results: Time step at which no agent, cell, or vector remained infected, if it happened so far
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    extinct_at: Option<usize>,
+    /// Simulation results: Infections caused so far by the agents infected at each time step; see the reproduction
+    /// module
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    secondary: Vec<u32>,
+    /// Simulation results: Time step at which the prevalence among agents first reached prevalence_threshold, if it
+    /// happened so far
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    threshold_at: Option<usize>,
+    /// Simulation results: Summary of the parameters drawn for the agents created so far, in the order of
+    /// AgentTraits::FIELDS, if they differ between agents
+    #[cfg_attr(feature = "checkpoint", serde(default))]
+    agent_traits: [RunningStats; 4],
+    /// Simulation results: Set of network degrees that ever ocurred in this scenario
+    #[cfg(feature = "net-graphics")]
+    histogram_degrees_set: BTreeSet<i32>,
+    /// Simulation results: Maximum network degree that ever ocurred in this scenario
+    #[cfg(feature = "net-graphics")]
+    histogram_max_degree: i32,
+    /// Simulation results: Height of the network degree histogram for this scenario
+    #[cfg(feature = "net-graphics")]
+    histogram_height: u32,
+    /// Simulation results: Height of each chart of the figures for this scenario
+    #[cfg(feature = "graphics")]
+    chart_heights: Vec<f64>,
+    /// Simulation results for all time steps
+    #[cfg(not(feature = "streaming"))]
+    time_series: std::vec::Vec<TimeStepResults>,
+    /// Simulation results: Summary of all time steps, when the time series streams to disk
+    #[cfg(feature = "streaming")]
+    summary: TimeSeriesSummary,
+}
+
+// end-similar-code 0
+
+#[cfg(feature = "graphics")]
+impl Scenario {
+    /// Keeps the largest values that the figures of this scenario show, to scale the charts
+    fn record_extremes(&mut self, time_step_results: &TimeStepResults, settings: &FigureSettings) {
+        #[cfg(feature = "net-graphics")]
+        for (&weight, &frequency) in &time_step_results.degree_histogram {
+            if settings.compress_histogram {
+                self.histogram_degrees_set.insert(weight);
+            } else if self.histogram_max_degree < weight {
+                self.histogram_max_degree = weight;
+            }
+            if self.histogram_height < frequency {
+                self.histogram_height = frequency;
+            }
+        }
+        let collector = settings.collector;
+        self.chart_heights.resize(collector.charts().len(), 0.0);
+        for (reporter, &value) in collector
+            .reporters()
+            .iter()
+            .zip(time_step_results.values.iter())
+        {
+            if let Some(line) = reporter.line {
+                if self.chart_heights[line.chart] < value {
+                    self.chart_heights[line.chart] = value;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "streaming", feature = "graphics"))]
+compile_error!("The streaming feature does not keep the time series needed by figures; please disable graphics in Cargo.toml");
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}.", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// The workflow of the program; errors that stop it, and the first error of the outputs that did not, set the exit
+/// status.
+fn run() -> error::Result<()> {
+    let started = std::time::SystemTime::now();
+    let clock = std::time::Instant::now();
+    let cli = Cli::parse();
+    let existing = cli.existing;
+    #[cfg(all(feature = "graphics", feature = "csv-output"))]
+    let plot = match &cli.command {
+        Some(Command::Plot {
+            run_dir,
+            output_dir,
+            ..
+        }) => Some((run_dir.clone(), output_dir.clone())),
+        _ => None,
+    };
+    // Figures and videos follow the simulation unless the sweep subcommand asks for results only
+    #[allow(unused_mut, unused_variables)]
+    let (mut args, figures) = match cli.command {
+        None => (cli.args, true),
+        Some(Command::Run(args)) => (args, true),
+        Some(Command::Sweep(args)) => (args, false),
+        #[cfg(all(feature = "graphics", feature = "csv-output"))]
+        Some(Command::Plot { figure_options, .. }) => (
+            Args {
+                figure_options,
+                ..cli.args
+            },
+            false,
+        ),
+        Some(Command::Video {
+            run_dir,
+            scenarios,
+            options,
+        }) => {
+            let scenario_dirs = scenario_dirs(&run_dir)?;
+            let scenario_dirs: Vec<&Path> = scenario_dirs
+                .iter()
+                .filter(|scenario_dir| {
+                    scenarios.is_empty()
+                        || scenario_dir
+                            .file_name()
+                            .and_then(|name| name.to_str()?.strip_prefix("scenario-")?.parse().ok())
+                            .is_some_and(|id| scenarios.contains(&id))
+                })
+                .map(PathBuf::as_path)
+                .collect();
+            let mut failures: Vec<Error> = scenario_dirs
+                .iter()
+                .filter_map(|scenario_dir| {
+                    encode_video(scenario_dir, &options, frame_stride(&run_dir), existing, "").err()
+                })
+                .inspect(|e| eprintln!("{}.", e))
+                .collect();
+            if options.join_videos {
+                if let Err(e) = join_videos(&run_dir, &scenario_dirs, &options, existing, "") {
+                    eprintln!("{}.", e);
+                    failures.push(e);
+                }
+            }
+            return failures.into_iter().next().map_or(Ok(()), Err);
+        }
+        Some(Command::Analyze {
+            command:
+                Some(AnalyzeCommand::Compare {
+                    first,
+                    second,
+                    output_dir,
+                    metrics,
+                    statistic,
+                }),
+            ..
+        }) => {
+            let statistic = Statistic::parse(&statistic)
+                .map_err(|e| Error::Invalid(vec![format!("--statistic: {}", e)]))?;
+            fs::create_dir_all(&output_dir).or_error(&format!(
+                "Could not create the output directory {}",
+                output_dir.display()
+            ))?;
+            return compare(&first, &second, &output_dir, &metrics, statistic, existing);
+        }
+        Some(Command::Analyze {
+            run_dir,
+            metrics,
+            max_lag,
+            statistic,
+            batches,
+            warm_up,
+            command: None,
+        }) => {
+            let mut problems = Problems::default();
+            let statistic = Statistic::parse(&statistic).unwrap_or_else(|e| {
+                problems.report("--statistic", e);
+                Statistic::Final
+            });
+            problems.require(batches >= 2, "--batches", "2 or more");
+            problems.finish()?;
+            let estimates = Estimates {
+                statistic,
+                batches,
+                warm_up,
+            };
+            // clap requires the run directory without a subcommand
+            return analyze(&run_dir.unwrap(), &metrics, max_lag, &estimates, existing);
+        }
+        #[cfg(feature = "csv-output")]
+        Some(Command::Pareto {
+            run_dirs,
+            minimize,
+            maximize,
+            output_dir,
+            #[cfg(feature = "graphics")]
+            figure_options,
+        }) => {
+            let mut problems = Problems::default();
+            let objectives: Vec<Objective> = (minimize.iter().map(|spec| (spec, false)))
+                .chain(maximize.iter().map(|spec| (spec, true)))
+                .filter_map(|(spec, maximize)| {
+                    Objective::parse(spec, maximize)
+                        .map_err(|e| {
+                            let name = if maximize { "--maximize" } else { "--minimize" };
+                            problems.report(name, e)
+                        })
+                        .ok()
+                })
+                .collect();
+            problems.require(
+                minimize.len() + maximize.len() >= 2,
+                "--minimize and --maximize",
+                "given two or more objectives between them",
+            );
+            problems.require(
+                output_dir.is_some() || run_dirs.len() == 1,
+                "--output-dir",
+                "given for several runs",
+            );
+            #[cfg(feature = "graphics")]
+            let themes = figure_options.themes(&mut problems);
+            problems.finish()?;
+            let output_dir = output_dir.unwrap_or_else(|| run_dirs[0].clone());
+            fs::create_dir_all(&output_dir).or_error(&format!(
+                "Could not create the output directory {}",
+                output_dir.display()
+            ))?;
+            let (points, front) = pareto_front(&run_dirs, &objectives, &output_dir, existing)?;
+            #[cfg(feature = "graphics")]
+            draw_pareto(
+                &objectives,
+                &points,
+                &front,
+                &output_dir,
+                &figure_options,
+                &themes,
+                existing,
+            )?;
+            #[cfg(not(feature = "graphics"))]
+            let _ = points;
+            eprintln!(
+                "{} of {} scenarios lie on the Pareto front.",
+                front.iter().filter(|&&on_front| on_front).count(),
+                front.len()
+            );
+            return Ok(());
+        }
+        #[cfg(feature = "csv-output")]
+        Some(Command::Design {
+            run_dirs,
+            output_dir,
+            standardize,
+        }) => {
+            let mut problems = Problems::default();
+            problems.require(
+                output_dir.is_some() || run_dirs.len() == 1,
+                "--output-dir",
+                "given for several runs",
+            );
+            problems.finish()?;
+            let output_dir = output_dir.unwrap_or_else(|| run_dirs[0].clone());
+            fs::create_dir_all(&output_dir).or_error(&format!(
+                "Could not create the output directory {}",
+                output_dir.display()
+            ))?;
+            let design = DesignMatrix::read(&run_dirs).or_error("Could not read the runs")?;
+            let design_path = output_dir.join("design.csv");
+            let design_err = &*format!("Error writing design matrix {}", design_path.display());
+            let mut design_file = create_output_file(&design_path, design_err, existing)?;
+            design
+                .write_csv(&mut design_file, standardize)
+                .and_then(|()| design_file.flush())
+                .or_error(design_err)?;
+            let metadata_path = output_dir.join("design.json");
+            let metadata_err = &*format!(
+                "Error writing design matrix metadata {}",
+                metadata_path.display()
+            );
+            let mut metadata_file = create_output_file(&metadata_path, metadata_err, existing)?;
+            serde_json::to_writer_pretty(&mut metadata_file, &design.metadata(standardize))
+                .map_err(std::io::Error::from)
+                .and_then(|()| metadata_file.flush())
+                .or_error(metadata_err)?;
+            eprintln!(
+                "Design matrix of {} scenarios saved to {} and {}.",
+                design.rows.len(),
+                design_path.display(),
+                metadata_path.display()
+            );
+            return Ok(());
+        }
+        #[cfg(feature = "html-report")]
+        Some(Command::Report {
+            run_dir,
+            theme,
+            key_frames,
+            plotly,
+        }) => {
+            let scenario_dirs = scenario_dirs(&run_dir)?;
+            let report_path = run_dir.join("report.html");
+            let report_err = &*format!("Error writing report {}", report_path.display());
+            let mut report_file = create_output_file(&report_path, report_err, existing)?;
+            report::write(
+                &mut report_file,
+                &run_dir,
+                &scenario_dirs,
+                &theme::frames_dir_name(&theme),
+                key_frames,
+                plotly.as_deref(),
+            )
+            .and_then(|()| report_file.flush())
+            .or_error(report_err)?;
+            eprintln!("Report saved to {}.", report_path.display());
+            if plotly.is_none() {
+                eprintln!(
+                    "Its charts load plotly from its CDN and are static images without internet access; \
+                     --plotly plotly.min.js embeds a copy."
+                );
+            }
+            return Ok(());
+        }
+        Some(Command::Merge { output_dir, shards }) => {
+            create_run_dir(&output_dir, existing)?;
+            merge::merge(&shards, &output_dir, existing == Existing::Skip).or_error(&format!(
+                "Could not merge the shards into {}",
+                output_dir.display()
+            ))?;
+            eprintln!("Shards merged into {}.", output_dir.display());
+            return Ok(());
+        }
+        Some(Command::Stages { plan, output_dir }) => {
+            let plan_err = &*format!("Error reading the plan {}", plan.display());
+            let plan = stages::Plan::load(&plan).or_error(plan_err)?;
+            create_run_dir(&output_dir, existing)?;
+            let program = std::env::current_exe().or_error("Could not find this program")?;
+            // The stages treat their existing outputs as this run does
+            let existing = clap::ValueEnum::to_possible_value(&existing).unwrap();
+            let extra_args = ["--existing".to_string(), existing.get_name().to_string()];
+            plan.run(&program, &output_dir, &extra_args)
+                .or_error("The stages did not finish")?;
+            eprintln!("Stages saved to {}.", output_dir.display());
+            return Ok(());
+        }
+        #[cfg(feature = "csv-output")]
+        Some(Command::Explore {
+            output_dir,
+            config,
+            min_probability,
+            max_probability,
+            initial,
+            batch_size,
+            iterations,
+            target,
+            jump,
+            tolerance,
+            args: batch_args,
+        }) => {
+            let mut problems = Problems::default();
+            problems.probability("--min-probability", min_probability);
+            problems.probability("--max-probability", max_probability);
+            problems.require(
+                min_probability < max_probability,
+                "--min-probability",
+                format_args!("less than --max-probability ({})", max_probability),
+            );
+            problems.positive("--initial", initial);
+            problems.positive("--batch-size", batch_size);
+            problems.require(target > 0.0, "--target", "positive");
+            problems.require(tolerance > 0.0, "--tolerance", "positive");
+            for arg in ["--config", "--output-dir"] {
+                problems.require(
+                    !batch_args
+                        .iter()
+                        .any(|batch_arg| batch_arg.starts_with(arg)),
+                    "ARGS",
+                    format_args!("free of {}, which the batches take from explore", arg),
+                );
+            }
+            // The batches add their infection probabilities to the parameters of the configuration file
+            let config = match &config {
+                Some(path) => match fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+                {
+                    Ok(serde_json::Value::Object(config)) => config,
+                    Ok(_) => {
+                        problems.report("--config", "must hold a JSON object");
+                        serde_json::Map::new()
+                    }
+                    Err(e) => {
+                        problems.report("--config", e);
+                        serde_json::Map::new()
+                    }
+                },
+                None => serde_json::Map::new(),
+            };
+            problems.finish()?;
+            create_run_dir(&output_dir, existing)?;
+            let program = std::env::current_exe().or_error("Could not find this program")?;
+            // The batches treat their existing outputs as this run does
+            let existing_name = clap::ValueEnum::to_possible_value(&existing).unwrap();
+            let batch_args: Vec<String> = batch_args
+                .into_iter()
+                .chain([
+                    "--existing".to_string(),
+                    existing_name.get_name().to_string(),
+                ])
+                .collect();
+            let criterion = if jump {
+                Criterion::Jump
+            } else {
+                Criterion::Crossing(target)
+            };
+            let mut explorer = Explorer::default();
+            let mut batch = exploration::grid(min_probability, max_probability, initial);
+            for number in 0..=iterations {
+                if batch.is_empty() {
+                    eprintln!("No interval is left to refine.");
+                    break;
+                }
+                eprintln!(
+                    "Batch {} of {}: {} infection probabilities from {} to {}.",
+                    number + 1,
+                    iterations + 1,
+                    batch.len(),
+                    batch[0],
+                    batch[batch.len() - 1]
+                );
+                let batch_dir = output_dir.join(format!("batch-{:02}", number));
+                exploration::run_batch(
+                    &mut explorer,
+                    &program,
+                    &batch_dir,
+                    number,
+                    &batch,
+                    &config,
+                    &batch_args,
+                )
+                .or_error(&format!("Batch {} failed", batch_dir.display()))?;
+                batch = explorer.next_batch(criterion, tolerance, batch_size);
+            }
+            let summary_path = output_dir.join("exploration.csv");
+            let summary_err = &*format!(
+                "Error writing exploration summary {}",
+                summary_path.display()
+            );
+            let mut summary_file = create_output_file(&summary_path, summary_err, existing)?;
+            tables::write_csv_header(&mut summary_file, &exploration_columns())
+                .or_error(summary_err)?;
+            for (infection_probability, batch, replicates, final_size) in explorer.points() {
+                let row = [
+                    Value::Float64(infection_probability),
+                    Value::UInt32(batch as u32),
+                    Value::UInt32(replicates as u32),
+                    Value::Float64(final_size),
+                ];
+                tables::write_csv_row(&mut summary_file, &row).or_error(summary_err)?;
+            }
+            summary_file.flush().or_error(summary_err)?;
+            for (low, high) in explorer.intervals(criterion, 0.0) {
+                eprintln!(
+                    "Region of interest: infection probabilities from {} to {}.",
+                    low, high
+                );
+            }
+            eprintln!("Exploration saved to {}.", summary_path.display());
+            return Ok(());
+        }
+        #[cfg(feature = "grpc")]
+        Some(Command::Grpc { listen }) => {
+            eprintln!("Serving the gRPC API at {}.", listen);
+            let program = std::env::current_exe().or_error("Could not find this program")?;
+            return grpc::serve(listen, program)
+                .or_error(&format!("Could not serve the gRPC API at {}", listen));
+        }
+    };
+    #[cfg(feature = "checkpoint")]
+    if let Some(checkpoint_path) = &args.inspect {
+        return inspect(checkpoint_path, &args);
+    }
+    #[cfg(feature = "rng-trace")]
+    if let Some(traces) = &args.compare_rng_traces {
+        match streams::compare_traces(&traces[0], &traces[1])
+            .or_error("Error reading the traces of random numbers")?
+        {
+            None => println!("The traces are identical."),
+            Some((draw, line0, line1)) => {
+                println!("The traces differ first at draw {}:", draw);
+                println!("{}: {}", traces[0].display(), line0);
+                println!("{}: {}", traces[1].display(), line1);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+    // A second interruption quits at once, for scenarios that take too long to finish their time step
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("\nInterrupted; saving results at the end of the current time step. Interrupt again to quit without saving.");
+    })
+    .map_err(std::io::Error::other)
+    .or_error("Could not install the handler of Ctrl+C")?;
+    // Under the grpc subcommand, the server closes the standard input to cancel the run, as Ctrl+C does
+    #[cfg(feature = "grpc")]
+    if args.managed {
+        std::thread::spawn(|| {
+            std::io::copy(&mut std::io::stdin(), &mut std::io::sink()).ok();
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        });
+    }
+    // Debug builds only use one thread to facilitate debugging. One thread makes the program sequential.
+    // Zero threads means one per CPU core.
+    let threads = args
+        .threads
+        .unwrap_or(if cfg!(debug_assertions) { 1 } else { 0 });
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .map_err(std::io::Error::other)
+        .or_error("Could not start the threads")?;
+    // Output parameter: Parallelism across scenarios and within each scenario
+    let across_scenarios = args.parallelism != Parallelism::Within;
+    #[allow(unused_variables)]
+    let within_scenarios = args.parallelism != Parallelism::Across;
+    // Model parameter: Components of the model that run; cargo features decide which ones this program has at all.
+    // Plots follow the components of their run.
+    #[cfg(feature = "net")]
+    let network = !args.no_network;
+    #[cfg(all(feature = "net", feature = "graphics", feature = "csv-output"))]
+    let network = network
+        && plot
+            .as_ref()
+            .is_none_or(|(run_dir, _)| ran_component(run_dir, "network"));
+    #[cfg(feature = "landscape")]
+    let landscape = !args.no_landscape;
+    #[cfg(all(feature = "landscape", feature = "graphics", feature = "csv-output"))]
+    let landscape = landscape
+        && plot
+            .as_ref()
+            .is_none_or(|(run_dir, _)| ran_component(run_dir, "landscape"));
+    // Output parameter: Figures leave out the panels of components that do not run
+    #[cfg(feature = "net-graphics")]
+    if !network {
+        args.figure_options
+            .hidden_panels
+            .extend([Panel::Degrees, Panel::Ccdf]);
+    }
+    #[cfg(feature = "landscape-graphics")]
+    if !landscape {
+        args.figure_options.hidden_panels.push(Panel::Landscape);
+    }
+    // begin-similar-code 1
+    // Model parameter: Initial number of agents
+    let n0: usize = 1000;
+    // Model parameter: Scale-free network parameter: new links per agent
+    #[cfg(feature = "net")]
+    let net_k: usize = 7;
+    // Model parameter: Dimensions of the virtual landscape, in number of cells
+    #[cfg(feature = "landscape")]
+    let (landscape_width, landscape_height) = (100i32, 100i32);
+    let birth_probability = 0.01;
+    let initial_infection_probability = 0.3;
+    // Model parameter: probability of infection
+    let infection_probabilities = [0.2f64, 0.4, 0.6];
+    // Normal distribution to choose cells in the landscape: mean and standard deviation, in cells
+    #[cfg(feature = "landscape")]
+    let (visit_mean, visit_sd) = (50.0, 10.0);
+    #[cfg(feature = "net")]
+    let link_probability = 0.01;
+    // Model parameter: probability of recovery
+    let recovery_probability = 0.8;
+    let survival_probability = 0.8;
+    // end-similar-code 1
+    // Model parameter: Initial state and stream of the random number generator of each scenario
+    #[allow(clippy::unreadable_literal)]
+    let (rng_state, rng_stream) = (
+        0xcafef00dd15ea5e5u128,
+        0xa02bdbf7bb3c0a7ac28fa16a64abf96u128,
+    );
+    // Model parameter: Last time step of the simulation in each scenario
+    let last_time_step = 100u32;
+    let time_series_len = last_time_step as usize + 1;
+    // Output parameter: Collect data once every sample_every time steps; long runs can collect less often.
+    let sample_every = 1usize;
+    // Output parameter: Draw figures and landscape frames once every render_every time steps.
+    // Figures show the data collected at the same time step, so render_every must be a multiple of sample_every.
+    #[cfg(feature = "graphics")]
+    let render_every = 1usize;
+    // Output parameter: Write the state of every cell once every cells_every time steps
+    #[cfg(any(feature = "cells-csv", feature = "netcdf-output"))]
+    let cells_every = 10usize;
+    // Output parameter: Debug builds keep the state of each scenario every snapshot_every time steps;
+    // the last snapshots_len states go to the snapshots directory of the scenario. Open them with --inspect.
+    #[cfg(all(debug_assertions, feature = "checkpoint"))]
+    let snapshot_every = 10usize;
+    #[cfg(all(debug_assertions, feature = "checkpoint"))]
+    let snapshots_len = 8usize;
+    // Bookkeeping: Every parameter is checked before the run starts, and every problem is reported at once.
+    let mut problems = Problems::default();
+    // Model parameter: The configuration file, if any, replaces distributions of the model by name; events, such as
+    // births, take the probability of a Bernoulli distribution.
+    let mut config = match &args.config {
+        Some(path) => Config::load(path, &mut problems).or_error(&format!(
+            "Could not read the configuration file {}",
+            path.display()
+        ))?,
+        None => Config::default(),
+    };
+    let infection_probabilities = config
+        .optional_numbers("infection_probabilities", &mut problems)
+        .unwrap_or_else(|| infection_probabilities.to_vec());
+    problems.require(
+        !infection_probabilities.is_empty(),
+        "infection_probabilities",
+        "a list of one or more probabilities",
+    );
+    let birth_probability = config.probability("birth_distro", birth_probability, &mut problems);
+    let initial_infection_probability = config.probability(
+        "initial_infection_distro",
+        initial_infection_probability,
+        &mut problems,
+    );
+    #[cfg(feature = "landscape")]
+    let visit_spec = config.distribution(
+        "visit_distro",
+        Spec::new("Normal", &[visit_mean, visit_sd]),
+        &mut problems,
+    );
+    #[cfg(feature = "net")]
+    let link_probability = config.probability("link_distro", link_probability, &mut problems);
+    let recovery_probability =
+        config.probability("recovery_distro", recovery_probability, &mut problems);
+    let survival_probability =
+        config.probability("survival_distro", survival_probability, &mut problems);
+    // Model parameter: Parameters of agents that differ between agents, each drawn from its distribution when the
+    // agent is created
+    let agent_recovery_spec =
+        config.optional_distribution("agent_recovery_probability", &mut problems);
+    let agent_susceptibility_spec =
+        config.optional_distribution("agent_susceptibility", &mut problems);
+    #[cfg(feature = "landscape")]
+    let agent_movement_scale_spec =
+        config.optional_distribution("agent_movement_scale", &mut problems);
+    #[cfg(feature = "net")]
+    let agent_sociability_spec = config.optional_distribution("agent_sociability", &mut problems);
+    // Model parameter: Agents born during the run inherit the parameters of their parent, each plus a mutation drawn
+    // from this distribution
+    let trait_mutation_spec = config.optional_distribution("trait_mutation", &mut problems);
+    // Model parameter: Demography: births slow down as the number of agents approaches the carrying capacity, and
+    // agents die of old age following the Gompertz law; see the demography module.
+    let carrying_capacity = config.optional_number("carrying_capacity", &mut problems);
+    let mortality_baseline = config
+        .optional_number("mortality_baseline", &mut problems)
+        .unwrap_or(0.0);
+    let mortality_growth = config
+        .optional_number("mortality_growth", &mut problems)
+        .unwrap_or(0.0);
+    let initial_age_spec = config.optional_distribution("initial_age", &mut problems);
+    // Model parameter: The population is open: immigration brings that many agents at each time step, on average,
+    // infected with the probability of immigrant_infected, and agents emigrate with the probability of
+    // emigration_probability; see the demography module.
+    let (immigration, immigrant_infected, emigration_probability) = (
+        config.optional_number("immigration", &mut problems),
+        config.optional_number("immigrant_infected", &mut problems),
+        config.optional_number("emigration_probability", &mut problems),
+    );
+    // Model parameter: Awareness of the disease spreads over the network as a second contagion and protects aware
+    // agents; see the awareness module.
+    #[cfg(feature = "net")]
+    let awareness_probability = config.optional_number("awareness_probability", &mut problems);
+    #[cfg(feature = "net")]
+    let (forgetting_probability, awareness_protection, initial_awareness) = (
+        config.optional_number("forgetting_probability", &mut problems),
+        config.optional_number("awareness_protection", &mut problems),
+        config.optional_number("initial_awareness", &mut problems),
+    );
+    // Model parameter: The probability of infection over each link depends on its layer and on the context of the
+    // exposure; see the modifiers module.
+    #[cfg(feature = "net")]
+    let link_factors = [
+        ("social_factor", 1.0),
+        ("household_factor", 1.0),
+        ("quarantine_factor", 0.0),
+        ("weekend_factor", 1.0),
+    ]
+    .map(|(name, default)| {
+        let factor = config.optional_number(name, &mut problems);
+        if let Some(factor) = factor {
+            problems.require(factor >= 0.0, name, "non-negative");
+        }
+        (name, factor.unwrap_or(default))
+    });
+    #[cfg(feature = "net")]
+    let weekend_days = config
+        .optional_numbers("weekend_days", &mut problems)
+        .unwrap_or_else(|| vec![5.0, 6.0]);
+    #[cfg(feature = "net")]
+    problems.require(
+        weekend_days
+            .iter()
+            .all(|&day| (0.0..7.0).contains(&day) && day.fract() == 0.0),
+        "weekend_days",
+        "a list of days of the week, from 0 to 6",
+    );
+    // Model parameter: A vaccination campaign protects the agents that a targeting rule chooses; see the interventions
+    // module.
+    let (vaccination_start, vaccination_every, vaccination_fraction, vaccine_efficacy) = (
+        config.optional_number("vaccination_start", &mut problems),
+        config.optional_number("vaccination_every", &mut problems),
+        config.optional_number("vaccination_fraction", &mut problems),
+        config.optional_number("vaccine_efficacy", &mut problems),
+    );
+    // Model parameter: With viral_load_peak, the viral load of each infected agent rises and falls, and it sets how
+    // infectious the agent is; see the viral_load module.
+    let viral_load_peak = config.optional_number("viral_load_peak", &mut problems);
+    let viral_load_parameters = [
+        ("viral_load_peak_time", 2.0),
+        ("viral_load_growth", 2.0),
+        ("viral_load_decay", 1.0),
+        ("viral_load_infectious", 5.0),
+        ("viral_load_detection", 3.0),
+    ]
+    .map(|(name, default)| (name, config.optional_number(name, &mut problems), default));
+    // Model parameter: With immunity_protection, each infection protects the agent against the next ones, and the
+    // protection wanes following immunity_waning; see the immunity module.
+    let (immunity_protection, immunity_waning_parameters) = (
+        config.optional_number("immunity_protection", &mut problems),
+        config.optional_numbers("immunity_waning", &mut problems),
+    );
+    let immunity_waning = immunity_waning_parameters.as_deref().and_then(|parameters| {
+        let waning = Waning::from_parameters(parameters);
+        problems.require(
+            waning.is_some(),
+            "immunity_waning",
+            "[half_life], positive, for exponential waning, or [scale, shape], both positive, for Weibull waning",
+        );
+        waning
+    });
+    // Model parameter: The initial population can be synthetic, with households, ages, and workplaces drawn from marginal
+    // distributions; see the population module. Household members link to each other, and workplaces become groups.
+    let household_sizes = config.optional_numbers("household_sizes", &mut problems);
+    let age_bands = config.optional_numbers("age_bands", &mut problems);
+    let age_weights = config.optional_numbers("age_weights", &mut problems);
+    let adult_age = config
+        .optional_number("adult_age", &mut problems)
+        .unwrap_or(18.0);
+    let retirement_age = config
+        .optional_number("retirement_age", &mut problems)
+        .unwrap_or(65.0);
+    let workplace_size_spec = config.optional_distribution("workplace_size", &mut problems);
+    // Output parameter: Bounds of the age groups of --stratify age, in time steps
+    #[cfg(feature = "csv-output")]
+    let age_groups = config
+        .optional_numbers("age_groups", &mut problems)
+        .unwrap_or_else(|| vec![0.0, adult_age, retirement_age]);
+    // Model parameter: Agents form groups, such as workplaces or herds, within which infection spreads and which go
+    // into quarantine as a whole; see the groups module.
+    let groups_len = config.optional_number("groups", &mut problems);
+    let group_parameters = [
+        ("group_contact_probability", 0.05),
+        ("quarantine_threshold", 1.0),
+        ("quarantine_duration", 14.0),
+    ]
+    .map(|(name, default)| (name, config.optional_number(name, &mut problems), default));
+    // Model parameter: Vectors, such as mosquitoes, carry the disease between hosts in the scenarios listed in
+    // vector_borne_scenarios, by id; see the vectors module.
+    #[cfg(feature = "landscape")]
+    let vector_borne_scenarios = config
+        .optional_numbers("vector_borne_scenarios", &mut problems)
+        .unwrap_or_default();
+    #[cfg(feature = "landscape")]
+    let vector_parameters = [
+        ("initial_vectors", 5000.0),
+        ("vector_capacity", 10000.0),
+        ("vector_birth_probability", 0.2),
+        ("vector_death_probability", 0.05),
+        ("vector_movement_probability", 0.5),
+        ("vector_infection_probability", 0.5),
+    ]
+    .map(|(name, default)| (name, config.optional_number(name, &mut problems), default));
+    // Model parameter: Agents visit cell_visits cells at each time step. With dose_response, they take in dose_per_visit
+    // at each infected cell and then become infected following the dose-response function; see the landscape module.
+    #[cfg(feature = "landscape")]
+    let (cell_visits, dose_response_parameters, dose_per_visit) = (
+        config.optional_number("cell_visits", &mut problems),
+        config.optional_numbers("dose_response", &mut problems),
+        config.optional_number("dose_per_visit", &mut problems),
+    );
+    #[cfg(feature = "landscape")]
+    let dose_response = dose_response_parameters.as_deref().and_then(|parameters| {
+        let dose_response = DoseResponse::from_parameters(parameters);
+        problems.require(
+            dose_response.is_some(),
+            "dose_response",
+            "[r], positive, for the exponential model, or [alpha, beta], both positive, for the beta-Poisson model",
+        );
+        dose_response
+    });
+    #[cfg(feature = "landscape")]
+    {
+        if let Some(cell_visits) = cell_visits {
+            problems.require(
+                cell_visits >= 1.0 && cell_visits.fract() == 0.0,
+                "cell_visits",
+                "a positive integer",
+            );
+        }
+        if let Some(dose_per_visit) = dose_per_visit {
+            problems.require(dose_per_visit > 0.0, "dose_per_visit", "positive");
+            problems.require(
+                dose_response_parameters.is_some(),
+                "dose_per_visit",
+                "absent without dose_response, which turns doses into infections",
+            );
+        }
+    }
+    #[cfg(feature = "landscape")]
+    let (cell_visits, dose_per_visit) = (
+        cell_visits.unwrap_or(1.0) as usize,
+        dose_per_visit.unwrap_or(1.0),
+    );
+    // Model parameter: Contamination of cells persists following cell_persistence, and decays faster or slower with the
+    // seasons following seasonal_amplitude; see the landscape module.
+    #[cfg(feature = "landscape")]
+    let (cell_persistence_parameters, seasonal_amplitude) = (
+        config.optional_numbers("cell_persistence", &mut problems),
+        config.optional_number("seasonal_amplitude", &mut problems),
+    );
+    #[cfg(feature = "landscape")]
+    let seasonal_parameters = [("seasonal_period", 365.0), ("seasonal_peak", 0.0)]
+        .map(|(name, default)| (name, config.optional_number(name, &mut problems), default));
+    #[cfg(feature = "landscape")]
+    let cell_persistence = cell_persistence_parameters.as_deref().and_then(|parameters| {
+        let persistence = Persistence::from_parameters(parameters);
+        problems.require(
+            persistence.is_some(),
+            "cell_persistence",
+            "[scale], positive, for exponential decay, or [scale, shape], both positive, for Weibull decay",
+        );
+        persistence
+    });
+    #[cfg(feature = "landscape")]
+    match seasonal_amplitude {
+        Some(amplitude) => {
+            problems.probability("seasonal_amplitude", amplitude);
+            let [(_, period, _), _] = seasonal_parameters;
+            if let Some(period) = period {
+                problems.require(period > 0.0, "seasonal_period", "positive");
+            }
+        }
+        None => {
+            for (name, value, _default) in seasonal_parameters {
+                if value.is_some() {
+                    problems.report(name, "describes the seasons, which need seasonal_amplitude");
+                }
+            }
+        }
+    }
+    // Model parameter: With energy_initial, agents spend energy to live and to visit cells, take it from the resources
+    // of the cells they visit, and starve when it runs out; see the energy module.
+    #[cfg(feature = "landscape")]
+    let energy_initial = config.optional_number("energy_initial", &mut problems);
+    #[cfg(feature = "landscape")]
+    let energy_parameters = [
+        ("energy_per_step", 1.0),
+        ("energy_per_visit", 0.5),
+        ("energy_infection", 0.0),
+        ("energy_intake", 5.0),
+        ("resource_capacity", 10.0),
+        ("resource_regrowth", 1.0),
+    ]
+    .map(|(name, default)| (name, config.optional_number(name, &mut problems), default));
+    #[cfg(feature = "landscape")]
+    match energy_initial {
+        Some(initial) => {
+            problems.require(initial > 0.0, "energy_initial", "positive");
+            problems.require(
+                landscape,
+                "energy_initial",
+                "absent with --no-landscape, which leaves agents without resources",
+            );
+            for (name, value, _default) in energy_parameters {
+                if let Some(value) = value {
+                    problems.require(value >= 0.0, name, "non-negative");
+                }
+            }
+        }
+        None => {
+            for (name, value, _default) in energy_parameters {
+                if value.is_some() {
+                    problems.report(name, "describes energy, which needs energy_initial");
+                }
+            }
+        }
+    }
+    // The landscape of the GPU recovers at a constant rate
+    #[cfg(feature = "gpu")]
+    problems.require(
+        cell_persistence_parameters.is_none() && seasonal_amplitude.is_none(),
+        "cell_persistence and seasonal_amplitude",
+        "absent with the gpu feature",
+    );
+    // Output parameter: A surveillance system reports the new infections of each time step with a probability, a
+    // delay, effects of the day of the week, and noise, to observed.csv; see the observation module.
+    let reporting_probability = config.optional_number("reporting_probability", &mut problems);
+    let reporting_delay_spec = config.optional_distribution("reporting_delay", &mut problems);
+    let weekday_effects = config.optional_numbers("weekday_effects", &mut problems);
+    let reporting_dispersion = config.optional_number("reporting_dispersion", &mut problems);
+    // Output parameter: Prevalence among agents whose first passage extinction.csv and first_passage.csv report
+    let prevalence_threshold = config
+        .optional_number("prevalence_threshold", &mut problems)
+        .unwrap_or(0.1);
+    // Output parameter: Prevalence among the agents of each range of network degrees, each range from one of these
+    // degrees to the next, such as [1, 3, 6] for degrees 1 to 2, 3 to 5, and 6 or more
+    #[cfg(feature = "net-metrics")]
+    let degree_bins = config.optional_numbers("degree_bins", &mut problems);
+    config.finish(&mut problems);
+    #[cfg(feature = "landscape")]
+    let agent_parameters = agent_movement_scale_spec.is_some();
+    #[cfg(not(feature = "landscape"))]
+    let agent_parameters = false;
+    #[cfg(feature = "net")]
+    let agent_parameters = agent_parameters || agent_sociability_spec.is_some();
+    let agent_parameters =
+        agent_parameters || agent_recovery_spec.is_some() || agent_susceptibility_spec.is_some();
+    if trait_mutation_spec.is_some() && !agent_parameters {
+        problems.report(
+            "trait_mutation",
+            "mutates only parameters that differ between agents; give a distribution to \
+             agent_recovery_probability, agent_susceptibility, agent_movement_scale, or agent_sociability",
+        );
+    }
+    if let Some(carrying_capacity) = carrying_capacity {
+        problems.require(carrying_capacity > 0.0, "carrying_capacity", "positive");
+    }
+    if let Some(immigration) = immigration {
+        problems.require(immigration >= 0.0, "immigration", "non-negative");
+    }
+    if let Some(infected) = immigrant_infected {
+        problems.probability("immigrant_infected", infected);
+        problems.require(
+            immigration.is_some(),
+            "immigrant_infected",
+            "absent without immigration, which brings the immigrants",
+        );
+    }
+    if let Some(emigration) = emigration_probability {
+        problems.probability("emigration_probability", emigration);
+    }
+    problems.probability("mortality_baseline", mortality_baseline);
+    problems.require(
+        prevalence_threshold > 0.0 && prevalence_threshold <= 1.0,
+        "prevalence_threshold",
+        format_args!("above 0 and at most 1, not {}", prevalence_threshold),
+    );
+    #[cfg(feature = "net")]
+    for (name, probability) in [
+        ("awareness_probability", awareness_probability),
+        ("forgetting_probability", forgetting_probability),
+        ("awareness_protection", awareness_protection),
+        ("initial_awareness", initial_awareness),
+    ] {
+        match probability {
+            Some(probability) if awareness_probability.is_some() => {
+                problems.probability(name, probability)
+            }
+            Some(_) => problems.report(
+                name,
+                "describes awareness, which spreads only with awareness_probability",
+            ),
+            None => {}
+        }
+    }
+    problems.require(mortality_growth.is_finite(), "mortality_growth", "finite");
+    #[cfg(feature = "csv-output")]
+    problems.require(
+        !age_groups.is_empty() && age_groups.windows(2).all(|pair| pair[0] < pair[1]),
+        "age_groups",
+        "increasing ages",
+    );
+    #[cfg(feature = "net-metrics")]
+    if let Some(degree_bins) = &degree_bins {
+        problems.require(
+            degree_bins
+                .iter()
+                .all(|&degree| degree >= 0.0 && degree.fract() == 0.0)
+                && degree_bins.windows(2).all(|pair| pair[0] < pair[1]),
+            "degree_bins",
+            "increasing degrees",
+        );
+        problems.require(
+            network,
+            "degree_bins",
+            "a network; please leave out --no-network",
+        );
+    }
+    #[cfg(feature = "landscape")]
+    if vector_borne_scenarios.is_empty() {
+        for (name, value, _default) in vector_parameters {
+            if value.is_some() {
+                problems.report(
+                    name,
+                    "describes vectors, which live only in the scenarios of vector_borne_scenarios",
+                );
+            }
+        }
+    } else {
+        problems.require(
+            landscape,
+            "vector_borne_scenarios",
+            "empty without the landscape, where vectors live",
+        );
+        for &id in &vector_borne_scenarios {
+            problems.require(
+                id.fract() == 0.0 && id >= 0.0 && id < infection_probabilities.len() as f64,
+                "vector_borne_scenarios",
+                format_args!(
+                    "a list of scenario ids, from 0 to {}",
+                    infection_probabilities.len() - 1
+                ),
+            );
+        }
+        for (name, value, default) in vector_parameters {
+            let value = value.unwrap_or(default);
+            if name.ends_with("_probability") {
+                problems.probability(name, value);
+            } else {
+                problems.require(value >= 1.0, name, "at least 1");
+            }
+        }
+    }
+    let synthetic_population = match &household_sizes {
+        Some(household_sizes) => {
+            let ages = match (&age_bands, &age_weights) {
+                (Some(age_bands), Some(age_weights)) => Some((&age_bands[..], &age_weights[..])),
+                (None, None) => None,
+                _ => {
+                    problems.report("age_bands", "goes together with age_weights");
+                    None
+                }
+            };
+            match SyntheticPopulation::new(
+                household_sizes,
+                ages,
+                adult_age,
+                retirement_age,
+                workplace_size_spec
+                    .as_ref()
+                    .and_then(|spec| spec.build().ok()),
+            ) {
+                Ok(synthetic_population) => Some(synthetic_population),
+                Err((name, e)) => {
+                    problems.report(name, e);
+                    None
+                }
+            }
+        }
+        None => {
+            if age_bands.is_some() || age_weights.is_some() || workplace_size_spec.is_some() {
+                problems.report(
+                    "household_sizes",
+                    "is missing; age_bands, age_weights, and workplace_size describe a synthetic population",
+                );
+            }
+            None
+        }
+    };
+    #[cfg(feature = "checkpoint")]
+    if household_sizes.is_some() {
+        problems.require(
+            args.warm_start.is_none(),
+            "household_sizes",
+            "absent with --warm-start, whose world has its own agents",
+        );
+    }
+    // Model parameter: The initial agents can come from a file instead; see the population module
+    let population_file = match &args.population {
+        Some(path) => Some(Population::read(path).or_error(&format!(
+            "Could not read the population file {}",
+            path.display()
+        ))?),
+        None => None,
+    };
+    // Model parameter: Agents can mix between age groups following a contact matrix; see the mixing module
+    let contact_matrix = match &args.contact_matrix {
+        Some(path) => Some(ContactMatrix::read(path).or_error(&format!(
+            "Could not read the contact matrix file {}",
+            path.display()
+        ))?),
+        None => None,
+    };
+    let n0 = population_file
+        .as_ref()
+        .map_or(n0, |population| population.persons.len());
+    let file_groups = population_file.as_ref().is_some_and(|population| {
+        population
+            .persons
+            .iter()
+            .any(|person| person.workplace.is_some())
+    });
+    if population_file.is_some() {
+        problems.require(
+            household_sizes.is_none(),
+            "household_sizes",
+            "absent with --population, whose file lists the agents",
+        );
+    }
+    if workplace_size_spec.is_some() {
+        problems.require(
+            groups_len.is_none(),
+            "groups",
+            "absent with workplace_size, whose workplaces are the groups",
+        );
+    }
+    if file_groups {
+        problems.require(
+            groups_len.is_none(),
+            "groups",
+            "absent with --population, whose file gives the group of each agent",
+        );
+    }
+    match groups_len.or((workplace_size_spec.is_some() || file_groups).then_some(1.0)) {
+        Some(groups_len) => {
+            problems.require(
+                groups_len >= 1.0 && groups_len.fract() == 0.0,
+                "groups",
+                "a positive integer",
+            );
+            for (name, value, _default) in group_parameters {
+                match (name, value) {
+                    (_, None) => {}
+                    ("quarantine_duration", Some(duration)) => problems.require(
+                        duration >= 1.0 && duration.fract() == 0.0,
+                        name,
+                        "a positive integer",
+                    ),
+                    (_, Some(probability)) => problems.probability(name, probability),
+                }
+            }
+        }
+        None => {
+            for (name, value, _default) in group_parameters {
+                if value.is_some() {
+                    problems.report(name, "describes groups, which agents form only with groups");
+                }
+            }
+        }
+    }
+    match reporting_probability {
+        Some(reporting_probability) => {
+            problems.probability("reporting_probability", reporting_probability);
+            problems.require(
+                cfg!(feature = "csv-output"),
+                "reporting_probability",
+                "absent without the csv-output feature, which writes observed.csv",
+            );
+            if let Some(weekday_effects) = &weekday_effects {
+                problems.require(
+                    weekday_effects.len() == 7
+                        && weekday_effects.iter().all(|&effect| effect >= 0.0),
+                    "weekday_effects",
+                    "a list of 7 non-negative multipliers, one per day of the week",
+                );
+            }
+            if let Some(reporting_dispersion) = reporting_dispersion {
+                problems.require(
+                    reporting_dispersion > 0.0,
+                    "reporting_dispersion",
+                    "positive",
+                );
+            }
+        }
+        None => {
+            if reporting_delay_spec.is_some()
+                || weekday_effects.is_some()
+                || reporting_dispersion.is_some()
+            {
+                problems.report(
+                    "reporting_probability",
+                    "is missing; reporting_delay, weekday_effects, and reporting_dispersion describe the \
+                     observation of new infections",
+                );
+            }
+        }
+    }
+    match viral_load_peak {
+        Some(peak) => {
+            for (name, value, _default) in viral_load_parameters {
+                match (name, value) {
+                    (_, None) => {}
+                    ("viral_load_peak_time", Some(value)) => {
+                        problems.require(value >= 0.0, name, "non-negative")
+                    }
+                    ("viral_load_growth" | "viral_load_decay", Some(value)) => {
+                        problems.require(value > 0.0, name, "positive")
+                    }
+                    (_, Some(_)) => {}
+                }
+            }
+            let [.., (_, infectious, default_infectious), _] = viral_load_parameters;
+            problems.require(
+                peak > infectious.unwrap_or(default_infectious),
+                "viral_load_peak",
+                "above viral_load_infectious",
+            );
+        }
+        None => {
+            for (name, value, _default) in viral_load_parameters {
+                if value.is_some() {
+                    problems.report(
+                        name,
+                        "describes the viral load, which needs viral_load_peak",
+                    );
+                }
+            }
+        }
+    }
+    match immunity_protection {
+        Some(protection) => problems.probability("immunity_protection", protection),
+        None => {
+            if immunity_waning_parameters.is_some() {
+                problems.report(
+                    "immunity_waning",
+                    "describes the waning of immunity, which needs immunity_protection",
+                );
+            }
+        }
+    }
+    match args.vaccinate {
+        Some(rule) => {
+            if let Some(start) = vaccination_start {
+                problems.require(
+                    start >= 0.0 && start.fract() == 0.0,
+                    "vaccination_start",
+                    "a non-negative integer",
+                );
+            }
+            if let Some(every) = vaccination_every {
+                problems.require(
+                    every >= 1.0 && every.fract() == 0.0,
+                    "vaccination_every",
+                    "a positive integer",
+                );
+            }
+            if let Some(fraction) = vaccination_fraction {
+                problems.probability("vaccination_fraction", fraction);
+            }
+            if let Some(efficacy) = vaccine_efficacy {
+                problems.probability("vaccine_efficacy", efficacy);
+            }
+            #[cfg(feature = "landscape")]
+            if let Rule::Patch(_) = rule {
+                problems.require(
+                    population_file.as_ref().is_some_and(|population| {
+                        population.persons.iter().any(|person| person.home.is_some())
+                    }),
+                    "--vaccinate",
+                    "a rule other than patch without a population file that gives the homes of agents",
+                );
+            }
+            #[cfg(not(feature = "landscape"))]
+            let _ = rule;
+        }
+        None => {
+            if vaccination_start.is_some()
+                || vaccination_every.is_some()
+                || vaccination_fraction.is_some()
+                || vaccine_efficacy.is_some()
+            {
+                problems.report(
+                    "--vaccinate",
+                    "is missing; vaccination_start, vaccination_every, vaccination_fraction, and \
+                     vaccine_efficacy describe the vaccination campaign",
+                );
+            }
+        }
+    }
+    problems.positive("n0", n0);
+    #[cfg(feature = "net")]
+    problems.require(net_k < n0, "net_k", format_args!("less than n0 ({})", n0));
+    #[cfg(feature = "landscape")]
+    {
+        problems.require(landscape_width > 0, "landscape_width", "positive");
+        problems.require(landscape_height > 0, "landscape_height", "positive");
+    }
+    for (i, &infection_probability) in infection_probabilities.iter().enumerate() {
+        problems.probability(
+            &format!("infection_probabilities[{}]", i),
+            infection_probability,
+        );
+    }
+    problems.positive("sample_every", sample_every);
+    #[cfg(feature = "graphics")]
+    {
+        problems.positive("render_every", render_every);
+        problems.require(
+            render_every.is_multiple_of(sample_every.max(1)),
+            "render_every",
+            format_args!("a multiple of sample_every ({})", sample_every),
+        );
+        problems.positive("--frame-stride", args.figure_options.frame_stride);
+        #[cfg(feature = "landscape-graphics")]
+        if let Some(heatmap_scale) = args.figure_options.heatmap_scale {
+            problems.positive("--heatmap-scale", heatmap_scale as usize);
+        }
+        if let Some(max_frames) = args.figure_options.max_frames {
+            problems.positive("--max-frames", max_frames);
+        }
+    }
+    #[cfg(any(feature = "cells-csv", feature = "netcdf-output"))]
+    problems.positive("cells_every", cells_every);
+    #[cfg(all(debug_assertions, feature = "checkpoint"))]
+    problems.positive("snapshot_every", snapshot_every);
+    #[cfg(feature = "checkpoint")]
+    if let Some(branch_at) = args.branch_at {
+        problems.require(
+            branch_at > 0 && branch_at < time_series_len,
+            "--branch-at",
+            format_args!("a time step between 1 and {}", last_time_step),
+        );
+    }
+    #[cfg(feature = "graphics")]
+    let themes = args.figure_options.themes(&mut problems);
+    problems.positive("--replicates", args.replicates as usize);
+    problems.require(
+        !args.antithetic || args.replicates.is_multiple_of(2),
+        "--replicates",
+        format_args!("even with --antithetic, not {}", args.replicates),
+    );
+    let scenario_count = infection_probabilities.len() * args.replicates as usize;
+    if let Some(scenario_index) = args.scenario_index {
+        problems.require(
+            (scenario_index as usize) < scenario_count,
+            "--scenario-index",
+            format_args!("less than the number of scenarios ({})", scenario_count),
+        );
+    }
+    // Output parameter: Shard of the scenarios that this process simulates; other processes simulate the rest
+    let shard = match args.scenario_index {
+        Some(_) => None,
+        None => args.shard.or_else(Shard::from_slurm),
+    };
+    if let Some(shard) = shard {
+        // Without --shard, the shard comes from the Slurm array job
+        let shard_name = if args.shard.is_some() {
+            "--shard"
+        } else {
+            "SLURM_ARRAY_TASK_ID"
+        };
+        problems.require(
+            shard.index < scenario_count,
+            shard_name,
+            format_args!(
+                "a shard with at least one of the {} scenarios, not {}",
+                scenario_count, shard
+            ),
+        );
+        // --branch-at needs every scenario in the same process
+        #[cfg(feature = "checkpoint")]
+        problems.require(
+            args.branch_at.is_none(),
+            "--branch-at",
+            format_args!("left out of an array job, such as shard {}", shard),
+        );
+    }
+    problems.finish()?;
+    // begin-similar-code 5
+    #[cfg(feature = "landscape")]
+    let coord = WrappingCoords2d::new(landscape_width, landscape_height).unwrap();
+    let birth_distro = Bernoulli::new(birth_probability).unwrap();
+    let initial_infection_distro = Bernoulli::new(initial_infection_probability).unwrap();
+    #[cfg(feature = "landscape")]
+    let visit_distro = visit_spec.build().unwrap();
+    #[cfg(feature = "net")]
+    let link_distro = Bernoulli::new(link_probability).unwrap();
+    let recovery_distro = Bernoulli::new(recovery_probability).unwrap();
+    let survival_distro = Bernoulli::new(survival_probability).unwrap();
+    // end-similar-code 5
+    // Center of the landscape, from which the movement scale of agents stretches the distances of their visits
+    #[cfg(feature = "landscape")]
+    let visit_center = (coord.width() as f64 / 2.0, coord.height() as f64 / 2.0);
+    let build = |spec: &Option<Spec>| spec.as_ref().map(|spec| spec.build().unwrap());
+    let heterogeneity = Heterogeneity {
+        recovery_probability,
+        recovery_distro: build(&agent_recovery_spec),
+        susceptibility_distro: build(&agent_susceptibility_spec),
+        #[cfg(feature = "landscape")]
+        movement_scale_distro: build(&agent_movement_scale_spec),
+        #[cfg(not(feature = "landscape"))]
+        movement_scale_distro: None,
+        #[cfg(feature = "net")]
+        sociability_distro: build(&agent_sociability_spec),
+        #[cfg(not(feature = "net"))]
+        sociability_distro: None,
+        mutation_distro: build(&trait_mutation_spec),
+    };
+    #[cfg(feature = "landscape")]
+    let metabolism = energy_initial.map(|initial| {
+        let [per_step, per_visit, infection, intake, capacity, regrowth] =
+            energy_parameters.map(|(_, value, default)| value.unwrap_or(default));
+        Metabolism {
+            initial,
+            per_step,
+            per_visit,
+            infection,
+            intake,
+            capacity,
+            regrowth,
+        }
+    });
+    #[cfg(feature = "landscape")]
+    let decay = (cell_persistence.is_some() || seasonal_amplitude.is_some()).then(|| {
+        let [period, peak] =
+            seasonal_parameters.map(|(_, value, default)| value.unwrap_or(default));
+        Decay {
+            persistence: cell_persistence,
+            seasonality: seasonal_amplitude.map(|amplitude| Seasonality {
+                amplitude,
+                period,
+                peak,
+            }),
+            recovery_probability,
+        }
+    });
+    let demography = Demography {
+        birth_probability,
+        carrying_capacity,
+        mortality_baseline,
+        mortality_growth,
+        initial_age: build(&initial_age_spec),
+    };
+    let migration =
+        (immigration.is_some() || emigration_probability.is_some()).then(|| Migration {
+            immigration: immigration.unwrap_or(0.0),
+            infected: immigrant_infected.unwrap_or(0.0),
+            emigration: emigration_probability.unwrap_or(0.0),
+        });
+    #[cfg(feature = "csv-output")]
+    let observation = reporting_probability.map(|reporting_probability| Observation {
+        reporting_probability,
+        delay: build(&reporting_delay_spec),
+        weekday_effects: weekday_effects
+            .as_ref()
+            .map(|weekday_effects| std::array::from_fn(|day| weekday_effects[day])),
+        dispersion: reporting_dispersion,
+    });
+    // Model initialization: The initial population, shared by all scenarios, comes from the file, or else the synthetic
+    // population draws from a stream that no scenario uses.
+    let population = population_file.or_else(|| {
+        synthetic_population.as_ref().map(|synthetic_population| {
+            synthetic_population.generate(
+                n0,
+                &mut rand_pcg::Pcg64::new(rng_state, rng_stream.wrapping_sub(1)),
+            )
+        })
+    });
+    let persons = population
+        .as_ref()
+        .map_or(&[][..], |population| &population.persons[..]);
+    // Model parameter: The model keeps the ages of agents for the demography, for the initial population, for mixing
+    // between age groups, or to vaccinate the oldest agents
+    let keep_ages = demography.ages()
+        || persons.iter().any(|person| person.age.is_some())
+        || contact_matrix.is_some()
+        || args.vaccinate == Some(Rule::Oldest);
+    // Model parameter: The model keeps the parameters of each agent if they differ between agents, or if the initial
+    // population gives some of them
+    let population_traits: [bool; 4] =
+        std::array::from_fn(|i| persons.iter().any(|person| person.traits[i].is_some()));
+    let keep_traits = heterogeneity.any() || population_traits.contains(&true);
+    // Model parameter: Agents of the initial population visit the landscape around their homes, if they have any
+    #[cfg(feature = "landscape")]
+    let keep_homes = persons.iter().any(|person| person.home.is_some());
+    let workplaces = population
+        .as_ref()
+        .filter(|_| persons.iter().any(|person| person.workplace.is_some()))
+        .map(|population| population.workplaces as f64);
+    let group_rules = groups_len.or(workplaces).map(|groups_len| {
+        let [(_, contact_probability, _), (_, quarantine_threshold, _), (_, quarantine_duration, default_duration)] =
+            group_parameters;
+        GroupRules {
+            groups: groups_len as usize,
+            contact_probability: contact_probability.unwrap_or(0.05),
+            quarantine_threshold,
+            quarantine_duration: quarantine_duration.unwrap_or(default_duration) as usize,
+        }
+    });
+    #[cfg(feature = "landscape")]
+    let vector_population = {
+        let [initial, capacity, birth_probability, death_probability, movement_probability, infection_probability] =
+            vector_parameters.map(|(_name, value, default)| value.unwrap_or(default));
+        VectorPopulation {
+            initial: initial as usize,
+            capacity,
+            birth_probability,
+            death_probability,
+            movement_probability,
+            infection_probability,
+        }
+    };
+    #[cfg(feature = "net")]
+    let awareness = awareness_probability.map(|spreading_probability| Awareness {
+        spreading_probability,
+        forgetting_probability: forgetting_probability.unwrap_or(0.0),
+        protection: awareness_protection.unwrap_or(0.0),
+        initial_probability: initial_awareness.unwrap_or(0.0),
+    });
+    // Model parameter: Modifiers of the probability of infection over each link, in order; add others here
+    #[cfg(feature = "net")]
+    let pipeline = {
+        let [(_, social), (_, household), (_, quarantine), (_, weekend)] = link_factors;
+        Pipeline::default()
+            .then(QuarantineFactor(quarantine))
+            .then(LayerFactors { social, household })
+            .then(WeekendFactor {
+                factor: weekend,
+                days: weekend_days.iter().map(|&day| day as usize).collect(),
+            })
+    };
+    let viral_load = viral_load_peak.map(|peak| {
+        let [peak_time, growth, decay, infectious, detection] =
+            viral_load_parameters.map(|(_, value, default)| value.unwrap_or(default));
+        ViralLoad {
+            peak,
+            peak_time,
+            growth,
+            decay,
+            infectious,
+            detection,
+        }
+    });
+    let immunity = immunity_protection.map(|protection| Immunity {
+        protection,
+        waning: immunity_waning,
+    });
+    // Model parameter: Write other targeting rules here, such as
+    // Box::new(Ranking(|world: &World, k| Some(world.traits?.sociability[k]))); see the interventions module
+    let vaccination = args.vaccinate.map(|rule| Vaccination {
+        targeting: rule.targeting(),
+        start: vaccination_start.unwrap_or(0.0) as usize,
+        every: vaccination_every.map(|every| every as usize),
+        fraction: vaccination_fraction.unwrap_or(0.01),
+        efficacy: vaccine_efficacy.unwrap_or(1.0),
+    });
+    // Model measurements: Reporters become the columns of the time series output file, in this order, and the series of the figures.
+    // Chart lines choose their colors from the palette of the figures: 0 foreground, 1 accent, 2 susceptibles, 3 infectious.
+    let mut collector = DataCollector::new();
+    collector.sample_every(sample_every);
+    #[cfg(feature = "agent-metrics")]
+    let agents_chart = collector.chart("Populations of agents", "Number of agents");
+    #[cfg(feature = "landscape-metrics")]
+    let cells_chart =
+        landscape.then(|| collector.chart("Infection of cells", "Number of infected cells"));
+    #[cfg(feature = "net-metrics")]
+    if network {
+        collector
+            .agents(
+                "d_s",
+                "Maximum network degree of susceptibles",
+                Aggregate::Max,
+                |world, k, h| match h {
+                    Health::S => Some(world.degree[k] as f64),
+                    Health::I => None,
+                },
+            )
+            .text();
+        collector
+            .agents(
+                "d_i",
+                "Maximum network degree of infectious",
+                Aggregate::Max,
+                |world, k, h| match h {
+                    Health::S => None,
+                    Health::I => Some(world.degree[k] as f64),
+                },
+            )
+            .text();
+    }
+    // Model measurements: Prevalence among the agents of each range of network degrees, to show how the position of
+    // an agent in the network shapes its risk
+    #[cfg(feature = "net-metrics")]
+    if let (Some(degree_bins), true) = (&degree_bins, network) {
+        for (idx, &low) in degree_bins.iter().enumerate() {
+            let high = degree_bins.get(idx + 1).copied();
+            let range = strata::band_label(degree_bins, idx);
+            collector
+                .agents(
+                    format!("i_d{}", low),
+                    format!("Prevalence among agents of network degree {}", range),
+                    Aggregate::Mean,
+                    move |world, k, h| {
+                        let degree = world.degree[k] as f64;
+                        (degree >= low && high.is_none_or(|high| degree < high))
+                            .then(|| (h == Health::I) as u8 as f64)
+                    },
+                )
+                .text();
+        }
+    }
+    #[cfg(feature = "landscape-metrics")]
+    if let Some(cells_chart) = cells_chart {
+        collector
+            .model("c_i", "Infected cells", |world| {
+                world
+                    .cell_health
+                    .iter()
+                    .filter(|&&h| h == Health::I)
+                    .count() as f64
+            })
+            .line(cells_chart, 3);
+        #[cfg(feature = "spatial-metrics")]
+        {
+            let width = landscape_width as usize;
+            collector
+                .model("m_i", "Moran's I of infected cells", move |world| {
+                    spatial::morans_i(world.cell_health, width)
+                })
+                .text();
+            collector
+                .model("k_i", "Clusters of infected cells", move |world| {
+                    spatial::clusters(world.cell_health, width).count as f64
+                })
+                .text();
+            collector
+                .model("k_max", "Cells of the largest cluster", move |world| {
+                    spatial::clusters(world.cell_health, width).largest as f64
+                })
+                .text();
+        }
+    }
+    #[cfg(feature = "landscape-metrics")]
+    if landscape && !vector_borne_scenarios.is_empty() {
+        let vectors_chart = collector.chart("Populations of vectors", "Number of vectors");
+        collector
+            .model("v", "Vectors", |world| {
+                world.vectors.map_or(0.0, |vectors| vectors.len() as f64)
+            })
+            .line(vectors_chart, 0);
+        collector
+            .model("v_i", "Infected vectors", |world| {
+                world.vectors.map_or(0.0, |vectors| {
+                    vectors
+                        .iter()
+                        .filter(|vector| vector.health == Health::I)
+                        .count() as f64
+                })
+            })
+            .line(vectors_chart, 3);
+    }
+    #[cfg(feature = "agent-metrics")]
+    {
+        collector
+            .agents("n", "Number of agents", Aggregate::Sum, |_world, _k, _h| {
+                Some(1.0)
+            })
+            .line(agents_chart, 0);
+        collector.agents("s", "Susceptibles", Aggregate::Sum, |_world, _k, h| {
+            Some((h == Health::S) as u8 as f64)
+        });
+        collector
+            .agents("i", "Infected", Aggregate::Sum, |_world, _k, h| {
+                Some((h == Health::I) as u8 as f64)
+            })
+            .line(agents_chart, 3);
+        collector.model("ci", "Cumulative incidence", |world| {
+            world.infections as f64
+        });
+        // Model measurements: Mean and variance of each parameter that differs between agents, over the agents alive,
+        // which show how the parameters evolve under selection
+        type Parameter = fn(&AgentTraits, AgentKey) -> Option<&f64>;
+        let parameters: [(_, _, _, _, Parameter); 4] = [
+            (
+                &heterogeneity.recovery_distro,
+                ("p_r", "p_r_var"),
+                "Mean probability of recovery of agents",
+                "Variance of the probability of recovery of agents",
+                AgentTraits::recovery_probability,
+            ),
+            (
+                &heterogeneity.susceptibility_distro,
+                ("sus", "sus_var"),
+                "Mean susceptibility of agents",
+                "Variance of the susceptibility of agents",
+                AgentTraits::susceptibility,
+            ),
+            (
+                &heterogeneity.movement_scale_distro,
+                ("mov", "mov_var"),
+                "Mean movement scale of agents",
+                "Variance of the movement scale of agents",
+                AgentTraits::movement_scale,
+            ),
+            (
+                &heterogeneity.sociability_distro,
+                ("soc", "soc_var"),
+                "Mean sociability of agents",
+                "Variance of the sociability of agents",
+                AgentTraits::sociability,
+            ),
+        ];
+        for (
+            &from_population,
+            (distro, (mean, variance), mean_description, variance_description, parameter),
+        ) in population_traits.iter().zip(parameters)
+        {
+            if distro.is_some() || from_population {
+                collector.agents(
+                    mean,
+                    mean_description,
+                    Aggregate::Mean,
+                    move |world, k, _h| parameter(world.traits?, k).copied(),
+                );
+                collector.agents(
+                    variance,
+                    variance_description,
+                    Aggregate::Variance,
+                    move |world, k, _h| parameter(world.traits?, k).copied(),
+                );
+            }
+        }
+        #[cfg(feature = "net")]
+        if awareness.is_some() {
+            collector
+                .agents("a", "Aware agents", Aggregate::Sum, |world, k, _h| {
+                    Some(world.aware?.is_aware(k) as u8 as f64)
+                })
+                .line(agents_chart, 1);
+            collector.agents(
+                "i_a",
+                "Infected aware agents",
+                Aggregate::Sum,
+                |world, k, h| Some((h == Health::I && world.aware?.is_aware(k)) as u8 as f64),
+            );
+        }
+        if group_rules.is_some() {
+            collector.model("g_i", "Groups with infected members", |world| {
+                world.groups.map_or(0.0, |groups| {
+                    groups
+                        .census(world.health)
+                        .iter()
+                        .filter(|census| census.infected > 0)
+                        .count() as f64
+                })
+            });
+            collector.model("g_q", "Groups in quarantine", |world| {
+                world.groups.map_or(0.0, |groups| {
+                    groups
+                        .quarantine_ends
+                        .iter()
+                        .filter(|&&quarantine_end| world.time_step < quarantine_end)
+                        .count() as f64
+                })
+            });
+            collector.agents(
+                "q",
+                "Agents in quarantine",
+                Aggregate::Sum,
+                |world, k, _h| Some(world.groups?.quarantined(k, world.time_step) as u8 as f64),
+            );
+        }
+        if vaccination.is_some() {
+            collector.agents("v", "Vaccinated agents", Aggregate::Sum, |world, k, _h| {
+                Some(world.vaccinated?.is_vaccinated(k) as u8 as f64)
+            });
+        }
+        if let Some(viral_load) = viral_load {
+            // Time steps since the infection of an agent
+            let infection_age = move |world: &World, k| {
+                viral_load.infection_age(world.transmissions.infected_at(k), world.time_step)
+            };
+            collector.agents(
+                "vl",
+                "Mean log10 viral load of infected agents",
+                Aggregate::Mean,
+                move |world, k, h| {
+                    (h == Health::I).then(|| viral_load.log10_load(infection_age(world, k)))
+                },
+            );
+            collector.agents(
+                "i_det",
+                "Infected agents with a detectable viral load",
+                Aggregate::Sum,
+                move |world, k, h| {
+                    Some(
+                        (h == Health::I && viral_load.detectable(infection_age(world, k))) as u8
+                            as f64,
+                    )
+                },
+            );
+        }
+        #[cfg(feature = "landscape")]
+        if metabolism.is_some() {
+            collector.agents(
+                "e",
+                "Mean energy of agents",
+                Aggregate::Mean,
+                |world, k, _h| world.energy?.energy(k).copied(),
+            );
+            collector.model("res", "Mean resource of cells", |world| {
+                world.resources.map_or(0.0, |resources| {
+                    resources.resource.iter().sum::<f64>() / resources.resource.len() as f64
+                })
+            });
+        }
+        if migration.is_some() {
+            collector.model("imm", "Cumulative immigrants", |world| {
+                world.immigrants as f64
+            });
+            collector.model("emi", "Cumulative emigrants", |world| {
+                world.emigrants as f64
+            });
+        }
+        if let Some(immunity) = immunity {
+            collector.model("ci_1", "Cumulative first infections", |world| {
+                world
+                    .history
+                    .map_or(0.0, |history| history.first_infections as f64)
+            });
+            collector.model("ci_r", "Cumulative reinfections", |world| {
+                world
+                    .history
+                    .map_or(0.0, |history| history.reinfections as f64)
+            });
+            collector.agents(
+                "prot",
+                "Mean protection of susceptible agents",
+                Aggregate::Mean,
+                move |world, k, h| {
+                    (h == Health::S).then(|| {
+                        world.history.map_or(0.0, |history| {
+                            immunity.protection(history, k, world.time_step)
+                        })
+                    })
+                },
+            );
+        }
+        if keep_ages {
+            collector.agents(
+                "age",
+                "Mean age of agents in time steps",
+                Aggregate::Mean,
+                |world, k, _h| Some((world.time_step as i64 - world.ages?.born(k)?) as f64),
+            );
+        }
+    }
+    #[cfg(not(feature = "streaming"))]
+    let samples_len = collector.samples_len(time_series_len);
+    // Bookkeeping: The charts of the figures are known once the data collector is ready, still before the run starts.
+    #[cfg(feature = "graphics")]
+    {
+        let mut problems = Problems::default();
+        args.figure_options.check(&collector, &mut problems);
+        problems.finish()?;
+    }
+    #[cfg(feature = "net-graphics")]
+    let compress_histogram = true;
+    #[cfg(feature = "graphics")]
+    let figure_settings = FigureSettings {
+        collector: &collector,
+        #[cfg(feature = "landscape")]
+        coord: &coord,
+        render_every,
+        time_series_len,
+        #[cfg(feature = "net-graphics")]
+        compress_histogram,
+        options: &args.figure_options,
+        themes,
+        frame_stride: {
+            let options = &args.figure_options;
+            let frames = time_series_len.div_ceil(render_every);
+            match options.max_frames {
+                Some(max_frames) => options.frame_stride.max(frames.div_ceil(max_frames)),
+                None => options.frame_stride,
+            }
+        },
+        existing,
+    };
+    let clean_term =
+        "\r                                                                         \r";
+    // Figures of an earlier run, drawn from its output files into a new directory
+    #[cfg(all(feature = "graphics", feature = "csv-output"))]
+    if let Some((data_dir, output_dir)) = plot {
+        let plot_dir = output_dir.unwrap_or_else(|| run_dir_name(started));
+        create_run_dir(&plot_dir, existing)?;
+        let mut scenarios: Vec<Scenario> = scenario_dirs(&data_dir)?
+            .iter()
+            .map(|scenario_dir| load_scenario(scenario_dir, &figure_settings))
+            .collect::<error::Result<_>>()?;
+        for scenario in &scenarios {
+            for theme in &figure_settings.themes {
+                let dir = plot_dir
+                    .join(scenario.dir.file_name().unwrap())
+                    .join(theme::frames_dir_name(&theme.name));
+                fs::create_dir_all(&dir).or_error(&format!(
+                    "Could not create the output directory {}",
+                    dir.display()
+                ))?;
+            }
+        }
+        let failures = create_figures(
+            &figure_settings,
+            &mut scenarios,
+            Vec::new(),
+            &plot_dir,
+            clean_term,
+        );
+        let manifest_path = plot_dir.join("manifest.json");
+        let manifest_err = &*format!("Error writing {}", manifest_path.display());
+        let manifest = serde_json::json!({
+            "plot_of": data_dir,
+            "figures": figure_settings.manifest(),
+        });
+        serde_json::to_string_pretty(&manifest)
+            .map_err(std::io::Error::from)
+            .and_then(|manifest| fs::write(&manifest_path, manifest))
+            .or_error(manifest_err)?;
+        return failures.into_iter().next().map_or(Ok(()), Err);
+    }
+    // Outputs of this run go to their own directory, so files from previous runs and other files stay untouched
+    let run_dir = args
+        .output_dir
+        .clone()
+        .unwrap_or_else(|| run_dir_name(started));
+    create_run_dir(&run_dir, existing)?;
+    let selected = |id: u32| {
+        args.scenario_index.is_none_or(|index| index == id)
+            && shard.is_none_or(|shard| shard.contains(id))
+    };
+    let mut scenarios = vec![Scenario::default(); scenario_count];
+    {
+        let mut scenarios_iter = scenarios.iter_mut();
+        let mut id = 0;
+        #[allow(clippy::explicit_counter_loop)]
+        for (&infection_probability, replicate) in infection_probabilities
+            .iter()
+            .flat_map(|p| std::iter::repeat(p).zip(0..args.replicates))
+        {
+            let scenario: &mut Scenario = scenarios_iter.next().unwrap();
+            scenario.id = id;
+            scenario.infection_probability = infection_probability;
+            scenario.replicate = replicate;
+            scenario.dir = run_dir.join(format!("scenario-{:02}", id));
+            scenario.rng_state = rng_state;
+            // Model parameter: Replicates draw from streams of their own, common random numbers included, except for
+            // antithetic pairs, which share one
+            let stream_replicate = if args.antithetic {
+                replicate / 2
+            } else {
+                replicate
+            };
+            scenario.rng_stream =
+                rng_stream.wrapping_add(stream_replicate as u128 * streams::PROCESSES as u128);
+            scenario.antithetic = args.antithetic && replicate % 2 == 1;
+            // vector_borne_scenarios counts infection probabilities, so that every replicate carries vectors
+            #[cfg(feature = "landscape")]
+            {
+                scenario.vector_borne =
+                    vector_borne_scenarios.contains(&((id / args.replicates) as f64));
+            }
+            #[allow(unused_mut)]
+            let mut dirs = vec![scenario.dir.clone()];
+            #[cfg(feature = "graphics")]
+            dirs.extend(
+                figure_settings
+                    .themes
+                    .iter()
+                    .map(|theme| scenario.dir.join(theme::frames_dir_name(&theme.name))),
+            );
+            // Other shards simulate the other scenarios
+            if selected(id) {
+                for dir in &dirs {
+                    fs::create_dir_all(dir).or_error(&format!(
+                        "Could not create the output directory {}",
+                        dir.display()
+                    ))?;
+                }
+            }
+            id += 1;
+        }
+        assert!(
+            scenarios_iter.next().is_none(),
+            "checking that all scenarios are initialized"
+        )
+    }
+    // Parameter checks leave at least one scenario to simulate
+    scenarios.retain(|scenario| selected(scenario.id));
+    if let Some(shard) = shard {
+        eprintln!(
+            "Shard {}: simulating scenarios {:?}.",
+            shard,
+            scenarios
+                .iter()
+                .map(|scenario| scenario.id)
+                .collect::<Vec<_>>()
+        );
+    }
+    // Model measurements: Columns of the time series
+    #[cfg(any(feature = "csv-output", feature = "arrow-output", feature = "jsonl"))]
+    let ts_columns = {
+        let mut ts_columns = vec![
+            Column::new(
+                "infection_probability",
+                "Infection Probability",
+                ColumnType::Float64,
+            ),
+            Column::new("time_step", "Time step", ColumnType::UInt32),
+        ];
+        ts_columns.extend(collector.reporters().iter().map(|reporter| {
+            Column::new(
+                &*reporter.name,
+                format!("{} {}", reporter.name, reporter.description),
+                ColumnType::Float64,
+            )
+        }));
+        ts_columns
+    };
+    // Output parameter: Stream the time series as JSON Lines to the standard output rather than to a file
+    #[cfg(feature = "jsonl")]
+    let jsonl_to_stdout = false;
+    #[cfg(feature = "jsonl")]
+    let jsonl_path = run_dir.join("ts.jsonl");
+    #[cfg(feature = "jsonl")]
+    let jsonl_name = if jsonl_to_stdout {
+        "the standard output".to_string()
+    } else {
+        jsonl_path.display().to_string()
+    };
+    #[cfg(feature = "jsonl")]
+    let jsonl_err = &*format!("Error writing JSON Lines output to {}", jsonl_name);
+    // Every line goes out as soon as it is complete, so other programs can follow the simulation.
+    #[cfg(feature = "jsonl")]
+    let jsonl_file = {
+        let jsonl_file: Box<dyn IoWrite + Send> = if jsonl_to_stdout {
+            Box::new(std::io::stdout())
+        } else {
+            if existing == Existing::Error && jsonl_path.exists() {
+                return Err(Error::Exists(jsonl_path));
+            }
+            Box::new(fs::File::create(&jsonl_path).or_error(jsonl_err)?)
+        };
+        std::sync::Mutex::new(std::io::LineWriter::new(jsonl_file))
+    };
+    // Model state: World of the warm start, shared by all scenarios
+    #[cfg(feature = "checkpoint")]
+    let warm_start: Option<Checkpoint> = args
+        .warm_start
+        .as_ref()
+        .map(|path| {
+            checkpoint::load(path)
+                .or_error(&format!("Could not read checkpoint {}", path.display()))
+        })
+        .transpose()?;
+    #[cfg(all(feature = "checkpoint", feature = "landscape"))]
+    if let (Some(world), Some(path)) = (&warm_start, &args.warm_start) {
+        if world.cell_health.len() != coord.size() {
+            return Err(Error::Invalid(vec![format!(
+                "--warm-start: the landscape of {} has {} cells, not the {} of this run",
+                path.display(),
+                world.cell_health.len(),
+                coord.size()
+            )]));
+        }
+    }
+    // The GPU device is shared by all scenarios; it is opened only if the landscape runs
+    #[cfg(feature = "gpu")]
+    let gpu = if landscape {
+        Some(gpu::Gpu::new()?)
+    } else {
+        None
+    };
+    // One progress bar per running scenario; the bars stay hidden unless the standard error is a terminal.
+    let progress = MultiProgress::new();
+    // The dashboard follows every scenario of this run, or of this shard
+    #[cfg(feature = "server")]
+    let server = args
+        .serve
+        .map(|address| -> error::Result<Server> {
+            let server = Server::start(
+                address,
+                collector
+                    .reporters()
+                    .iter()
+                    .map(|reporter| format!("{} {}", reporter.name, reporter.description))
+                    .collect(),
+                scenarios
+                    .iter()
+                    .map(|scenario| {
+                        let label =
+                            format!("infection probability {}", scenario.infection_probability);
+                        (scenario.id, label)
+                    })
+                    .collect(),
+                time_series_len,
+            )
+            .or_error(&format!("Could not start the dashboard at {}", address))?;
+            eprintln!("Dashboard at http://{}/", server.address());
+            Ok(server)
+        })
+        .transpose()?;
+    // Telemetry: Subscribers of the broker follow every scenario of this run, or of this shard
+    #[cfg(feature = "mqtt")]
+    let publisher = args.mqtt_broker.as_ref().map(|broker| {
+        Publisher::connect(
+            broker,
+            &args.mqtt_topic,
+            collector
+                .reporters()
+                .iter()
+                .map(|reporter| reporter.name.to_string())
+                .collect(),
+        )
+    });
+    // Under the grpc subcommand, the server learns the metrics and the scenarios of the run first
+    #[cfg(feature = "grpc")]
+    if args.managed {
+        let metrics: Vec<&str> = collector
+            .reporters()
+            .iter()
+            .map(|reporter| &*reporter.name)
+            .collect();
+        let ids: Vec<u32> = scenarios.iter().map(|scenario| scenario.id).collect();
+        println!(
+            "{}",
+            serde_json::json!({"metrics": metrics, "time_steps": time_series_len, "scenarios": ids})
+        );
+    }
+    let progress_style = ProgressStyle::with_template(
+        "{prefix} [{bar:30}] {pos}/{len} time steps, {steps_per_sec}, ETA {eta}",
+    )
+    .unwrap()
+    .with_key(
+        "steps_per_sec",
+        |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| {
+            write!(w, "{:.0} steps/s", state.per_sec()).unwrap()
+        },
+    )
+    .progress_chars("=> ");
+    // Simulates a scenario from time step 0, or from a checkpoint, until time step stop_at
+    let simulate = |scenario: &mut Scenario,
+                    #[cfg(feature = "checkpoint")] resume_from: Option<PathBuf>,
+                    stop_at: usize|
+     -> error::Result<()> {
+        let scenario_clock = std::time::Instant::now();
+        // begin-similar-code 2
+        // Use Pcg64 for reproducible random numbers; see the streams module for common random numbers
+        let mut rng = RandomStreams::new(
+            scenario.rng_state,
+            scenario.rng_stream,
+            args.common_random_numbers,
+        )
+        .with_antithetic(scenario.antithetic);
+        // Model hooks: Attach observers of this scenario here; see the hooks module
+        #[allow(unused_mut)]
+        let mut hooks = Hooks::new();
+        // Model events: Dynamics publish events here; systems consume them at the end of each time step
+        let mut events = EventBus::new();
+        // Model state: Agent health, now and in the next time step. This implements parallel updating of agents.
+        let mut health: Buffered<Agents<Health>> = Buffered::new(Storage::with_capacity(2 * n0));
+        // Model state: Bidirectional links between agents
+        #[cfg(feature = "net")]
+        let mut links: Links = Storage::with_capacity(n0 * n0);
+        // Model state: Health status of each cell in the landscape, now and in the next time step.
+        // This implements parallel updating of cells.
+        #[cfg(feature = "landscape")]
+        let mut cell_health = Buffered::new(vec![Health::S; coord.size()]);
+        // Model state: Energy of each agent and resource of each cell, if agents forage
+        #[cfg(feature = "landscape")]
+        let mut energy = metabolism.as_ref().map(|_| AgentEnergy::default());
+        #[cfg(feature = "landscape")]
+        let mut resources: Option<CellResources> =
+            metabolism.map(|metabolism| metabolism.resources(coord.size()));
+        // Model state: Time step since which each cell is contaminated, if contamination persists
+        #[cfg(all(feature = "landscape", not(feature = "gpu")))]
+        let mut cell_contamination =
+            cell_persistence.map(|_| CellContamination::with_size(coord.size()));
+        #[cfg(feature = "landscape-graphics")]
+        let frames_path = landscape_frames_path(&scenario.dir);
+        #[cfg(feature = "landscape-graphics")]
+        let frames_err = &*format!(
+            "Error writing landscape frames file {}",
+            frames_path.display()
+        );
+        #[cfg(feature = "landscape-graphics")]
+        let mut frames_file = create_output_file(&frames_path, frames_err, existing)?;
+        // Model initialization: Agents
+        while health.write().len() < n0 {
+            let _k: AgentKey = health.write().insert(Health::S);
+        }
+        health.swap();
+        let infection_distro = Bernoulli::new(scenario.infection_probability).unwrap();
+        // end-similar-code 2
+        // Model state: Parameters of each agent, if they differ between agents
+        let mut traits = keep_traits.then(AgentTraits::default);
+        // Model state: Time step of birth of each agent, if the model keeps ages
+        let mut ages = keep_ages.then(AgentDemography::default);
+        // Model state: Whether each agent is aware of the disease, if awareness spreads
+        #[cfg(feature = "net")]
+        let mut aware = awareness.as_ref().map(|_| AgentAwareness::default());
+        // Model state: Vectors, in vector-borne scenarios
+        #[cfg(feature = "landscape")]
+        let mut vectors: Option<Vec<Vector>> = None;
+        // Model state: Group of each agent and quarantines of the groups, if agents form groups
+        let mut groups = group_rules.as_ref().map(GroupRules::create);
+        // Model state: Cell around which each agent visits the landscape, if agents have homes
+        #[cfg(feature = "landscape")]
+        let mut homes = keep_homes.then(AgentHomes::default);
+        // Model state: Layer of each link
+        #[cfg(feature = "net")]
+        let mut link_layers = LinkLayers::default();
+        // Model state: Whether each agent is vaccinated, if a vaccination campaign runs
+        let mut vaccinated = vaccination.as_ref().map(|_| AgentVaccination::default());
+        // Model state: Who infected whom, for the effective reproduction number
+        let mut transmissions = Transmissions::default();
+        // Model state: Infections of each agent so far, if infections leave immunity
+        let mut history = immunity.as_ref().map(|_| InfectionHistory::default());
+        // Model initialization: Agents of the initial population take their health, ages, groups, homes, and
+        // parameters, and the members of each household link to each other.
+        if let Some(population) = &population {
+            let keys: Vec<AgentKey> = health.read().keys().collect();
+            for (&k, person) in keys.iter().zip(&population.persons) {
+                if person.infected {
+                    health.write()[k] = Health::I;
+                    events.infections.publish(InfectionEvent {
+                        source: None,
+                        target: k,
+                        via: Via::Seed,
+                    });
+                }
+                if let (Some(ages), Some(age)) = (&mut ages, person.age) {
+                    ages.set_born(k, -(age.max(0.0).round() as i64));
+                }
+                if let (Some(groups), Some(workplace)) = (&mut groups, person.workplace) {
+                    groups.membership.set_group(k, workplace);
+                }
+                #[cfg(feature = "landscape")]
+                if let (Some(homes), Some(home)) = (&mut homes, person.home) {
+                    homes.set_home(k, home);
+                }
+                // Parameters missing from the population are drawn as usual
+                if let Some(traits) = &mut traits {
+                    if person.traits.iter().any(Option::is_some) {
+                        let mut values = heterogeneity.draw(traits, k, rng.get(Process::Births));
+                        let [recovery_probability, susceptibility, movement_scale, sociability] =
+                            person.traits;
+                        if let Some(value) = recovery_probability {
+                            traits.set_recovery_probability(k, value);
+                        }
+                        if let Some(value) = susceptibility {
+                            traits.set_susceptibility(k, value);
+                        }
+                        if let Some(value) = movement_scale {
+                            traits.set_movement_scale(k, value);
+                        }
+                        if let Some(value) = sociability {
+                            traits.set_sociability(k, value);
+                        }
+                        for (value, given) in values.iter_mut().zip(person.traits) {
+                            *value = given.unwrap_or(*value);
+                        }
+                        for (stats, value) in scenario.agent_traits.iter_mut().zip(values) {
+                            stats.push(value);
+                        }
+                    }
+                }
+            }
+            health.swap();
+            #[cfg(feature = "net")]
+            if network {
+                let mut households = std::collections::BTreeMap::<u32, Vec<AgentKey>>::new();
+                for (&k, person) in keys.iter().zip(&population.persons) {
+                    if let Some(household) = person.household {
+                        let members = households.entry(household).or_default();
+                        for &member in members.iter() {
+                            let agents = (member, k);
+                            let link = links.insert(agents);
+                            link_layers.set_layer(link, Layer::Household);
+                            events.links.publish(LinkEvent { link, agents });
+                        }
+                        members.push(k);
+                    }
+                }
+            }
+        }
+        // Model initialization: The world of the warm start replaces the default initialization
+        #[cfg(feature = "checkpoint")]
+        if let Some(world) = &warm_start {
+            health = Buffered::new(world.health.clone());
+            #[cfg(feature = "net")]
+            if network {
+                links = world.links.clone();
+                link_layers = world.link_layers.clone();
+            }
+            #[cfg(feature = "landscape")]
+            {
+                // The size of the landscape was checked with the checkpoint
+                cell_health = Buffered::new(world.cell_health.clone());
+            }
+            if keep_traits && world.traits.is_some() {
+                traits = world.traits.clone();
+            }
+            if keep_ages && world.ages.is_some() {
+                ages = world.ages.clone();
+            }
+            #[cfg(feature = "net")]
+            if awareness.is_some() && world.aware.is_some() {
+                aware = world.aware.clone();
+            }
+            #[cfg(feature = "landscape")]
+            if scenario.vector_borne {
+                vectors = world.vectors.clone();
+            }
+            if group_rules.is_some() && world.groups.is_some() {
+                groups = world.groups.clone();
+            }
+            // Agents of the world keep their homes
+            #[cfg(feature = "landscape")]
+            if world.homes.is_some() {
+                homes = world.homes.clone();
+            }
+        }
+        // Model state: Continue from a checkpoint: this scenario in an interrupted run, or the baseline of branches
+        #[cfg(feature = "checkpoint")]
+        let first_time_step = match resume_from {
+            Some(checkpoint_path) => {
+                let checkpoint: Checkpoint = checkpoint::load(&checkpoint_path).or_error(
+                    &format!("Could not read checkpoint {}", checkpoint_path.display()),
+                )?;
+                if args.resume.is_some()
+                    && (checkpoint.scenario.id != scenario.id
+                        || checkpoint.scenario.infection_probability
+                            != scenario.infection_probability)
+                {
+                    return Err(Error::Invalid(vec![format!(
+                        "--resume: checkpoint {} belongs to scenario {}, with infection probability {}, not to this \
+                         one; please check the parameters",
+                        checkpoint_path.display(),
+                        checkpoint.scenario.id,
+                        checkpoint.scenario.infection_probability
+                    )]));
+                }
+                #[cfg(feature = "landscape")]
+                if checkpoint.cell_health.len() != coord.size() {
+                    return Err(Error::Invalid(vec![format!(
+                        "--resume: the landscape of {} has {} cells, not the {} of this run",
+                        checkpoint_path.display(),
+                        checkpoint.cell_health.len(),
+                        coord.size()
+                    )]));
+                }
+                rng = checkpoint.rng;
+                health = Buffered::new(checkpoint.health);
+                #[cfg(feature = "net")]
+                {
+                    links = checkpoint.links;
+                    link_layers = checkpoint.link_layers;
+                }
+                #[cfg(feature = "landscape")]
+                {
+                    cell_health = Buffered::new(checkpoint.cell_health);
+                    #[cfg(not(feature = "gpu"))]
+                    if cell_persistence.is_some() && checkpoint.cell_contamination.is_some() {
+                        cell_contamination = checkpoint.cell_contamination;
+                    }
+                }
+                if keep_traits && checkpoint.traits.is_some() {
+                    traits = checkpoint.traits;
+                }
+                if keep_ages && checkpoint.ages.is_some() {
+                    ages = checkpoint.ages;
+                }
+                #[cfg(feature = "net")]
+                if awareness.is_some() && checkpoint.aware.is_some() {
+                    aware = checkpoint.aware;
+                }
+                #[cfg(feature = "landscape")]
+                if scenario.vector_borne {
+                    vectors = checkpoint.vectors;
+                }
+                if group_rules.is_some() && checkpoint.groups.is_some() {
+                    groups = checkpoint.groups;
+                }
+                #[cfg(feature = "landscape")]
+                if keep_homes && checkpoint.homes.is_some() {
+                    homes = checkpoint.homes;
+                }
+                #[cfg(feature = "landscape")]
+                if metabolism.is_some() && checkpoint.energy.is_some() {
+                    energy = checkpoint.energy;
+                    resources = checkpoint.resources;
+                }
+                if vaccination.is_some() && checkpoint.vaccinated.is_some() {
+                    vaccinated = checkpoint.vaccinated;
+                }
+                transmissions = checkpoint.transmissions;
+                if immunity.is_some() && checkpoint.history.is_some() {
+                    history = checkpoint.history;
+                }
+                // Results so far come from the checkpoint; branches keep their own parameters.
+                let id = scenario.id;
+                let infection_probability = scenario.infection_probability;
+                #[cfg(feature = "landscape")]
+                let vector_borne = scenario.vector_borne;
+                let dir = std::mem::take(&mut scenario.dir);
+                *scenario = checkpoint.scenario;
+                scenario.id = id;
+                scenario.infection_probability = infection_probability;
+                #[cfg(feature = "landscape")]
+                {
+                    scenario.vector_borne = vector_borne;
+                }
+                scenario.dir = dir;
+                // Figures need the landscape frames drawn before the checkpoint
+                #[cfg(feature = "landscape-graphics")]
+                if landscape {
+                    let frames_len = checkpoint.time_step.div_ceil(render_every);
+                    let mut earlier_frames =
+                        fs::File::open(landscape_frames_path(checkpoint_path.parent().unwrap()))
+                            .or_error(frames_err)?;
+                    let copied = std::io::copy(
+                        &mut (&mut earlier_frames).take((frames_len * coord.size()) as u64),
+                        &mut frames_file,
+                    )
+                    .or_error(frames_err)?;
+                    if copied != (frames_len * coord.size()) as u64 {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "the earlier run lost landscape frames before its checkpoint",
+                        ))
+                        .or_error(frames_err);
+                    }
+                }
+                checkpoint.time_step
+            }
+            None => 0,
+        };
+        #[cfg(not(feature = "checkpoint"))]
+        let first_time_step = 0;
+        // Model initialization: Agents draw their parameters; those of a checkpoint keep theirs
+        if let Some(traits) = &mut traits {
+            for (k, _h) in health.read().iter() {
+                if traits.recovery_probability(k).is_none() {
+                    let values = heterogeneity.draw(traits, k, rng.get(Process::Births));
+                    for (stats, value) in scenario.agent_traits.iter_mut().zip(values) {
+                        stats.push(value);
+                    }
+                }
+            }
+        }
+        // Model initialization: Agents of the initial population take their ages, such as those of an established
+        // population; agents of a checkpoint keep theirs.
+        if let Some(ages) = &mut ages {
+            for (k, _h) in health.read().iter() {
+                if ages.born(k).is_none() {
+                    let age = demography.initial_age.as_ref().map_or(0.0, |initial_age| {
+                        initial_age.sample(rng.get(Process::Births))
+                    });
+                    ages.set_born(k, first_time_step as i64 - age.max(0.0).round() as i64);
+                }
+            }
+        }
+        // Model initialization: Agents of the initial population are aware with the probability of initial_awareness;
+        // agents of a checkpoint keep their awareness.
+        #[cfg(feature = "net")]
+        if let (Some(awareness), Some(aware)) = (&awareness, &mut aware) {
+            for (k, _h) in health.read().iter() {
+                if aware.aware(k).is_none() {
+                    let is_aware = rng
+                        .get(Process::Awareness)
+                        .gen_bool(awareness.initial_probability);
+                    aware.set_aware(k, is_aware);
+                }
+            }
+        }
+        #[cfg(feature = "net")]
+        let mut aware = aware.map(Buffered::new);
+        // Model initialization: Agents of the initial population join random groups, unless workplaces are the groups;
+        // agents of a checkpoint keep theirs.
+        if let (Some(group_rules), Some(groups), None) = (&group_rules, &mut groups, workplaces) {
+            for (k, _h) in health.read().iter() {
+                if groups.membership.group(k).is_none() {
+                    group_rules.join(groups, k, rng.get(Process::Groups));
+                }
+            }
+        }
+        // Model initialization: Vectors of vector-borne scenarios start on random cells; those of a checkpoint stay
+        #[cfg(feature = "landscape")]
+        if scenario.vector_borne && vectors.is_none() {
+            vectors = Some(vector_population.populate(coord.size(), rng.get(Process::Vectors)));
+        }
+        let progress_bar = progress.add(
+            ProgressBar::new(stop_at as u64)
+                .with_style(progress_style.clone())
+                .with_prefix(format!(
+                    "{} (infection probability {})",
+                    scenario.dir.file_name().unwrap().to_string_lossy(),
+                    scenario.infection_probability
+                ))
+                .with_position(first_time_step as u64),
+        );
+        #[cfg(feature = "live-view")]
+        let mut live_view = (args.live_view == Some(scenario.id)).then(|| {
+            LiveView::new(
+                scenario.id,
+                collector
+                    .reporters()
+                    .iter()
+                    .map(|reporter| format!("{} {}", reporter.name, reporter.description))
+                    .collect(),
+                &theme::Theme::builtin("dark").unwrap(),
+            )
+        });
+        // Model measurements: The time series goes to files in the directory of the scenario, written in chunks
+        #[cfg(feature = "csv-output")]
+        let ts_path = scenario.dir.join("ts.csv");
+        #[cfg(feature = "csv-output")]
+        let ts_err = &*format!(
+            "Error writing time series output file {}",
+            ts_path.display()
+        );
+        #[cfg(feature = "csv-output")]
+        let mut ts_file = {
+            let mut ts_file = create_output_file(&ts_path, ts_err, existing)?;
+            tables::write_csv_header(&mut ts_file, &ts_columns).or_error(ts_err)?;
+            ts_file
+        };
+        #[cfg(feature = "arrow-output")]
+        let mut ts_tables = Tables::create(&scenario.dir.join("ts"), &ts_columns, existing)?;
+        // Model measurements: Network degree histograms go to a file per scenario, for the plot subcommand
+        #[cfg(all(feature = "net-graphics", feature = "csv-output"))]
+        let degrees_path = scenario.dir.join("degrees.csv");
+        #[cfg(all(feature = "net-graphics", feature = "csv-output"))]
+        let degrees_err = &*format!("Error writing degrees file {}", degrees_path.display());
+        #[cfg(all(feature = "net-graphics", feature = "csv-output"))]
+        let mut degrees_file = {
+            let mut degrees_file = create_output_file(&degrees_path, degrees_err, existing)?;
+            writeln!(&mut degrees_file, "Time step,Degree,Agents").or_error(degrees_err)?;
+            degrees_file
+        };
+        // Model measurements: Trajectories of agents go to a file per scenario, written in chunks
+        #[cfg(feature = "trajectories")]
+        let trajectories_path = scenario.dir.join("trajectories.csv");
+        #[cfg(feature = "trajectories")]
+        let trajectories_err = &*format!(
+            "Error writing trajectories file {}",
+            trajectories_path.display()
+        );
+        #[cfg(feature = "trajectories")]
+        let mut trajectories_file = {
+            let mut trajectories_file =
+                create_output_file(&trajectories_path, trajectories_err, existing)?;
+            tables::write_csv_header(
+                &mut trajectories_file,
+                &trajectories_columns(
+                    #[cfg(feature = "landscape")]
+                    landscape,
+                    viral_load.is_some(),
+                ),
+            )
+            .or_error(trajectories_err)?;
+            trajectories_file
+        };
+        #[cfg(all(feature = "trajectories", feature = "arrow-output"))]
+        let mut trajectories_tables = Tables::create(
+            &scenario.dir.join("trajectories"),
+            &trajectories_columns(
+                #[cfg(feature = "landscape")]
+                landscape,
+                viral_load.is_some(),
+            ),
+            existing,
+        )?;
+        // Model measurements: Parents and children go to a file per scenario, written in chunks
+        #[cfg(feature = "genealogy")]
+        let genealogy_path = scenario.dir.join("genealogy.csv");
+        #[cfg(feature = "genealogy")]
+        let genealogy_err = &*format!("Error writing genealogy file {}", genealogy_path.display());
+        #[cfg(feature = "genealogy")]
+        let mut genealogy_file = {
+            let mut genealogy_file = create_output_file(&genealogy_path, genealogy_err, existing)?;
+            tables::write_csv_header(&mut genealogy_file, &genealogy_columns())
+                .or_error(genealogy_err)?;
+            genealogy_file
+        };
+        // Model measurements: Snapshots of the network go to a compressed file per scenario
+        #[cfg(feature = "network-snapshots")]
+        let network_path = scenario.dir.join("network.csv.gz");
+        #[cfg(feature = "network-snapshots")]
+        let network_err = &*format!("Error writing network file {}", network_path.display());
+        #[cfg(feature = "network-snapshots")]
+        let mut network_file = if network && args.network_every > 0 {
+            let mut network_file = flate2::write::GzEncoder::new(
+                create_output_file(&network_path, network_err, existing)?,
+                flate2::Compression::default(),
+            );
+            tables::write_csv_header(&mut network_file, &network_columns())
+                .or_error(network_err)?;
+            Some(network_file)
+        } else {
+            None
+        };
+        // Model measurements: The group of each agent goes to a file per scenario, written in chunks
+        #[cfg(feature = "csv-output")]
+        let groups_path = scenario.dir.join("groups.csv");
+        #[cfg(feature = "csv-output")]
+        let groups_err = &*format!("Error writing groups file {}", groups_path.display());
+        #[cfg(feature = "csv-output")]
+        let mut groups_file = match &groups {
+            Some(groups) => {
+                let mut groups_file = create_output_file(&groups_path, groups_err, existing)?;
+                tables::write_csv_header(&mut groups_file, &groups_columns())
+                    .or_error(groups_err)?;
+                for (k, &group) in groups.membership.group.iter() {
+                    let row = [
+                        Value::Float64(scenario.infection_probability),
+                        Value::UInt32(first_time_step as u32),
+                        Value::UInt64(k.data().as_ffi()),
+                        Value::UInt32(group),
+                    ];
+                    tables::write_csv_row(&mut groups_file, &row).or_error(groups_err)?;
+                }
+                Some(groups_file)
+            }
+            None => None,
+        };
+        // Model measurements: Health of the agents by each attribute of --stratify goes to a file per scenario
+        #[cfg(feature = "csv-output")]
+        let strata_path = scenario.dir.join("strata.csv");
+        #[cfg(feature = "csv-output")]
+        let strata_err = &*format!("Error writing strata file {}", strata_path.display());
+        #[cfg(feature = "csv-output")]
+        let mut strata_file = if args.stratify.is_empty() {
+            None
+        } else {
+            let mut strata_file = create_output_file(&strata_path, strata_err, existing)?;
+            tables::write_csv_header(&mut strata_file, &strata_columns()).or_error(strata_err)?;
+            Some(strata_file)
+        };
+        // Model measurements: Cell states go to a file per scenario, written in chunks
+        #[cfg(feature = "cells-csv")]
+        let cells_path = scenario.dir.join("cells.csv");
+        #[cfg(feature = "cells-csv")]
+        let cells_err = &*format!("Error writing cells file {}", cells_path.display());
+        #[cfg(feature = "cells-csv")]
+        let mut cells_file = {
+            let mut cells_file = create_output_file(&cells_path, cells_err, existing)?;
+            tables::write_csv_header(&mut cells_file, &cells_columns()).or_error(cells_err)?;
+            cells_file
+        };
+        #[cfg(all(feature = "cells-csv", feature = "arrow-output"))]
+        let mut cells_tables =
+            Tables::create(&scenario.dir.join("cells"), &cells_columns(), existing)?;
+        // Model measurements: The landscape over time goes to a NetCDF file per scenario
+        #[cfg(feature = "netcdf-output")]
+        let netcdf_path = scenario.dir.join("landscape.nc");
+        #[cfg(feature = "netcdf-output")]
+        let netcdf_err = &*format!("Error writing NetCDF file {}", netcdf_path.display());
+        #[cfg(feature = "netcdf-output")]
+        let mut netcdf_file = LandscapeWriter::create(
+            create_output_file(&netcdf_path, netcdf_err, existing)?,
+            coord.width() as usize,
+            coord.height() as usize,
+            "Health of cells in rust-agent-based-models",
+            &[("infection_probability", scenario.infection_probability)],
+        )
+        .or_error(netcdf_err)?;
+        // Model state: Cell dynamics
+        #[cfg(all(feature = "landscape", not(feature = "gpu")))]
+        let mut cell_dynamics = landscape::CellDynamics::new(
+            &coord,
+            scenario.infection_probability,
+            recovery_distro,
+            within_scenarios,
+        )
+        .with_decay(decay);
+        // Model state: Cell health on the GPU
+        #[cfg(feature = "gpu")]
+        let mut gpu_landscape = gpu.as_ref().map(|gpu| {
+            gpu::Landscape::new(
+                gpu,
+                coord.width() as u32,
+                coord.height() as u32,
+                cell_health.read(),
+                scenario.infection_probability,
+                recovery_probability,
+            )
+        });
+        #[cfg(not(feature = "streaming"))]
+        scenario.time_series.reserve_exact(samples_len);
+        #[cfg(feature = "rng-trace")]
+        if args.trace_rng {
+            rng.trace(&scenario.dir.join("rng.trace.gz"))
+                .or_error("Error creating the trace of random numbers")?;
+        }
+        #[cfg(all(debug_assertions, feature = "checkpoint"))]
+        let mut snapshots =
+            checkpoint::Snapshots::new(scenario.dir.join("snapshots"), snapshots_len);
+        let mut timer = PhaseTimer::new();
+        for time_step in first_time_step..stop_at {
+            #[cfg(feature = "rng-trace")]
+            rng.set_time_step(time_step);
+            hooks.step(time_step);
+            timer.lap(Phase::Bookkeeping);
+            // begin-similar-code 3
+            // Initialization of this time step: Network seed
+            #[cfg(feature = "net")]
+            let (keys_vec, weights_vec) = {
+                if network && links.is_empty() && health.read().len() > 1 {
+                    let mut h_it = health.read().iter();
+                    let (key0, _value) = h_it.next().unwrap();
+                    let (key1, _value) = h_it.next().unwrap();
+                    let link_id: LinkKey = links.insert((key0, key1));
+                    events.links.publish(LinkEvent {
+                        link: link_id,
+                        agents: (key0, key1),
+                    });
+                }
+                // Initialization of this time step: Network
+                let keys_vec: Vec<AgentKey> = health.read().keys().collect();
+                let weights_vec = if network {
+                    network::form_links_with(
+                        &keys_vec,
+                        &mut links,
+                        net_k,
+                        |rng: &mut Stream, k| match &traits {
+                            Some(traits) => {
+                                rng.gen_bool((link_probability * traits.sociability[k]).min(1.0))
+                            }
+                            None => link_distro.sample(rng),
+                        },
+                        rng.get(Process::Network),
+                        |link, agents| events.links.publish(LinkEvent { link, agents }),
+                    )
+                } else {
+                    vec![0; keys_vec.len()]
+                };
+                (keys_vec, weights_vec)
+            };
+            // Model state: Network degree of each agent
+            #[cfg(feature = "net")]
+            let degree: SecondaryMap<AgentKey, i32> = keys_vec
+                .iter()
+                .copied()
+                .zip(weights_vec.iter().copied())
+                .collect();
+            timer.lap(Phase::Network);
+            let sampled = collector.samples(time_step);
+            // Model measurements: State of every cell
+            #[cfg(any(feature = "cells-csv", feature = "netcdf-output"))]
+            if landscape && time_step.is_multiple_of(cells_every) {
+                #[cfg(feature = "cells-csv")]
+                {
+                    let width = coord.width() as usize;
+                    for (idx, &h) in cell_health.read().iter().enumerate() {
+                        let row = [
+                            Value::Float64(scenario.infection_probability),
+                            Value::UInt32(time_step as u32),
+                            Value::UInt32(idx as u32),
+                            Value::UInt32((idx % width) as u32),
+                            Value::UInt32((idx / width) as u32),
+                            Value::Utf8(health_label(h)),
+                        ];
+                        tables::write_csv_row(&mut cells_file, &row).or_error(cells_err)?;
+                        #[cfg(feature = "arrow-output")]
+                        cells_tables.push(&row)?;
+                    }
+                }
+                #[cfg(feature = "netcdf-output")]
+                netcdf_file
+                    .push(time_step as u32, cell_health.read())
+                    .or_error(netcdf_err)?;
+            }
+            // Model measurements: Every link, with the health of the agents at both ends
+            #[cfg(feature = "network-snapshots")]
+            if let Some(network_file) = &mut network_file {
+                if time_step.is_multiple_of(args.network_every) {
+                    let current_health = health.read();
+                    for &(key0, key1) in links.values() {
+                        let row = [
+                            Value::Float64(scenario.infection_probability),
+                            Value::UInt32(time_step as u32),
+                            Value::UInt64(key0.data().as_ffi()),
+                            Value::UInt64(key1.data().as_ffi()),
+                            Value::Utf8(health_label(current_health[key0])),
+                            Value::Utf8(health_label(current_health[key1])),
+                        ];
+                        tables::write_csv_row(network_file, &row).or_error(network_err)?;
+                    }
+                }
+            }
+            timer.lap(Phase::Output);
+            // Model measurements: The world at the start of this time step, for reporters and interventions
+            let world = World {
+                time_step,
+                health: health.read(),
+                #[cfg(feature = "net")]
+                links: &links,
+                #[cfg(feature = "net")]
+                degree: &degree,
+                #[cfg(feature = "landscape")]
+                cell_health: cell_health.read(),
+                traits: traits.as_ref(),
+                ages: ages.as_ref(),
+                #[cfg(feature = "net")]
+                aware: aware.as_ref().map(Buffered::read),
+                #[cfg(feature = "landscape")]
+                vectors: vectors.as_deref(),
+                groups: groups.as_ref(),
+                #[cfg(feature = "landscape")]
+                homes: homes.as_ref(),
+                #[cfg(feature = "landscape")]
+                energy: energy.as_ref(),
+                #[cfg(feature = "landscape")]
+                resources: resources.as_ref(),
+                vaccinated: vaccinated.as_ref(),
+                transmissions: &transmissions,
+                history: history.as_ref(),
+                infections: scenario.infections,
+                immigrants: scenario.immigrants,
+                emigrants: scenario.emigrants,
+                events: &events,
+            };
+            if sampled {
+                #[allow(unused_mut)]
+                let mut time_step_results = TimeStepResults {
+                    time_step: time_step as u32,
+                    values: collector.collect(&world),
+                    #[cfg(feature = "net-graphics")]
+                    degree_histogram: BTreeMap::new(),
+                };
+                #[cfg(feature = "net-graphics")]
+                for &weight in &weights_vec {
+                    *time_step_results
+                        .degree_histogram
+                        .entry(weight)
+                        .or_insert(0) += 1;
+                }
+                #[cfg(all(feature = "net-graphics", feature = "csv-output"))]
+                for (&degree, &agents) in &time_step_results.degree_histogram {
+                    writeln!(&mut degrees_file, "{},{},{}", time_step, degree, agents)
+                        .or_error(degrees_err)?;
+                }
+                #[cfg(feature = "graphics")]
+                scenario.record_extremes(&time_step_results, &figure_settings);
+                timer.lap(Phase::Metrics);
+                #[cfg(feature = "landscape-graphics")]
+                if landscape && time_step.is_multiple_of(render_every) {
+                    let frame: Vec<u8> = cell_health.read().iter().map(|&h| h as u8).collect();
+                    frames_file.write_all(&frame).or_error(frames_err)?;
+                }
+                #[cfg(feature = "jsonl")]
+                tables::write_json_line(
+                    &mut *jsonl_file.lock().unwrap(),
+                    &ts_columns,
+                    &time_step_results.row(scenario.infection_probability),
+                )
+                .or_error(jsonl_err)?;
+                // Model measurements: write to disk as the simulation runs
+                #[cfg(any(feature = "csv-output", feature = "arrow-output"))]
+                {
+                    let row = time_step_results.row(scenario.infection_probability);
+                    #[cfg(feature = "csv-output")]
+                    tables::write_csv_row(&mut ts_file, &row).or_error(ts_err)?;
+                    #[cfg(feature = "arrow-output")]
+                    ts_tables.push(&row)?;
+                }
+                #[cfg(feature = "csv-output")]
+                if let Some(strata_file) = &mut strata_file {
+                    let agents = || health.read().iter();
+                    for &attribute in &args.stratify {
+                        // Strata in increasing order, agents without the attribute first
+                        let counts: Vec<(Option<String>, strata::HealthCounts)> = match attribute {
+                            Attribute::Age => strata::cross_tabulate(agents(), |k| {
+                                let age = time_step as i64 - ages.as_ref()?.born(k)?;
+                                strata::band(&age_groups, age as f64)
+                            })
+                            .into_iter()
+                            .map(|(band, counts)| {
+                                (
+                                    band.map(|band| strata::band_label(&age_groups, band)),
+                                    counts,
+                                )
+                            })
+                            .collect(),
+                            Attribute::Group => strata::cross_tabulate(agents(), |k| {
+                                groups.as_ref()?.membership.group(k).copied()
+                            })
+                            .into_iter()
+                            .map(|(group, counts)| (group.map(|group| group.to_string()), counts))
+                            .collect(),
+                            Attribute::GroupSize => {
+                                let census =
+                                    groups.as_ref().map(|groups| groups.census(health.read()));
+                                strata::cross_tabulate(agents(), |k| {
+                                    let &group = groups.as_ref()?.membership.group(k)?;
+                                    Some(census.as_ref()?[group as usize].members)
+                                })
+                                .into_iter()
+                                .map(|(size, counts)| (size.map(|size| size.to_string()), counts))
+                                .collect()
+                            }
+                        };
+                        for (stratum, counts) in &counts {
+                            for (&h, &agents) in [Health::S, Health::I].iter().zip(counts) {
+                                let row = [
+                                    Value::Float64(scenario.infection_probability),
+                                    Value::UInt32(time_step as u32),
+                                    Value::Utf8(attribute.name()),
+                                    Value::Utf8(stratum.as_deref().unwrap_or("unknown")),
+                                    Value::Utf8(health_label(h)),
+                                    Value::UInt32(agents),
+                                ];
+                                tables::write_csv_row(strata_file, &row).or_error(strata_err)?;
+                            }
+                        }
+                    }
+                }
+                // Live view: draw this time step, then wait as the keys say
+                #[cfg(feature = "live-view")]
+                if let Some(view) = &mut live_view {
+                    #[cfg(feature = "landscape")]
+                    let cells =
+                        landscape.then(|| (cell_health.read().as_slice(), coord.width() as usize));
+                    #[cfg(not(feature = "landscape"))]
+                    let cells = None;
+                    view.show(time_step, cells, &time_step_results.values);
+                }
+                #[cfg(feature = "server")]
+                if let Some(server) = &server {
+                    server.update(scenario.id, time_step, &time_step_results.values);
+                }
+                #[cfg(feature = "mqtt")]
+                if let Some(publisher) = &publisher {
+                    publisher.publish(scenario.id, time_step, &time_step_results.values);
+                }
+                #[cfg(feature = "grpc")]
+                if args.managed {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "scenario": scenario.id,
+                            "time_step": time_step,
+                            "values": time_step_results.values,
+                        })
+                    );
+                }
+                // Model measurements: summarize, or keep for figures
+                #[cfg(feature = "streaming")]
+                scenario.summary.push(&time_step_results);
+                #[cfg(not(feature = "streaming"))]
+                scenario.time_series.push(time_step_results);
+                timer.lap(Phase::Output);
+            }
+            // Dynamics: A round of the vaccination campaign vaccinates the agents that its rule chooses
+            let targets = match &vaccination {
+                Some(vaccination) if vaccination.due(time_step) => {
+                    vaccination.targets(&world, rng.get(Process::Interventions))
+                }
+                _ => Vec::new(),
+            };
+            if let Some(vaccinated) = &mut vaccinated {
+                for k in targets {
+                    vaccinated.set_vaccinated(k, true);
+                }
+            }
+            // Dynamics: Groups whose fraction of infected members reaches the threshold go into quarantine
+            if let (Some(group_rules), Some(groups)) = (&group_rules, &mut groups) {
+                group_rules.quarantine(groups, health.read(), time_step);
+            }
+            // Dynamics: infection spreads
+            {
+                let (current_health, next_health) = health.read_write();
+                // Whether the group of an agent is in quarantine at this time step
+                #[cfg_attr(
+                    not(any(feature = "landscape", feature = "net")),
+                    allow(unused_variables)
+                )]
+                let quarantined = |k: AgentKey| {
+                    groups
+                        .as_ref()
+                        .is_some_and(|groups| groups.quarantined(k, time_step))
+                };
+                #[cfg(feature = "landscape")]
+                let (current_cell_health, next_cell_health) = cell_health.read_write();
+                #[cfg(feature = "net")]
+                let (current_aware, mut next_aware) = match &mut aware {
+                    Some(aware) => {
+                        let (current_aware, next_aware) = aware.read_write();
+                        (Some(current_aware), Some(next_aware))
+                    }
+                    None => (None, None),
+                };
+                // Awareness of the disease multiplies the probability of infection
+                #[cfg(feature = "net")]
+                let aware_susceptibility = |k: AgentKey| match (&awareness, current_aware) {
+                    (Some(awareness), Some(current_aware)) => {
+                        awareness.susceptibility(current_aware, k)
+                    }
+                    _ => 1.0,
+                };
+                #[cfg(not(feature = "net"))]
+                let aware_susceptibility = |_k: AgentKey| 1.0;
+                // So does the vaccine, for vaccinated agents
+                let vaccine_susceptibility = |k: AgentKey| match (&vaccination, &vaccinated) {
+                    (Some(vaccination), Some(vaccinated)) => {
+                        vaccination.susceptibility(vaccinated, k)
+                    }
+                    _ => 1.0,
+                };
+                // And so does the immunity left by earlier infections
+                let immunity_susceptibility = |k: AgentKey| match (&immunity, &history) {
+                    (Some(immunity), Some(history)) => {
+                        immunity.susceptibility(history, k, time_step)
+                    }
+                    _ => 1.0,
+                };
+                // Susceptibility of an agent, which multiplies its probability of infection
+                let susceptibility = |k: AgentKey| {
+                    aware_susceptibility(k)
+                        * vaccine_susceptibility(k)
+                        * immunity_susceptibility(k)
+                        * traits
+                            .as_ref()
+                            .map_or(1.0, |traits| traits.susceptibility[k])
+                };
+                // Infectiousness of an infected agent, which multiplies the probability that it infects others
+                #[cfg_attr(
+                    not(any(feature = "landscape", feature = "net")),
+                    allow(unused_variables)
+                )]
+                let infectiousness = |k: AgentKey| {
+                    viral_load.map_or(1.0, |viral_load| {
+                        let infected_at = transmissions.infected_at(k);
+                        viral_load.infectiousness(viral_load.infection_age(infected_at, time_step))
+                    })
+                };
+                // Whether an infected agent infects a susceptible cell that it visits
+                #[cfg(feature = "landscape")]
+                let infects_cell = |rng: &mut Stream, k: AgentKey| match viral_load {
+                    Some(_) => rng.gen_bool(scenario.infection_probability * infectiousness(k)),
+                    None => infection_distro.sample(rng),
+                };
+                // Whether an exposure with this factor infects an agent
+                let infects_by = |rng: &mut Stream, k: AgentKey, factor: f64| {
+                    let susceptibility = susceptibility(k) * factor;
+                    if susceptibility == 1.0 {
+                        infection_distro.sample(rng)
+                    } else {
+                        rng.gen_bool((scenario.infection_probability * susceptibility).min(1.0))
+                    }
+                };
+                #[cfg_attr(
+                    not(any(feature = "landscape", feature = "net")),
+                    allow(unused_variables)
+                )]
+                let infects = |rng: &mut Stream, k: AgentKey| infects_by(rng, k, 1.0);
+                // Infected vectors on each cell, in vector-borne scenarios, where hosts do not infect each other
+                #[cfg(feature = "landscape")]
+                let infected_vectors = vectors
+                    .as_ref()
+                    .map(|vectors| vectors::infected_by_cell(vectors, coord.size()));
+                #[cfg(feature = "landscape")]
+                let mut infectious_visits = vectors.as_ref().map(|_| vec![0u32; coord.size()]);
+                #[cfg(feature = "landscape")]
+                let vector_borne = vectors.is_some();
+                #[cfg(not(feature = "landscape"))]
+                let vector_borne = false;
+                #[cfg(feature = "net")]
+                if network && !vector_borne {
+                    // Modifiers and the infectiousness of the source weigh each exposure; agents in quarantine do not
+                    // meet their friends by default
+                    network::transmit_modified(
+                        &links,
+                        |link, source, target| {
+                            pipeline.factor(&Exposure {
+                                link,
+                                layer: link_layers.layer(link).copied().unwrap_or_default(),
+                                source,
+                                target,
+                                quarantined: quarantined(source) || quarantined(target),
+                                time_step,
+                            }) * infectiousness(source)
+                        },
+                        current_health,
+                        next_health,
+                        infects_by,
+                        rng.get(Process::Infection),
+                        &mut events.infections,
+                    );
+                }
+                // Dynamics: Infection spreads within groups
+                if let (Some(group_rules), Some(groups), false) =
+                    (&group_rules, &groups, vector_borne)
+                {
+                    group_rules.transmit(
+                        groups,
+                        current_health,
+                        next_health,
+                        infects,
+                        rng.get(Process::Infection),
+                        &mut events.infections,
+                    );
+                }
+                // Dynamics: Infection spreads between age groups; agents in quarantine do not mix
+                if let (Some(contact_matrix), false) = (&contact_matrix, vector_borne) {
+                    let age_group = |k: AgentKey| {
+                        let age = time_step as i64 - ages.as_ref()?.born(k)?;
+                        (!quarantined(k))
+                            .then(|| strata::band(&contact_matrix.bounds, age as f64))
+                            .flatten()
+                    };
+                    contact_matrix.transmit(
+                        age_group,
+                        current_health,
+                        next_health,
+                        infects,
+                        rng.get(Process::Infection),
+                        &mut events.infections,
+                    );
+                }
+                // Dynamics: Awareness spreads over the network; infected agents become aware, and aware agents forget
+                #[cfg(feature = "net")]
+                if let (Some(awareness), Some(current_aware), Some(next_aware)) =
+                    (&awareness, current_aware, &mut next_aware)
+                {
+                    awareness.spread(
+                        &links,
+                        current_health,
+                        current_aware,
+                        next_aware,
+                        rng.get(Process::Awareness),
+                    );
+                }
+                if time_step == 0 {
+                    current_health.iter().for_each(|(k, &h)| {
+                        if h == Health::S
+                            && initial_infection_distro.sample(rng.get(Process::Infection))
+                        {
+                            if next_health[k] == Health::S {
+                                events.infections.publish(InfectionEvent {
+                                    source: None,
+                                    target: k,
+                                    via: Via::Seed,
+                                });
+                            }
+                            next_health[k] = Health::I;
+                        }
+                    });
+                }
+                // Dynamics: Resources regrow, and agents spend the energy of the time step
+                #[cfg(feature = "landscape")]
+                if let (Some(metabolism), Some(energy), Some(resources)) =
+                    (&metabolism, &mut energy, &mut resources)
+                {
+                    metabolism.regrow(resources);
+                    for (k, &h) in current_health.iter() {
+                        metabolism.metabolize(energy, k, h);
+                    }
+                }
+                // Agents that visit a cell forage on it, if they forage
+                #[cfg(feature = "landscape")]
+                let mut forage = |k: AgentKey, idx: usize| {
+                    if let (Some(metabolism), Some(energy), Some(resources)) =
+                        (&metabolism, &mut energy, &mut resources)
+                    {
+                        metabolism.forage(energy, resources, k, idx);
+                    }
+                };
+                // Random cell for an agent to visit
+                #[cfg(feature = "landscape")]
+                let visit = |rng: &mut Stream, k: AgentKey| {
+                    let x = visit_distro.sample(rng);
+                    let y = visit_distro.sample(rng);
+                    // The movement scale of the agent stretches its distance from the center
+                    let (x, y) = match &traits {
+                        Some(traits) => {
+                            let scale = traits.movement_scale[k];
+                            let (cx, cy) = visit_center;
+                            (cx + scale * (x - cx), cy + scale * (y - cy))
+                        }
+                        None => (x, y),
+                    };
+                    // Agents with a home visit around it instead of around the center
+                    let (x, y) = match homes.as_ref().and_then(|homes| homes.home(k)) {
+                        Some(&[hx, hy]) => (hx + x - visit_center.0, hy + y - visit_center.1),
+                        None => (x, y),
+                    };
+                    coord.index(x as i32, y as i32)
+                };
+                for (k, &h) in current_health.iter() {
+                    // Choose a random cell to visit, unless in quarantine; the other visits of this time step, if
+                    // any, follow below
+                    #[cfg(feature = "landscape")]
+                    let idx = (landscape && !quarantined(k))
+                        .then(|| visit(rng.get(Process::Movement), k));
+                    // Model measurements: Trajectory of this agent
+                    #[cfg(feature = "trajectories")]
+                    if sampled {
+                        #[allow(unused_mut)]
+                        let mut row = vec![
+                            Value::Float64(scenario.infection_probability),
+                            Value::UInt32(time_step as u32),
+                            Value::UInt64(k.data().as_ffi()),
+                            Value::Utf8(health_label(h)),
+                            #[cfg(feature = "net")]
+                            Value::UInt32(degree[k] as u32),
+                        ];
+                        if let Some(viral_load) = viral_load {
+                            row.push(match h {
+                                Health::S => Value::Utf8(""),
+                                Health::I => Value::Float64(
+                                    viral_load.log10_load(
+                                        viral_load
+                                            .infection_age(transmissions.infected_at(k), time_step),
+                                    ),
+                                ),
+                            });
+                        }
+                        #[cfg(feature = "landscape")]
+                        if let Some(idx) = idx {
+                            row.push(Value::UInt32((idx % coord.width() as usize) as u32));
+                            row.push(Value::UInt32((idx / coord.width() as usize) as u32));
+                        }
+                        tables::write_csv_row(&mut trajectories_file, &row)
+                            .or_error(trajectories_err)?;
+                        #[cfg(feature = "arrow-output")]
+                        trajectories_tables.push(&row)?;
+                    }
+                    match h {
+                        Health::S => {
+                            #[cfg(feature = "landscape")]
+                            if let Some(first_idx) = idx {
+                                // Where the agent catches the disease, if it does
+                                let mut via = None;
+                                // Dose taken in at infected cells, with dose-response, and the first of them
+                                let (mut dose, mut dose_idx) = (0.0, None);
+                                for visit_number in 0..cell_visits {
+                                    let idx = match visit_number {
+                                        0 => first_idx,
+                                        _ => visit(rng.get(Process::Movement), k),
+                                    };
+                                    forage(k, idx);
+                                    if let Some(infected_vectors) = &infected_vectors {
+                                        // Infected vectors on the cell bite the agent
+                                        if (0..infected_vectors[idx])
+                                            .any(|_| infects(rng.get(Process::Infection), k))
+                                        {
+                                            via = Some(Via::Vector(idx));
+                                            break;
+                                        }
+                                    } else if current_cell_health[idx] == Health::I {
+                                        if dose_response.is_some() {
+                                            dose += dose_per_visit;
+                                            dose_idx.get_or_insert(idx);
+                                        } else if infects(rng.get(Process::Infection), k) {
+                                            // Cell infects agent
+                                            via = Some(Via::Cell(idx));
+                                            break;
+                                        }
+                                    }
+                                }
+                                if let (Some(dose_response), Some(idx)) = (&dose_response, dose_idx)
+                                {
+                                    let probability =
+                                        dose_response.probability(dose) * susceptibility(k);
+                                    if rng.get(Process::Infection).gen_bool(probability.min(1.0)) {
+                                        via = Some(Via::Cell(idx));
+                                    }
+                                }
+                                if let Some(via) = via {
+                                    if next_health[k] == Health::S {
+                                        events.infections.publish(InfectionEvent {
+                                            source: None,
+                                            target: k,
+                                            via,
+                                        });
+                                    }
+                                    next_health[k] = Health::I;
+                                }
+                            }
+                        }
+                        Health::I => {
+                            #[cfg(feature = "landscape")]
+                            if let Some(first_idx) = idx {
+                                for visit_number in 0..cell_visits {
+                                    let idx = match visit_number {
+                                        0 => first_idx,
+                                        _ => visit(rng.get(Process::Movement), k),
+                                    };
+                                    forage(k, idx);
+                                    if let Some(infectious_visits) = &mut infectious_visits {
+                                        // The agent can infect the vectors on the cell
+                                        infectious_visits[idx] += 1;
+                                    } else if current_cell_health[idx] == Health::S
+                                        && infects_cell(rng.get(Process::Infection), k)
+                                    {
+                                        // Agent infects cell
+                                        if next_cell_health[idx] == Health::S {
+                                            events.cell_infections.publish(CellInfectionEvent {
+                                                source: k,
+                                                cell: idx,
+                                            });
+                                        }
+                                        next_cell_health[idx] = Health::I;
+                                    }
+                                }
+                            }
+                            let recovers = match &traits {
+                                Some(traits) => rng
+                                    .get(Process::Recovery)
+                                    .gen_bool(traits.recovery_probability[k]),
+                                None => recovery_distro.sample(rng.get(Process::Recovery)),
+                            };
+                            if recovers {
+                                events.recoveries.publish(RecoveryEvent { agent: k });
+                                next_health[k] = Health::S;
+                            }
+                        }
+                    };
+                }
+                // Dynamics: Vectors become infected, move, give birth, and die
+                #[cfg(feature = "landscape")]
+                if let (Some(vectors), Some(infectious_visits)) = (&mut vectors, &infectious_visits)
+                {
+                    vector_population.step(
+                        vectors,
+                        infectious_visits,
+                        &coord,
+                        rng.get(Process::Vectors),
+                    );
+                }
+                timer.lap(Phase::Agents);
+                // Dynamics: Disease spreads across cells and infectious cells recover
+                #[cfg(feature = "gpu")]
+                if let Some(gpu_landscape) = &mut gpu_landscape {
+                    gpu_landscape.step(next_cell_health, rng.get(Process::Infection).gen())?;
+                }
+                #[cfg(all(feature = "landscape", not(feature = "gpu")))]
+                if landscape {
+                    cell_dynamics.step(
+                        current_cell_health,
+                        next_cell_health,
+                        time_step,
+                        cell_contamination.as_mut(),
+                        rng.get(Process::Infection),
+                    );
+                }
+                timer.lap(Phase::Cells);
+                // Dynamics: After spreading the infection, some infectious agents die; so do their links.
+                // Register here any other component that refers to agents; see define_components!
+                #[allow(unused_mut)]
+                let mut dependents = Dependents::new();
+                let mut emigrants = 0;
+                #[cfg(feature = "net")]
+                dependents.register(&mut links);
+                if let Some(traits) = &mut traits {
+                    dependents.register(traits);
+                }
+                #[cfg(feature = "net")]
+                if let Some(next_aware) = next_aware {
+                    dependents.register(next_aware);
+                }
+                if let Some(groups) = &mut groups {
+                    dependents.register(groups);
+                }
+                #[cfg(feature = "landscape")]
+                if let Some(homes) = &mut homes {
+                    dependents.register(homes);
+                }
+                if let Some(vaccinated) = &mut vaccinated {
+                    dependents.register(vaccinated);
+                }
+                if let Some(history) = &mut history {
+                    dependents.register(history);
+                }
+                dependents.retain(next_health, |agent_key, _next_h| {
+                    let survives = match current_health[agent_key] {
+                        Health::S => true,
+                        Health::I => survival_distro.sample(rng.get(Process::Survival)),
+                    };
+                    // Agents that survive the disease can still starve
+                    #[cfg(feature = "landscape")]
+                    let survives = survives
+                        && !metabolism.as_ref().zip(energy.as_ref()).is_some_and(
+                            |(metabolism, energy)| metabolism.starved(energy, agent_key),
+                        );
+                    // Agents that survive the disease can still die of old age
+                    let survives = survives
+                        && ages.as_ref().is_none_or(|ages| {
+                            let age = time_step as i64 - ages.born[agent_key];
+                            !rng.get(Process::Survival)
+                                .gen_bool(demography.mortality(age))
+                        });
+                    if !survives {
+                        events.deaths.publish(DeathEvent { agent: agent_key });
+                    }
+                    // Agents that stay alive can still leave the population
+                    let emigrates = survives
+                        && migration.as_ref().is_some_and(|migration| {
+                            migration.emigrates(rng.get(Process::Migration))
+                        });
+                    emigrants += emigrates as usize;
+                    survives && !emigrates
+                });
+                scenario.emigrants += emigrants;
+                // Ages refer to agents, but the deaths above depend on them, so they forget the dead afterwards; so does
+                // energy
+                if let Some(ages) = &mut ages {
+                    ages.forget_dead(&|agent_key| next_health.contains_key(agent_key));
+                }
+                #[cfg(feature = "landscape")]
+                if let Some(energy) = &mut energy {
+                    energy.forget_dead(&|agent_key| next_health.contains_key(agent_key));
+                }
+                // Layers refer to links, which died with their agents
+                #[cfg(feature = "net")]
+                link_layers.forget_dead(&|link_key| links.contains_key(link_key));
+            }
+            timer.lap(Phase::Agents);
+            // Dynamics: cells update in parallel
+            #[cfg(feature = "landscape")]
+            cell_health.swap();
+            timer.lap(Phase::Cells);
+            // Dynamics: New agents emerge
+            let next_health = health.write();
+            let parents: Vec<AgentKey> = match demography.carrying_capacity {
+                Some(_) => {
+                    let birth_probability = demography.birth_probability(next_health.len());
+                    next_health
+                        .iter()
+                        .filter(|&(_, &h)| {
+                            h == Health::S && rng.get(Process::Births).gen_bool(birth_probability)
+                        })
+                        .map(|(k, _)| k)
+                        .collect()
+                }
+                None => next_health
+                    .iter()
+                    .filter(|&(_, &h)| {
+                        h == Health::S && birth_distro.sample(rng.get(Process::Births))
+                    })
+                    .map(|(k, _)| k)
+                    .collect(),
+            };
+            for parent in parents {
+                let agent = next_health.insert(Health::S);
+                if let Some(ages) = &mut ages {
+                    ages.set_born(agent, time_step as i64 + 1);
+                }
+                // Children share the home of their parent
+                #[cfg(feature = "landscape")]
+                if let Some(homes) = &mut homes {
+                    if let Some(&home) = homes.home(parent) {
+                        homes.set_home(agent, home);
+                    }
+                }
+                if let Some(traits) = &mut traits {
+                    let values =
+                        heterogeneity.inherit(traits, parent, agent, rng.get(Process::Births));
+                    for (stats, value) in scenario.agent_traits.iter_mut().zip(values) {
+                        stats.push(value);
+                    }
+                }
+                // Children join the group of their parent, if any: in a synthetic population, only workers have one
+                if let Some((groups, group)) = groups.as_mut().and_then(|groups| {
+                    let group = *groups.membership.group(parent)?;
+                    Some((groups, group))
+                }) {
+                    groups.membership.set_group(agent, group);
+                    #[cfg(feature = "csv-output")]
+                    if let Some(groups_file) = &mut groups_file {
+                        let row = [
+                            Value::Float64(scenario.infection_probability),
+                            Value::UInt32(time_step as u32 + 1),
+                            Value::UInt64(agent.data().as_ffi()),
+                            Value::UInt32(group),
+                        ];
+                        tables::write_csv_row(groups_file, &row).or_error(groups_err)?;
+                    }
+                }
+                // Model measurements: Genealogy of the agents born during the run
+                #[cfg(feature = "genealogy")]
+                {
+                    let row = [
+                        Value::Float64(scenario.infection_probability),
+                        Value::UInt32(time_step as u32 + 1),
+                        Value::UInt64(parent.data().as_ffi()),
+                        Value::UInt64(agent.data().as_ffi()),
+                    ];
+                    tables::write_csv_row(&mut genealogy_file, &row).or_error(genealogy_err)?;
+                }
+                events.births.publish(BirthEvent { parent, agent });
+            }
+            // Dynamics: Immigrants arrive, some of them infected, with parameters and ages like those of the initial
+            // population
+            if let Some(migration) = &migration {
+                for _ in 0..migration.immigrants(rng.get(Process::Migration)) {
+                    let infected = migration.arrives_infected(rng.get(Process::Migration));
+                    let agent = next_health.insert(if infected { Health::I } else { Health::S });
+                    if infected {
+                        events.infections.publish(InfectionEvent {
+                            source: None,
+                            target: agent,
+                            via: Via::Import,
+                        });
+                    }
+                    if let Some(ages) = &mut ages {
+                        let age = demography.initial_age.as_ref().map_or(0.0, |initial_age| {
+                            initial_age.sample(rng.get(Process::Migration))
+                        });
+                        ages.set_born(agent, time_step as i64 + 1 - age.max(0.0).round() as i64);
+                    }
+                    if let Some(traits) = &mut traits {
+                        let values = heterogeneity.draw(traits, agent, rng.get(Process::Migration));
+                        for (stats, value) in scenario.agent_traits.iter_mut().zip(values) {
+                            stats.push(value);
+                        }
+                    }
+                    scenario.immigrants += 1;
+                }
+            }
+            // Dynamics: Agents update in parallel
+            health.swap();
+            #[cfg(feature = "net")]
+            if let Some(aware) = &mut aware {
+                aware.swap();
+            }
+            timer.lap(Phase::Agents);
+            // Bookkeeping: Systems consume the events of this time step
+            hooks.consume(&events, time_step);
+            scenario.infections += events.infections.len();
+            scenario.incidence.push(events.infections.len() as u32);
+            transmissions.consume(&events, time_step, &mut scenario.secondary);
+            if let Some(history) = &mut history {
+                history.consume(&events, time_step);
+            }
+            events.clear();
+            // end-similar-code 3
+            // Model measurements: First passages of this scenario; nothing infects again after extinction
+            if scenario.extinct_at.is_none() || scenario.threshold_at.is_none() {
+                let agents = health.read().len();
+                let infected_agents = health.read().values().filter(|&&h| h == Health::I).count();
+                let infected = infected_agents;
+                #[cfg(feature = "landscape")]
+                let infected = infected
+                    + cell_health
+                        .read()
+                        .iter()
+                        .filter(|&&h| h == Health::I)
+                        .count()
+                    + vectors
+                        .iter()
+                        .flatten()
+                        .filter(|v| v.health == Health::I)
+                        .count();
+                if infected == 0 && scenario.extinct_at.is_none() {
+                    scenario.extinct_at = Some(time_step + 1);
+                }
+                if infected_agents as f64 >= prevalence_threshold * agents as f64
+                    && agents > 0
+                    && scenario.threshold_at.is_none()
+                {
+                    scenario.threshold_at = Some(time_step + 1);
+                }
+            }
+            progress_bar.inc(1);
+            // Bookkeeping: Stop early if interrupted, keeping the results of the time steps simulated so far
+            let interrupted = INTERRUPTED.load(Ordering::Relaxed) && time_step + 1 < stop_at;
+            // Bookkeeping: Save the state of this scenario at the beginning of the next time step
+            #[cfg(feature = "checkpoint")]
+            let save_checkpoint = interrupted
+                || time_step + 1 == stop_at && stop_at < time_series_len
+                || args.checkpoint_every > 0
+                    && ((time_step + 1).is_multiple_of(args.checkpoint_every)
+                        || time_step + 1 == time_series_len);
+            #[cfg(all(debug_assertions, feature = "checkpoint"))]
+            let save_snapshot = (time_step + 1).is_multiple_of(snapshot_every);
+            #[cfg(all(not(debug_assertions), feature = "checkpoint"))]
+            let save_snapshot = false;
+            #[cfg(feature = "checkpoint")]
+            if save_checkpoint || save_snapshot {
+                let state = Checkpoint {
+                    time_step: time_step + 1,
+                    rng: rng.clone(),
+                    health: health.read().clone(),
+                    #[cfg(feature = "net")]
+                    links: links.clone(),
+                    #[cfg(feature = "net")]
+                    link_layers: link_layers.clone(),
+                    #[cfg(feature = "landscape")]
+                    cell_health: cell_health.read().clone(),
+                    #[cfg(all(feature = "landscape", not(feature = "gpu")))]
+                    cell_contamination: cell_contamination.clone(),
+                    traits: traits.clone(),
+                    ages: ages.clone(),
+                    #[cfg(feature = "net")]
+                    aware: aware.as_ref().map(|aware| aware.read().clone()),
+                    #[cfg(feature = "landscape")]
+                    vectors: vectors.clone(),
+                    groups: groups.clone(),
+                    #[cfg(feature = "landscape")]
+                    homes: homes.clone(),
+                    #[cfg(feature = "landscape")]
+                    energy: energy.clone(),
+                    #[cfg(feature = "landscape")]
+                    resources: resources.clone(),
+                    vaccinated: vaccinated.clone(),
+                    transmissions: transmissions.clone(),
+                    history: history.clone(),
+                    scenario: scenario.clone(),
+                };
+                if save_checkpoint {
+                    // Text outputs reach the disk first, so they cover at least the time steps before the checkpoint
+                    #[cfg(feature = "csv-output")]
+                    ts_file.flush().or_error(ts_err)?;
+                    #[cfg(all(feature = "net-graphics", feature = "csv-output"))]
+                    degrees_file.flush().or_error(degrees_err)?;
+                    #[cfg(feature = "landscape-graphics")]
+                    frames_file.flush().or_error(frames_err)?;
+                    #[cfg(feature = "trajectories")]
+                    trajectories_file.flush().or_error(trajectories_err)?;
+                    #[cfg(feature = "cells-csv")]
+                    cells_file.flush().or_error(cells_err)?;
+                    #[cfg(feature = "network-snapshots")]
+                    if let Some(network_file) = &mut network_file {
+                        network_file.flush().or_error(network_err)?;
+                    }
+                    #[cfg(feature = "genealogy")]
+                    genealogy_file.flush().or_error(genealogy_err)?;
+                    #[cfg(feature = "csv-output")]
+                    if let Some(groups_file) = &mut groups_file {
+                        groups_file.flush().or_error(groups_err)?;
+                    }
+                    #[cfg(feature = "csv-output")]
+                    if let Some(strata_file) = &mut strata_file {
+                        strata_file.flush().or_error(strata_err)?;
+                    }
+                    let checkpoint_path = scenario.dir.join("checkpoint.json");
+                    checkpoint::save(&checkpoint_path, &state).or_error(&format!(
+                        "Could not save checkpoint {}",
+                        checkpoint_path.display()
+                    ))?;
+                }
+                #[cfg(debug_assertions)]
+                if save_snapshot {
+                    snapshots
+                        .push(time_step + 1, &state)
+                        .or_error("Error serializing a snapshot")?;
+                }
+            }
+            if interrupted {
+                scenario.interrupted_at = Some(time_step + 1);
+                break;
+            }
+        }
+        #[cfg(feature = "csv-output")]
+        ts_file.flush().or_error(ts_err)?;
+        #[cfg(all(feature = "net-graphics", feature = "csv-output"))]
+        degrees_file.flush().or_error(degrees_err)?;
+        #[cfg(feature = "arrow-output")]
+        ts_tables.finish()?;
+        #[cfg(feature = "landscape-graphics")]
+        frames_file.flush().or_error(frames_err)?;
+        #[cfg(feature = "trajectories")]
+        trajectories_file.flush().or_error(trajectories_err)?;
+        #[cfg(all(feature = "trajectories", feature = "arrow-output"))]
+        trajectories_tables.finish()?;
+        #[cfg(feature = "cells-csv")]
+        cells_file.flush().or_error(cells_err)?;
+        #[cfg(all(feature = "cells-csv", feature = "arrow-output"))]
+        cells_tables.finish()?;
+        #[cfg(feature = "network-snapshots")]
+        if let Some(network_file) = network_file {
+            network_file
+                .finish()
+                .and_then(|mut network_file| network_file.flush())
+                .or_error(network_err)?;
+        }
+        #[cfg(feature = "genealogy")]
+        genealogy_file.flush().or_error(genealogy_err)?;
+        #[cfg(feature = "csv-output")]
+        if let Some(groups_file) = &mut groups_file {
+            groups_file.flush().or_error(groups_err)?;
+        }
+        #[cfg(feature = "csv-output")]
+        if let Some(strata_file) = &mut strata_file {
+            strata_file.flush().or_error(strata_err)?;
+        }
+        // Model measurements: Surveillance observes the new infections of the time steps simulated so far
+        #[cfg(feature = "csv-output")]
+        if let Some(observation) = &observation {
+            let observed_path = scenario.dir.join("observed.csv");
+            let observed_err = &*format!("Error writing observed file {}", observed_path.display());
+            let mut observed_file = create_output_file(&observed_path, observed_err, existing)?;
+            tables::write_csv_header(&mut observed_file, &observed_columns())
+                .or_error(observed_err)?;
+            let observed = observation.observe(&scenario.incidence, rng.get(Process::Observation));
+            for (time_step, (&incidence, observed)) in
+                scenario.incidence.iter().zip(observed).enumerate()
+            {
+                let row = [
+                    Value::Float64(scenario.infection_probability),
+                    Value::UInt32(time_step as u32),
+                    Value::UInt32(incidence),
+                    Value::UInt32(observed),
+                ];
+                tables::write_csv_row(&mut observed_file, &row).or_error(observed_err)?;
+            }
+            observed_file.flush().or_error(observed_err)?;
+        }
+        // Model measurements: Effective reproduction number of the agents infected at each time step
+        #[cfg(feature = "csv-output")]
+        {
+            let reproduction_path = scenario.dir.join("reproduction.csv");
+            let reproduction_err = &*format!(
+                "Error writing reproduction file {}",
+                reproduction_path.display()
+            );
+            let mut reproduction_file =
+                create_output_file(&reproduction_path, reproduction_err, existing)?;
+            tables::write_csv_header(&mut reproduction_file, &reproduction_columns())
+                .or_error(reproduction_err)?;
+            let r_t = reproduction::reproduction(&scenario.incidence, &scenario.secondary);
+            for (time_step, (&incidence, r_t)) in scenario.incidence.iter().zip(r_t).enumerate() {
+                let row = [
+                    Value::Float64(scenario.infection_probability),
+                    Value::UInt32(time_step as u32),
+                    Value::UInt32(incidence),
+                    Value::UInt32(scenario.secondary.get(time_step).copied().unwrap_or(0)),
+                    // Time steps without infections have no R_t
+                    r_t.map_or(Value::Utf8(""), Value::Float64),
+                ];
+                tables::write_csv_row(&mut reproduction_file, &row).or_error(reproduction_err)?;
+            }
+            reproduction_file.flush().or_error(reproduction_err)?;
+        }
+        #[cfg(feature = "netcdf-output")]
+        netcdf_file.finish().or_error(netcdf_err)?;
+        timer.lap(Phase::Output);
+        scenario
+            .phase_seconds
+            .iter_mut()
+            .zip(timer.seconds)
+            .for_each(|(total, seconds)| *total += seconds);
+        progress_bar.finish();
+        #[cfg(feature = "live-view")]
+        if let Some(view) = live_view {
+            view.finish();
+        }
+        #[cfg(feature = "server")]
+        if let Some(server) = &server {
+            server.finish(scenario.id);
+        }
+        #[cfg(feature = "grpc")]
+        if args.managed {
+            println!(
+                "{}",
+                serde_json::json!({"scenario": scenario.id, "finished": true})
+            );
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = &publisher {
+            publisher.finish(scenario.id);
+        }
+        #[cfg(all(debug_assertions, feature = "checkpoint"))]
+        snapshots.write().or_error("Error writing snapshots")?;
+        #[cfg(feature = "rng-trace")]
+        rng.finish_trace()
+            .or_error("Error writing the trace of random numbers")?;
+        scenario.seconds = scenario_clock.elapsed().as_secs_f64();
+        Ok(())
+    };
+    // Counterfactual branching: a baseline with the parameters of the first scenario runs until branch_at;
+    // then every scenario branches from the state of the baseline, random numbers included.
+    #[cfg(feature = "checkpoint")]
+    let baseline = args
+        .branch_at
+        .map(|branch_at| -> error::Result<Scenario> {
+            let mut baseline = scenarios[0].clone();
+            baseline.dir = run_dir.join("baseline");
+            fs::create_dir_all(&baseline.dir).or_error(&format!(
+                "Could not create the output directory {}",
+                baseline.dir.display()
+            ))?;
+            simulate(&mut baseline, None, branch_at)?;
+            Ok(baseline)
+        })
+        .transpose()?;
+    let run_scenario = |scenario: &mut Scenario| -> error::Result<()> {
+        #[cfg(feature = "checkpoint")]
+        {
+            let resume_from = match &baseline {
+                // Branches cannot start before the baseline finishes
+                Some(baseline) if baseline.interrupted_at.is_some() => {
+                    scenario.interrupted_at = Some(0);
+                    return Ok(());
+                }
+                Some(baseline) => Some(baseline.dir.join("checkpoint.json")),
+                None => args
+                    .resume
+                    .as_ref()
+                    .map(|resume_dir| {
+                        resume_dir
+                            .join(scenario.dir.file_name().unwrap())
+                            .join("checkpoint.json")
+                    })
+                    .filter(|checkpoint_path| checkpoint_path.exists()),
+            };
+            simulate(scenario, resume_from, time_series_len)
+        }
+        #[cfg(not(feature = "checkpoint"))]
+        simulate(scenario, time_series_len)
+    };
+    // Bookkeeping: A scenario that fails stops alone; the run goes on without it. Each scenario that finishes goes to
+    // whoever waits for it, such as the figures.
+    let mut run_all =
+        |finished: Option<std::sync::mpsc::SyncSender<Scenario>>| -> Vec<(u32, Error)> {
+            let run_or_fail = |scenario: &mut Scenario| {
+                let result = run_scenario(scenario);
+                if let (Ok(()), None, Some(finished)) =
+                    (&result, scenario.interrupted_at, &finished)
+                {
+                    // The figures drawn after the run cover a scenario that finished unseen
+                    let _ = finished.send(scenario.clone());
+                }
+                result.err().map(|e| (scenario.id, e))
+            };
+            if across_scenarios {
+                scenarios.par_iter_mut().filter_map(run_or_fail).collect()
+            } else {
+                scenarios.iter_mut().filter_map(run_or_fail).collect()
+            }
+        };
+    // Figures: each scenario is drawn as it finishes, while the others still run. Figures that an earlier run left
+    // behind stay until the run finishes, when create_figures knows which ones to skip.
+    #[cfg(feature = "graphics")]
+    let (scenario_failures, drawn) = if figures && existing != Existing::Skip {
+        std::thread::scope(|scope| {
+            let (finished, to_draw) = std::sync::mpsc::sync_channel(1);
+            let drawer = scope.spawn(|| draw_during_run(&figure_settings, &run_dir, to_draw));
+            let scenario_failures = run_all(Some(finished));
+            (scenario_failures, drawer.join().unwrap())
+        })
+    } else {
+        (run_all(None), Vec::new())
+    };
+    #[cfg(not(feature = "graphics"))]
+    let scenario_failures = run_all(None);
+    let mut failures = Vec::with_capacity(scenario_failures.len());
+    let mut failed_scenarios = Vec::with_capacity(scenario_failures.len());
+    for (id, e) in scenario_failures {
+        eprintln!("{}Scenario {} failed: {}.", clean_term, id, e);
+        failed_scenarios.push(serde_json::json!({"id": id, "error": e.to_string()}));
+        failures.push(e);
+    }
+    scenarios.retain(|scenario| {
+        !failed_scenarios
+            .iter()
+            .any(|failed| failed["id"] == scenario.id)
+    });
+    #[cfg(feature = "mqtt")]
+    if let Some(publisher) = publisher {
+        let dropped = publisher.close();
+        if dropped > 0 {
+            eprintln!(
+                "{}The MQTT broker missed {} messages; it was slow or unreachable.",
+                clean_term, dropped
+            );
+        }
+    }
+    let simulation_seconds = clock.elapsed().as_secs_f64();
+    let interrupted = scenarios
+        .iter()
+        .any(|scenario| scenario.interrupted_at.is_some());
+    if interrupted {
+        eprint!(
+            "{}Simulation interrupted. Saving partial results to disk... ",
+            clean_term
+        );
+    } else {
+        eprint!("{}Simulation complete. Saving to disk... ", clean_term);
+    }
+    eprintln!("{}Results saved to {}.", clean_term, run_dir.display());
+    #[cfg(feature = "jsonl")]
+    jsonl_file
+        .into_inner()
+        .unwrap()
+        .flush()
+        .or_error(jsonl_err)?;
+    #[cfg(feature = "streaming")]
+    {
+        let summary_path = run_dir.join("summary.csv");
+        let summary_name = summary_path.display();
+        let summary_err = &*format!("Error writing summary output file {}", summary_name);
+        let mut summary_file = create_output_file(&summary_path, summary_err, existing)?;
+        writeln!(
+            &mut summary_file,
+            "Infection Probability,Metric,Time steps,Mean,Variance"
+        )
+        .or_error(summary_err)?;
+        for scenario in &scenarios {
+            for (reporter, stats) in collector
+                .reporters()
+                .iter()
+                .zip(scenario.summary.stats.iter())
+            {
+                writeln!(
+                    &mut summary_file,
+                    "{},{},{},{},{}",
+                    scenario.infection_probability,
+                    reporter.name,
+                    stats.count(),
+                    stats.mean(),
+                    stats.variance()
+                )
+                .or_error(summary_err)?;
+            }
+        }
+        summary_file.flush().or_error(summary_err)?;
+        eprintln!("{}Summary saved to {}.", clean_term, summary_name);
+    }
+    // Counterfactual branching: paired differences between each branch and the first scenario, which continues the baseline
+    #[cfg(all(
+        feature = "checkpoint",
+        feature = "csv-output",
+        not(feature = "streaming")
+    ))]
+    // Branches need all of their scenarios, the first one included
+    if let (Some(branch_at), false) = (args.branch_at, interrupted || !failed_scenarios.is_empty())
+    {
+        let branches_path = run_dir.join("branches.csv");
+        let branches_name = branches_path.display();
+        let branches_err = &*format!("Error writing branches output file {}", branches_name);
+        let mut branches_file = create_output_file(&branches_path, branches_err, existing)?;
+        tables::write_csv_header(&mut branches_file, &ts_columns).or_error(branches_err)?;
+        for scenario in scenarios.iter().skip(1) {
+            for (results, reference) in scenario
+                .time_series
+                .iter()
+                .zip(scenarios[0].time_series.iter())
+                .filter(|(results, _)| results.time_step as usize >= branch_at)
+            {
+                let mut row = vec![
+                    Value::Float64(scenario.infection_probability),
+                    Value::UInt32(results.time_step),
+                ];
+                row.extend(
+                    results
+                        .values
+                        .iter()
+                        .zip(reference.values.iter())
+                        .map(|(&value, &reference_value)| Value::Float64(value - reference_value)),
+                );
+                tables::write_csv_row(&mut branches_file, &row).or_error(branches_err)?;
+            }
+        }
+        branches_file.flush().or_error(branches_err)?;
+        eprintln!(
+            "{}Paired differences saved to {}.",
+            clean_term, branches_name
+        );
+    }
+    // First passages: extinction and the prevalence threshold, in each scenario and across the replicates of each
+    // infection probability. An interrupted run has no horizon to compare against.
+    #[cfg(feature = "csv-output")]
+    if !interrupted {
+        let passages: Vec<outcomes::Passages> = scenarios
+            .iter()
+            .map(|scenario| outcomes::Passages {
+                infection_probability: scenario.infection_probability,
+                replicate: scenario.replicate,
+                scenario: scenario.id,
+                extinct_at: scenario.extinct_at,
+                threshold_at: scenario.threshold_at,
+            })
+            .collect();
+        let passages_path = run_dir.join("first_passage.csv");
+        let passages_name = passages_path.display();
+        let passages_err = &*format!("Error writing first passage output file {}", passages_name);
+        let mut passages_file = create_output_file(&passages_path, passages_err, existing)?;
+        outcomes::write_passages(&mut passages_file, &passages).or_error(passages_err)?;
+        passages_file.flush().or_error(passages_err)?;
+        let extinction_path = run_dir.join("extinction.csv");
+        let extinction_name = extinction_path.display();
+        let extinction_err = &*format!("Error writing extinction output file {}", extinction_name);
+        let mut extinction_file = create_output_file(&extinction_path, extinction_err, existing)?;
+        outcomes::write_extinction(&mut extinction_file, &passages).or_error(extinction_err)?;
+        extinction_file.flush().or_error(extinction_err)?;
+        eprintln!(
+            "{}Extinction and first passages saved to {} and {}.",
+            clean_term, extinction_name, passages_name
+        );
+    }
+    // Final sizes: infections of each scenario, seeds included, and their distribution across the replicates of each
+    // infection probability, in bins that double in width: [1, 1], [2, 3], [4, 7], and so on
+    #[cfg(feature = "csv-output")]
+    if !interrupted {
+        let sizes: Vec<outcomes::FinalSize> = scenarios
+            .iter()
+            .map(|scenario| outcomes::FinalSize {
+                infection_probability: scenario.infection_probability,
+                replicate: scenario.replicate,
+                scenario: scenario.id,
+                infections: scenario.infections,
+            })
+            .collect();
+        let sizes_path = run_dir.join("final_sizes.csv");
+        let sizes_name = sizes_path.display();
+        let sizes_err = &*format!("Error writing final sizes output file {}", sizes_name);
+        let mut sizes_file = create_output_file(&sizes_path, sizes_err, existing)?;
+        outcomes::write_final_sizes(&mut sizes_file, &sizes).or_error(sizes_err)?;
+        sizes_file.flush().or_error(sizes_err)?;
+        let histogram_path = run_dir.join("final_size_histogram.csv");
+        let histogram_name = histogram_path.display();
+        let histogram_err = &*format!(
+            "Error writing final size histogram output file {}",
+            histogram_name
+        );
+        let mut histogram_file = create_output_file(&histogram_path, histogram_err, existing)?;
+        outcomes::write_final_size_histogram(&mut histogram_file, &sizes)
+            .or_error(histogram_err)?;
+        histogram_file.flush().or_error(histogram_err)?;
+        eprintln!(
+            "{}Final sizes saved to {} and {}.",
+            clean_term, sizes_name, histogram_name
+        );
+    }
+    // Figures and videos cover whole runs, so an interrupted run skips them.
+    #[cfg(feature = "graphics")]
+    if !interrupted && figures {
+        failures.extend(create_figures(
+            &figure_settings,
+            &mut scenarios,
+            drawn,
+            &run_dir,
+            clean_term,
+        ));
+        for scenario in &mut scenarios {
+            let video_clock = std::time::Instant::now();
+            if let Err(e) = encode_video(
+                &scenario.dir,
+                &args.video,
+                figure_settings.frame_stride,
+                existing,
+                clean_term,
+            ) {
+                eprintln!("{}{}.", clean_term, e);
+                failures.push(e);
+            }
+            scenario.phase_seconds[Phase::Video as usize] += video_clock.elapsed().as_secs_f64();
+        }
+        // Replicates of each infection probability, drawn together
+        if args.replicates > 1 {
+            for (replicates_dir, replicates) in
+                replicates_figures(&figure_settings, &scenarios, &run_dir, args.replicates)
+            {
+                match replicates {
+                    Ok(()) => {
+                        if let Err(e) = encode_video(
+                            &replicates_dir,
+                            &args.video,
+                            figure_settings.frame_stride,
+                            existing,
+                            clean_term,
+                        ) {
+                            eprintln!("{}{}.", clean_term, e);
+                            failures.push(e);
+                        }
+                    }
+                    Err(replicates_failures) => {
+                        for e in &replicates_failures {
+                            eprintln!("{}{}.", clean_term, e);
+                        }
+                        failures.extend(replicates_failures);
+                    }
+                }
+            }
+        }
+        if args.video.join_videos {
+            let scenario_dirs: Vec<&Path> = scenarios
+                .iter()
+                .map(|scenario| scenario.dir.as_path())
+                .collect();
+            if let Err(e) = join_videos(&run_dir, &scenario_dirs, &args.video, existing, clean_term)
+            {
+                eprintln!("{}{}.", clean_term, e);
+                failures.push(e);
+            }
+        }
+    }
+    // Performance report: time spent in each phase, to know what to optimize
+    {
+        let phases_path = run_dir.join("phases.csv");
+        let phases_name = phases_path.display();
+        let phases_err = &*format!("Error writing phases output file {}", phases_name);
+        let mut phases_file = create_output_file(&phases_path, phases_err, existing)?;
+        writeln!(&mut phases_file, "Infection Probability,Phase,Seconds").or_error(phases_err)?;
+        let mut total_seconds = [0.0; PHASES];
+        for scenario in &scenarios {
+            for (phase, &seconds) in scenario.phase_seconds.iter().enumerate() {
+                writeln!(
+                    &mut phases_file,
+                    "{},{},{}",
+                    scenario.infection_probability, PHASE_NAMES[phase], seconds
+                )
+                .or_error(phases_err)?;
+                total_seconds[phase] += seconds;
+            }
+        }
+        phases_file.flush().or_error(phases_err)?;
+        let sum: f64 = total_seconds.iter().sum();
+        eprintln!("{}Time spent in each phase, all scenarios:", clean_term);
+        for (name, seconds) in PHASE_NAMES.iter().zip(total_seconds) {
+            if seconds > 0.0 {
+                eprintln!(
+                    "  {:<18} {:>10.3} s {:>5.1}%",
+                    name,
+                    seconds,
+                    100.0 * seconds / sum
+                );
+            }
+        }
+        eprintln!("Time per phase and scenario saved to {}.", phases_name);
+    }
+    // Run manifest: the program, parameters, and seeds that produced these results, for tracing them months later
+    {
+        let manifest_path = run_dir.join("manifest.json");
+        let manifest_err = &*format!("Error writing manifest {}", manifest_path.display());
+        #[allow(unused_mut)]
+        let mut manifest = serde_json::json!({
+            "program": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "git_commit": option_env!("BUILD_GIT_COMMIT"),
+            "features": env!("BUILD_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .collect::<Vec<_>>(),
+            "command_line": std::env::args().collect::<Vec<_>>(),
+            "started": iso_8601(started),
+            "finished": iso_8601(std::time::SystemTime::now()),
+            "interrupted": interrupted,
+            "parameters": {
+                "n0": n0,
+                "birth_probability": birth_probability,
+                "initial_infection_probability": initial_infection_probability,
+                "infection_probabilities": infection_probabilities,
+                "replicates": args.replicates,
+                "recovery_probability": recovery_probability,
+                "survival_probability": survival_probability,
+                "agent_recovery_probability": agent_recovery_spec.as_ref().map(Spec::to_string),
+                "agent_susceptibility": agent_susceptibility_spec.as_ref().map(Spec::to_string),
+                "trait_mutation": trait_mutation_spec.as_ref().map(Spec::to_string),
+                "carrying_capacity": carrying_capacity,
+                "mortality_baseline": mortality_baseline,
+                "mortality_growth": mortality_growth,
+                "initial_age": initial_age_spec.as_ref().map(Spec::to_string),
+                "migration": migration.map(|migration| {
+                    serde_json::json!({
+                        "immigration": migration.immigration,
+                        "immigrant_infected": migration.infected,
+                        "emigration_probability": migration.emigration,
+                    })
+                }),
+                "last_time_step": last_time_step,
+                "common_random_numbers": args.common_random_numbers,
+                "antithetic": args.antithetic,
+            },
+            "output_parameters": {
+                "sample_every": sample_every,
+                "threads": rayon::current_num_threads(),
+                "parallelism_across_scenarios": across_scenarios,
+                "parallelism_within_scenarios": within_scenarios,
+                "existing": clap::ValueEnum::to_possible_value(&existing).map(|value| value.get_name().to_string()),
+            },
+            "video": {
+                "fps": args.video.fps,
+                "codec": args.video.codec,
+                "container": args.video.container,
+                "crf": args.video.crf,
+                "theme": args.video.theme,
+                "join_videos": args.video.join_videos,
+            },
+            "scenario_index": args.scenario_index,
+            "shard": shard.map(|shard| shard.to_string()),
+            "reporters": collector
+                .reporters()
+                .iter()
+                .map(|reporter| serde_json::json!({
+                    "name": reporter.name,
+                    "description": reporter.description,
+                }))
+                .collect::<Vec<_>>(),
+            "scenarios": scenarios
+                .iter()
+                .map(|scenario| serde_json::json!({
+                    "id": scenario.id,
+                    "infection_probability": scenario.infection_probability,
+                    "replicate": scenario.replicate,
+                    "dir": scenario.dir.display().to_string(),
+                    "rng": {
+                        "algorithm": "Pcg64",
+                        "state": format!("{:#x}", scenario.rng_state),
+                        "stream": format!("{:#x}", scenario.rng_stream),
+                        "antithetic": scenario.antithetic,
+                    },
+                    "seconds": scenario.seconds,
+                    "interrupted_at": scenario.interrupted_at,
+                    "agent_traits": keep_traits.then(|| AgentTraits::FIELDS
+                        .iter()
+                        .zip(&scenario.agent_traits)
+                        .map(|(&name, stats)| (name.to_string(), serde_json::json!({
+                            "agents": stats.count(),
+                            "mean": stats.mean(),
+                            "variance": stats.variance(),
+                        })))
+                        .collect::<serde_json::Map<_, _>>()),
+                    "phase_seconds": PHASE_NAMES
+                        .iter()
+                        .zip(scenario.phase_seconds)
+                        .map(|(&name, seconds)| (name.to_string(), seconds.into()))
+                        .collect::<serde_json::Map<_, _>>(),
+                }))
+                .collect::<Vec<_>>(),
+            "failed_scenarios": failed_scenarios,
+            "timings": {
+                "simulation_seconds": simulation_seconds,
+                "total_seconds": clock.elapsed().as_secs_f64(),
+            },
+        });
+        manifest["parameters"]["synthetic_population"] = synthetic_population
+            .as_ref()
+            .zip(population.as_ref())
+            .map(|(_, population)| {
+                serde_json::json!({
+                    "household_sizes": household_sizes,
+                    "age_bands": age_bands,
+                    "age_weights": age_weights,
+                    "adult_age": adult_age,
+                    "retirement_age": retirement_age,
+                    "workplace_size": workplace_size_spec.as_ref().map(Spec::to_string),
+                    "households": population.households,
+                    "workplaces": population.workplaces,
+                })
+            })
+            .into();
+        manifest["parameters"]["observation"] = reporting_probability
+            .map(|reporting_probability| {
+                serde_json::json!({
+                    "reporting_probability": reporting_probability,
+                    "reporting_delay": reporting_delay_spec.as_ref().map(Spec::to_string),
+                    "weekday_effects": weekday_effects,
+                    "reporting_dispersion": reporting_dispersion,
+                })
+            })
+            .into();
+        manifest["parameters"]["prevalence_threshold"] = prevalence_threshold.into();
+        manifest["parameters"]["contact_matrix"] = args
+            .contact_matrix
+            .as_ref()
+            .zip(contact_matrix.as_ref())
+            .map(|(path, contact_matrix)| {
+                serde_json::json!({
+                    "path": path,
+                    "age_groups": contact_matrix.bounds,
+                    "contacts": contact_matrix.contacts,
+                })
+            })
+            .into();
+        manifest["parameters"]["vaccination"] = args
+            .vaccinate
+            .zip(vaccination.as_ref())
+            .map(|(rule, vaccination)| {
+                serde_json::json!({
+                    "rule": rule.to_string(),
+                    "start": vaccination.start,
+                    "every": vaccination.every,
+                    "fraction": vaccination.fraction,
+                    "efficacy": vaccination.efficacy,
+                })
+            })
+            .into();
+        manifest["parameters"]["viral_load"] = viral_load
+            .map(|viral_load| {
+                serde_json::json!({
+                    "peak": viral_load.peak,
+                    "peak_time": viral_load.peak_time,
+                    "growth": viral_load.growth,
+                    "decay": viral_load.decay,
+                    "infectious": viral_load.infectious,
+                    "detection": viral_load.detection,
+                })
+            })
+            .into();
+        manifest["parameters"]["immunity"] = immunity
+            .map(|immunity| {
+                serde_json::json!({
+                    "protection": immunity.protection,
+                    "waning": immunity.waning.as_ref().map(Waning::parameters),
+                })
+            })
+            .into();
+        manifest["parameters"]["population_file"] = args
+            .population
+            .as_ref()
+            .zip(population.as_ref())
+            .map(|(path, population)| {
+                serde_json::json!({
+                    "path": path,
+                    "agents": population.persons.len(),
+                    "households": population.households,
+                    "groups": population.workplaces,
+                })
+            })
+            .into();
+        manifest["parameters"]["groups"] = group_rules
+            .as_ref()
+            .map(|group_rules| {
+                serde_json::json!({
+                    "groups": group_rules.groups,
+                    "contact_probability": group_rules.contact_probability,
+                    "quarantine_threshold": group_rules.quarantine_threshold,
+                    "quarantine_duration": group_rules.quarantine_duration,
+                })
+            })
+            .into();
+        #[cfg(feature = "net")]
+        {
+            manifest["parameters"]["net_k"] = net_k.into();
+            manifest["parameters"]["link_probability"] = link_probability.into();
+            manifest["parameters"]["agent_sociability"] =
+                agent_sociability_spec.as_ref().map(Spec::to_string).into();
+            for (name, factor) in link_factors {
+                manifest["parameters"][name] = factor.into();
+            }
+            manifest["parameters"]["weekend_days"] = weekend_days.clone().into();
+            manifest["parameters"]["awareness"] = awareness
+                .as_ref()
+                .map(|awareness| {
+                    serde_json::json!({
+                        "spreading_probability": awareness.spreading_probability,
+                        "forgetting_probability": awareness.forgetting_probability,
+                        "protection": awareness.protection,
+                        "initial_probability": awareness.initial_probability,
+                    })
+                })
+                .into();
+            manifest["components"]["network"] = network.into();
+        }
+        #[cfg(feature = "net-metrics")]
+        {
+            manifest["parameters"]["degree_bins"] = degree_bins.clone().into();
+        }
+        #[cfg(feature = "landscape")]
+        {
+            manifest["parameters"]["landscape_width"] = coord.width().into();
+            manifest["parameters"]["landscape_height"] = coord.height().into();
+            manifest["parameters"]["visit_distro"] = visit_spec.to_string().into();
+            manifest["parameters"]["agent_movement_scale"] = agent_movement_scale_spec
+                .as_ref()
+                .map(Spec::to_string)
+                .into();
+            manifest["parameters"]["cell_visits"] = cell_visits.into();
+            manifest["parameters"]["energy"] = metabolism
+                .map(|metabolism| {
+                    serde_json::json!({
+                        "initial": metabolism.initial,
+                        "per_step": metabolism.per_step,
+                        "per_visit": metabolism.per_visit,
+                        "infection": metabolism.infection,
+                        "intake": metabolism.intake,
+                        "resource_capacity": metabolism.capacity,
+                        "resource_regrowth": metabolism.regrowth,
+                    })
+                })
+                .into();
+            manifest["parameters"]["cell_decay"] = decay
+                .map(|decay| {
+                    serde_json::json!({
+                        "persistence": decay.persistence.map(|persistence| [persistence.scale, persistence.shape]),
+                        "seasonality": decay.seasonality.map(|seasonality| {
+                            serde_json::json!({
+                                "amplitude": seasonality.amplitude,
+                                "period": seasonality.period,
+                                "peak": seasonality.peak,
+                            })
+                        }),
+                    })
+                })
+                .into();
+            manifest["parameters"]["dose_response"] = dose_response
+                .as_ref()
+                .map(|dose_response| {
+                    serde_json::json!({
+                        "parameters": dose_response.parameters(),
+                        "dose_per_visit": dose_per_visit,
+                    })
+                })
+                .into();
+            manifest["components"]["landscape"] = landscape.into();
+            manifest["parameters"]["vectors"] = (!vector_borne_scenarios.is_empty())
+                .then(|| {
+                    serde_json::json!({
+                        "initial": vector_population.initial,
+                        "capacity": vector_population.capacity,
+                        "birth_probability": vector_population.birth_probability,
+                        "death_probability": vector_population.death_probability,
+                        "movement_probability": vector_population.movement_probability,
+                        "infection_probability": vector_population.infection_probability,
+                    })
+                })
+                .into();
+            for (entry, scenario) in manifest["scenarios"]
+                .as_array_mut()
+                .unwrap()
+                .iter_mut()
+                .zip(&scenarios)
+            {
+                entry["vector_borne"] = scenario.vector_borne.into();
+            }
+        }
+        #[cfg(feature = "graphics")]
+        {
+            manifest["output_parameters"]["render_every"] = render_every.into();
+            manifest["figures"] = figure_settings.manifest();
+        }
+        #[cfg(any(feature = "cells-csv", feature = "netcdf-output"))]
+        {
+            manifest["output_parameters"]["cells_every"] = cells_every.into();
+        }
+        #[cfg(feature = "network-snapshots")]
+        {
+            manifest["output_parameters"]["network_every"] = args.network_every.into();
+        }
+        #[cfg(feature = "csv-output")]
+        {
+            manifest["output_parameters"]["stratify"] = args
+                .stratify
+                .iter()
+                .map(|attribute| attribute.name())
+                .collect::<Vec<_>>()
+                .into();
+            manifest["output_parameters"]["age_groups"] = age_groups.clone().into();
+        }
+        #[cfg(feature = "jsonl")]
+        {
+            manifest["output_parameters"]["jsonl_to_stdout"] = jsonl_to_stdout.into();
+        }
+        #[cfg(feature = "rng-trace")]
+        {
+            manifest["output_parameters"]["trace_rng"] = args.trace_rng.into();
+        }
+        #[cfg(feature = "checkpoint")]
+        {
+            manifest["output_parameters"]["checkpoint_every"] = args.checkpoint_every.into();
+            manifest["resumed_from"] = args
+                .resume
+                .as_ref()
+                .map(|dir| dir.display().to_string())
+                .into();
+            manifest["branch_at"] = args.branch_at.into();
+            #[cfg(debug_assertions)]
+            {
+                manifest["output_parameters"]["snapshot_every"] = snapshot_every.into();
+                manifest["output_parameters"]["snapshots_len"] = snapshots_len.into();
+            }
+            manifest["warm_start"] = args
+                .warm_start
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .into();
+        }
+        let mut manifest_file = create_output_file(&manifest_path, manifest_err, existing)?;
+        serde_json::to_writer_pretty(&mut manifest_file, &manifest)
+            .map_err(std::io::Error::from)
+            .or_error(manifest_err)?;
+        writeln!(manifest_file).or_error(manifest_err)?;
+        manifest_file.flush().or_error(manifest_err)?;
+        eprintln!("Manifest saved to {}.", manifest_path.display());
+    }
+    // Bookkeeping: The first error of the outputs that did not stop the run sets the exit status
+    if !failures.is_empty() {
+        eprintln!(
+            "{} errors occurred; the results of this run are incomplete.",
+            failures.len()
+        );
+    }
+    failures.into_iter().next().map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shard_reads_k_of_n() {
+        let shard: Shard = "1/3".parse().unwrap();
+        assert_eq!((shard.index, shard.count), (1, 3));
+        assert_eq!(shard.to_string(), "1/3");
+        for s in ["3/3", "1", "a/3", "1/b", "-1/3", ""] {
+            assert!(s.parse::<Shard>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn shards_split_the_scenarios() {
+        let shards: Vec<Shard> = ["0/3", "1/3", "2/3"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(
+            (0..7)
+                .filter(|&id| shards[1].contains(id))
+                .collect::<Vec<_>>(),
+            [1, 4]
+        );
+        // Each scenario belongs to exactly one shard
+        for id in 0..10 {
+            assert_eq!(shards.iter().filter(|shard| shard.contains(id)).count(), 1);
         }
-        eprintln!("Move important output files to a safe location.\nAny csv, png, and mkv files will be removed next time you run this program.");
     }
 }
@@ -0,0 +1,234 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Merging the outputs of a run split into shards, such as the jobs of a cluster array job.
+//!
+//! Each shard simulates a slice of the scenarios into its own directory. Scenario directories keep the numbering of
+//! the whole run, so they never collide; `merge` copies them into one directory. Files at the top of the shard
+//! directories hold rows of several scenarios: CSV files and JSON Lines files are concatenated, keeping one header,
+//! and the manifests become one manifest listing the scenarios of every shard. Each shard summarizes only its own
+//! replicates, so summaries across replicates, such as extinction.csv and final_size_histogram.csv, are rebuilt
+//! from the outcomes of every scenario, sorted by scenario.
+
+use crate::outcomes;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+fn conflict(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} appears in more than one shard", path.display()),
+    )
+}
+
+/// Copies the directory `from` to `to`; files that exist in `to` are kept if `keep_existing` is set, or rewritten.
+fn copy_dir(from: &Path, to: &Path, keep_existing: bool) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target, keep_existing)?;
+        } else if !(keep_existing && target.exists()) {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends the lines of `from` to `to`, skipping the first `skip` lines.
+fn append_lines(from: &Path, to: &mut impl Write, skip: usize) -> io::Result<()> {
+    for line in BufReader::new(fs::File::open(from)?).lines().skip(skip) {
+        writeln!(to, "{}", line?)?;
+    }
+    Ok(())
+}
+
+/// Creates the file `path` and writes it with `write`.
+fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<fs::File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(fs::File::create(path)?);
+    write(&mut w)?;
+    w.flush()
+}
+
+/// Combines the manifests of the shards: the first manifest, with the scenarios of all shards sorted by id.
+fn merge_manifests(paths: &[PathBuf]) -> io::Result<serde_json::Value> {
+    let mut manifests = paths
+        .iter()
+        .map(|path| -> io::Result<serde_json::Value> {
+            Ok(serde_json::from_reader(BufReader::new(fs::File::open(
+                path,
+            )?))?)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut scenarios: Vec<serde_json::Value> = manifests
+        .iter_mut()
+        .filter_map(|manifest| manifest["scenarios"].as_array_mut().map(std::mem::take))
+        .flatten()
+        .collect();
+    scenarios.sort_by_key(|scenario| scenario["id"].as_u64());
+    let mut merged = manifests.swap_remove(0);
+    merged["scenarios"] = scenarios.into();
+    merged["shard"] = serde_json::Value::Null;
+    merged["merged_from"] = paths
+        .iter()
+        .map(|path| path.parent().unwrap_or(path).display().to_string())
+        .collect::<Vec<_>>()
+        .into();
+    Ok(merged)
+}
+
+/// Merges the output directories `shards` into `output_dir`.
+///
+/// The caller decides whether `output_dir` may hold earlier outputs. Files at the top of it are always rewritten;
+/// files of the scenario directories that exist are kept if `keep_existing` is set, as figures and videos are.
+pub fn merge(shards: &[PathBuf], output_dir: &Path, keep_existing: bool) -> io::Result<()> {
+    fs::create_dir_all(output_dir)?;
+    // Directories copied from the shards, to catch one that appears in more than one shard
+    let mut dirs: Vec<std::ffi::OsString> = Vec::new();
+    // Files at the top of the shard directories, by name, in the order of the shards
+    let mut files: Vec<(std::ffi::OsString, Vec<PathBuf>)> = Vec::new();
+    for shard in shards {
+        for entry in fs::read_dir(shard)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let name = entry.file_name();
+                if dirs.contains(&name) {
+                    return Err(conflict(&output_dir.join(name)));
+                }
+                copy_dir(&entry.path(), &output_dir.join(&name), keep_existing)?;
+                dirs.push(name);
+            } else {
+                let name = entry.file_name();
+                match files.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, paths)) => paths.push(entry.path()),
+                    None => files.push((name, vec![entry.path()])),
+                }
+            }
+        }
+    }
+    for (name, paths) in &files {
+        let target = output_dir.join(name);
+        let extension = Path::new(name).extension().and_then(|e| e.to_str());
+        if name == "manifest.json" {
+            let mut w = BufWriter::new(fs::File::create(&target)?);
+            serde_json::to_writer_pretty(&mut w, &merge_manifests(paths)?)?;
+            writeln!(w)?;
+            w.flush()?;
+        } else if name == "first_passage.csv" {
+            let mut passages = Vec::new();
+            for path in paths {
+                passages.extend(outcomes::read_passages(path)?);
+            }
+            passages.sort_by_key(|scenario| scenario.scenario);
+            write_file(&target, |w| outcomes::write_passages(w, &passages))?;
+            write_file(&output_dir.join("extinction.csv"), |w| {
+                outcomes::write_extinction(w, &passages)
+            })?;
+        } else if name == "final_sizes.csv" {
+            let mut sizes = Vec::new();
+            for path in paths {
+                sizes.extend(outcomes::read_final_sizes(path)?);
+            }
+            sizes.sort_by_key(|scenario| scenario.scenario);
+            write_file(&target, |w| outcomes::write_final_sizes(w, &sizes))?;
+            write_file(&output_dir.join("final_size_histogram.csv"), |w| {
+                outcomes::write_final_size_histogram(w, &sizes)
+            })?;
+        } else if name == "extinction.csv" || name == "final_size_histogram.csv" {
+            // Rebuilt along with first_passage.csv and final_sizes.csv
+        } else if extension == Some("csv") || extension == Some("jsonl") {
+            let header_lines = (extension == Some("csv")) as usize;
+            let mut w = BufWriter::new(fs::File::create(&target)?);
+            for (idx, path) in paths.iter().enumerate() {
+                append_lines(path, &mut w, if idx == 0 { 0 } else { header_lines })?;
+            }
+            w.flush()?;
+        } else if let [path] = paths.as_slice() {
+            fs::copy(path, target)?;
+        } else {
+            return Err(conflict(&target));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A new directory for the files of a test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("merge-{}-{}", name, std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A shard with the scenario `id`, its row of phases.csv, and its manifest
+    fn shard(dir: &Path, id: u32) -> PathBuf {
+        let shard = dir.join(format!("shard-{}", id));
+        fs::create_dir_all(shard.join(format!("scenario-{:02}", id))).unwrap();
+        fs::write(
+            shard.join(format!("scenario-{:02}/ts.csv", id)),
+            "Time\n0\n",
+        )
+        .unwrap();
+        fs::write(shard.join("phases.csv"), format!("Scenario\n{}\n", id)).unwrap();
+        let manifest = serde_json::json!({"scenarios": [{"id": id}], "shard": format!("{}/2", id)});
+        fs::write(shard.join("manifest.json"), manifest.to_string()).unwrap();
+        shard
+    }
+
+    #[test]
+    fn merge_keeps_one_header_and_every_scenario() {
+        let dir = test_dir("shards");
+        let shards = [shard(&dir, 1), shard(&dir, 0)];
+        let output_dir = dir.join("merged");
+        merge(&shards, &output_dir, false).unwrap();
+        assert!(output_dir.join("scenario-00/ts.csv").exists());
+        assert!(output_dir.join("scenario-01/ts.csv").exists());
+        let phases = fs::read_to_string(output_dir.join("phases.csv")).unwrap();
+        assert_eq!(phases, "Scenario\n1\n0\n");
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join("manifest.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            manifest["scenarios"],
+            serde_json::json!([{"id": 0}, {"id": 1}])
+        );
+        assert_eq!(manifest["shard"], serde_json::Value::Null);
+        assert_eq!(manifest["merged_from"].as_array().unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_refuses_a_scenario_in_two_shards() {
+        let dir = test_dir("conflict");
+        let shards = [shard(&dir, 0), shard(&dir, 0)];
+        let e = merge(&shards, &dir.join("merged"), false).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Mixing between age groups from a contact matrix, such as those of the POLYMOD survey, for transmission between
+//! agents whether they have links or not.
+//!
+//! A contact matrix file is a CSV file whose header and first column hold the lower bound of each age group, in time
+//! steps; the value at row i and column j is the mean number of contacts that a member of age group i has with members
+//! of age group j at each time step:
+//!
+//! ```text
+//! Age,0,20,65
+//! 0,8.5,4.1,0.6
+//! 20,3.2,9.7,1.1
+//! 65,1.0,2.4,3.9
+//! ```
+//!
+//! A survey measures contacts in its own population, whose age groups differ in size from those of the model, and
+//! contacts must be reciprocal: the members of group i meet the members of group j as often as the other way around.
+//! At each time step, the matrix is renormalized to the agents alive, N_i of them in group i: the contacts between
+//! groups i and j become the mean of those that each group reports, c_ij N_i and c_ji N_j, so that each member of
+//! group i has (c_ij N_i + c_ji N_j) / (2 N_i) contacts with group j. Each contact with an infected agent infects a
+//! susceptible agent as a contact over a link does.
+
+use crate::events::{InfectionEvent, Queue, Via};
+use crate::storage::Storage;
+use crate::tables;
+use crate::{AgentKey, Health};
+use rand::Rng;
+use rand_distr::{Distribution, Poisson};
+use std::io;
+use std::path::Path;
+
+/// Contacts between age groups
+pub struct ContactMatrix {
+    /// Lower bound of each age group, in time steps
+    pub bounds: Vec<f64>,
+    /// Mean contacts that a member of each age group has with the members of each age group, at each time step
+    pub contacts: Vec<Vec<f64>>,
+}
+
+impl ContactMatrix {
+    /// Reads a contact matrix file; see the module documentation for its format.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let (titles, rows) = tables::read_csv(io::BufReader::new(std::fs::File::open(path)?))?;
+        let bounds = titles
+            .iter()
+            .skip(1)
+            .map(|title| {
+                title
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid(format!("the age {} is not a number", title)))
+            })
+            .collect::<io::Result<Vec<f64>>>()?;
+        if bounds.is_empty() || bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(invalid(
+                "the header needs the increasing lower bounds of the age groups".to_string(),
+            ));
+        }
+        if rows.len() != bounds.len() || rows.iter().zip(&bounds).any(|(row, &b)| row[0] != b) {
+            return Err(invalid(
+                "the rows need the age groups of the header, in the same order".to_string(),
+            ));
+        }
+        let contacts: Vec<Vec<f64>> = rows.into_iter().map(|row| row[1..].to_vec()).collect();
+        if contacts
+            .iter()
+            .flatten()
+            .any(|&c| !(c >= 0.0 && c.is_finite()))
+        {
+            return Err(invalid("contacts are numbers, 0 or more".to_string()));
+        }
+        Ok(Self { bounds, contacts })
+    }
+
+    /// Contacts that each member of each age group has with each age group at each time step, reciprocal among groups
+    /// of these sizes
+    pub fn renormalized(&self, sizes: &[u32]) -> Vec<Vec<f64>> {
+        let groups = self.bounds.len();
+        (0..groups)
+            .map(|i| {
+                (0..groups)
+                    .map(|j| match sizes[i] {
+                        0 => 0.0,
+                        n_i => {
+                            (self.contacts[i][j] * n_i as f64
+                                + self.contacts[j][i] * sizes[j] as f64)
+                                / (2.0 * n_i as f64)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Infection spreads between age groups: each susceptible agent meets infected agents of each age group as many
+    /// times as a Poisson distribution draws, given the renormalized contacts and the fraction of infected agents in the
+    /// group, and `infects` decides whether each meeting infects it. `age_group` gives the age group of each agent, or
+    /// None for agents that do not mix, such as those in quarantine.
+    pub fn transmit<A, G, R, F>(
+        &self,
+        age_group: G,
+        health: &A,
+        next_health: &mut A,
+        mut infects: F,
+        rng: &mut R,
+        infections: &mut Queue<InfectionEvent>,
+    ) where
+        A: Storage<AgentKey, Health>,
+        G: Fn(AgentKey) -> Option<usize>,
+        R: Rng,
+        F: FnMut(&mut R, AgentKey) -> bool,
+    {
+        let groups = self.bounds.len();
+        let mut sizes = vec![0u32; groups];
+        let mut infected = vec![Vec::new(); groups];
+        for (k, &h) in health.iter() {
+            if let Some(group) = age_group(k) {
+                sizes[group] += 1;
+                if h == Health::I {
+                    infected[group].push(k);
+                }
+            }
+        }
+        let contacts = self.renormalized(&sizes);
+        for (k, &h) in health.iter() {
+            let group = match age_group(k) {
+                Some(group) if h == Health::S => group,
+                _ => continue,
+            };
+            // The first infected agent whose contact infects this one
+            let source = (0..groups).find_map(|j| {
+                let mean = contacts[group][j] * infected[j].len() as f64 / sizes[j].max(1) as f64;
+                if mean <= 0.0 {
+                    return None;
+                }
+                let meetings = Poisson::new(mean).unwrap().sample(rng) as u64;
+                (0..meetings)
+                    .find(|_| infects(rng, k))
+                    .map(|_| infected[j][rng.gen_range(0..infected[j].len())])
+            });
+            if let Some(source) = source {
+                if next_health[k] == Health::S {
+                    infections.publish(InfectionEvent {
+                        source: Some(source),
+                        target: k,
+                        via: Via::Mixing,
+                    });
+                }
+                next_health[k] = Health::I;
+            }
+        }
+    }
+}
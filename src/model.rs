@@ -0,0 +1,84 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Models other than the SIS model of `main()`, simulated by the same engine.
+//!
+//! A model keeps its own agents and cells, built from the storage, components, and deletion modules, and tells the
+//! engine how to advance one time step, what to measure, and when nothing is left to simulate. `simulate` steps a
+//! model through time and hands the model to the caller at the sampled time steps, to measure it, write the
+//! measurements with the tables module, or show it. The models at `examples/` are built this way; run one with
+//! `cargo run --release --example predator_prey`.
+
+use crate::streams::RandomStreams;
+use crate::tables::{Column, ColumnType};
+
+/// A model that the engine simulates
+pub trait Model {
+    /// Short name and description of each measurement, in the order of `measure`
+    fn metrics(&self) -> Vec<(&'static str, &'static str)>;
+
+    /// Measures the model at the start of a time step.
+    fn measure(&self) -> Vec<f64>;
+
+    /// Advances the model by one time step, drawing random numbers from the stream of each process.
+    fn step(&mut self, time_step: usize, rng: &mut RandomStreams);
+
+    /// Whether nothing is left to simulate, such as when every agent died
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+/// Columns of the time series of a model: the time step, then each measurement
+pub fn columns<M: Model + ?Sized>(model: &M) -> Vec<Column> {
+    let mut columns = vec![Column::new("time_step", "Time step", ColumnType::UInt32)];
+    columns.extend(model.metrics().into_iter().map(|(name, description)| {
+        Column::new(
+            name,
+            format!("{} {}", name, description),
+            ColumnType::Float64,
+        )
+    }));
+    columns
+}
+
+/// Simulates up to `time_steps` time steps of a model, sampling it every `sample_every` time steps, starting at time
+/// step 0; `sample` receives each sampled time step and the model at its start. The simulation stops early, after
+/// sampling the last time step, when the model finishes. Returns the number of time steps simulated.
+pub fn simulate<M: Model + ?Sized, E, F: FnMut(usize, &M) -> Result<(), E>>(
+    model: &mut M,
+    time_steps: usize,
+    sample_every: usize,
+    rng: &mut RandomStreams,
+    mut sample: F,
+) -> Result<usize, E> {
+    assert!(sample_every > 0, "sample_every must be positive");
+    for time_step in 0..time_steps {
+        let finished = model.finished();
+        if finished || time_step.is_multiple_of(sample_every) {
+            sample(time_step, model)?;
+        }
+        if finished {
+            return Ok(time_step);
+        }
+        #[cfg(feature = "rng-trace")]
+        rng.set_time_step(time_step);
+        model.step(time_step, rng);
+    }
+    Ok(time_steps)
+}
@@ -0,0 +1,135 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Modifiers of the probability of infection over each link, by the layer of the link and by the context of the
+//! exposure, such as a quarantine or a weekend.
+//!
+//! Each exposure of a susceptible agent to an infected friend passes through a `Pipeline` of modifiers; the product of
+//! their factors multiplies the probability of infection of the scenario, together with the susceptibility of the
+//! target. Exposures with a factor of 0 draw no random numbers, so a link with a quarantined end is inactive unless
+//! `QuarantineFactor` says otherwise. Write other modifiers, which see both ends of the link, where `main()` builds
+//! the pipeline.
+
+use crate::define_components;
+use crate::{AgentKey, LinkKey};
+
+/// Kind of relation that a link stands for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub enum Layer {
+    /// Friendship, formed by the network
+    #[default]
+    Social,
+    /// Members of the same household, from the initial population
+    Household,
+}
+
+define_components! {
+    /// Layer of each link; links without a value, such as those formed by the network, are social.
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct LinkLayers for LinkKey {
+        layer: Layer,
+    }
+}
+
+/// Exposure of a susceptible agent to an infected agent over a link
+pub struct Exposure {
+    pub link: LinkKey,
+    pub layer: Layer,
+    pub source: AgentKey,
+    pub target: AgentKey,
+    /// Whether either end of the link is in quarantine
+    pub quarantined: bool,
+    pub time_step: usize,
+}
+
+/// Factor of the probability of infection of an exposure
+pub trait Modifier: Send + Sync {
+    fn factor(&self, exposure: &Exposure) -> f64;
+}
+
+/// Factors of the social and household layers
+pub struct LayerFactors {
+    pub social: f64,
+    pub household: f64,
+}
+
+impl Modifier for LayerFactors {
+    fn factor(&self, exposure: &Exposure) -> f64 {
+        match exposure.layer {
+            Layer::Social => self.social,
+            Layer::Household => self.household,
+        }
+    }
+}
+
+/// Factor of links with an end in quarantine
+pub struct QuarantineFactor(pub f64);
+
+impl Modifier for QuarantineFactor {
+    fn factor(&self, exposure: &Exposure) -> f64 {
+        if exposure.quarantined {
+            self.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Factor of the days of the weekend; time steps count as days, and time step 0 is day 0 of the week.
+pub struct WeekendFactor {
+    pub factor: f64,
+    /// Days of the weekend, from 0 to 6
+    pub days: Vec<usize>,
+}
+
+impl Modifier for WeekendFactor {
+    fn factor(&self, exposure: &Exposure) -> f64 {
+        if self.days.contains(&(exposure.time_step % 7)) {
+            self.factor
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Modifiers applied to every exposure over a link, in order
+#[derive(Default)]
+pub struct Pipeline {
+    modifiers: Vec<Box<dyn Modifier>>,
+}
+
+impl Pipeline {
+    /// Adds a modifier at the end of the pipeline.
+    pub fn then<M: Modifier + 'static>(mut self, modifier: M) -> Self {
+        self.modifiers.push(Box::new(modifier));
+        self
+    }
+
+    /// Product of the factors of the modifiers; the pipeline stops at the first factor of 0.
+    pub fn factor(&self, exposure: &Exposure) -> f64 {
+        let mut product = 1.0;
+        for modifier in &self.modifiers {
+            product *= modifier.factor(exposure);
+            if product == 0.0 {
+                break;
+            }
+        }
+        product
+    }
+}
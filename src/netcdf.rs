@@ -0,0 +1,257 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! NetCDF output of the landscape: the health of every cell over time, as a `time × y × x` variable.
+//!
+//! The file follows the classic NetCDF format with 64-bit offsets and the CF conventions, so xarray, Panoply,
+//! and the NetCDF tools open it directly. The format is simple enough to write without the NetCDF C library:
+//! a header describes the dimensions and variables, the coordinates of cells follow, and then one record
+//! per time step holds the time step and the health of every cell. Records are appended as the simulation runs;
+//! `finish` writes their number into the header.
+
+use crate::Health;
+use std::io::{self, Seek, SeekFrom, Write};
+
+const NC_DIMENSION: u32 = 0x0A;
+const NC_VARIABLE: u32 = 0x0B;
+const NC_ATTRIBUTE: u32 = 0x0C;
+const NC_BYTE: u32 = 1;
+const NC_CHAR: u32 = 2;
+const NC_INT: u32 = 4;
+const NC_DOUBLE: u32 = 6;
+
+/// Dimension ids
+const TIME: u32 = 0;
+const Y: u32 = 1;
+const X: u32 = 2;
+
+enum Attribute<'a> {
+    Text(&'a str, &'a str),
+    Double(&'a str, f64),
+    Bytes(&'a str, &'a [u8]),
+}
+
+/// Bytes needed to round `len` up to a multiple of 4
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+struct Header(Vec<u8>);
+
+impl Header {
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.0.extend_from_slice(b);
+        self.0.resize(self.0.len() + padding(b.len()), 0);
+    }
+
+    fn name(&mut self, name: &str) {
+        self.u32(name.len() as u32);
+        self.bytes(name.as_bytes());
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        if attributes.is_empty() {
+            self.u32(0);
+            self.u32(0);
+            return;
+        }
+        self.u32(NC_ATTRIBUTE);
+        self.u32(attributes.len() as u32);
+        for attribute in attributes {
+            match *attribute {
+                Attribute::Text(name, text) => {
+                    self.name(name);
+                    self.u32(NC_CHAR);
+                    self.u32(text.len() as u32);
+                    self.bytes(text.as_bytes());
+                }
+                Attribute::Double(name, v) => {
+                    self.name(name);
+                    self.u32(NC_DOUBLE);
+                    self.u32(1);
+                    self.0.extend_from_slice(&v.to_be_bytes());
+                }
+                Attribute::Bytes(name, b) => {
+                    self.name(name);
+                    self.u32(NC_BYTE);
+                    self.u32(b.len() as u32);
+                    self.bytes(b);
+                }
+            }
+        }
+    }
+
+    fn variable(
+        &mut self,
+        name: &str,
+        dims: &[u32],
+        attributes: &[Attribute],
+        nc_type: u32,
+        vsize: usize,
+        begin: u64,
+    ) {
+        self.name(name);
+        self.u32(dims.len() as u32);
+        dims.iter().for_each(|&d| self.u32(d));
+        self.attributes(attributes);
+        self.u32(nc_type);
+        self.u32(vsize as u32);
+        self.0.extend_from_slice(&begin.to_be_bytes());
+    }
+}
+
+/// Builds the header; the offsets of the variables depend on its length, which does not depend on the offsets.
+fn header(width: usize, height: usize, attributes: &[Attribute], header_len: u64) -> Vec<u8> {
+    let mut h = Header(Vec::new());
+    h.bytes(b"CDF\x02");
+    // Number of records; finish() writes the final value
+    h.u32(0);
+    h.u32(NC_DIMENSION);
+    h.u32(3);
+    h.name("time");
+    h.u32(0);
+    h.name("y");
+    h.u32(height as u32);
+    h.name("x");
+    h.u32(width as u32);
+    h.attributes(attributes);
+    let y_begin = header_len;
+    let x_begin = y_begin + 4 * height as u64;
+    let time_begin = x_begin + 4 * width as u64;
+    let health_begin = time_begin + 4;
+    h.u32(NC_VARIABLE);
+    h.u32(4);
+    h.variable(
+        "time",
+        &[TIME],
+        &[
+            Attribute::Text("long_name", "time step"),
+            Attribute::Text("units", "1"),
+            Attribute::Text("axis", "T"),
+        ],
+        NC_INT,
+        4,
+        time_begin,
+    );
+    h.variable(
+        "y",
+        &[Y],
+        &[
+            Attribute::Text("long_name", "row of the cell"),
+            Attribute::Text("units", "1"),
+            Attribute::Text("axis", "Y"),
+        ],
+        NC_INT,
+        4 * height,
+        y_begin,
+    );
+    h.variable(
+        "x",
+        &[X],
+        &[
+            Attribute::Text("long_name", "column of the cell"),
+            Attribute::Text("units", "1"),
+            Attribute::Text("axis", "X"),
+        ],
+        NC_INT,
+        4 * width,
+        x_begin,
+    );
+    h.variable(
+        "health",
+        &[TIME, Y, X],
+        &[
+            Attribute::Text("long_name", "health of the cell"),
+            Attribute::Bytes("flag_values", &[Health::S as u8, Health::I as u8]),
+            Attribute::Text("flag_meanings", "susceptible infected"),
+        ],
+        NC_BYTE,
+        width * height + padding(width * height),
+        health_begin,
+    );
+    h.0
+}
+
+/// NetCDF file holding the landscape of one scenario
+pub struct LandscapeWriter<W: Write + Seek> {
+    w: W,
+    cells: usize,
+    records: u32,
+    /// Storage for one record, reused across time steps
+    record: Vec<u8>,
+}
+
+impl<W: Write + Seek> LandscapeWriter<W> {
+    /// Writes the header and the coordinates of cells; `attributes` become global attributes of the file.
+    pub fn create(
+        mut w: W,
+        width: usize,
+        height: usize,
+        title: &str,
+        attributes: &[(&str, f64)],
+    ) -> io::Result<Self> {
+        let mut global = vec![
+            Attribute::Text("Conventions", "CF-1.8"),
+            Attribute::Text("title", title),
+        ];
+        global.extend(
+            attributes
+                .iter()
+                .map(|&(name, v)| Attribute::Double(name, v)),
+        );
+        let header_len = header(width, height, &global, 0).len() as u64;
+        w.write_all(&header(width, height, &global, header_len))?;
+        for y in 0..height as u32 {
+            w.write_all(&y.to_be_bytes())?;
+        }
+        for x in 0..width as u32 {
+            w.write_all(&x.to_be_bytes())?;
+        }
+        let cells = width * height;
+        Ok(Self {
+            w,
+            cells,
+            records: 0,
+            record: Vec::with_capacity(4 + cells + padding(cells)),
+        })
+    }
+
+    /// Appends the health of every cell at a time step; cells are stored row by row.
+    pub fn push(&mut self, time_step: u32, cell_health: &[Health]) -> io::Result<()> {
+        assert_eq!(cell_health.len(), self.cells, "The landscape changed size");
+        self.record.clear();
+        self.record.extend_from_slice(&time_step.to_be_bytes());
+        self.record.extend(cell_health.iter().map(|&h| h as u8));
+        self.record.resize(4 + self.cells + padding(self.cells), 0);
+        self.w.write_all(&self.record)?;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Writes the number of records into the header.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.w.seek(SeekFrom::Start(4))?;
+        self.w.write_all(&self.records.to_be_bytes())?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
@@ -0,0 +1,328 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Preferential attachment: agents choose new friends with probability proportional to network degree.
+//!
+//! Degrees change every time a link forms, so a static sampler such as an alias table would need rebuilding after each link.
+//! `DegreeSampler` is a sum tree instead: sampling an agent and updating its weight both take O(log n).
+//! Excluding the agent itself and its friends means setting their weights to zero while the agent forms links,
+//! which costs O(degree log n) rather than a copy of the whole weight vector.
+
+use crate::deletion::forget_dead_pairs;
+use crate::events::{InfectionEvent, Queue, Via};
+use crate::storage::Storage;
+use crate::{AgentKey, Health, LinkKey};
+use rand::distributions::{Bernoulli, Distribution};
+use rand::Rng;
+use slotmap::SecondaryMap;
+
+/// Sum tree (Fenwick tree) of the sampling weights of agents
+pub struct DegreeSampler {
+    /// Partial sums; `tree[i]` holds the sum of the weights in `(i - (i & -i), i]`, counting from 1
+    tree: Vec<i64>,
+    /// Current weight of each agent
+    weights: Vec<i32>,
+}
+
+impl DegreeSampler {
+    pub fn new(weights: &[i32]) -> Self {
+        let n = weights.len();
+        let mut tree = vec![0i64; n + 1];
+        for (idx, &w) in weights.iter().enumerate() {
+            let i = idx + 1;
+            tree[i] += w as i64;
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= n {
+                tree[parent] += tree[i];
+            }
+        }
+        Self {
+            tree,
+            weights: weights.to_vec(),
+        }
+    }
+
+    /// Sum of all weights
+    pub fn total(&self) -> i64 {
+        let mut i = self.weights.len();
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.tree[i];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Changes the weight of an agent
+    pub fn set(&mut self, idx: usize, weight: i32) {
+        let delta = (weight - self.weights[idx]) as i64;
+        if delta == 0 {
+            return;
+        }
+        self.weights[idx] = weight;
+        let mut i = idx + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Chooses an agent with probability proportional to its weight; `None` if all weights are zero.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+        let total = self.total();
+        if total <= 0 {
+            return None;
+        }
+        let mut target = rng.gen_range(0..total);
+        let n = self.weights.len();
+        let mut pos = 0;
+        let mut step = n.checked_next_power_of_two().unwrap_or(0);
+        while step > 0 {
+            let next = pos + step;
+            if next <= n && self.tree[next] <= target {
+                pos = next;
+                target -= self.tree[next];
+            }
+            step >>= 1;
+        }
+        Some(pos)
+    }
+}
+
+/// Agents without friends make `net_k` new links; other agents make one new link with probability `link_distro`.
+/// `on_link` sees every new link. Returns the network degree of each agent in `keys_vec` after the new links form.
+pub fn form_links<
+    L: Storage<LinkKey, (AgentKey, AgentKey)>,
+    R: Rng,
+    F: FnMut(LinkKey, (AgentKey, AgentKey)),
+>(
+    keys_vec: &[AgentKey],
+    links: &mut L,
+    net_k: usize,
+    link_distro: Bernoulli,
+    rng: &mut R,
+    on_link: F,
+) -> Vec<i32> {
+    form_links_with(
+        keys_vec,
+        links,
+        net_k,
+        |rng, _agent| link_distro.sample(rng),
+        rng,
+        on_link,
+    )
+}
+
+/// Like `form_links`, with `makes_link` deciding whether each agent with friends makes a new link, such as by the
+/// sociability of the agent.
+pub fn form_links_with<
+    L: Storage<LinkKey, (AgentKey, AgentKey)>,
+    R: Rng,
+    M: FnMut(&mut R, AgentKey) -> bool,
+    F: FnMut(LinkKey, (AgentKey, AgentKey)),
+>(
+    keys_vec: &[AgentKey],
+    links: &mut L,
+    net_k: usize,
+    mut makes_link: M,
+    rng: &mut R,
+    mut on_link: F,
+) -> Vec<i32> {
+    let mut idx_map = SecondaryMap::with_capacity(keys_vec.len());
+    keys_vec.iter().enumerate().for_each(|(idx, &k)| {
+        idx_map.insert(k, idx);
+    });
+    // Friends of each agent, as indices into keys_vec
+    let mut friends = vec![Vec::new(); keys_vec.len()];
+    links.values().for_each(|&(key0, key1)| {
+        friends[idx_map[key0]].push(idx_map[key1]);
+        friends[idx_map[key1]].push(idx_map[key0]);
+    });
+    let mut weights_vec: Vec<i32> = friends.iter().map(|f| f.len() as i32).collect();
+    let mut sampler = DegreeSampler::new(&weights_vec);
+    keys_vec
+        .iter()
+        .enumerate()
+        .for_each(|(agent_idx, &agent_key)| {
+            let new_links = if weights_vec[agent_idx] == 0 {
+                net_k
+            } else if makes_link(rng, agent_key) {
+                1
+            } else {
+                0
+            };
+            if new_links > 0 {
+                // This agent cannot make a link to itself; set its weight to 0.
+                sampler.set(agent_idx, 0);
+                // Friends are ineligible for a new link; set friends' weights to 0.
+                for &friend_idx in &friends[agent_idx] {
+                    sampler.set(friend_idx, 0);
+                }
+                let mut new_friends = Vec::with_capacity(new_links);
+                while new_friends.len() < new_links {
+                    match sampler.sample(rng) {
+                        Some(friend_idx) => {
+                            new_friends.push(friend_idx);
+                            // Make friend ineligible for a new link; set its weight to 0.
+                            sampler.set(friend_idx, 0);
+                        }
+                        None => break,
+                    }
+                }
+                for &friend_idx in &friends[agent_idx] {
+                    sampler.set(friend_idx, weights_vec[friend_idx]);
+                }
+                for &friend_idx in &new_friends {
+                    let agents = (agent_key, keys_vec[friend_idx]);
+                    on_link(links.insert(agents), agents);
+                    weights_vec[agent_idx] += 1;
+                    weights_vec[friend_idx] += 1;
+                    friends[agent_idx].push(friend_idx);
+                    friends[friend_idx].push(agent_idx);
+                    sampler.set(friend_idx, weights_vec[friend_idx]);
+                }
+                sampler.set(agent_idx, weights_vec[agent_idx]);
+            }
+        });
+    weights_vec
+}
+
+/// Infection spreads along links: susceptible agents linked to infectious agents become infected next time step.
+pub fn transmit<L: Storage<LinkKey, (AgentKey, AgentKey)>, A: Storage<AgentKey, Health>, R: Rng>(
+    links: &L,
+    health: &A,
+    next_health: &mut A,
+    infection_distro: Bernoulli,
+    rng: &mut R,
+    infections: &mut Queue<InfectionEvent>,
+) {
+    transmit_with(
+        links,
+        health,
+        next_health,
+        |rng, _target| infection_distro.sample(rng),
+        rng,
+        infections,
+    );
+}
+
+/// Like `transmit`, with `infects` deciding whether each exposure infects its target, such as by the susceptibility
+/// of the target.
+pub fn transmit_with<
+    L: Storage<LinkKey, (AgentKey, AgentKey)>,
+    A: Storage<AgentKey, Health>,
+    R: Rng,
+    F: FnMut(&mut R, AgentKey) -> bool,
+>(
+    links: &L,
+    health: &A,
+    next_health: &mut A,
+    infects: F,
+    rng: &mut R,
+    infections: &mut Queue<InfectionEvent>,
+) {
+    transmit_over(
+        links,
+        |_key0, _key1| true,
+        health,
+        next_health,
+        infects,
+        rng,
+        infections,
+    );
+}
+
+/// Like `transmit_with`, only over the links between agents for which `active` returns true, such as agents out of
+/// quarantine. Inactive links draw no random numbers.
+pub fn transmit_over<
+    L: Storage<LinkKey, (AgentKey, AgentKey)>,
+    P: Fn(AgentKey, AgentKey) -> bool,
+    A: Storage<AgentKey, Health>,
+    R: Rng,
+    F: FnMut(&mut R, AgentKey) -> bool,
+>(
+    links: &L,
+    active: P,
+    health: &A,
+    next_health: &mut A,
+    mut infects: F,
+    rng: &mut R,
+    infections: &mut Queue<InfectionEvent>,
+) {
+    transmit_modified(
+        links,
+        |_link_key, source, target| active(source, target) as u8 as f64,
+        health,
+        next_health,
+        |rng, target, _factor| infects(rng, target),
+        rng,
+        infections,
+    );
+}
+
+/// Like `transmit_with`, with `factor` weighing each exposure over a link from a source to a target, such as by the
+/// layer of the link; `infects` receives the factor along with the target. Exposures with a factor of 0 draw no
+/// random numbers; see the modifiers module.
+pub fn transmit_modified<
+    L: Storage<LinkKey, (AgentKey, AgentKey)>,
+    M: Fn(LinkKey, AgentKey, AgentKey) -> f64,
+    A: Storage<AgentKey, Health>,
+    R: Rng,
+    F: FnMut(&mut R, AgentKey, f64) -> bool,
+>(
+    links: &L,
+    factor: M,
+    health: &A,
+    next_health: &mut A,
+    mut infects: F,
+    rng: &mut R,
+    infections: &mut Queue<InfectionEvent>,
+) {
+    let mut infect = |source: AgentKey, target: AgentKey, link_key: LinkKey| {
+        if next_health[target] == Health::S {
+            infections.publish(InfectionEvent {
+                source: Some(source),
+                target,
+                via: Via::Link(link_key),
+            });
+        }
+        next_health[target] = Health::I;
+    };
+    links.iter().for_each(|(link_key, &(key0, key1))| {
+        let h0 = health[key0];
+        let h1 = health[key1];
+        let mut exposed = |source: AgentKey, target: AgentKey| {
+            let factor = factor(link_key, source, target);
+            factor > 0.0 && infects(rng, target, factor)
+        };
+        if h0 == Health::S && h1 == Health::I && exposed(key1, key0) {
+            infect(key1, key0, link_key);
+        }
+        if h1 == Health::S && h0 == Health::I && exposed(key0, key1) {
+            infect(key0, key1, link_key);
+        }
+    });
+}
+
+/// Removes the links of agents that died
+pub fn prune<L: Storage<LinkKey, (AgentKey, AgentKey)>, A: Storage<AgentKey, Health>>(
+    links: &mut L,
+    health: &A,
+) {
+    forget_dead_pairs(links, &|k| health.contains_key(k));
+}
@@ -0,0 +1,83 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Observation model: the counts that a surveillance system would report from the true incidence of a scenario.
+//!
+//! Each new infection is reported with `reporting_probability`, after a delay, in time steps, drawn from `delay`;
+//! reports that would arrive after the last time step are missing, as in data collected in real time. Time steps are
+//! days: the reports of each day are scaled by the effect of its day of the week, with time step 0 on the first day,
+//! and then observed with negative binomial noise of dispersion `dispersion`, where smaller dispersions give noisier
+//! counts. Without a dispersion, the observed count is the scaled number of reports, rounded.
+
+use crate::distributions::BoxedSampler;
+use rand::RngCore;
+use rand_distr::{Binomial, Distribution, Gamma, Poisson};
+
+/// Reporting process of a surveillance system
+pub struct Observation {
+    pub reporting_probability: f64,
+    pub delay: Option<BoxedSampler>,
+    /// Multiplier of the reports of each day of the week; effects that average 1 keep the weekly totals
+    pub weekday_effects: Option<[f64; 7]>,
+    pub dispersion: Option<f64>,
+}
+
+impl Observation {
+    /// Observed counts at each time step, from the new infections at each time step
+    pub fn observe(&self, incidence: &[u32], rng: &mut dyn RngCore) -> Vec<u32> {
+        let mut reports = vec![0u32; incidence.len()];
+        for (time_step, &infections) in incidence.iter().enumerate() {
+            let reported = Binomial::new(infections as u64, self.reporting_probability)
+                .unwrap()
+                .sample(rng);
+            for _ in 0..reported {
+                let delay = self
+                    .delay
+                    .as_ref()
+                    .map_or(0.0, |delay| delay.sample(rng).round().max(0.0));
+                if let Some(reports) = reports.get_mut(time_step.saturating_add(delay as usize)) {
+                    *reports += 1;
+                }
+            }
+        }
+        reports
+            .iter()
+            .enumerate()
+            .map(|(time_step, &reports)| {
+                let expected = reports as f64
+                    * self
+                        .weekday_effects
+                        .map_or(1.0, |effects| effects[time_step % 7]);
+                match self.dispersion {
+                    // Negative binomial counts, as a Poisson distribution whose rate follows a gamma distribution
+                    Some(dispersion) if expected > 0.0 => {
+                        let rate = Gamma::new(dispersion, expected / dispersion)
+                            .unwrap()
+                            .sample(rng);
+                        if rate > 0.0 {
+                            Poisson::new(rate).unwrap().sample(rng) as u32
+                        } else {
+                            0
+                        }
+                    }
+                    _ => expected.round() as u32,
+                }
+            })
+            .collect()
+    }
+}
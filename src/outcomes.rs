@@ -0,0 +1,364 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Outcomes of each scenario, and their summaries across the replicates of each infection probability.
+//!
+//! A run writes the outcomes of its scenarios and their summaries together. The summaries depend only on the outcomes,
+//! so `merge` rebuilds them from the outcomes of every shard instead of concatenating summaries of partial replicates.
+
+use crate::stats::quantile;
+use crate::tables::{self, Column, ColumnType, Value};
+use std::fs;
+use std::io::{self, BufReader, Write};
+use std::path::Path;
+
+/// First passages of a scenario
+#[derive(Clone, Copy, Debug)]
+pub struct Passages {
+    pub infection_probability: f64,
+    pub replicate: u32,
+    pub scenario: u32,
+    /// Time step at which the infection went extinct, with no infected agents, cells, or vectors left
+    pub extinct_at: Option<usize>,
+    /// Time step at which the prevalence among agents first reached prevalence_threshold
+    pub threshold_at: Option<usize>,
+}
+
+/// First passages of each scenario: the time steps at which the infection went extinct and at which the prevalence
+/// among agents reached prevalence_threshold, missing when they did not happen
+pub fn first_passage_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("replicate", "Replicate", ColumnType::UInt32),
+        Column::new("scenario", "Scenario", ColumnType::UInt32),
+        Column::new("extinction_time", "Extinction time", ColumnType::UInt32),
+        Column::new("threshold_time", "Threshold time", ColumnType::UInt32),
+    ]
+}
+
+/// Extinction output: one row per infection probability, summarizing the first passages of its replicates
+pub fn extinction_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("replicates", "Replicates", ColumnType::UInt32),
+        Column::new("extinct", "Extinct", ColumnType::UInt32),
+        Column::new(
+            "extinction_probability",
+            "Extinction probability",
+            ColumnType::Float64,
+        ),
+        Column::new(
+            "mean_extinction_time",
+            "Mean extinction time",
+            ColumnType::Float64,
+        ),
+        Column::new(
+            "median_extinction_time",
+            "Median extinction time",
+            ColumnType::Float64,
+        ),
+        Column::new(
+            "threshold_probability",
+            "Threshold probability",
+            ColumnType::Float64,
+        ),
+        Column::new(
+            "mean_threshold_time",
+            "Mean threshold time",
+            ColumnType::Float64,
+        ),
+        Column::new(
+            "median_threshold_time",
+            "Median threshold time",
+            ColumnType::Float64,
+        ),
+    ]
+}
+
+/// Reads a CSV file of outcomes, checking that its header has these columns.
+fn read_outcomes(path: &Path, columns: &[Column]) -> io::Result<Vec<Vec<f64>>> {
+    let (titles, rows) = tables::read_csv(BufReader::new(fs::File::open(path)?))?;
+    if !titles.iter().eq(columns.iter().map(|column| &column.title)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} does not have the expected columns", path.display()),
+        ));
+    }
+    Ok(rows)
+}
+
+/// Scenarios of each infection probability, which follow one another by replicate
+fn by_infection_probability<T>(
+    outcomes: &[T],
+    first_scenario: impl Fn(&T) -> u32,
+) -> impl Iterator<Item = &[T]> {
+    outcomes.chunk_by(move |a, b| first_scenario(a) == first_scenario(b))
+}
+
+/// Reads first_passage.csv.
+pub fn read_passages(path: &Path) -> io::Result<Vec<Passages>> {
+    // Missing time steps read as NaN
+    let time_step = |at: f64| (!at.is_nan()).then_some(at as usize);
+    Ok(read_outcomes(path, &first_passage_columns())?
+        .into_iter()
+        .map(|row| Passages {
+            infection_probability: row[0],
+            replicate: row[1] as u32,
+            scenario: row[2] as u32,
+            extinct_at: time_step(row[3]),
+            threshold_at: time_step(row[4]),
+        })
+        .collect())
+}
+
+/// Writes first_passage.csv.
+pub fn write_passages<W: Write>(w: &mut W, passages: &[Passages]) -> io::Result<()> {
+    // Missing values stay empty
+    let time_step = |at: Option<usize>| at.map_or(Value::Utf8(""), |at| Value::UInt32(at as u32));
+    tables::write_csv_header(w, &first_passage_columns())?;
+    for scenario in passages {
+        let row = [
+            Value::Float64(scenario.infection_probability),
+            Value::UInt32(scenario.replicate),
+            Value::UInt32(scenario.scenario),
+            time_step(scenario.extinct_at),
+            time_step(scenario.threshold_at),
+        ];
+        tables::write_csv_row(w, &row)?;
+    }
+    Ok(())
+}
+
+/// Writes extinction.csv from the first passages of every scenario of a run, sorted by scenario.
+pub fn write_extinction<W: Write>(w: &mut W, passages: &[Passages]) -> io::Result<()> {
+    tables::write_csv_header(w, &extinction_columns())?;
+    let statistic = |times: &[f64], q: f64| {
+        quantile(times.iter().copied(), q).map_or(Value::Utf8(""), Value::Float64)
+    };
+    for replicates in by_infection_probability(passages, |s| s.scenario - s.replicate) {
+        let [extinction_times, threshold_times]: [Vec<f64>; 2] =
+            [|s: &Passages| s.extinct_at, |s: &Passages| s.threshold_at].map(|passage| {
+                replicates
+                    .iter()
+                    .filter_map(passage)
+                    .map(|at| at as f64)
+                    .collect()
+            });
+        let mean = |times: &[f64]| match times.len() {
+            0 => Value::Utf8(""),
+            len => Value::Float64(times.iter().sum::<f64>() / len as f64),
+        };
+        let n = replicates.len() as f64;
+        let row = [
+            Value::Float64(replicates[0].infection_probability),
+            Value::UInt32(replicates.len() as u32),
+            Value::UInt32(extinction_times.len() as u32),
+            Value::Float64(extinction_times.len() as f64 / n),
+            mean(&extinction_times),
+            statistic(&extinction_times, 0.5),
+            Value::Float64(threshold_times.len() as f64 / n),
+            mean(&threshold_times),
+            statistic(&threshold_times, 0.5),
+        ];
+        tables::write_csv_row(w, &row)?;
+    }
+    Ok(())
+}
+
+/// Final size of a scenario
+#[derive(Clone, Copy, Debug)]
+pub struct FinalSize {
+    pub infection_probability: f64,
+    pub replicate: u32,
+    pub scenario: u32,
+    /// Infections of the scenario, seeds included
+    pub infections: usize,
+}
+
+/// Final size of each scenario: its infections, seeds included
+pub fn final_size_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("replicate", "Replicate", ColumnType::UInt32),
+        Column::new("scenario", "Scenario", ColumnType::UInt32),
+        Column::new("final_size", "Final size", ColumnType::UInt64),
+    ]
+}
+
+/// Histogram of final sizes: one row per infection probability and bin, with the replicates whose final size falls in
+/// the bin, their fraction, and that fraction per final size in the bin
+pub fn final_size_histogram_columns() -> Vec<Column> {
+    vec![
+        Column::new(
+            "infection_probability",
+            "Infection Probability",
+            ColumnType::Float64,
+        ),
+        Column::new("smallest", "Smallest final size", ColumnType::UInt64),
+        Column::new("largest", "Largest final size", ColumnType::UInt64),
+        Column::new("replicates", "Replicates", ColumnType::UInt32),
+        Column::new("fraction", "Fraction", ColumnType::Float64),
+        Column::new("density", "Density", ColumnType::Float64),
+    ]
+}
+
+/// Reads final_sizes.csv.
+pub fn read_final_sizes(path: &Path) -> io::Result<Vec<FinalSize>> {
+    Ok(read_outcomes(path, &final_size_columns())?
+        .into_iter()
+        .map(|row| FinalSize {
+            infection_probability: row[0],
+            replicate: row[1] as u32,
+            scenario: row[2] as u32,
+            infections: row[3] as usize,
+        })
+        .collect())
+}
+
+/// Writes final_sizes.csv.
+pub fn write_final_sizes<W: Write>(w: &mut W, sizes: &[FinalSize]) -> io::Result<()> {
+    tables::write_csv_header(w, &final_size_columns())?;
+    for scenario in sizes {
+        let row = [
+            Value::Float64(scenario.infection_probability),
+            Value::UInt32(scenario.replicate),
+            Value::UInt32(scenario.scenario),
+            Value::UInt64(scenario.infections as u64),
+        ];
+        tables::write_csv_row(w, &row)?;
+    }
+    Ok(())
+}
+
+/// Writes final_size_histogram.csv from the final sizes of every scenario of a run, sorted by scenario. The bins double
+/// in width: [1, 1], [2, 3], [4, 7], and so on; every infection probability has the same bins.
+pub fn write_final_size_histogram<W: Write>(w: &mut W, sizes: &[FinalSize]) -> io::Result<()> {
+    // Bin 0 holds the final size 0, and bin k the final sizes from 2^(k-1) to 2^k - 1
+    let bin = |size: usize| (usize::BITS - size.leading_zeros()) as usize;
+    let bin_range = |bin: usize| match bin {
+        0 => (0, 0),
+        bin => (1 << (bin - 1), (1 << bin) - 1),
+    };
+    let bins = sizes.iter().map(|scenario| bin(scenario.infections));
+    let (first_bin, last_bin) = (bins.clone().min().unwrap_or(0), bins.max().unwrap_or(0));
+    tables::write_csv_header(w, &final_size_histogram_columns())?;
+    for replicates in by_infection_probability(sizes, |s| s.scenario - s.replicate) {
+        let mut counts = vec![0; last_bin - first_bin + 1];
+        for scenario in replicates {
+            counts[bin(scenario.infections) - first_bin] += 1;
+        }
+        for (idx, &count) in counts.iter().enumerate() {
+            let (smallest, largest) = bin_range(first_bin + idx);
+            let fraction = count as f64 / replicates.len() as f64;
+            let row = [
+                Value::Float64(replicates[0].infection_probability),
+                Value::UInt64(smallest),
+                Value::UInt64(largest),
+                Value::UInt32(count),
+                Value::Float64(fraction),
+                Value::Float64(fraction / (largest - smallest + 1) as f64),
+            ];
+            tables::write_csv_row(w, &row)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two infection probabilities with two replicates each
+    fn passages() -> Vec<Passages> {
+        let passage =
+            |infection_probability, replicate, scenario, extinct_at, threshold_at| Passages {
+                infection_probability,
+                replicate,
+                scenario,
+                extinct_at,
+                threshold_at,
+            };
+        vec![
+            passage(0.1, 0, 0, Some(10), None),
+            passage(0.1, 1, 1, Some(20), None),
+            passage(0.2, 0, 2, Some(30), Some(5)),
+            passage(0.2, 1, 3, None, Some(7)),
+        ]
+    }
+
+    #[test]
+    fn extinction_summarizes_the_replicates_of_each_infection_probability() {
+        let mut csv = Vec::new();
+        write_extinction(&mut csv, &passages()).unwrap();
+        let rows: Vec<&str> = std::str::from_utf8(&csv).unwrap().lines().skip(1).collect();
+        assert_eq!(rows, ["0.1,2,2,1,15,15,0,,", "0.2,2,1,0.5,30,30,1,6,6"]);
+    }
+
+    #[test]
+    fn first_passages_read_back_as_written() {
+        let path = std::env::temp_dir().join(format!("first_passage-{}.csv", std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        write_passages(&mut file, &passages()).unwrap();
+        drop(file);
+        let read = read_passages(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", passages()));
+    }
+
+    #[test]
+    fn final_size_bins_double_in_width() {
+        let size = |infection_probability, replicate, scenario, infections| FinalSize {
+            infection_probability,
+            replicate,
+            scenario,
+            infections,
+        };
+        let sizes = [
+            size(0.1, 0, 0, 1),
+            size(0.1, 1, 1, 3),
+            size(0.2, 0, 2, 2),
+            size(0.2, 1, 3, 6),
+        ];
+        let mut csv = Vec::new();
+        write_final_size_histogram(&mut csv, &sizes).unwrap();
+        let rows: Vec<&str> = std::str::from_utf8(&csv).unwrap().lines().skip(1).collect();
+        assert_eq!(
+            rows,
+            [
+                "0.1,1,1,1,0.5,0.5",
+                "0.1,2,3,1,0.5,0.25",
+                "0.1,4,7,0,0,0",
+                "0.2,1,1,0,0,0",
+                "0.2,2,3,1,0.5,0.25",
+                "0.2,4,7,1,0.5,0.125",
+            ]
+        );
+    }
+}
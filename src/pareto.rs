@@ -0,0 +1,92 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Multi-objective comparison of scenarios: summaries of their time series to minimize or maximize, such as the peak
+//! of infections and the final number of vaccinated agents, and the scenarios that no other scenario beats on every
+//! objective at once, the Pareto front.
+
+use crate::analysis::Statistic;
+use std::fmt;
+
+/// Summary of a metric to minimize or maximize
+#[derive(Clone, Debug, PartialEq)]
+pub struct Objective {
+    pub statistic: Statistic,
+    /// Name of the metric, such as i
+    pub metric: String,
+    pub maximize: bool,
+}
+
+impl Objective {
+    /// Objective from a specification such as `peak:i`: a statistic, either final, peak, mean, or total, and the name
+    /// of a metric.
+    pub fn parse(spec: &str, maximize: bool) -> Result<Self, String> {
+        let (statistic, metric) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("{} must be STATISTIC:METRIC, such as peak:i", spec))?;
+        Ok(Self {
+            statistic: Statistic::parse(statistic)?,
+            metric: metric.to_string(),
+            maximize,
+        })
+    }
+
+    /// Value of the objective for a time series; NaN for empty series
+    pub fn evaluate(&self, series: &[f64]) -> f64 {
+        self.statistic.evaluate(series)
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let goal = if self.maximize {
+            "maximized"
+        } else {
+            "minimized"
+        };
+        write!(f, "{} {} ({})", self.statistic.name(), self.metric, goal)
+    }
+}
+
+/// Whether the values `a` beat the values `b`: they are as good on every objective and better on one.
+fn dominates(objectives: &[Objective], a: &[f64], b: &[f64]) -> bool {
+    let mut better = false;
+    for ((objective, &a), &b) in objectives.iter().zip(a).zip(b) {
+        let (a, b) = if objective.maximize { (-a, -b) } else { (a, b) };
+        if a > b {
+            return false;
+        }
+        better |= a < b;
+    }
+    better
+}
+
+/// Whether each point, the values of the objectives for a scenario, lies on the Pareto front. Points with missing
+/// values never do, nor do they beat other points.
+pub fn front(objectives: &[Objective], points: &[Vec<f64>]) -> Vec<bool> {
+    let complete = |point: &[f64]| point.iter().all(|value| !value.is_nan());
+    points
+        .iter()
+        .map(|point| {
+            complete(point)
+                && !points
+                    .iter()
+                    .any(|other| complete(other) && dominates(objectives, other, point))
+        })
+        .collect()
+}
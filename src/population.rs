@@ -0,0 +1,461 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Initial populations: synthetic agents with ages, households, and workplaces that match marginal distributions, or
+//! agents read from a file.
+//!
+//! Households come first: each household draws its size from the relative frequencies of households of 1, 2, 3, ...
+//! members, until the population is complete. Ages follow conditional sampling: the first member of each household is
+//! an adult, drawn from the marginal distribution of ages above `adult_age`, and the other members are drawn from the
+//! whole marginal distribution. The marginal distribution of ages gives the relative frequency of each band between
+//! consecutive bounds, and ages are uniform within a band. Last, the members of working age, from `adult_age` until
+//! `retirement_age`, fill workplaces in a random order, one workplace at a time, each of a size drawn from its
+//! distribution.
+//!
+//! A population file lists one agent per row, in CSV or, with the parquet-input feature, in Parquet. Every column is
+//! optional: `health`, S or I, or 0 or 1; `age`, in time steps; `household` and `group`, as non-negative integers;
+//! `x` and `y`, the cell around which the agent visits the landscape; and the parameters of the agent, named as in
+//! `AgentTraits::FIELDS`.
+
+use crate::define_components;
+use crate::distributions::BoxedSampler;
+use crate::heterogeneity::AgentTraits;
+use crate::AgentKey;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// An agent of an initial population
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Person {
+    /// Age, in time steps, if the population has ages
+    pub age: Option<f64>,
+    pub household: Option<u32>,
+    pub workplace: Option<u32>,
+    pub infected: bool,
+    /// Cell around which the agent visits the landscape, if the population has positions
+    pub home: Option<[f64; 2]>,
+    /// Parameters of the agent that the population gives, in the order of `AgentTraits::FIELDS`
+    pub traits: [Option<f64>; 4],
+}
+
+define_components! {
+    /// Cell around which each agent visits the landscape, instead of the center of the landscape
+    #[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AgentHomes for AgentKey {
+        home: [f64; 2],
+    }
+}
+
+/// Marginal distribution of ages: relative frequencies of the bands between consecutive bounds
+struct AgeMarginal {
+    bounds: Vec<f64>,
+    /// Bands of every age, and bands of adults, truncated at the age of adulthood
+    all: WeightedIndex<f64>,
+    adults: WeightedIndex<f64>,
+    adult_age: f64,
+}
+
+impl AgeMarginal {
+    fn sample<R: Rng>(&self, adult: bool, rng: &mut R) -> f64 {
+        let band = if adult {
+            self.adults.sample(rng)
+        } else {
+            self.all.sample(rng)
+        };
+        let (low, high) = (self.bounds[band], self.bounds[band + 1]);
+        let low = if adult { low.max(self.adult_age) } else { low };
+        rng.gen_range(low..high)
+    }
+}
+
+/// Marginal distributions of a synthetic population
+pub struct SyntheticPopulation {
+    household_sizes: WeightedIndex<f64>,
+    ages: Option<AgeMarginal>,
+    workplace_size: Option<BoxedSampler>,
+    retirement_age: f64,
+}
+
+/// An initial population, and its number of households and workplaces
+pub struct Population {
+    pub persons: Vec<Person>,
+    pub households: u32,
+    pub workplaces: u32,
+}
+
+impl SyntheticPopulation {
+    /// Checks the marginal distributions: `household_sizes` holds the relative frequency of households of 1, 2, 3, ...
+    /// members, and `ages`, if any, holds the bounds of the bands of ages and the relative frequency of each band.
+    /// Errors name the parameter at fault.
+    pub fn new(
+        household_sizes: &[f64],
+        ages: Option<(&[f64], &[f64])>,
+        adult_age: f64,
+        retirement_age: f64,
+        workplace_size: Option<BoxedSampler>,
+    ) -> Result<Self, (&'static str, String)> {
+        let household_sizes =
+            WeightedIndex::new(household_sizes).map_err(|e| ("household_sizes", e.to_string()))?;
+        let ages = match ages {
+            Some((bounds, weights)) => {
+                if bounds.len() != weights.len() + 1 || bounds.windows(2).any(|w| w[0] >= w[1]) {
+                    return Err((
+                        "age_bands",
+                        "increasing bounds, one more than the weights in age_weights".to_string(),
+                    ));
+                }
+                let all =
+                    WeightedIndex::new(weights).map_err(|e| ("age_weights", e.to_string()))?;
+                let adults =
+                    WeightedIndex::new(bounds.windows(2).zip(weights).map(|(w, &weight)| {
+                        weight * ((w[1] - adult_age.max(w[0])) / (w[1] - w[0])).max(0.0)
+                    }))
+                    .map_err(|e| {
+                        (
+                            "adult_age",
+                            format!("leaves no adults in age_weights: {}", e),
+                        )
+                    })?;
+                Some(AgeMarginal {
+                    bounds: bounds.to_vec(),
+                    all,
+                    adults,
+                    adult_age,
+                })
+            }
+            None => None,
+        };
+        Ok(Self {
+            household_sizes,
+            ages,
+            workplace_size,
+            retirement_age,
+        })
+    }
+
+    /// Whether the agents of the population have ages
+    pub fn ages(&self) -> bool {
+        self.ages.is_some()
+    }
+
+    /// Draws a population of `n` agents; the last household can be smaller than its draw.
+    pub fn generate<R: Rng>(&self, n: usize, rng: &mut R) -> Population {
+        let mut persons = Vec::with_capacity(n);
+        let mut households = 0;
+        while persons.len() < n {
+            let size = (self.household_sizes.sample(rng) + 1).min(n - persons.len());
+            for member in 0..size {
+                persons.push(Person {
+                    age: self.ages.as_ref().map(|ages| ages.sample(member == 0, rng)),
+                    household: Some(households),
+                    ..Person::default()
+                });
+            }
+            households += 1;
+        }
+        let mut workplaces = 0;
+        if let Some(workplace_size) = &self.workplace_size {
+            let adult_age = self.ages.as_ref().map_or(0.0, |ages| ages.adult_age);
+            let mut workers: Vec<usize> = (0..n)
+                .filter(|&i| {
+                    persons[i]
+                        .age
+                        .is_none_or(|age| age >= adult_age && age < self.retirement_age)
+                })
+                .collect();
+            workers.shuffle(rng);
+            let mut workers = workers.into_iter().peekable();
+            while workers.peek().is_some() {
+                let size = workplace_size.sample(rng).round().max(1.0) as usize;
+                for i in workers.by_ref().take(size) {
+                    persons[i].workplace = Some(workplaces);
+                }
+                workplaces += 1;
+            }
+        }
+        Population {
+            persons,
+            households,
+            workplaces,
+        }
+    }
+}
+
+/// Columns of a population file
+const COLUMNS: [&str; 10] = [
+    "health",
+    "age",
+    "household",
+    "group",
+    "x",
+    "y",
+    "recovery_probability",
+    "susceptibility",
+    "movement_scale",
+    "sociability",
+];
+
+impl Population {
+    /// Reads the agents of a population file: Parquet if its extension is `.parquet`, and CSV otherwise. Groups are
+    /// numbered from 0, so the population has as many workplaces as its largest group plus one.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let (titles, rows) = if path
+            .extension()
+            .is_some_and(|extension| extension == "parquet")
+        {
+            read_parquet(path)?
+        } else {
+            read_csv(io::BufReader::new(std::fs::File::open(path)?))?
+        };
+        let columns = titles
+            .iter()
+            .map(|title| {
+                COLUMNS
+                    .iter()
+                    .position(|column| column == title)
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "unknown column {}; the columns are {}",
+                            title,
+                            COLUMNS.join(", ")
+                        ))
+                    })
+            })
+            .collect::<io::Result<Vec<usize>>>()?;
+        let has = |name: &str| titles.iter().any(|title| title == name);
+        if has("x") != has("y") {
+            return Err(invalid("x and y go together".to_string()));
+        }
+        if rows.is_empty() {
+            return Err(invalid("the file lists no agents".to_string()));
+        }
+        let mut persons = Vec::with_capacity(rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            let mut person = Person::default();
+            let mut home = [0.0; 2];
+            for (&column, &value) in columns.iter().zip(row) {
+                let name = COLUMNS[column];
+                let id = || {
+                    if value >= 0.0 && value.fract() == 0.0 && value <= u32::MAX as f64 {
+                        Ok(value as u32)
+                    } else {
+                        Err(invalid(format!(
+                            "agent {} has {} {}, which is not a non-negative integer",
+                            i, name, value
+                        )))
+                    }
+                };
+                let check = |valid: bool, requirement: &str| {
+                    if valid {
+                        Ok(value)
+                    } else {
+                        Err(invalid(format!(
+                            "agent {} has {} {}, which is not {}",
+                            i, name, value, requirement
+                        )))
+                    }
+                };
+                match name {
+                    "health" => {
+                        person.infected = check(value == 0.0 || value == 1.0, "S or I")? == 1.0
+                    }
+                    "age" => person.age = Some(check(value >= 0.0, "non-negative")?),
+                    "household" => person.household = Some(id()?),
+                    "group" => person.workplace = Some(id()?),
+                    "x" => home[0] = check(value.is_finite(), "finite")?,
+                    "y" => home[1] = check(value.is_finite(), "finite")?,
+                    "recovery_probability" => {
+                        person.traits[0] =
+                            Some(check((0.0..=1.0).contains(&value), "a probability")?)
+                    }
+                    _ => {
+                        let field = AgentTraits::FIELDS
+                            .iter()
+                            .position(|&field| field == name)
+                            .unwrap();
+                        person.traits[field] = Some(check(value >= 0.0, "non-negative")?);
+                    }
+                }
+            }
+            if has("x") {
+                person.home = Some(home);
+            }
+            persons.push(person);
+        }
+        let households = persons
+            .iter()
+            .filter_map(|person| person.household)
+            .collect::<BTreeSet<u32>>()
+            .len() as u32;
+        let workplaces = persons
+            .iter()
+            .filter_map(|person| person.workplace)
+            .max()
+            .map_or(0, |group| group + 1);
+        Ok(Self {
+            persons,
+            households,
+            workplaces,
+        })
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Number in a column of a population file; health takes the labels S and I as well.
+fn parse(title: &str, text: &str) -> io::Result<f64> {
+    match (title, text.trim()) {
+        ("health", "S") => Ok(0.0),
+        ("health", "I") => Ok(1.0),
+        ("health", text) if text.parse::<f64>().is_err() => {
+            Err(invalid(format!("health {} is neither S nor I", text)))
+        }
+        (_, text) => text
+            .parse()
+            .map_err(|_| invalid(format!("{} {} is not a number", title, text))),
+    }
+}
+
+/// Reads a CSV file of agents: the titles of the columns and the rows.
+fn read_csv<R: BufRead>(r: R) -> io::Result<(Vec<String>, Vec<Vec<f64>>)> {
+    let mut lines = r.lines();
+    let titles: Vec<String> = match lines.next() {
+        Some(line) => line?
+            .split(',')
+            .map(|title| title.trim().to_string())
+            .collect(),
+        None => Vec::new(),
+    };
+    let mut rows = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let texts: Vec<&str> = line.split(',').collect();
+        if texts.len() != titles.len() {
+            return Err(invalid(format!(
+                "a row has {} values for {} columns",
+                texts.len(),
+                titles.len()
+            )));
+        }
+        let row = texts
+            .iter()
+            .zip(&titles)
+            .map(|(text, title)| parse(title, text))
+            .collect::<io::Result<Vec<f64>>>()?;
+        rows.push(row);
+    }
+    Ok((titles, rows))
+}
+
+/// Reads a Parquet file of agents: the titles of the columns and the rows. Columns hold numbers, or health labels as
+/// strings, without nulls.
+#[cfg(feature = "parquet-input")]
+fn read_parquet(path: &Path) -> io::Result<(Vec<String>, Vec<Vec<f64>>)> {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{
+        Float32Type, Float64Type, Int32Type, Int64Type, UInt32Type, UInt64Type,
+    };
+    use arrow_array::{Array, RecordBatchReader};
+    use arrow_schema::DataType;
+    let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+        std::fs::File::open(path)?,
+    )
+    .and_then(|builder| builder.build())
+    .map_err(|e| invalid(e.to_string()))?;
+    let titles: Vec<String> = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect();
+    let mut rows = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| invalid(e.to_string()))?;
+        let first = rows.len();
+        rows.resize(first + batch.num_rows(), Vec::with_capacity(titles.len()));
+        for (title, column) in titles.iter().zip(batch.columns()) {
+            if column.null_count() > 0 {
+                return Err(invalid(format!("column {} has missing values", title)));
+            }
+            let values: Vec<f64> = match column.data_type() {
+                DataType::Float64 => column.as_primitive::<Float64Type>().values().to_vec(),
+                DataType::Float32 => column
+                    .as_primitive::<Float32Type>()
+                    .values()
+                    .iter()
+                    .map(|&v| v as f64)
+                    .collect(),
+                DataType::Int32 => column
+                    .as_primitive::<Int32Type>()
+                    .values()
+                    .iter()
+                    .map(|&v| v as f64)
+                    .collect(),
+                DataType::Int64 => column
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .iter()
+                    .map(|&v| v as f64)
+                    .collect(),
+                DataType::UInt32 => column
+                    .as_primitive::<UInt32Type>()
+                    .values()
+                    .iter()
+                    .map(|&v| v as f64)
+                    .collect(),
+                DataType::UInt64 => column
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .iter()
+                    .map(|&v| v as f64)
+                    .collect(),
+                DataType::Utf8 => column
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|text| parse(title, text.unwrap_or_default()))
+                    .collect::<io::Result<_>>()?,
+                data_type => {
+                    return Err(invalid(format!(
+                        "column {} holds {}, neither numbers nor strings",
+                        title, data_type
+                    )))
+                }
+            };
+            for (row, value) in rows[first..].iter_mut().zip(values) {
+                row.push(value);
+            }
+        }
+    }
+    Ok((titles, rows))
+}
+
+#[cfg(not(feature = "parquet-input"))]
+fn read_parquet(_path: &Path) -> io::Result<(Vec<String>, Vec<Vec<f64>>)> {
+    Err(invalid(
+        "reading Parquet files needs the parquet-input feature".to_string(),
+    ))
+}
@@ -0,0 +1,145 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! A report of a run in one HTML file, to share with people who do not run the model.
+//!
+//! The report shows the time series of every scenario as interactive plotly charts, tables of the final and mean
+//! values of each metric across the scenarios, and a few figures of each scenario, embedded as PNG images. The time
+//! series and the figures go inside the file; plotly comes from its CDN, or from a copy of `plotly.min.js` embedded
+//! as well, for reports that must open offline. Where plotly cannot load, the page draws the charts itself as static
+//! SVG images and says why, so that the report still shows every result.
+
+use base64::Engine;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The page of the report, with the data of the run in place of `/*REPORT*/null`
+const TEMPLATE: &str = include_str!("report.html");
+/// plotly from its CDN, for reports without an embedded copy
+const PLOTLY_CDN: &str =
+    r#"<script src="https://cdn.plot.ly/plotly-2.35.2.min.js" charset="utf-8"></script>"#;
+
+/// Numbers of the figures to show out of `figures`, numbered from 1: the first, the last, and evenly spaced ones
+/// between them.
+fn key_figures(figures: usize, count: usize) -> Vec<usize> {
+    let mut numbers: Vec<usize> = match count {
+        0 => Vec::new(),
+        1 => vec![figures],
+        _ => (0..count)
+            .map(|k| 1 + k * (figures - 1) / (count - 1))
+            .collect(),
+    };
+    numbers.retain(|&number| number >= 1);
+    numbers.dedup();
+    numbers
+}
+
+/// Number of the figures in a frames directory, numbered from 1; zero when the directory is missing.
+fn figure_count(frames_dir: &Path) -> usize {
+    (1..)
+        .take_while(|number| frames_dir.join(format!("{}.png", number)).is_file())
+        .count()
+}
+
+/// Time series, summary, and key figures of a scenario
+fn scenario(scenario_dir: &Path, frames_dir_name: &str, key_frames: usize) -> io::Result<Value> {
+    let ts_path = scenario_dir.join("ts.csv");
+    let (titles, rows) = fs::File::open(&ts_path)
+        .and_then(|f| crate::tables::read_csv(io::BufReader::new(f)))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", ts_path.display(), e)))?;
+    // The first two columns are the infection probability and the time step
+    let metrics: Vec<Value> = titles
+        .iter()
+        .enumerate()
+        .skip(2)
+        .map(|(column, title)| {
+            let values: Vec<f64> = rows.iter().map(|row| row[column]).collect();
+            let mut stats = crate::stats::RunningStats::default();
+            values.iter().for_each(|&value| stats.push(value));
+            json!({
+                "title": title,
+                "values": values,
+                "mean": stats.mean(),
+                "final": values.last(),
+            })
+        })
+        .collect();
+    let frames_dir = scenario_dir.join(frames_dir_name);
+    let figures = figure_count(&frames_dir);
+    let engine = base64::engine::general_purpose::STANDARD;
+    let key_figures = key_figures(figures, key_frames.min(figures))
+        .into_iter()
+        .map(|number| {
+            let png = fs::read(frames_dir.join(format!("{}.png", number)))?;
+            Ok(json!({
+                "number": number,
+                "src": format!("data:image/png;base64,{}", engine.encode(png)),
+            }))
+        })
+        .collect::<io::Result<Vec<Value>>>()?;
+    Ok(json!({
+        "name": scenario_dir.file_name().map(|name| name.to_string_lossy()),
+        "infection_probability": rows.first().map(|row| row[0]),
+        "time_steps": rows.iter().map(|row| row[1]).collect::<Vec<f64>>(),
+        "metrics": metrics,
+        "figures": figures,
+        "key_figures": key_figures,
+    }))
+}
+
+/// Writes the report of the run in `run_dir`, whose scenarios have these directories, in order. The key figures of
+/// each scenario come from its frames directory `frames_dir_name`, such as `frames`; `plotly` embeds this copy of
+/// `plotly.min.js`.
+pub fn write<W: Write>(
+    out: &mut W,
+    run_dir: &Path,
+    scenario_dirs: &[std::path::PathBuf],
+    frames_dir_name: &str,
+    key_frames: usize,
+    plotly: Option<&Path>,
+) -> io::Result<()> {
+    let scenarios = scenario_dirs
+        .iter()
+        .map(|scenario_dir| scenario(scenario_dir, frames_dir_name, key_frames))
+        .collect::<io::Result<Vec<Value>>>()?;
+    let manifest: Option<Value> = fs::read_to_string(run_dir.join("manifest.json"))
+        .ok()
+        .and_then(|manifest| serde_json::from_str(&manifest).ok());
+    let report = json!({
+        "run": run_dir.display().to_string(),
+        "manifest": manifest,
+        "scenarios": scenarios,
+    });
+    let plotly = match plotly {
+        Some(path) => format!(
+            "<script>{}</script>",
+            fs::read_to_string(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?
+        ),
+        None => PLOTLY_CDN.to_string(),
+    };
+    // Text such as </script> in the data must not end the script early
+    let report = report.to_string().replace("</", "<\\/");
+    let page =
+        TEMPLATE
+            .replacen("<!--PLOTLY-->", &plotly, 1)
+            .replacen("/*REPORT*/null", &report, 1);
+    out.write_all(page.as_bytes())
+}
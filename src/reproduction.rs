@@ -0,0 +1,84 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Effective reproduction number R_t from who infected whom: the mean number of agents that each agent infected at
+//! time step t goes on to infect, the cohort reproduction number.
+//!
+//! Infections over links and between age groups name their source, and infections from a cell count for the agent
+//! that infected the cell.
+//! Infections from vectors and within groups have no known source, so R_t leaves them out. The cohorts of the last time
+//! steps are still infecting when the scenario ends, so their R_t falls short.
+
+use crate::components::SecondaryMap;
+use crate::events::{EventBus, Via};
+use crate::AgentKey;
+
+/// Who infected whom so far in a scenario
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transmissions {
+    /// Time step at which each agent infected during the scenario was infected
+    infected_at: SecondaryMap<AgentKey, u32>,
+    /// Agent that infected each cell last, indexed like cell_health
+    cell_sources: Vec<Option<AgentKey>>,
+}
+
+impl Transmissions {
+    /// Time step of the last infection of an agent, if the scenario infected it
+    pub fn infected_at(&self, agent: AgentKey) -> Option<u32> {
+        self.infected_at.get(agent).copied()
+    }
+
+    /// Follows the infections of a time step, adding the secondary infections of each cohort to `secondary`, indexed
+    /// by the time step at which the cohort was infected.
+    pub fn consume(&mut self, events: &EventBus, time_step: usize, secondary: &mut Vec<u32>) {
+        if secondary.len() <= time_step {
+            secondary.resize(time_step + 1, 0);
+        }
+        // Cells infect agents with their state at the beginning of the time step, before the infections of cells
+        for event in events.infections.iter() {
+            let source = match event.via {
+                Via::Cell(idx) => self.cell_sources.get(idx).copied().flatten(),
+                _ => event.source,
+            };
+            if let Some(&infected_at) = source.and_then(|source| self.infected_at.get(source)) {
+                secondary[infected_at as usize] += 1;
+            }
+            self.infected_at.insert(event.target, time_step as u32);
+        }
+        for event in events.cell_infections.iter() {
+            if self.cell_sources.len() <= event.cell {
+                self.cell_sources.resize(event.cell + 1, None);
+            }
+            self.cell_sources[event.cell] = Some(event.source);
+        }
+    }
+}
+
+/// R_t of each time step: the secondary infections of the agents infected at that time step, over their number.
+/// None for time steps without infections.
+pub fn reproduction(incidence: &[u32], secondary: &[u32]) -> Vec<Option<f64>> {
+    incidence
+        .iter()
+        .enumerate()
+        .map(|(time_step, &infected)| {
+            let secondary = secondary.get(time_step).copied().unwrap_or(0);
+            (infected > 0).then(|| secondary as f64 / infected as f64)
+        })
+        .collect()
+}
@@ -0,0 +1,209 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Web dashboard of a running simulation, for runs on remote machines without a display.
+//!
+//! The server runs on its own threads, apart from those of the simulation, and answers three routes:
+//! - `/` serves the dashboard, a single HTML page bundled into the program;
+//! - `/progress` returns the progress of every scenario as JSON;
+//! - `/ws` is a WebSocket that sends the series names and the recent history of every scenario, then one message per
+//!   sampled time step per scenario, as the simulation runs.
+//!
+//! Slow clients skip messages rather than slowing the simulation down.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::{Html, IntoResponse};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// The dashboard, served at `/`
+const DASHBOARD: &str = include_str!("dashboard.html");
+/// Sampled time steps of each scenario that new clients receive when they connect
+const HISTORY: usize = 2000;
+/// Messages kept for clients that fall behind
+const BACKLOG: usize = 1024;
+
+/// Progress and recent history of a scenario
+struct Progress {
+    label: String,
+    time_step: usize,
+    finished: bool,
+    /// Time step and values of the most recent sampled time steps
+    history: VecDeque<(usize, Vec<f64>)>,
+}
+
+struct Shared {
+    series: Vec<String>,
+    time_steps: usize,
+    scenarios: Mutex<BTreeMap<u32, Progress>>,
+    updates: broadcast::Sender<String>,
+}
+
+impl Shared {
+    /// Progress of every scenario, with its history when asked
+    fn progress(&self, history: bool) -> Value {
+        let scenarios = self.scenarios.lock().unwrap();
+        let scenarios: Vec<Value> = scenarios
+            .iter()
+            .map(|(id, progress)| {
+                let mut scenario = json!({
+                    "scenario": id,
+                    "label": progress.label,
+                    "time_step": progress.time_step,
+                    "finished": progress.finished,
+                });
+                if history {
+                    scenario["history"] = progress
+                        .history
+                        .iter()
+                        .map(|(time_step, values)| json!([time_step, values]))
+                        .collect();
+                }
+                scenario
+            })
+            .collect();
+        json!({
+            "series": self.series,
+            "time_steps": self.time_steps,
+            "scenarios": scenarios,
+        })
+    }
+}
+
+pub struct Server {
+    shared: Arc<Shared>,
+    address: SocketAddr,
+    // The server stops when the runtime drops
+    _runtime: tokio::runtime::Runtime,
+}
+
+impl Server {
+    /// Starts the server at this address for these series and scenarios, given as id and label, which run for this
+    /// many time steps. Port 0 picks a free port; see address().
+    pub fn start(
+        address: SocketAddr,
+        series: Vec<String>,
+        scenarios: Vec<(u32, String)>,
+        time_steps: usize,
+    ) -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("dashboard")
+            .enable_io()
+            .build()?;
+        let listener = runtime.block_on(tokio::net::TcpListener::bind(address))?;
+        let address = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            series,
+            time_steps,
+            scenarios: Mutex::new(
+                scenarios
+                    .into_iter()
+                    .map(|(id, label)| {
+                        let progress = Progress {
+                            label,
+                            time_step: 0,
+                            finished: false,
+                            history: VecDeque::new(),
+                        };
+                        (id, progress)
+                    })
+                    .collect(),
+            ),
+            updates: broadcast::channel(BACKLOG).0,
+        });
+        let app = Router::new()
+            .route("/", get(|| async { Html(DASHBOARD) }))
+            .route("/progress", get(progress))
+            .route("/ws", get(updates))
+            .with_state(Arc::clone(&shared));
+        runtime.spawn(async move {
+            axum::serve(listener, app)
+                .await
+                .unwrap_or_else(|e| eprintln!("The dashboard stopped: {}", e))
+        });
+        Ok(Self {
+            shared,
+            address,
+            _runtime: runtime,
+        })
+    }
+
+    /// Address where the server listens
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Records the values of a sampled time step of a scenario, and sends them to the clients.
+    pub fn update(&self, scenario_id: u32, time_step: usize, values: &[f64]) {
+        if let Some(progress) = self.shared.scenarios.lock().unwrap().get_mut(&scenario_id) {
+            progress.time_step = time_step;
+            if progress.history.len() == HISTORY {
+                progress.history.pop_front();
+            }
+            progress.history.push_back((time_step, values.to_vec()));
+        }
+        // Without clients, nobody receives the message
+        let message = json!({"scenario": scenario_id, "time_step": time_step, "values": values});
+        self.shared.updates.send(message.to_string()).ok();
+    }
+
+    /// Marks a scenario as finished, and tells the clients.
+    pub fn finish(&self, scenario_id: u32) {
+        if let Some(progress) = self.shared.scenarios.lock().unwrap().get_mut(&scenario_id) {
+            progress.finished = true;
+        }
+        let message = json!({"scenario": scenario_id, "finished": true});
+        self.shared.updates.send(message.to_string()).ok();
+    }
+}
+
+async fn progress(State(shared): State<Arc<Shared>>) -> Json<Value> {
+    Json(shared.progress(false))
+}
+
+async fn updates(ws: WebSocketUpgrade, State(shared): State<Arc<Shared>>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| send_updates(socket, shared))
+}
+
+/// Sends the history so far, then every update, until the client leaves.
+async fn send_updates(mut socket: WebSocket, shared: Arc<Shared>) {
+    // Subscribing first loses no update between the history and the stream
+    let mut updates = shared.updates.subscribe();
+    let hello = shared.progress(true).to_string();
+    if socket.send(Message::Text(hello.into())).await.is_err() {
+        return;
+    }
+    loop {
+        match updates.recv().await {
+            Ok(message) => {
+                if socket.send(Message::Text(message.into())).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
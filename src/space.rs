@@ -0,0 +1,139 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Continuous space: positions of agents on a torus, and an index for finding the agents near a position.
+//!
+//! Positions are pairs of coordinates between 0 and the width or height of the space, which wraps around at its edges
+//! like the landscape. `SpatialIndex` sorts agents into square buckets as wide as the radius of the queries, so that
+//! finding the neighbors of an agent only looks at the nine buckets around it instead of every agent. Positions
+//! change at every time step; rebuild the index at the start of each one.
+
+/// Position or displacement in continuous space
+pub type Point = [f64; 2];
+
+/// Rectangle of continuous space that wraps around at its edges
+#[derive(Clone, Copy, Debug)]
+pub struct Space {
+    width: f64,
+    height: f64,
+}
+
+impl Space {
+    pub fn new(width: f64, height: f64) -> Self {
+        assert!(
+            width > 0.0 && height > 0.0,
+            "The space must have a positive width and height"
+        );
+        Self { width, height }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// The same position, wrapped into the space
+    pub fn wrap(&self, [x, y]: Point) -> Point {
+        [x.rem_euclid(self.width), y.rem_euclid(self.height)]
+    }
+
+    /// Shortest displacement from `a` to `b`, which may cross the edges of the space
+    pub fn displacement(&self, a: Point, b: Point) -> Point {
+        let shortest = |d: f64, size: f64| d - size * (d / size).round();
+        [
+            shortest(b[0] - a[0], self.width),
+            shortest(b[1] - a[1], self.height),
+        ]
+    }
+
+    pub fn distance(&self, a: Point, b: Point) -> f64 {
+        let [dx, dy] = self.displacement(a, b);
+        dx.hypot(dy)
+    }
+}
+
+/// Agents sorted into buckets by their positions, for queries within a radius
+pub struct SpatialIndex<K> {
+    space: Space,
+    radius: f64,
+    columns: usize,
+    rows: usize,
+    /// Agents in each bucket, row by row, with their positions
+    buckets: Vec<Vec<(K, Point)>>,
+}
+
+impl<K: Copy> SpatialIndex<K> {
+    /// Sorts agents, with their positions, into buckets for queries within `radius`.
+    pub fn new<I: IntoIterator<Item = (K, Point)>>(space: Space, radius: f64, agents: I) -> Self {
+        assert!(radius > 0.0, "The radius of queries must be positive");
+        let columns = ((space.width / radius).floor() as usize).max(1);
+        let rows = ((space.height / radius).floor() as usize).max(1);
+        let mut index = Self {
+            space,
+            radius,
+            columns,
+            rows,
+            buckets: vec![Vec::new(); columns * rows],
+        };
+        for (k, position) in agents {
+            let bucket = index.bucket(position);
+            index.buckets[bucket].push((k, position));
+        }
+        index
+    }
+
+    fn coordinates(&self, [x, y]: Point) -> (usize, usize) {
+        let [x, y] = self.space.wrap([x, y]);
+        (
+            ((x / self.space.width * self.columns as f64) as usize).min(self.columns - 1),
+            ((y / self.space.height * self.rows as f64) as usize).min(self.rows - 1),
+        )
+    }
+
+    fn bucket(&self, position: Point) -> usize {
+        let (column, row) = self.coordinates(position);
+        row * self.columns + column
+    }
+
+    /// Calls `f` with every agent within the radius of `position`, the agent at `position` included, and its
+    /// displacement from `position`.
+    pub fn for_each_within<F: FnMut(K, Point)>(&self, position: Point, mut f: F) {
+        let (column, row) = self.coordinates(position);
+        // Small spaces have fewer than three buckets across; each bucket is visited once
+        let around = |center: usize, len: usize| {
+            let mut around = [(center + len - 1) % len, center, (center + 1) % len];
+            around.sort_unstable();
+            let mut unique = around.to_vec();
+            unique.dedup();
+            unique
+        };
+        for r in around(row, self.rows) {
+            for c in around(column, self.columns) {
+                for &(k, other) in &self.buckets[r * self.columns + c] {
+                    let displacement = self.space.displacement(position, other);
+                    if displacement[0].hypot(displacement[1]) <= self.radius {
+                        f(k, displacement);
+                    }
+                }
+            }
+        }
+    }
+}
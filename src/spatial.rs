@@ -0,0 +1,112 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Spatial statistics of the infected cells of the landscape, which wraps around at its edges.
+//!
+//! Cells are neighbors when they touch at a side or a corner, as in the spread of the infection between cells.
+
+use crate::Health;
+
+/// Offsets of the neighbors of a cell
+const NEIGHBORS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Index of the neighbors of cell `idx` in a landscape of `width` by `height` cells, stored row by row
+fn neighbors(idx: usize, width: usize, height: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = ((idx % width) as isize, (idx / width) as isize);
+    NEIGHBORS.iter().map(move |&(dx, dy)| {
+        let nx = (x + dx).rem_euclid(width as isize) as usize;
+        let ny = (y + dy).rem_euclid(height as isize) as usize;
+        ny * width + nx
+    })
+}
+
+/// Moran's I of infection: near 1 when infected cells clump together, near 0 when they scatter at random, and
+/// negative when they avoid each other. A landscape where every cell has the same health has no spatial structure to
+/// measure; its Moran's I is 0.
+pub fn morans_i(cells: &[Health], width: usize) -> f64 {
+    if cells.is_empty() || width == 0 {
+        return 0.0;
+    }
+    let height = cells.len() / width;
+    let infected = |idx: usize| (cells[idx] == Health::I) as u8 as f64;
+    let mean = (0..cells.len()).map(infected).sum::<f64>() / cells.len() as f64;
+    let variance: f64 = (0..cells.len())
+        .map(|idx| (infected(idx) - mean).powi(2))
+        .sum();
+    if variance == 0.0 {
+        return 0.0;
+    }
+    let covariance: f64 = (0..cells.len())
+        .map(|idx| {
+            (infected(idx) - mean)
+                * neighbors(idx, width, height)
+                    .map(|neighbor| infected(neighbor) - mean)
+                    .sum::<f64>()
+        })
+        .sum();
+    // Every cell has the same number of neighbors, so the weights add up to that number times the cells
+    covariance / variance / NEIGHBORS.len() as f64
+}
+
+/// Clusters of infected cells, each made of the infected cells that neighbors connect
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Clusters {
+    pub count: usize,
+    /// Cells of the largest cluster
+    pub largest: usize,
+}
+
+/// Finds the clusters of infected cells: hotspots of the infection.
+pub fn clusters(cells: &[Health], width: usize) -> Clusters {
+    let mut clusters = Clusters::default();
+    if width == 0 {
+        return clusters;
+    }
+    let height = cells.len() / width;
+    let mut visited = vec![false; cells.len()];
+    let mut stack = Vec::new();
+    for start in 0..cells.len() {
+        if visited[start] || cells[start] != Health::I {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let mut size = 0;
+        while let Some(idx) = stack.pop() {
+            size += 1;
+            for neighbor in neighbors(idx, width, height) {
+                if !visited[neighbor] && cells[neighbor] == Health::I {
+                    visited[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        clusters.count += 1;
+        clusters.largest = clusters.largest.max(size);
+    }
+    clusters
+}
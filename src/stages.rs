@@ -0,0 +1,265 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Staged experiments: runs that depend on other runs, such as projections that start from the end states of a
+//! calibration.
+//!
+//! A plan is a JSON file that lists the stages, each a run of this program with its command line arguments:
+//!
+//! ```json
+//! {"stages": [
+//!     {"name": "calibration", "args": ["sweep", "--config", "calibration.json", "--checkpoint-every", "100"]},
+//!     {"name": "projection", "depends_on": ["calibration"],
+//!      "args": ["--warm-start", "{calibration}/scenario-00/checkpoint.json"]}
+//! ]}
+//! ```
+//!
+//! Each stage writes its outputs to a directory named after it, and `{name}` in its arguments becomes the directory of
+//! stage `name`, which must be one of its dependencies. Stages run in waves: a stage joins the first wave after all its
+//! dependencies, the stages of a wave run at the same time as child processes, and each of them simulates its
+//! scenarios in parallel as usual; `--threads` in their arguments shares the cores between them. A stage that fails
+//! stops the plan at the end of its wave.
+
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+/// Run of a plan
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    /// Stages that must finish before this one starts
+    pub depends_on: Vec<String>,
+    /// Command line arguments of the run, without the output directory
+    pub args: Vec<String>,
+}
+
+/// Stages of a staged experiment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub stages: Vec<Stage>,
+}
+
+fn invalid(problem: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, problem)
+}
+
+/// A list of strings of a stage
+fn strings(stage: &Value, key: &str, name: &str) -> Result<Vec<String>, String> {
+    match &stage[key] {
+        Value::Null => Ok(Vec::new()),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("{} of stage {} must be a list of strings", key, name)),
+        _ => Err(format!(
+            "{} of stage {} must be a list of strings",
+            key, name
+        )),
+    }
+}
+
+impl Plan {
+    /// Reads a plan and checks that its stages can run in order.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let plan = Self::from_json(&value).map_err(invalid)?;
+        plan.waves().map_err(invalid)?;
+        Ok(plan)
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let stages = value["stages"]
+            .as_array()
+            .ok_or("The plan must be an object with a list of stages")?;
+        let stages = stages
+            .iter()
+            .map(|stage| {
+                let name = stage["name"]
+                    .as_str()
+                    .filter(|name| !name.is_empty())
+                    .ok_or("Every stage must have a name")?
+                    .to_string();
+                Ok(Stage {
+                    depends_on: strings(stage, "depends_on", &name)?,
+                    args: strings(stage, "args", &name)?,
+                    name,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self { stages })
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.stages.iter().position(|stage| stage.name == name)
+    }
+
+    /// Indices of the stages in each wave, in order: every stage comes in the first wave after its dependencies.
+    pub fn waves(&self) -> Result<Vec<Vec<usize>>, String> {
+        for (idx, stage) in self.stages.iter().enumerate() {
+            if self.index(&stage.name) != Some(idx) {
+                return Err(format!("Two stages are named {}", stage.name));
+            }
+            if stage.name.contains(['/', '\\']) || stage.name.starts_with('.') {
+                return Err(format!(
+                    "Stage {} must have a name that works as a directory name",
+                    stage.name
+                ));
+            }
+            if stage.args.iter().any(|arg| arg.starts_with("--output-dir")) {
+                return Err(format!(
+                    "Stage {} must leave --output-dir to the plan",
+                    stage.name
+                ));
+            }
+            for dependency in &stage.depends_on {
+                if self.index(dependency).is_none() {
+                    return Err(format!(
+                        "Stage {} depends on {}, which is not a stage",
+                        stage.name, dependency
+                    ));
+                }
+            }
+            for other in &self.stages {
+                let placeholder = format!("{{{}}}", other.name);
+                if !stage.depends_on.contains(&other.name)
+                    && stage.args.iter().any(|arg| arg.contains(&placeholder))
+                {
+                    return Err(format!(
+                        "Stage {} refers to {}, which is not among its dependencies",
+                        stage.name, placeholder
+                    ));
+                }
+            }
+        }
+        // Wave of each stage, once known: one after the last wave of its dependencies
+        let mut wave_of: Vec<Option<usize>> = vec![None; self.stages.len()];
+        while wave_of.iter().any(Option::is_none) {
+            let mut progress = false;
+            for (idx, stage) in self.stages.iter().enumerate() {
+                if wave_of[idx].is_some() {
+                    continue;
+                }
+                let dependencies: Option<Vec<usize>> = stage
+                    .depends_on
+                    .iter()
+                    .map(|dependency| wave_of[self.index(dependency).unwrap()])
+                    .collect();
+                if let Some(dependencies) = dependencies {
+                    wave_of[idx] = Some(dependencies.into_iter().max().map_or(0, |wave| wave + 1));
+                    progress = true;
+                }
+            }
+            if !progress {
+                let waiting: Vec<&str> = self
+                    .stages
+                    .iter()
+                    .zip(&wave_of)
+                    .filter(|(_, wave)| wave.is_none())
+                    .map(|(stage, _)| stage.name.as_str())
+                    .collect();
+                return Err(format!(
+                    "Stages {} depend on each other in a cycle",
+                    waiting.join(", ")
+                ));
+            }
+        }
+        let mut waves = vec![Vec::new(); wave_of.iter().flatten().max().map_or(0, |wave| wave + 1)];
+        for (idx, wave) in wave_of.into_iter().enumerate() {
+            waves[wave.unwrap()].push(idx);
+        }
+        Ok(waves)
+    }
+
+    /// Command line arguments of a stage, with the directories of its dependencies and its own
+    fn args(&self, stage: &Stage, output_dir: &Path) -> Vec<String> {
+        let mut args: Vec<String> = stage
+            .args
+            .iter()
+            .map(|arg| {
+                stage
+                    .depends_on
+                    .iter()
+                    .fold(arg.clone(), |arg, dependency| {
+                        let dir = output_dir.join(dependency);
+                        arg.replace(&format!("{{{}}}", dependency), &dir.to_string_lossy())
+                    })
+            })
+            .collect();
+        args.push("--output-dir".to_string());
+        args.push(output_dir.join(&stage.name).to_string_lossy().into_owned());
+        args
+    }
+
+    /// Runs the stages with `program`, wave after wave, into directories of `output_dir`; `extra_args` go to every
+    /// stage. The standard error of each stage goes to `<name>.log` in `output_dir`.
+    pub fn run(&self, program: &Path, output_dir: &Path, extra_args: &[String]) -> io::Result<()> {
+        let waves = self.waves().map_err(invalid)?;
+        for (number, wave) in waves.iter().enumerate() {
+            let names: Vec<&str> = wave
+                .iter()
+                .map(|&idx| self.stages[idx].name.as_str())
+                .collect();
+            eprintln!(
+                "Wave {} of {}: {}.",
+                number + 1,
+                waves.len(),
+                names.join(", ")
+            );
+            let children = wave
+                .iter()
+                .map(|&idx| -> io::Result<(&Stage, PathBuf, Child)> {
+                    let stage = &self.stages[idx];
+                    let log_path = output_dir.join(format!("{}.log", stage.name));
+                    let child = Command::new(program)
+                        .args(self.args(stage, output_dir))
+                        .args(extra_args)
+                        .stdin(Stdio::null())
+                        .stderr(fs::File::create(&log_path)?)
+                        .spawn()?;
+                    Ok((stage, log_path, child))
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            let mut failed = Vec::new();
+            for (stage, log_path, mut child) in children {
+                let status = child.wait()?;
+                if status.success() {
+                    eprintln!("Stage {} finished.", stage.name);
+                } else {
+                    eprintln!(
+                        "Stage {} ended with {}; see {}.",
+                        stage.name,
+                        status,
+                        log_path.display()
+                    );
+                    failed.push(stage.name.as_str());
+                }
+            }
+            if !failed.is_empty() {
+                return Err(io::Error::other(format!(
+                    "Stages {} failed, so the stages after them did not run",
+                    failed.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Summary statistics, most of which do not need to keep the data in memory, and tests that compare two samples.
+
+use std::cmp::Ordering;
+
+/// Running count, mean, and variance of a series (Welford's algorithm)
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunningStats {
+    count: u64,
+    mean: f64,
+    /// Sum of squared deviations from the mean
+    m2: f64,
+}
+
+impl RunningStats {
+    pub fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Sample variance; zero for fewer than two values
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+}
+
+/// Maximum-likelihood exponent alpha of a discrete power law, P(k) proportional to k^-alpha for k >= k_min,
+/// from a histogram of values and their frequencies; uses the approximation of Clauset, Shalizi, and Newman (2009).
+/// None if no value reaches k_min.
+pub fn power_law_exponent(
+    histogram: impl IntoIterator<Item = (f64, f64)>,
+    k_min: f64,
+) -> Option<f64> {
+    let (count, log_sum) = histogram
+        .into_iter()
+        .filter(|&(k, _frequency)| k >= k_min)
+        .fold((0.0, 0.0), |(count, log_sum), (k, frequency)| {
+            (
+                count + frequency,
+                log_sum + frequency * (k / (k_min - 0.5)).ln(),
+            )
+        });
+    (count > 0.0 && log_sum > 0.0).then(|| 1.0 + count / log_sum)
+}
+
+/// Gini coefficient of non-negative values, such as the wealth of agents: 0 when all values are equal, and close to 1
+/// when one value holds nearly the total. None without values, or when they add up to zero.
+pub fn gini(values: impl IntoIterator<Item = f64>) -> Option<f64> {
+    let mut values: Vec<f64> = values.into_iter().collect();
+    values.sort_by(f64::total_cmp);
+    let n = values.len() as f64;
+    let total: f64 = values.iter().sum();
+    // With values in increasing order, G = 2 Σ i x_i / (n Σ x_i) - (n + 1) / n, for i from 1 to n
+    let weighted: f64 = values
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (i + 1) as f64 * x)
+        .sum();
+    (total > 0.0).then(|| 2.0 * weighted / (n * total) - (n + 1.0) / n)
+}
+
+/// Quantile q of values, between 0 and 1, interpolating linearly between the two nearest values. None without values.
+pub fn quantile(values: impl IntoIterator<Item = f64>, q: f64) -> Option<f64> {
+    let mut values: Vec<f64> = values.into_iter().collect();
+    values.sort_by(f64::total_cmp);
+    let position = q * (values.len() as f64 - 1.0);
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    let (low, high) = (*values.get(below)?, *values.get(above)?);
+    Some(low + (high - low) * (position - below as f64))
+}
+
+/// Complementary error function, with a relative error below 1.2e-7 (Numerical Recipes, section 6.2)
+pub fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let polynomial = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |sum, &c| sum * t + c);
+    let value = t * (-z * z + polynomial).exp();
+    if x >= 0.0 {
+        value
+    } else {
+        2.0 - value
+    }
+}
+
+/// Two-sample Kolmogorov-Smirnov test: the largest distance between the empirical distribution functions of the
+/// samples, and its asymptotic p-value (Numerical Recipes, section 14.3). None if a sample is empty.
+pub fn kolmogorov_smirnov(a: &[f64], b: &[f64]) -> Option<(f64, f64)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    a.sort_by(f64::total_cmp);
+    b.sort_by(f64::total_cmp);
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j, mut distance) = (0, 0, 0.0f64);
+    while i < a.len() && j < b.len() {
+        // Ties advance both samples, so that the distance is measured between distinct values
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        distance = distance.max((i as f64 / n - j as f64 / m).abs());
+    }
+    let effective = (n * m / (n + m)).sqrt();
+    let lambda = (effective + 0.12 + 0.11 / effective) * distance;
+    // Q(λ) = 2 Σ (-1)^(k-1) exp(-2 k² λ²), which converges quickly except near 0, where it is 1
+    let p = if lambda < 0.2 {
+        1.0
+    } else {
+        (1..=100)
+            .map(|k| {
+                let sign = if k % 2 == 1 { 2.0 } else { -2.0 };
+                sign * (-2.0 * (k * k) as f64 * lambda * lambda).exp()
+            })
+            .sum::<f64>()
+            .clamp(0.0, 1.0)
+    };
+    Some((distance, p))
+}
+
+/// Wilcoxon rank-sum, or Mann-Whitney, test: the statistic U of the first sample, the number of pairs in which its
+/// value is larger plus half the ties, and the two-sided p-value from the normal approximation, corrected for ties
+/// and continuity. None if a sample is empty.
+pub fn rank_sum(a: &[f64], b: &[f64]) -> Option<(f64, f64)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let mut values: Vec<(f64, bool)> = a
+        .iter()
+        .map(|&x| (x, true))
+        .chain(b.iter().map(|&x| (x, false)))
+        .collect();
+    values.sort_by(|x, y| x.0.total_cmp(&y.0));
+    let total = values.len() as f64;
+    // Tied values share the mean of their ranks
+    let (mut rank_sum, mut ties) = (0.0, 0.0);
+    let mut start = 0;
+    while start < values.len() {
+        let end = start
+            + values[start..]
+                .iter()
+                .take_while(|(x, _)| *x == values[start].0)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let tied = (end - start) as f64;
+        ties += tied * tied * tied - tied;
+        rank_sum += rank
+            * values[start..end]
+                .iter()
+                .filter(|(_, first)| *first)
+                .count() as f64;
+        start = end;
+    }
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let u = rank_sum - n * (n + 1.0) / 2.0;
+    let variance = n * m / 12.0 * ((total + 1.0) - ties / (total * (total - 1.0)));
+    let p = if variance > 0.0 {
+        let z = ((u - n * m / 2.0).abs() - 0.5).max(0.0) / variance.sqrt();
+        erfc(z / std::f64::consts::SQRT_2)
+    } else {
+        1.0
+    };
+    Some((u, p))
+}
+
+/// Cohen's d: the difference between the means of the second and the first sample, in units of their pooled standard
+/// deviation. None with fewer than two values in a sample or without variation.
+pub fn cohens_d(a: &[f64], b: &[f64]) -> Option<f64> {
+    let stats = |values: &[f64]| {
+        let mut stats = RunningStats::default();
+        values.iter().for_each(|&x| stats.push(x));
+        stats
+    };
+    let (a, b) = (stats(a), stats(b));
+    let (n, m) = (a.count() as f64, b.count() as f64);
+    let pooled = ((n - 1.0) * a.variance() + (m - 1.0) * b.variance()) / (n + m - 2.0);
+    (n >= 2.0 && m >= 2.0 && pooled > 0.0).then(|| (b.mean() - a.mean()) / pooled.sqrt())
+}
+
+/// Cliff's delta: the probability that a value of the second sample exceeds one of the first, minus the probability
+/// of the reverse, from -1 to 1. None if a sample is empty.
+pub fn cliffs_delta(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let balance: f64 = b
+        .iter()
+        .flat_map(|&y| {
+            a.iter().map(move |&x| match y.partial_cmp(&x) {
+                Some(Ordering::Greater) => 1.0,
+                Some(Ordering::Less) => -1.0,
+                _ => 0.0,
+            })
+        })
+        .sum();
+    Some(balance / (a.len() * b.len()) as f64)
+}
+
+/// Quantile 0.975 of Student's t distribution with `df` degrees of freedom, for 95% confidence intervals: from a table
+/// up to 30 degrees of freedom, and from the Cornish-Fisher expansion beyond, both accurate to three decimals.
+pub fn t_975(df: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    match df {
+        0 => f64::NAN,
+        1..=30 => TABLE[df - 1],
+        _ => {
+            let (z, df) = (1.959964f64, df as f64);
+            z + (z.powi(3) + z) / (4.0 * df)
+                + (5.0 * z.powi(5) + 16.0 * z.powi(3) + 3.0 * z) / (96.0 * df * df)
+        }
+    }
+}
+
+/// Mean of independent values and the half-width of its 95% confidence interval. None with fewer than two values.
+pub fn confidence_interval(values: &[f64]) -> Option<(f64, f64)> {
+    let mut stats = RunningStats::default();
+    values.iter().for_each(|&x| stats.push(x));
+    let n = values.len();
+    (n >= 2).then(|| {
+        (
+            stats.mean(),
+            t_975(n - 1) * (stats.variance() / n as f64).sqrt(),
+        )
+    })
+}
+
+/// Batch means for the long series of a single run, whose values are autocorrelated: the mean of the series and the
+/// half-width of its 95% confidence interval, treating the means of `batches` consecutive batches of equal length as
+/// independent. The first values, which do not fill a batch, are left out. None with fewer than two batches or
+/// fewer values than batches.
+pub fn batch_means(series: &[f64], batches: usize) -> Option<(f64, f64)> {
+    let len = series.len().checked_div(batches)?;
+    if len == 0 {
+        return None;
+    }
+    let means: Vec<f64> = series[series.len() - len * batches..]
+        .chunks(len)
+        .map(|batch| batch.iter().sum::<f64>() / len as f64)
+        .collect();
+    confidence_interval(&means)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(x: f64, y: f64) -> bool {
+        (x - y).abs() < 1e-6
+    }
+
+    #[test]
+    fn kolmogorov_smirnov_measures_the_distance_between_samples() {
+        let (distance, p) = kolmogorov_smirnov(&[1.0, 2.0, 3.0], &[3.0, 1.0, 2.0]).unwrap();
+        assert_eq!((distance, p), (0.0, 1.0));
+        let (distance, p) =
+            kolmogorov_smirnov(&[1.0, 2.0, 3.0, 4.0], &[5.0, 6.0, 7.0, 8.0]).unwrap();
+        assert_eq!(distance, 1.0);
+        assert!(close(p, 0.011065637));
+        assert_eq!(kolmogorov_smirnov(&[], &[1.0]), None);
+    }
+
+    #[test]
+    fn rank_sum_counts_larger_pairs_and_half_the_ties() {
+        let (u, p) = rank_sum(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(u, 0.0);
+        assert!(close(p, 0.080855598));
+        // Only ties: no variance, and no evidence of a difference
+        assert_eq!(rank_sum(&[1.0, 1.0], &[1.0, 1.0]), Some((2.0, 1.0)));
+        assert_eq!(rank_sum(&[1.0], &[]), None);
+    }
+
+    #[test]
+    fn cohens_d_uses_the_pooled_standard_deviation() {
+        let (a, b) = ([1.0, 2.0, 3.0], [2.0, 3.0, 4.0]);
+        assert!(close(cohens_d(&a, &b).unwrap(), 1.0));
+        assert!(close(cohens_d(&b, &a).unwrap(), -1.0));
+        assert_eq!(cohens_d(&[1.0, 1.0], &[2.0, 2.0]), None);
+        assert_eq!(cohens_d(&[1.0], &[2.0, 3.0]), None);
+    }
+
+    #[test]
+    fn cliffs_delta_ranges_from_minus_one_to_one() {
+        assert_eq!(cliffs_delta(&[1.0, 2.0], &[3.0, 4.0]), Some(1.0));
+        assert_eq!(cliffs_delta(&[3.0, 4.0], &[1.0, 2.0]), Some(-1.0));
+        assert_eq!(cliffs_delta(&[1.0, 3.0], &[2.0]), Some(0.0));
+        assert_eq!(cliffs_delta(&[1.0], &[]), None);
+    }
+
+    #[test]
+    fn t_975_follows_the_table_then_the_expansion() {
+        assert!(t_975(0).is_nan());
+        assert_eq!(t_975(1), 12.706);
+        assert_eq!(t_975(30), 2.042);
+        // Tables give 1.980 for 120 degrees of freedom
+        assert!((t_975(120) - 1.980).abs() < 5e-4);
+    }
+
+    #[test]
+    fn confidence_interval_needs_two_values() {
+        let (mean, half_width) = confidence_interval(&[1.0, 2.0, 3.0]).unwrap();
+        assert!(close(mean, 2.0));
+        assert!(close(half_width, 4.303 * (1.0f64 / 3.0).sqrt()));
+        assert_eq!(confidence_interval(&[1.0]), None);
+    }
+
+    #[test]
+    fn batch_means_leave_out_the_first_values() {
+        let series: Vec<f64> = (0..10).map(f64::from).collect();
+        // Batches 1-3, 4-6, and 7-9, whose means are 2, 5, and 8
+        let (mean, half_width) = batch_means(&series, 3).unwrap();
+        assert!(close(mean, 5.0));
+        assert!(close(half_width, 4.303 * 3f64.sqrt()));
+        assert_eq!(batch_means(&series, 0), None);
+        assert_eq!(batch_means(&series, 1), None);
+        assert_eq!(batch_means(&series, 11), None);
+    }
+}
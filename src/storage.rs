@@ -0,0 +1,231 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Storage of entities and their components.
+//!
+//! `Storage` lists the few operations the model needs from a container of entities. `slotmap::SlotMap` is the default;
+//! the `vec-storage` feature switches agents and links to `GenerationalVec`, a plain vector with generational indices.
+//! Both hand out slotmap keys, so `SecondaryMap` keeps working for components. The benchmarks compare both containers.
+
+use slotmap::{Key, KeyData, SlotMap};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// Container of entities of type `V`, identified by keys of type `K`
+pub trait Storage<K: Key, V>: Index<K, Output = V> + IndexMut<K> {
+    fn with_capacity(capacity: usize) -> Self;
+    fn insert(&mut self, value: V) -> K;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn capacity(&self) -> usize;
+    fn contains_key(&self, key: K) -> bool;
+    fn get_mut(&mut self, key: K) -> Option<&mut V>;
+    /// Keeps only the entities for which `f` returns true
+    fn retain<F: FnMut(K, &mut V) -> bool>(&mut self, f: F);
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (K, &'a V)>
+    where
+        V: 'a;
+    fn keys<'a>(&'a self) -> impl Iterator<Item = K>
+    where
+        V: 'a,
+    {
+        self.iter().map(|(k, _)| k)
+    }
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a V>
+    where
+        V: 'a,
+    {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Key, V> Storage<K, V> for SlotMap<K, V> {
+    fn with_capacity(capacity: usize) -> Self {
+        SlotMap::with_capacity_and_key(capacity)
+    }
+    fn insert(&mut self, value: V) -> K {
+        SlotMap::insert(self, value)
+    }
+    fn len(&self) -> usize {
+        SlotMap::len(self)
+    }
+    fn capacity(&self) -> usize {
+        SlotMap::capacity(self)
+    }
+    fn contains_key(&self, key: K) -> bool {
+        SlotMap::contains_key(self, key)
+    }
+    fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        SlotMap::get_mut(self, key)
+    }
+    fn retain<F: FnMut(K, &mut V) -> bool>(&mut self, f: F) {
+        SlotMap::retain(self, f)
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (K, &'a V)>
+    where
+        V: 'a,
+    {
+        SlotMap::iter(self)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Slot<V> {
+    /// Odd while the slot holds a value, even while it is free
+    version: u32,
+    value: Option<V>,
+}
+
+impl<V: Clone> Clone for Slot<V> {
+    fn clone(&self) -> Self {
+        Self {
+            version: self.version,
+            value: self.value.clone(),
+        }
+    }
+}
+
+/// Vector of entities with generational indices; a removed entity leaves a free slot that the next insertion reuses.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "V: serde::Serialize",
+        deserialize = "V: serde::Deserialize<'de>"
+    ))
+)]
+pub struct GenerationalVec<K, V> {
+    slots: Vec<Slot<V>>,
+    /// Indices of the free slots
+    free: Vec<u32>,
+    len: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _key: PhantomData<fn(K) -> K>,
+}
+
+impl<K, V: Clone> Clone for GenerationalVec<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            free: self.free.clone(),
+            len: self.len,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K: Key, V> GenerationalVec<K, V> {
+    fn key(idx: usize, version: u32) -> K {
+        KeyData::from_ffi((u64::from(version) << 32) | idx as u64).into()
+    }
+
+    /// Slot index of a key, if the key is still valid
+    fn slot(&self, key: K) -> Option<usize> {
+        let ffi = key.data().as_ffi();
+        let idx = (ffi & 0xffff_ffff) as usize;
+        match self.slots.get(idx) {
+            Some(slot) if slot.version == (ffi >> 32) as u32 && slot.value.is_some() => Some(idx),
+            _ => None,
+        }
+    }
+}
+
+impl<K: Key, V> Index<K> for GenerationalVec<K, V> {
+    type Output = V;
+    fn index(&self, key: K) -> &V {
+        match self.slot(key) {
+            Some(idx) => self.slots[idx].value.as_ref().unwrap(),
+            None => panic!("invalid GenerationalVec key used"),
+        }
+    }
+}
+
+impl<K: Key, V> IndexMut<K> for GenerationalVec<K, V> {
+    fn index_mut(&mut self, key: K) -> &mut V {
+        match self.get_mut(key) {
+            Some(value) => value,
+            None => panic!("invalid GenerationalVec key used"),
+        }
+    }
+}
+
+impl<K: Key, V> Storage<K, V> for GenerationalVec<K, V> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+            _key: PhantomData,
+        }
+    }
+    fn insert(&mut self, value: V) -> K {
+        self.len += 1;
+        match self.free.pop() {
+            Some(idx) => {
+                let slot = &mut self.slots[idx as usize];
+                slot.version += 1;
+                slot.value = Some(value);
+                Self::key(idx as usize, slot.version)
+            }
+            None => {
+                self.slots.push(Slot {
+                    version: 1,
+                    value: Some(value),
+                });
+                Self::key(self.slots.len() - 1, 1)
+            }
+        }
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+    fn contains_key(&self, key: K) -> bool {
+        self.slot(key).is_some()
+    }
+    fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let idx = self.slot(key)?;
+        self.slots[idx].value.as_mut()
+    }
+    fn retain<F: FnMut(K, &mut V) -> bool>(&mut self, mut f: F) {
+        for (idx, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(value) = slot.value.as_mut() {
+                if !f(Self::key(idx, slot.version), value) {
+                    slot.value = None;
+                    slot.version += 1;
+                    self.free.push(idx as u32);
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (K, &'a V)>
+    where
+        V: 'a,
+    {
+        self.slots.iter().enumerate().filter_map(|(idx, slot)| {
+            slot.value
+                .as_ref()
+                .map(|value| (Self::key(idx, slot.version), value))
+        })
+    }
+}
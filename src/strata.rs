@@ -0,0 +1,54 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Health of agents broken down by an attribute, such as their age group or the size of their group, for tables in long
+//! format: one row per stratum and health state.
+
+use crate::{AgentKey, Health};
+use std::collections::BTreeMap;
+
+/// Agents in each health state, in the order of `Health`
+pub type HealthCounts = [u32; 2];
+
+/// Agents of each stratum in each health state, strata in increasing order. Agents that `stratum` leaves without one,
+/// such as agents without an age, count under `None`, first.
+pub fn cross_tabulate<'a, S: Ord>(
+    health: impl IntoIterator<Item = (AgentKey, &'a Health)>,
+    stratum: impl Fn(AgentKey) -> Option<S>,
+) -> BTreeMap<Option<S>, HealthCounts> {
+    let mut counts = BTreeMap::new();
+    for (k, &h) in health {
+        let count: &mut HealthCounts = counts.entry(stratum(k)).or_default();
+        count[h as usize] += 1;
+    }
+    counts
+}
+
+/// Band of a value among the bands between consecutive bounds, the last band open above; None below the first bound
+pub fn band(bounds: &[f64], value: f64) -> Option<usize> {
+    bounds.iter().rposition(|&bound| bound <= value)
+}
+
+/// Whole numbers in a band: "3 to 5", or "6 or more" for the last band
+pub fn band_label(bounds: &[f64], idx: usize) -> String {
+    match bounds.get(idx + 1) {
+        Some(&high) if high == bounds[idx] + 1.0 => format!("{}", bounds[idx]),
+        Some(&high) => format!("{} to {}", bounds[idx], high - 1.0),
+        None => format!("{} or more", bounds[idx]),
+    }
+}
@@ -0,0 +1,245 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Random number streams of a scenario.
+//!
+//! By default every process draws from one generator, so a change in one process shifts the random numbers of all
+//! the others. With common random numbers, each process that scenarios share (movement, births, deaths, ...) draws
+//! from a stream of its own, seeded equally in every scenario; scenarios that differ only in the infection probability
+//! then see the same visits and births for as long as their populations agree, which reduces the variance of their
+//! differences. Infection keeps the main stream, so results without common random numbers stay the same.
+//!
+//! Antithetic streams complement every bit they draw, so that a uniform number u becomes close to 1 - u. A pair of
+//! replicates, one drawing from a stream and the other from its antithetic copy, then tends to err in opposite
+//! directions, and the mean of the pair varies less than that of two independent replicates. Distributions sampled by
+//! inversion, such as Bernoulli trials and uniform choices, benefit the most; rejection samplers keep only part of the
+//! negative correlation.
+//!
+//! The `rng-trace` feature can log every draw, with its time step and process, to a gzip-compressed text file.
+//! When two runs that should be identical diverge, `compare_traces` finds the first draw that differs.
+
+use rand::RngCore;
+use rand_pcg::Pcg64;
+#[cfg(feature = "rng-trace")]
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+#[cfg(feature = "rng-trace")]
+use std::path::Path;
+#[cfg(feature = "rng-trace")]
+use std::sync::{Arc, Mutex};
+
+/// Stochastic processes of the model
+#[derive(Clone, Copy, Debug)]
+pub enum Process {
+    Infection = 0,
+    Network,
+    Movement,
+    Recovery,
+    Survival,
+    Births,
+    Awareness,
+    Vectors,
+    Groups,
+    Observation,
+    Interventions,
+    Migration,
+}
+
+/// Number of processes, and of streams per scenario with common random numbers
+pub const PROCESSES: usize = 12;
+
+/// Log of the draws of all the streams of a scenario
+#[cfg(feature = "rng-trace")]
+struct Trace {
+    time_step: usize,
+    out: flate2::write::GzEncoder<BufWriter<std::fs::File>>,
+}
+
+/// Random number generator of one or more processes
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stream {
+    rng: Pcg64,
+    /// Complement every draw; see the antithetic streams above
+    #[cfg_attr(feature = "serde", serde(default))]
+    antithetic: bool,
+    /// Process drawing now, and the log of the draws
+    #[cfg(feature = "rng-trace")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    trace: Option<(Process, Arc<Mutex<Trace>>)>,
+}
+
+impl Stream {
+    #[cfg(feature = "rng-trace")]
+    fn log(&self, kind: &str, value: u64) {
+        if let Some((process, trace)) = &self.trace {
+            let mut trace = trace.lock().unwrap();
+            let time_step = trace.time_step;
+            writeln!(
+                trace.out,
+                "{}\t{:?}\t{}\t{}",
+                time_step, process, kind, value
+            )
+            .expect("Error writing the trace of random numbers");
+        }
+    }
+}
+
+impl RngCore for Stream {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        let value = self.rng.next_u32() ^ if self.antithetic { u32::MAX } else { 0 };
+        #[cfg(feature = "rng-trace")]
+        self.log("u32", value.into());
+        value
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        let value = self.rng.next_u64() ^ if self.antithetic { u64::MAX } else { 0 };
+        #[cfg(feature = "rng-trace")]
+        self.log("u64", value);
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+        if self.antithetic {
+            dest.iter_mut().for_each(|byte| *byte = !*byte);
+        }
+        #[cfg(feature = "rng-trace")]
+        dest.iter().for_each(|&byte| self.log("u8", byte.into()));
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Random number generators of a scenario
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RandomStreams {
+    /// One stream per process with common random numbers; only the main stream otherwise
+    streams: Vec<Stream>,
+}
+
+impl RandomStreams {
+    /// Process `p` draws from stream `stream + p` with common random numbers.
+    pub fn new(state: u128, stream: u128, common: bool) -> Self {
+        let len = if common { PROCESSES } else { 1 };
+        Self {
+            streams: (0..len)
+                .map(|p| Stream {
+                    rng: Pcg64::new(state, stream.wrapping_add(p as u128)),
+                    antithetic: false,
+                    #[cfg(feature = "rng-trace")]
+                    trace: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// The antithetic copy of these streams, if asked; a scenario with the same state and stream, but not antithetic,
+    /// forms a pair with it.
+    pub fn with_antithetic(mut self, antithetic: bool) -> Self {
+        self.streams
+            .iter_mut()
+            .for_each(|stream| stream.antithetic = antithetic);
+        self
+    }
+
+    /// Generator for a process
+    pub fn get(&mut self, process: Process) -> &mut Stream {
+        let idx = if self.streams.len() == PROCESSES {
+            process as usize
+        } else {
+            0
+        };
+        let stream = &mut self.streams[idx];
+        #[cfg(feature = "rng-trace")]
+        if let Some((traced_process, _)) = &mut stream.trace {
+            *traced_process = process;
+        }
+        stream
+    }
+
+    /// Logs every draw from now on to the gzip-compressed file at `path`, one line per draw:
+    /// time step, process, type, and value, separated by tabs.
+    #[cfg(feature = "rng-trace")]
+    pub fn trace(&mut self, path: &Path) -> io::Result<()> {
+        let trace = Arc::new(Mutex::new(Trace {
+            time_step: 0,
+            out: flate2::write::GzEncoder::new(
+                BufWriter::with_capacity(1 << 16, std::fs::File::create(path)?),
+                flate2::Compression::fast(),
+            ),
+        }));
+        self.streams
+            .iter_mut()
+            .for_each(|stream| stream.trace = Some((Process::Infection, trace.clone())));
+        Ok(())
+    }
+
+    /// Time step of the next draws, for the trace
+    #[cfg(feature = "rng-trace")]
+    pub fn set_time_step(&mut self, time_step: usize) {
+        if let Some((_, trace)) = &self.streams[0].trace {
+            trace.lock().unwrap().time_step = time_step;
+        }
+    }
+
+    /// Completes the trace file, if any.
+    #[cfg(feature = "rng-trace")]
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        if let Some((_, trace)) = &self.streams[0].trace {
+            let mut trace = trace.lock().unwrap();
+            trace.out.try_finish()?;
+            trace.out.get_mut().flush()?;
+        }
+        self.streams
+            .iter_mut()
+            .for_each(|stream| stream.trace = None);
+        Ok(())
+    }
+}
+
+/// First draw that differs between two traces: its number, counting from 1, and the line of each trace.
+/// A trace that ends early shows an empty line. `None` if the traces are identical.
+#[cfg(feature = "rng-trace")]
+pub fn compare_traces(a: &Path, b: &Path) -> io::Result<Option<(u64, String, String)>> {
+    let open = |path: &Path| -> io::Result<_> {
+        Ok(BufReader::new(flate2::read::GzDecoder::new(std::fs::File::open(path)?)).lines())
+    };
+    let (mut a, mut b) = (open(a)?, open(b)?);
+    let mut draw = 0;
+    loop {
+        draw += 1;
+        match (a.next().transpose()?, b.next().transpose()?) {
+            (None, None) => return Ok(None),
+            (line_a, line_b) if line_a == line_b => continue,
+            (line_a, line_b) => {
+                return Ok(Some((
+                    draw,
+                    line_a.unwrap_or_default(),
+                    line_b.unwrap_or_default(),
+                )))
+            }
+        }
+    }
+}
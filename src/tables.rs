@@ -0,0 +1,164 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Tabular outputs: the time series, the trajectories of agents, and the states of cells.
+//!
+//! Each table is a list of `Column`s and rows of `Value`s. The CSV files use the titles of the columns as headers;
+//! binary formats such as Parquet use the short names and the types.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Type of the values of a column
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnType {
+    Float64,
+    UInt32,
+    UInt64,
+    Utf8,
+}
+
+/// Column of a table
+#[derive(Clone, Debug)]
+pub struct Column {
+    /// Short name, such as `time_step`
+    pub name: String,
+    /// Header of the column in CSV files, such as `Time step`
+    pub title: String,
+    pub ty: ColumnType,
+}
+
+impl Column {
+    pub fn new<N: Into<String>, T: Into<String>>(name: N, title: T, ty: ColumnType) -> Self {
+        Self {
+            name: name.into(),
+            title: title.into(),
+            ty,
+        }
+    }
+}
+
+/// Value of a cell of a table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value<'a> {
+    Float64(f64),
+    UInt32(u32),
+    UInt64(u64),
+    Utf8(&'a str),
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Float64(v) => write!(f, "{}", v),
+            Value::UInt32(v) => write!(f, "{}", v),
+            Value::UInt64(v) => write!(f, "{}", v),
+            Value::Utf8(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Writes the header of a CSV file.
+pub fn write_csv_header<W: Write>(w: &mut W, columns: &[Column]) -> io::Result<()> {
+    write_csv_line(w, columns.iter().map(|c| &c.title))
+}
+
+/// Writes a row of a CSV file.
+pub fn write_csv_row<W: Write>(w: &mut W, row: &[Value]) -> io::Result<()> {
+    write_csv_line(w, row.iter())
+}
+
+/// Reads a CSV file of numbers written by `write_csv_header` and `write_csv_row`: the titles of the columns and the rows.
+/// Empty values, which stand for missing ones, read as NaN.
+pub fn read_csv<R: BufRead>(r: R) -> io::Result<(Vec<String>, Vec<Vec<f64>>)> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut lines = r.lines();
+    let titles: Vec<String> = match lines.next() {
+        Some(line) => line?.split(',').map(String::from).collect(),
+        None => Vec::new(),
+    };
+    let rows = lines
+        .map(|line| {
+            let row = line?
+                .split(',')
+                .map(|v| {
+                    if v.is_empty() {
+                        return Ok(f64::NAN);
+                    }
+                    v.parse()
+                        .map_err(|_| invalid(format!("{} is not a number", v)))
+                })
+                .collect::<io::Result<Vec<f64>>>()?;
+            if row.len() == titles.len() {
+                Ok(row)
+            } else {
+                Err(invalid(format!(
+                    "a row has {} values for {} columns",
+                    row.len(),
+                    titles.len()
+                )))
+            }
+        })
+        .collect::<io::Result<_>>()?;
+    Ok((titles, rows))
+}
+
+/// Writes a row as a JSON object on one line, keyed by the names of the columns.
+pub fn write_json_line<W: Write>(w: &mut W, columns: &[Column], row: &[Value]) -> io::Result<()> {
+    write!(w, "{{")?;
+    for (idx, (column, value)) in columns.iter().zip(row.iter()).enumerate() {
+        if idx > 0 {
+            write!(w, ",")?;
+        }
+        write_json_string(w, &column.name)?;
+        write!(w, ":")?;
+        match *value {
+            // JSON has no infinities or NaN
+            Value::Float64(v) if !v.is_finite() => write!(w, "null")?,
+            Value::Utf8(v) => write_json_string(w, v)?,
+            _ => write!(w, "{}", value)?,
+        }
+    }
+    writeln!(w, "}}")
+}
+
+fn write_json_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    write!(w, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(w, "\\\"")?,
+            '\\' => write!(w, "\\\\")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => write!(w, "{}", c)?,
+        }
+    }
+    write!(w, "\"")
+}
+
+fn write_csv_line<W: Write, D: fmt::Display, I: Iterator<Item = D>>(
+    w: &mut W,
+    items: I,
+) -> io::Result<()> {
+    for (idx, item) in items.enumerate() {
+        if idx > 0 {
+            write!(w, ",")?;
+        }
+        write!(w, "{}", item)?;
+    }
+    writeln!(w)
+}
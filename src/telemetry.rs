@@ -0,0 +1,146 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Telemetry of a running simulation, published to an MQTT broker.
+//!
+//! Each scenario publishes one JSON message per sampled time step to `<topic>/scenario-NN`, with the value of each
+//! metric by name, and a last message when it finishes. Messages go out at most once: when the broker is slow or
+//! unreachable, the simulation drops messages rather than waiting, and reports how many at the end.
+
+use rumqttc::{Client, Event, MqttOptions, Outgoing, QoS};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Messages waiting for the broker before the publisher drops new ones
+const CAPACITY: usize = 4096;
+
+pub struct Publisher {
+    client: Client,
+    topic: String,
+    /// Names of the metrics, in the order of their values
+    metrics: Vec<String>,
+    /// Messages published so far, and those that reached the connection to the broker
+    published: AtomicUsize,
+    sent: Arc<AtomicUsize>,
+    /// Set when the publisher closes, so that the connection stops retrying
+    closing: Arc<AtomicBool>,
+    connection: JoinHandle<()>,
+}
+
+impl Publisher {
+    /// Connects to the broker at `host:port`, in the background, to publish these metrics under this topic.
+    pub fn connect(broker: &str, topic: &str, metrics: Vec<String>) -> Self {
+        let (host, port) = match broker.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .unwrap_or_else(|e| panic!("Invalid port in MQTT broker {}: {}", broker, e)),
+            ),
+            None => (broker, 1883),
+        };
+        let mut options = MqttOptions::new(
+            format!("rust-agent-based-models-{}", std::process::id()),
+            host,
+            port,
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, CAPACITY);
+        let closing = Arc::new(AtomicBool::new(false));
+        let broker = broker.to_string();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let (connection_closing, connection_sent) = (Arc::clone(&closing), Arc::clone(&sent));
+        // The connection retries every second until it reaches the broker; the first error says why.
+        let connection = std::thread::spawn(move || {
+            let mut reported = false;
+            for notification in connection.iter() {
+                match notification {
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+                    Ok(Event::Outgoing(Outgoing::Publish(_))) => {
+                        connection_sent.fetch_add(1, Ordering::Relaxed);
+                        reported = false;
+                    }
+                    Ok(_) => reported = false,
+                    Err(e) => {
+                        if connection_closing.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        if !reported {
+                            eprintln!("MQTT broker {}: {}; retrying.", broker, e);
+                            reported = true;
+                        }
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                }
+            }
+        });
+        Self {
+            client,
+            topic: topic.trim_end_matches('/').to_string(),
+            metrics,
+            published: AtomicUsize::new(0),
+            sent,
+            closing,
+            connection,
+        }
+    }
+
+    fn send(&self, scenario_id: u32, message: serde_json::Value) {
+        let topic = format!("{}/scenario-{:02}", self.topic, scenario_id);
+        self.published.fetch_add(1, Ordering::Relaxed);
+        // A full queue drops the message
+        self.client
+            .try_publish(topic, QoS::AtMostOnce, false, message.to_string())
+            .ok();
+    }
+
+    /// Publishes the metrics of a sampled time step of a scenario.
+    pub fn publish(&self, scenario_id: u32, time_step: usize, values: &[f64]) {
+        let values: serde_json::Map<String, serde_json::Value> = self
+            .metrics
+            .iter()
+            .zip(values)
+            .map(|(name, &value)| (name.clone(), json!(value)))
+            .collect();
+        self.send(
+            scenario_id,
+            json!({"scenario": scenario_id, "time_step": time_step, "values": values}),
+        );
+    }
+
+    /// Tells the subscribers that a scenario finished.
+    pub fn finish(&self, scenario_id: u32) {
+        self.send(
+            scenario_id,
+            json!({"scenario": scenario_id, "finished": true}),
+        );
+    }
+
+    /// Sends the messages still waiting, then disconnects from the broker. Returns the number of messages that never
+    /// reached the broker.
+    pub fn close(self) -> usize {
+        self.closing.store(true, Ordering::Relaxed);
+        // With the queue full, the disconnection cannot wait in line; the program then exits without it.
+        if self.client.try_disconnect().is_ok() {
+            self.connection.join().ok();
+        }
+        self.published.load(Ordering::Relaxed) - self.sent.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,221 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Color schemes of the figures.
+//!
+//! A theme gives the background and foreground of the figures, the colors of the lines of the charts, the colors of
+//! susceptible and infected cells, and a gradient for heatmaps. Built-in themes are `light` and `dark`, the classic
+//! figures, and `colorblind` and `colorblind-dark`, which take the lines from the Okabe-Ito palette and the cells and
+//! heatmaps from viridis, so that they read the same for every kind of color vision. A theme file adds user-defined
+//! themes as a JSON object, such as
+//! `{"print": {"base": "colorblind", "background": "#FFFFF0", "series": ["#000000", "#D55E00", "#009E73", "#0072B2"]}}`;
+//! colors missing from a user-defined theme come from its `base` theme, `light` by default.
+
+use plotters::style::RGBColor;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Names of the built-in themes
+pub const BUILTIN: [&str; 4] = ["light", "dark", "colorblind", "colorblind-dark"];
+
+/// Stops of the viridis colormap, from low to high values
+const VIRIDIS: [RGBColor; 5] = [
+    RGBColor(68, 1, 84),
+    RGBColor(59, 82, 139),
+    RGBColor(33, 145, 140),
+    RGBColor(94, 201, 98),
+    RGBColor(253, 231, 37),
+];
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub background: RGBColor,
+    /// Color of text, axes, and grids
+    pub foreground: RGBColor,
+    /// Colors of the lines of the charts, which the reporters of the data collector choose by index
+    pub series: [RGBColor; 4],
+    pub susceptible: RGBColor,
+    pub infected: RGBColor,
+    /// Stops of the gradient of heatmaps, from low to high values
+    pub heatmap: Vec<RGBColor>,
+}
+
+impl Theme {
+    /// Built-in theme with this name, if any
+    pub fn builtin(name: &str) -> Option<Self> {
+        let green = RGBColor(0, 176, 80);
+        let navy = RGBColor(32, 56, 100);
+        let (background, foreground, series, susceptible, infected) = match name {
+            "light" => (
+                RGBColor(255, 255, 255),
+                RGBColor(0, 0, 0),
+                [RGBColor(0, 0, 0), RGBColor(255, 0, 0), green, navy],
+                green,
+                navy,
+            ),
+            "dark" => (
+                RGBColor(0, 0, 0),
+                RGBColor(255, 255, 255),
+                [RGBColor(255, 255, 255), RGBColor(255, 192, 0), green, navy],
+                green,
+                navy,
+            ),
+            "colorblind" => (
+                RGBColor(255, 255, 255),
+                RGBColor(0, 0, 0),
+                [
+                    RGBColor(0, 0, 0),
+                    RGBColor(213, 94, 0),
+                    VIRIDIS[2],
+                    VIRIDIS[0],
+                ],
+                VIRIDIS[2],
+                VIRIDIS[0],
+            ),
+            "colorblind-dark" => (
+                RGBColor(0, 0, 0),
+                RGBColor(255, 255, 255),
+                [
+                    RGBColor(255, 255, 255),
+                    RGBColor(230, 159, 0),
+                    VIRIDIS[4],
+                    VIRIDIS[1],
+                ],
+                VIRIDIS[4],
+                VIRIDIS[1],
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            background,
+            foreground,
+            series,
+            susceptible,
+            infected,
+            heatmap: VIRIDIS.to_vec(),
+        })
+    }
+
+    /// Color of a value between 0 and 1 on the heatmap gradient; values outside the range take the color of the end.
+    pub fn heatmap_color(&self, value: f64) -> RGBColor {
+        let last = self.heatmap.len() - 1;
+        let position = value.clamp(0.0, 1.0) * last as f64;
+        let index = (position.floor() as usize).min(last.saturating_sub(1));
+        let (RGBColor(r0, g0, b0), RGBColor(r1, g1, b1)) =
+            (self.heatmap[index], self.heatmap[(index + 1).min(last)]);
+        let fraction = position - index as f64;
+        let mix = |c0: u8, c1: u8| (c0 as f64 + (c1 as f64 - c0 as f64) * fraction).round() as u8;
+        RGBColor(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+    }
+}
+
+/// Directory of the figures in this theme, inside the directory of each scenario
+pub fn frames_dir_name(theme: &str) -> String {
+    match theme {
+        "light" => "frames".to_string(),
+        "dark" => "frames_dark".to_string(),
+        _ => format!("frames_{}", theme),
+    }
+}
+
+/// Directory of the heatmaps of the landscape in this theme, inside the directory of each scenario
+pub fn heatmaps_dir_name(theme: &str) -> String {
+    match theme {
+        "light" => "heatmaps".to_string(),
+        "dark" => "heatmaps_dark".to_string(),
+        _ => format!("heatmaps_{}", theme),
+    }
+}
+
+fn parse_color(text: &str) -> Option<RGBColor> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Reads the user-defined themes of a theme file; see the module documentation.
+pub fn load(path: &Path) -> io::Result<Vec<Theme>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let file: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| invalid(format!("{}: {}", path.display(), e)))?;
+    let themes = file
+        .as_object()
+        .ok_or_else(|| invalid(format!("{} is not a JSON object of themes", path.display())))?;
+    themes
+        .iter()
+        .map(|(name, fields)| {
+            let err = |field: &str| invalid(format!("Theme {}: invalid {}", name, field));
+            let base = fields
+                .get("base")
+                .map_or(Some("light"), |base| base.as_str());
+            let mut theme = base
+                .and_then(Theme::builtin)
+                .ok_or_else(|| err("base; it must be a built-in theme"))?;
+            theme.name = name.clone();
+            let color = |field: &str| match fields.get(field) {
+                Some(value) => value
+                    .as_str()
+                    .and_then(parse_color)
+                    .map(Some)
+                    .ok_or_else(|| err(field)),
+                None => Ok(None),
+            };
+            let colors = |field: &str| match fields.get(field) {
+                Some(value) => value
+                    .as_array()
+                    .and_then(|values| {
+                        values
+                            .iter()
+                            .map(|value| value.as_str().and_then(parse_color))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .map(Some)
+                    .ok_or_else(|| err(field)),
+                None => Ok(None),
+            };
+            if let Some(background) = color("background")? {
+                theme.background = background;
+            }
+            if let Some(foreground) = color("foreground")? {
+                theme.foreground = foreground;
+            }
+            if let Some(susceptible) = color("susceptible")? {
+                theme.susceptible = susceptible;
+            }
+            if let Some(infected) = color("infected")? {
+                theme.infected = infected;
+            }
+            if let Some(series) = colors("series")? {
+                theme.series = series
+                    .try_into()
+                    .map_err(|_| err("series; it needs 4 colors"))?;
+            }
+            if let Some(heatmap) = colors("heatmap")? {
+                if heatmap.len() < 2 {
+                    return Err(err("heatmap; it needs at least 2 colors"));
+                }
+                theme.heatmap = heatmap;
+            }
+            Ok(theme)
+        })
+        .collect()
+}
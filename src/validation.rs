@@ -0,0 +1,107 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Checks of the parameters of a run before it starts.
+//!
+//! Every check runs, so that a run with mistakes stops at once with all of them, each named after its parameter, such
+//! as `infection_probabilities[2]` or `--frame-stride`, instead of panicking in the middle of the run.
+
+use crate::error::Error;
+use std::fmt::Display;
+
+/// Problems found so far in the parameters of a run
+#[derive(Default)]
+pub struct Problems(Vec<String>);
+
+impl Problems {
+    /// Records a problem with a parameter unless the condition holds; the requirement says what the parameter must be.
+    pub fn require(&mut self, condition: bool, name: &str, requirement: impl Display) {
+        if !condition {
+            self.0.push(format!("{} must be {}", name, requirement));
+        }
+    }
+
+    /// Checks that a parameter is a probability, between 0 and 1
+    pub fn probability(&mut self, name: &str, value: f64) {
+        self.require(
+            (0.0..=1.0).contains(&value),
+            name,
+            format_args!("between 0 and 1, not {}", value),
+        );
+    }
+
+    /// Checks that a parameter, such as a dimension of the landscape, is positive
+    pub fn positive(&mut self, name: &str, value: usize) {
+        self.require(value > 0, name, "positive");
+    }
+
+    /// Records a problem with a parameter, such as an error of a file or of a specification.
+    pub fn report(&mut self, name: &str, problem: impl Display) {
+        self.0.push(format!("{}: {}", name, problem));
+    }
+
+    /// Checks that a distribution can be built from its parameters, such as a normal distribution with a finite
+    /// standard deviation.
+    pub fn distribution<D, E: Display>(&mut self, name: &str, distribution: Result<D, E>) {
+        if let Err(e) = distribution {
+            self.report(name, e);
+        }
+    }
+
+    /// Ends the checks: the run goes on only without problems.
+    pub fn finish(self) -> Result<(), Error> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid(self.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems_collect_every_check_by_name() {
+        let mut problems = Problems::default();
+        problems.probability("infection_probabilities[2]", 1.5);
+        problems.probability("recovery_probability", 0.5);
+        problems.positive("--frame-stride", 0);
+        problems.distribution("visit_distro", Err::<(), _>("a negative shape"));
+        match problems.finish() {
+            Err(Error::Invalid(problems)) => assert_eq!(
+                problems,
+                [
+                    "infection_probabilities[2] must be between 0 and 1, not 1.5",
+                    "--frame-stride must be positive",
+                    "visit_distro: a negative shape",
+                ]
+            ),
+            _ => panic!("expected the problems of the parameters"),
+        }
+    }
+
+    #[test]
+    fn problems_let_valid_parameters_through() {
+        let mut problems = Problems::default();
+        problems.probability("recovery_probability", 0.0);
+        problems.positive("--frame-stride", 1);
+        assert!(problems.finish().is_ok());
+    }
+}
@@ -0,0 +1,126 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Vectors, such as mosquitoes: a second population that lives on the cells of the landscape and carries the disease
+//! between hosts.
+//!
+//! In a vector-borne scenario, hosts no longer infect each other, over links or through cells. Instead, each infected
+//! vector bites the susceptible hosts that visit its cell, each of which becomes infected with the probability of
+//! infection of the scenario; each infectious host that visits a cell, in turn, infects each susceptible vector there
+//! with `infection_probability`. Vectors never recover. At each time step, each vector moves to one of the eight
+//! neighbors of its cell with `movement_probability`, gives birth to a susceptible vector in its cell with a
+//! probability that falls logistically as the population approaches `capacity`, and dies with `death_probability`.
+//!
+//! Nothing refers to a vector, so vectors live in a plain `Vec`, in the order they were born.
+
+use crate::Health;
+use rand::Rng;
+use wrapping_coords2d::WrappingCoords2d;
+
+/// Offsets of the eight neighbors of a cell
+const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A vector, on a cell of the landscape
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vector {
+    pub cell: usize,
+    pub health: Health,
+}
+
+/// Parameters of the population of vectors
+pub struct VectorPopulation {
+    /// Vectors at the start of each vector-borne scenario, on random cells
+    pub initial: usize,
+    /// Number of vectors at which births stop
+    pub capacity: f64,
+    /// Probability that a vector gives birth at each time step, in an empty landscape
+    pub birth_probability: f64,
+    pub death_probability: f64,
+    pub movement_probability: f64,
+    /// Probability that an infectious host infects a susceptible vector in the cell it visits
+    pub infection_probability: f64,
+}
+
+impl VectorPopulation {
+    /// Susceptible vectors of the start of a scenario, each on a random cell
+    pub fn populate<R: Rng>(&self, cells: usize, rng: &mut R) -> Vec<Vector> {
+        (0..self.initial)
+            .map(|_| Vector {
+                cell: rng.gen_range(0..cells),
+                health: Health::S,
+            })
+            .collect()
+    }
+
+    /// Vectors become infected, move, give birth, and die. `infectious_visits` holds the number of infectious hosts
+    /// that visited each cell at this time step.
+    pub fn step<R: Rng>(
+        &self,
+        vectors: &mut Vec<Vector>,
+        infectious_visits: &[u32],
+        coord: &WrappingCoords2d,
+        rng: &mut R,
+    ) {
+        let width = coord.width();
+        let birth_probability =
+            (self.birth_probability * (1.0 - vectors.len() as f64 / self.capacity)).max(0.0);
+        let mut births = Vec::new();
+        vectors.retain_mut(|vector| {
+            if vector.health == Health::S
+                && (0..infectious_visits[vector.cell])
+                    .any(|_| rng.gen_bool(self.infection_probability))
+            {
+                vector.health = Health::I;
+            }
+            if rng.gen_bool(self.movement_probability) {
+                let (dx, dy) = NEIGHBORS[rng.gen_range(0..NEIGHBORS.len())];
+                let (x, y) = (vector.cell as i32 % width, vector.cell as i32 / width);
+                vector.cell = coord.index(x + dx, y + dy);
+            }
+            if rng.gen_bool(birth_probability) {
+                births.push(Vector {
+                    cell: vector.cell,
+                    health: Health::S,
+                });
+            }
+            !rng.gen_bool(self.death_probability)
+        });
+        vectors.extend(births);
+    }
+}
+
+/// Number of infected vectors on each cell
+pub fn infected_by_cell(vectors: &[Vector], cells: usize) -> Vec<u32> {
+    let mut infected = vec![0; cells];
+    for vector in vectors {
+        if vector.health == Health::I {
+            infected[vector.cell] += 1;
+        }
+    }
+    infected
+}
@@ -0,0 +1,73 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Within-host viral load: the load of each infected agent rises to a peak and then falls, and it sets how infectious
+//! the agent is and whether a test would detect the infection.
+//!
+//! The load follows the tent shape, on a log10 scale, that fits the viral kinetics of many acute infections: it grows
+//! by `growth` log10 copies at each time step since the infection until it reaches `peak` at `peak_time`, and then
+//! decays by `decay` log10 copies at each time step. Infectiousness rises linearly with the log10 load above the
+//! `infectious` threshold, from 0 there to 1 at the peak, and multiplies the probability that the agent infects a
+//! friend or a cell. A load of at least `detection` is detectable.
+
+/// Parameters of the viral load; see the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViralLoad {
+    /// Highest log10 load
+    pub peak: f64,
+    /// Time steps from the infection to the peak
+    pub peak_time: f64,
+    /// Increase of the log10 load at each time step before the peak
+    pub growth: f64,
+    /// Decrease of the log10 load at each time step after the peak
+    pub decay: f64,
+    /// Log10 load below which the agent infects no one
+    pub infectious: f64,
+    /// Lowest log10 load that a test detects
+    pub detection: f64,
+}
+
+impl ViralLoad {
+    /// Time steps since the last infection of an infected agent, at the time step of that infection, if known;
+    /// agents infected before the records start, such as those of a warm start, are at the peak.
+    pub fn infection_age(&self, infected_at: Option<u32>, time_step: usize) -> f64 {
+        infected_at.map_or(self.peak_time, |infected_at| {
+            time_step as f64 - infected_at as f64
+        })
+    }
+
+    /// Log10 load of an agent this many time steps after its infection
+    pub fn log10_load(&self, infection_age: f64) -> f64 {
+        if infection_age < self.peak_time {
+            self.peak - self.growth * (self.peak_time - infection_age)
+        } else {
+            self.peak - self.decay * (infection_age - self.peak_time)
+        }
+    }
+
+    /// Factor of the probability that an agent infects others, from 0 below the infectious threshold to 1 at the peak
+    pub fn infectiousness(&self, infection_age: f64) -> f64 {
+        ((self.log10_load(infection_age) - self.infectious) / (self.peak - self.infectious))
+            .clamp(0.0, 1.0)
+    }
+
+    /// Whether a test detects the infection of an agent
+    pub fn detectable(&self, infection_age: f64) -> bool {
+        self.log10_load(infection_age) >= self.detection
+    }
+}
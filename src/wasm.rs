@@ -0,0 +1,394 @@
+/* This file is part of rust-agent-based-models:
+   Reliable and efficient agent-based models in Rust
+
+    Copyright 2020 Fabio A. Correa Duran facorread@gmail.com
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! A scenario of the model in the web browser, for teaching and sharing.
+//!
+//! `Simulation` runs the core loop of `main()` one time step at a time, without threads, files, or figures; the page
+//! at `www/index.html` calls `step()` on every animation frame and draws `pixels()` on a canvas. Blocks between the
+//! tags begin-similar-code and end-similar-code follow those of `main.rs`, so that both programs simulate the same
+//! model: with seed 0, a simulation here reproduces the scenario of `main()` with the same probability of infection.
+//!
+//! Build the module for the page with
+//! `cargo rustc --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm,net --crate-type cdylib`
+//! and then `wasm-bindgen --target web --out-dir www/pkg target/wasm32-unknown-unknown/release/rust_agent_based_models.wasm`.
+
+use crate::buffered::Buffered;
+use crate::deletion::Dependents;
+#[cfg(feature = "net")]
+use crate::events::LinkEvent;
+use crate::events::{
+    BirthEvent, CellInfectionEvent, DeathEvent, EventBus, InfectionEvent, RecoveryEvent, Via,
+};
+use crate::hooks::Hooks;
+use crate::landscape::CellDynamics;
+#[cfg(feature = "net")]
+use crate::network;
+use crate::storage::Storage;
+use crate::streams::{Process, RandomStreams};
+use crate::theme::Theme;
+use crate::{AgentKey, Agents, Health};
+#[cfg(feature = "net")]
+use crate::{LinkKey, Links};
+use plotters::style::RGBColor;
+use rand::distributions::{Bernoulli, Distribution};
+use rand_distr::Normal;
+use wasm_bindgen::prelude::*;
+use wrapping_coords2d::WrappingCoords2d;
+
+/// Initial state and stream of the random number generator, as in `main()`; the seed changes the state.
+#[allow(clippy::unreadable_literal)]
+const RNG_STATE: u128 = 0xcafef00dd15ea5e5u128;
+#[allow(clippy::unreadable_literal)]
+const RNG_STREAM: u128 = 0xa02bdbf7bb3c0a7ac28fa16a64abf96u128;
+
+/// One scenario of the model, with the parameters of `main()`
+#[wasm_bindgen]
+pub struct Simulation {
+    time_step: usize,
+    /// Infections since the start, seeds included
+    infections: usize,
+    rng: RandomStreams,
+    hooks: Hooks<'static>,
+    events: EventBus,
+    health: Buffered<Agents<Health>>,
+    #[cfg(feature = "net")]
+    links: Links,
+    cell_health: Buffered<Vec<Health>>,
+    cell_dynamics: CellDynamics,
+    coord: WrappingCoords2d,
+    #[cfg(feature = "net")]
+    net_k: usize,
+    birth_distro: Bernoulli,
+    initial_infection_distro: Bernoulli,
+    infection_distro: Bernoulli,
+    visit_distro: Normal<f32>,
+    #[cfg(feature = "net")]
+    link_distro: Bernoulli,
+    recovery_distro: Bernoulli,
+    survival_distro: Bernoulli,
+    /// Colors of cells, as RGBA
+    susceptible: [u8; 4],
+    infected: [u8; 4],
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    /// Starts a scenario with this probability of infection; other seeds give other random numbers.
+    #[wasm_bindgen(constructor)]
+    pub fn new(infection_probability: f64, seed: u32) -> Simulation {
+        // begin-similar-code 1
+        // Model parameter: Initial number of agents
+        let n0: usize = 1000;
+        // Model parameter: Scale-free network parameter: new links per agent
+        #[cfg(feature = "net")]
+        let net_k: usize = 7;
+        // Model parameter: Dimensions of the virtual landscape, in number of cells
+        let (landscape_width, landscape_height) = (100i32, 100i32);
+        let birth_probability = 0.01;
+        let initial_infection_probability = 0.3;
+        // Normal distribution to choose cells in the landscape: mean and standard deviation, in cells
+        let (visit_mean, visit_sd) = (50.0f32, 10f32);
+        #[cfg(feature = "net")]
+        let link_probability = 0.01;
+        // Model parameter: probability of recovery
+        let recovery_probability = 0.8;
+        let survival_probability = 0.8;
+        // end-similar-code 1
+        // begin-similar-code 5
+        let coord = WrappingCoords2d::new(landscape_width, landscape_height).unwrap();
+        let birth_distro = Bernoulli::new(birth_probability).unwrap();
+        let initial_infection_distro = Bernoulli::new(initial_infection_probability).unwrap();
+        let visit_distro = Normal::new(visit_mean, visit_sd).unwrap();
+        #[cfg(feature = "net")]
+        let link_distro = Bernoulli::new(link_probability).unwrap();
+        let recovery_distro = Bernoulli::new(recovery_probability).unwrap();
+        let survival_distro = Bernoulli::new(survival_probability).unwrap();
+        // end-similar-code 5
+        // begin-similar-code 2
+        // Use Pcg64 for reproducible random numbers; see the streams module for common random numbers
+        let rng = RandomStreams::new(RNG_STATE ^ seed as u128, RNG_STREAM, false);
+        // Model state: Agent health, now and in the next time step. This implements parallel updating of agents.
+        let mut health: Buffered<Agents<Health>> = Buffered::new(Storage::with_capacity(2 * n0));
+        // Model state: Health status of each cell in the landscape, now and in the next time step.
+        // This implements parallel updating of cells.
+        let cell_health = Buffered::new(vec![Health::S; coord.size()]);
+        // Model initialization: Agents
+        while health.write().len() < n0 {
+            let _k: AgentKey = health.write().insert(Health::S);
+        }
+        health.swap();
+        let infection_distro = Bernoulli::new(infection_probability).unwrap();
+        // end-similar-code 2
+        // The browser has no threads for rayon
+        let cell_dynamics =
+            CellDynamics::new(&coord, infection_probability, recovery_distro, false);
+        let theme = Theme::builtin("light").unwrap();
+        let rgba = |RGBColor(r, g, b): RGBColor| [r, g, b, 255];
+        Simulation {
+            time_step: 0,
+            infections: 0,
+            rng,
+            hooks: Hooks::new(),
+            events: EventBus::new(),
+            health,
+            #[cfg(feature = "net")]
+            links: Storage::with_capacity(n0 * n0),
+            cell_health,
+            cell_dynamics,
+            coord,
+            #[cfg(feature = "net")]
+            net_k,
+            birth_distro,
+            initial_infection_distro,
+            infection_distro,
+            visit_distro,
+            #[cfg(feature = "net")]
+            link_distro,
+            recovery_distro,
+            survival_distro,
+            susceptible: rgba(theme.susceptible),
+            infected: rgba(theme.infected),
+        }
+    }
+
+    /// Simulates one time step.
+    pub fn step(&mut self) {
+        let Simulation {
+            time_step,
+            infections,
+            rng,
+            hooks,
+            events,
+            health,
+            #[cfg(feature = "net")]
+            links,
+            cell_health,
+            cell_dynamics,
+            coord,
+            #[cfg(feature = "net")]
+            net_k,
+            birth_distro,
+            initial_infection_distro,
+            infection_distro,
+            visit_distro,
+            #[cfg(feature = "net")]
+            link_distro,
+            recovery_distro,
+            survival_distro,
+            ..
+        } = self;
+        let (time_step, time_step_slot) = (*time_step, time_step);
+        hooks.step(time_step);
+        // begin-similar-code 3
+        // Initialization of this time step: Network seed
+        #[cfg(feature = "net")]
+        {
+            if links.is_empty() && health.read().len() > 1 {
+                let mut h_it = health.read().iter();
+                let (key0, _value) = h_it.next().unwrap();
+                let (key1, _value) = h_it.next().unwrap();
+                let link_id: LinkKey = links.insert((key0, key1));
+                events.links.publish(LinkEvent {
+                    link: link_id,
+                    agents: (key0, key1),
+                });
+            }
+            // Initialization of this time step: Network
+            let keys_vec: Vec<AgentKey> = health.read().keys().collect();
+            network::form_links(
+                &keys_vec,
+                links,
+                *net_k,
+                *link_distro,
+                rng.get(Process::Network),
+                |link, agents| events.links.publish(LinkEvent { link, agents }),
+            );
+        }
+        // Dynamics: infection spreads
+        {
+            let (current_health, next_health) = health.read_write();
+            let (current_cell_health, next_cell_health) = cell_health.read_write();
+            #[cfg(feature = "net")]
+            network::transmit(
+                links,
+                current_health,
+                next_health,
+                *infection_distro,
+                rng.get(Process::Infection),
+                &mut events.infections,
+            );
+            if time_step == 0 {
+                current_health.iter().for_each(|(k, &h)| {
+                    if h == Health::S
+                        && initial_infection_distro.sample(rng.get(Process::Infection))
+                    {
+                        if next_health[k] == Health::S {
+                            events.infections.publish(InfectionEvent {
+                                source: None,
+                                target: k,
+                                via: Via::Seed,
+                            });
+                        }
+                        next_health[k] = Health::I;
+                    }
+                });
+            }
+            current_health.iter().for_each(|(k, &h)| {
+                // Choose a random cell to visit
+                let x = visit_distro.sample(rng.get(Process::Movement)) as i32;
+                let y = visit_distro.sample(rng.get(Process::Movement)) as i32;
+                let idx = coord.index(x, y);
+                match h {
+                    Health::S => {
+                        if current_cell_health[idx] == Health::I
+                            && infection_distro.sample(rng.get(Process::Infection))
+                        {
+                            // Cell infects agent
+                            if next_health[k] == Health::S {
+                                events.infections.publish(InfectionEvent {
+                                    source: None,
+                                    target: k,
+                                    via: Via::Cell(idx),
+                                });
+                            }
+                            next_health[k] = Health::I;
+                        }
+                    }
+                    Health::I => {
+                        if current_cell_health[idx] == Health::S
+                            && infection_distro.sample(rng.get(Process::Infection))
+                        {
+                            // Agent infects cell
+                            if next_cell_health[idx] == Health::S {
+                                events.cell_infections.publish(CellInfectionEvent {
+                                    source: k,
+                                    cell: idx,
+                                });
+                            }
+                            next_cell_health[idx] = Health::I;
+                        }
+                        if recovery_distro.sample(rng.get(Process::Recovery)) {
+                            events.recoveries.publish(RecoveryEvent { agent: k });
+                            next_health[k] = Health::S;
+                        }
+                    }
+                };
+            });
+            // Dynamics: Disease spreads across cells and infectious cells recover
+            cell_dynamics.step(
+                current_cell_health,
+                next_cell_health,
+                time_step,
+                None,
+                rng.get(Process::Infection),
+            );
+            // Dynamics: After spreading the infection, some infectious agents die; so do their links.
+            // Register here any other component that refers to agents; see define_components!
+            #[allow(unused_mut)]
+            let mut dependents = Dependents::new();
+            #[cfg(feature = "net")]
+            dependents.register(links);
+            dependents.retain(next_health, |agent_key, _next_h| {
+                let survives = match current_health[agent_key] {
+                    Health::S => true,
+                    Health::I => survival_distro.sample(rng.get(Process::Survival)),
+                };
+                if !survives {
+                    events.deaths.publish(DeathEvent { agent: agent_key });
+                }
+                survives
+            });
+        }
+        // Dynamics: cells update in parallel
+        cell_health.swap();
+        // Dynamics: New agents emerge
+        let next_health = health.write();
+        let parents: Vec<AgentKey> = next_health
+            .iter()
+            .filter(|&(_, &h)| h == Health::S && birth_distro.sample(rng.get(Process::Births)))
+            .map(|(k, _)| k)
+            .collect();
+        for parent in parents {
+            let agent = next_health.insert(Health::S);
+            events.births.publish(BirthEvent { parent, agent });
+        }
+        // Dynamics: Agents update in parallel
+        health.swap();
+        // Bookkeeping: Systems consume the events of this time step
+        hooks.consume(events, time_step);
+        *infections += events.infections.len();
+        events.clear();
+        // end-similar-code 3
+        *time_step_slot += 1;
+    }
+
+    /// Time step that step() simulates next
+    pub fn time_step(&self) -> usize {
+        self.time_step
+    }
+
+    /// Number of agents
+    pub fn agents(&self) -> usize {
+        self.health.read().len()
+    }
+
+    /// Number of infected agents
+    pub fn infected(&self) -> usize {
+        self.health
+            .read()
+            .values()
+            .filter(|&&h| h == Health::I)
+            .count()
+    }
+
+    /// Number of infected cells
+    pub fn infected_cells(&self) -> usize {
+        self.cell_health
+            .read()
+            .iter()
+            .filter(|&&h| h == Health::I)
+            .count()
+    }
+
+    /// Infections since the start, seeds included
+    pub fn infections(&self) -> usize {
+        self.infections
+    }
+
+    /// Width of the landscape, in cells
+    pub fn width(&self) -> u32 {
+        self.coord.width() as u32
+    }
+
+    /// Height of the landscape, in cells
+    pub fn height(&self) -> u32 {
+        self.coord.height() as u32
+    }
+
+    /// Colors of the cells, row by row, as RGBA bytes for an ImageData of width() × height() pixels
+    pub fn pixels(&self) -> Vec<u8> {
+        self.cell_health
+            .read()
+            .iter()
+            .flat_map(|&h| match h {
+                Health::S => self.susceptible,
+                Health::I => self.infected,
+            })
+            .collect()
+    }
+}